@@ -0,0 +1,44 @@
+//! Embeds build metadata for `pulse version --verbose`: the git commit, the
+//! build time, the cargo profile, and the target triple.
+
+use std::{
+    env,
+    process::Command,
+    time::{SystemTime, UNIX_EPOCH},
+};
+
+fn main() {
+    // Rebuild when the checked-out commit changes, not on every build.
+    println!("cargo:rerun-if-changed=.git/HEAD");
+    println!("cargo:rerun-if-changed=.git/refs/heads");
+
+    let commit = git(&["rev-parse", "--short=12", "HEAD"]);
+    let built_at = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|elapsed| elapsed.as_secs())
+        .unwrap_or_default();
+
+    println!(
+        "cargo:rustc-env=PULSE_GIT_COMMIT={}",
+        commit.unwrap_or_default()
+    );
+    println!("cargo:rustc-env=PULSE_BUILD_TIMESTAMP={built_at}");
+    println!(
+        "cargo:rustc-env=PULSE_BUILD_PROFILE={}",
+        env::var("PROFILE").unwrap_or_default()
+    );
+    println!(
+        "cargo:rustc-env=PULSE_BUILD_TARGET={}",
+        env::var("TARGET").unwrap_or_default()
+    );
+}
+
+/// Output of a git command, or `None` outside a git checkout (e.g. a
+/// crates.io tarball) or without git installed.
+fn git(args: &[&str]) -> Option<String> {
+    let output = Command::new("git").args(args).output().ok()?;
+    output
+        .status
+        .success()
+        .then(|| String::from_utf8_lossy(&output.stdout).trim().to_string())
+}
@@ -3,13 +3,37 @@ import { spawn } from "child_process";
 const SOURCE = "openclaw";
 
 function emitSpan(eventType: string, payload: Record<string, unknown>): void {
-  const proc = spawn("pulse", ["emit", eventType], {
+  const proc = spawn("pulse", ["emit", eventType, "--source", SOURCE], {
     stdio: ["pipe", "ignore", "ignore"],
   });
   proc.stdin!.write(JSON.stringify({ ...payload, source: SOURCE }));
   proc.stdin!.end();
 }
 
+interface OpenClawUsage {
+  input?: number;
+  output?: number;
+  reasoning?: number;
+  cacheRead?: number;
+  cacheWrite?: number;
+  cost?: { total?: number } | number;
+}
+
+// Maps OpenClaw's per-turn usage onto the `assistant_message` payload.
+function assistantUsage(model: string | undefined, usage: OpenClawUsage) {
+  const cost = typeof usage.cost === "number" ? usage.cost : usage.cost?.total;
+  return {
+    model,
+    tokens: {
+      input: usage.input ?? 0,
+      output: usage.output ?? 0,
+      reasoning: usage.reasoning ?? 0,
+      cache: { read: usage.cacheRead ?? 0, write: usage.cacheWrite ?? 0 },
+    },
+    cost: cost ?? 0,
+  };
+}
+
 interface OpenClawEvent {
   type: string;
   action: string;
@@ -35,6 +59,8 @@ interface OpenClawEvent {
     // message:sent context
     to?: string;
     success?: boolean;
+    model?: string;
+    usage?: OpenClawUsage;
     [key: string]: unknown;
   };
 }
@@ -67,11 +93,19 @@ export default async function handler(event: OpenClawEvent): Promise<void> {
         prompt: event.context?.content,
       });
       break;
-    case "message:sent":
+    case "message:sent": {
       emitSpan("notification", {
         ...base,
         message: event.context?.content,
       });
+      const usage = event.context?.usage;
+      if (usage) {
+        const model =
+          event.context?.model ??
+          event.context?.cfg?.agents?.defaults?.model?.primary;
+        emitSpan("assistant_message", { ...base, ...assistantUsage(model, usage) });
+      }
       break;
+    }
   }
 }
@@ -3,7 +3,7 @@ import type { Plugin } from "@opencode-ai/plugin";
 const SOURCE = "opencode";
 
 function emitSpan(eventType: string, payload: Record<string, unknown>) {
-  const proc = Bun.spawn(["pulse", "emit", eventType], {
+  const proc = Bun.spawn(["pulse", "emit", eventType, "--source", SOURCE], {
     stdin: "pipe",
     stdout: "ignore",
     stderr: "ignore",
@@ -12,10 +12,30 @@ function emitSpan(eventType: string, payload: Record<string, unknown>) {
   proc.stdin.end();
 }
 
+// `message.updated` fires while a reply streams in; usage is final once the
+// message has a completion time.
+function assistantUsage(info: any) {
+  const tokens = info.tokens ?? {};
+  return {
+    model: info.modelID,
+    tokens: {
+      input: tokens.input ?? 0,
+      output: tokens.output ?? 0,
+      reasoning: tokens.reasoning ?? 0,
+      cache: {
+        read: tokens.cache?.read ?? 0,
+        write: tokens.cache?.write ?? 0,
+      },
+    },
+    cost: info.cost ?? 0,
+  };
+}
+
 export const PulsePlugin: Plugin = async (ctx) => {
   const cwd = ctx.directory;
 
   const base = (sessionID: string) => ({ session_id: sessionID, cwd });
+  const reported = new Set<string>();
 
   return {
     event: async ({ event }) => {
@@ -49,12 +69,15 @@ export const PulsePlugin: Plugin = async (ctx) => {
                   ? info.content
                   : JSON.stringify(info.content),
             });
-          } else if (info?.role === "assistant") {
+          } else if (
+            info?.role === "assistant" &&
+            info.time?.completed &&
+            !reported.has(info.id)
+          ) {
+            reported.add(info.id);
             emitSpan("assistant_message", {
               ...base(info.sessionID),
-              model: info.modelID,
-              tokens: info.tokens,
-              cost: info.cost,
+              ...assistantUsage(info),
             });
           }
           break;
@@ -0,0 +1,318 @@
+//! Circuit breaker for `pulse emit`. While the trace service is down every
+//! hook would otherwise wait out the emit timeout; after a few failures in a
+//! row the breaker opens and emits queue their spans offline right away
+//! until a cooldown passes. The state lives in a file because every emit is
+//! its own process.
+
+use std::{
+    fs::{self, File, OpenOptions},
+    io::{Read, Seek, SeekFrom, Write},
+    path::PathBuf,
+    time::Duration,
+};
+
+use chrono::{DateTime, TimeDelta, Utc};
+use serde::{Deserialize, Serialize};
+use uuid::Uuid;
+
+use crate::{config::PulseConfig, error::Result, paths};
+
+/// Longest cooldown, however many probes in a row have failed.
+const MAX_COOLDOWN: Duration = Duration::from_secs(10 * 60);
+
+/// When to open the circuit and for how long.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct BreakerSettings {
+    /// Failures that open the circuit...
+    pub failures: u32,
+    /// ...when they all happen within this long of the first.
+    pub window: Duration,
+    /// Cooldown after the circuit first opens. It doubles each time the
+    /// probe sent after a cooldown fails too.
+    pub cooldown: Duration,
+    /// How long other emits keep skipping while one probes, normally the
+    /// emit timeout.
+    pub probe_timeout: Duration,
+}
+
+impl BreakerSettings {
+    pub fn from_config(config: &PulseConfig) -> Self {
+        Self {
+            failures: config.breaker_failures(),
+            window: config.breaker_window(),
+            cooldown: config.breaker_cooldown(),
+            probe_timeout: config.emit_timeout(),
+        }
+    }
+
+    /// The cooldown after `trips` openings in a row, doubled per trip up to
+    /// `MAX_COOLDOWN` and then spread by ±20% with `jitter` (0 to 1), so
+    /// hooks across machines do not all probe at the same moment.
+    fn cooldown(&self, trips: u32, jitter: f64) -> Duration {
+        let doublings = trips.saturating_sub(1).min(16);
+        let base = self
+            .cooldown
+            .saturating_mul(1 << doublings)
+            .min(MAX_COOLDOWN);
+        base.mul_f64(0.8 + 0.4 * jitter.clamp(0.0, 1.0))
+    }
+}
+
+/// What an emit should do with its spans.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Decision {
+    /// The circuit is closed: send as usual.
+    Send,
+    /// The cooldown is over and this emit tests the service for everyone.
+    Probe,
+    /// The circuit is open: queue the spans without trying.
+    Skip,
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct BreakerState {
+    /// Consecutive failures while closed.
+    #[serde(default)]
+    failures: u32,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    first_failure_at: Option<DateTime<Utc>>,
+    /// Times the circuit has opened without a success in between.
+    #[serde(default)]
+    trips: u32,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    open_until: Option<DateTime<Utc>>,
+}
+
+impl BreakerState {
+    pub fn is_open(&self) -> bool {
+        self.open_until.is_some()
+    }
+
+    pub fn open_until(&self) -> Option<DateTime<Utc>> {
+        self.open_until
+    }
+
+    /// Closed sends; open skips until the cooldown ends. The first emit
+    /// after that probes, and pushes `open_until` out by the probe timeout
+    /// so the others keep skipping while it runs.
+    pub fn decide(&mut self, settings: &BreakerSettings, now: DateTime<Utc>) -> Decision {
+        match self.open_until {
+            None => Decision::Send,
+            Some(until) if now < until => Decision::Skip,
+            Some(_) => {
+                self.open_until = Some(now + delta(settings.probe_timeout));
+                Decision::Probe
+            }
+        }
+    }
+
+    pub fn record_success(&mut self) {
+        *self = Self::default();
+    }
+
+    /// Counts a failure that waiting could fix. Opens the circuit on the
+    /// `settings.failures`th within the window, and reopens it for longer
+    /// when a probe fails.
+    pub fn record_failure(&mut self, settings: &BreakerSettings, now: DateTime<Utc>, jitter: f64) {
+        if self.trips > 0 {
+            self.open(settings, now, jitter);
+            return;
+        }
+        let in_window = self
+            .first_failure_at
+            .is_some_and(|first| now - first <= delta(settings.window));
+        if !in_window {
+            self.failures = 0;
+            self.first_failure_at = Some(now);
+        }
+        self.failures += 1;
+        if self.failures >= settings.failures {
+            self.open(settings, now, jitter);
+        }
+    }
+
+    fn open(&mut self, settings: &BreakerSettings, now: DateTime<Utc>, jitter: f64) {
+        self.trips += 1;
+        self.failures = 0;
+        self.first_failure_at = None;
+        self.open_until = Some(now + delta(settings.cooldown(self.trips, jitter)));
+    }
+}
+
+fn delta(duration: Duration) -> TimeDelta {
+    TimeDelta::from_std(duration).unwrap_or(TimeDelta::MAX)
+}
+
+/// A random jitter between 0 and 1.
+fn jitter() -> f64 {
+    Uuid::new_v4().as_u128() as u16 as f64 / f64::from(u16::MAX)
+}
+
+/// The breaker state file (`breaker.json`), updated under an exclusive lock
+/// so concurrent emits see each other's results.
+#[derive(Debug, Clone)]
+pub struct CircuitBreaker {
+    path: PathBuf,
+    settings: BreakerSettings,
+}
+
+impl CircuitBreaker {
+    /// The breaker for `config`, or `None` when `circuit_breaker = false`.
+    pub fn new(config: &PulseConfig) -> Result<Option<Self>> {
+        if !config.circuit_breaker() {
+            return Ok(None);
+        }
+        Ok(Some(Self::at(
+            paths::breaker_state()?,
+            BreakerSettings::from_config(config),
+        )))
+    }
+
+    pub fn at(path: PathBuf, settings: BreakerSettings) -> Self {
+        Self { path, settings }
+    }
+
+    pub fn decide(&self) -> Result<Decision> {
+        let settings = self.settings;
+        self.update(|state| state.decide(&settings, Utc::now()))
+    }
+
+    pub fn record_success(&self) -> Result<()> {
+        self.update(BreakerState::record_success)
+    }
+
+    pub fn record_failure(&self) -> Result<()> {
+        let settings = self.settings;
+        self.update(|state| state.record_failure(&settings, Utc::now(), jitter()))
+    }
+
+    fn update<T>(&self, apply: impl FnOnce(&mut BreakerState) -> T) -> Result<T> {
+        if let Some(parent) = self.path.parent() {
+            fs::create_dir_all(parent)?;
+        }
+        let mut file = OpenOptions::new()
+            .read(true)
+            .write(true)
+            .create(true)
+            .truncate(false)
+            .open(&self.path)?;
+        file.lock()?;
+        let mut state = read_state(&mut file)?;
+        let before = state.clone();
+        let result = apply(&mut state);
+        if state != before {
+            file.set_len(0)?;
+            file.seek(SeekFrom::Start(0))?;
+            file.write_all(&serde_json::to_vec(&state)?)?;
+        }
+        Ok(result)
+    }
+}
+
+/// A missing, empty, or unreadable file counts as a closed circuit.
+fn read_state(file: &mut File) -> Result<BreakerState> {
+    let mut contents = String::new();
+    file.read_to_string(&mut contents)?;
+    Ok(serde_json::from_str(&contents).unwrap_or_default())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::TempDir;
+
+    fn settings() -> BreakerSettings {
+        BreakerSettings {
+            failures: 3,
+            window: Duration::from_secs(60),
+            cooldown: Duration::from_secs(30),
+            probe_timeout: Duration::from_secs(2),
+        }
+    }
+
+    fn secs(n: i64) -> TimeDelta {
+        TimeDelta::seconds(n)
+    }
+
+    #[test]
+    fn test_opens_after_failures_within_window() {
+        let settings = settings();
+        let now = Utc::now();
+        let mut state = BreakerState::default();
+
+        state.record_failure(&settings, now, 0.5);
+        state.record_failure(&settings, now + secs(10), 0.5);
+        assert_eq!(state.decide(&settings, now + secs(10)), Decision::Send);
+
+        state.record_failure(&settings, now + secs(20), 0.5);
+        assert!(state.is_open());
+        assert_eq!(state.open_until(), Some(now + secs(50)));
+        assert_eq!(state.decide(&settings, now + secs(49)), Decision::Skip);
+    }
+
+    #[test]
+    fn test_failures_outside_window_start_over() {
+        let settings = settings();
+        let now = Utc::now();
+        let mut state = BreakerState::default();
+
+        state.record_failure(&settings, now, 0.5);
+        state.record_failure(&settings, now + secs(30), 0.5);
+        state.record_failure(&settings, now + secs(90), 0.5);
+        assert!(!state.is_open());
+        state.record_failure(&settings, now + secs(100), 0.5);
+        state.record_failure(&settings, now + secs(110), 0.5);
+        assert!(state.is_open());
+    }
+
+    #[test]
+    fn test_probe_after_cooldown() {
+        let settings = settings();
+        let now = Utc::now();
+        let mut state = BreakerState::default();
+        for _ in 0..3 {
+            state.record_failure(&settings, now, 0.5);
+        }
+
+        // One emit probes; the rest skip while it runs.
+        let after = now + secs(31);
+        assert_eq!(state.decide(&settings, after), Decision::Probe);
+        assert_eq!(state.decide(&settings, after), Decision::Skip);
+
+        // A failed probe reopens for twice as long.
+        state.record_failure(&settings, after, 0.5);
+        assert_eq!(state.open_until(), Some(after + secs(60)));
+
+        assert_eq!(state.decide(&settings, after + secs(61)), Decision::Probe);
+        state.record_success();
+        assert_eq!(state, BreakerState::default());
+        assert_eq!(state.decide(&settings, after + secs(61)), Decision::Send);
+    }
+
+    #[test]
+    fn test_cooldown_is_capped_and_jittered() {
+        let settings = settings();
+        assert_eq!(settings.cooldown(1, 0.5), Duration::from_secs(30));
+        assert_eq!(settings.cooldown(2, 0.0), Duration::from_secs(48));
+        assert_eq!(settings.cooldown(2, 1.0), Duration::from_secs(72));
+        assert_eq!(settings.cooldown(50, 0.5), MAX_COOLDOWN);
+    }
+
+    #[test]
+    fn test_state_is_shared_through_the_file() {
+        let tmp = TempDir::new().unwrap();
+        let path = tmp.path().join(paths::BREAKER_FILE);
+        let first = CircuitBreaker::at(path.clone(), settings());
+        let second = CircuitBreaker::at(path.clone(), settings());
+
+        for _ in 0..3 {
+            first.record_failure().unwrap();
+        }
+        assert_eq!(second.decide().unwrap(), Decision::Skip);
+        second.record_success().unwrap();
+        assert_eq!(first.decide().unwrap(), Decision::Send);
+
+        fs::write(&path, "not json").unwrap();
+        assert_eq!(first.decide().unwrap(), Decision::Send);
+    }
+}
@@ -0,0 +1,443 @@
+use clap::{Args, Subcommand};
+use reqwest::Url;
+
+use crate::{
+    commands::status::mask_key,
+    config::{ConfigStore, ExportTarget, PulseConfig, normalize_project_id, validate_project_id},
+    error::{PulseError, Result},
+    http::{
+        AuthHeader, check_client_identity, load_ca_certs, parse_proxy, validate_endpoint_path,
+        validate_user_agent_suffix,
+    },
+};
+
+const KEYS: &[&str] = &[
+    "api_url",
+    "api_key",
+    "project_id",
+    "api_key_file",
+    "api_key_command",
+    "local_email",
+    "local_password",
+    "pulse_bin",
+    "dashboard_url",
+    "sample_rate",
+    "buffer",
+    "buffer_max_spans",
+    "async_emit",
+    "flush_batch_size",
+    "flush_concurrency",
+    "heartbeat",
+    "heartbeat_interval_secs",
+    "max_field_bytes",
+    "capture_git",
+    "include_raw",
+    "source_autodetect",
+    "capture_host",
+    "capture_emit_errors",
+    "record_spans",
+    "spans_path",
+    "health_path",
+    "auth_header",
+    "user_agent_suffix",
+    "compress",
+    "http_timeout_ms",
+    "emit_timeout_ms",
+    "stdin_timeout_ms",
+    "circuit_breaker",
+    "breaker_failures",
+    "breaker_window_secs",
+    "breaker_cooldown_secs",
+    "proxy_url",
+    "ca_cert_path",
+    "client_cert_path",
+    "client_key_path",
+    "danger_accept_invalid_certs",
+    "export",
+    "otlp.endpoint",
+    "redaction.enabled",
+];
+const SECRET_KEYS: &[&str] = &["api_key", "local_password"];
+
+#[derive(Debug, Args)]
+pub struct ConfigArgs {
+    #[command(subcommand)]
+    pub command: ConfigCommand,
+}
+
+#[derive(Debug, Subcommand)]
+pub enum ConfigCommand {
+    /// Print one config value
+    Get {
+        key: String,
+        /// Print secrets such as the API key in full
+        #[arg(long)]
+        reveal: bool,
+    },
+    /// Validate and save one config value
+    Set { key: String, value: String },
+}
+
+pub fn run_config(args: ConfigArgs) -> Result<()> {
+    match args.command {
+        ConfigCommand::Get { key, reveal } => {
+            let config = ConfigStore::load()?;
+            let value = get_value(&config, &key)?;
+            if SECRET_KEYS.contains(&key.as_str()) && !reveal && !value.is_empty() {
+                println!("{}", mask_key(&value));
+            } else {
+                println!("{value}");
+            }
+            Ok(())
+        }
+        ConfigCommand::Set { key, value } => {
+            // Edit the file as written so env overrides are not persisted.
+            let mut config = match ConfigStore::load_file() {
+                Ok(config) => config,
+                Err(PulseError::ConfigMissing) => PulseConfig::default(),
+                Err(err) => return Err(err),
+            };
+            set_value(&mut config, &key, &value)?;
+            ConfigStore::save(&config.sanitized())?;
+            println!("Set {key}");
+            Ok(())
+        }
+    }
+}
+
+/// Effective value of `key`, with defaults applied where the config has one.
+fn get_value(config: &PulseConfig, key: &str) -> Result<String> {
+    let value = match key {
+        "api_url" => config.api_url.clone(),
+        "api_key" => config.api_key.clone(),
+        "project_id" => config.project_id.clone(),
+        "api_key_file" => config.api_key_file.clone().unwrap_or_default(),
+        "api_key_command" => config.api_key_command.clone().unwrap_or_default(),
+        "local_email" => config.local_email.clone().unwrap_or_default(),
+        "local_password" => config.local_password.clone().unwrap_or_default(),
+        "pulse_bin" => config.pulse_bin.clone().unwrap_or_default(),
+        "dashboard_url" => config.dashboard_url.clone().unwrap_or_default(),
+        "sample_rate" => config.sample_rate.unwrap_or(1.0).to_string(),
+        "buffer" => config.buffer_enabled().to_string(),
+        "buffer_max_spans" => config.buffer_max_spans().to_string(),
+        "async_emit" => config.async_emit().to_string(),
+        "flush_batch_size" => config.flush_batch_size().to_string(),
+        "flush_concurrency" => config.flush_concurrency().to_string(),
+        "heartbeat" => config.heartbeat().to_string(),
+        "heartbeat_interval_secs" => config.heartbeat_interval().as_secs().to_string(),
+        "max_field_bytes" => config.max_field_bytes().to_string(),
+        "capture_git" => config.capture_git().to_string(),
+        "include_raw" => config.include_raw().to_string(),
+        "source_autodetect" => config.source_autodetect().to_string(),
+        "capture_host" => config.capture_host().to_string(),
+        "capture_emit_errors" => config.capture_emit_errors().to_string(),
+        "record_spans" => config.record_spans().to_string(),
+        "spans_path" => config.spans_path().to_string(),
+        "health_path" => config.health_path().to_string(),
+        "auth_header" => config.auth_header().to_string(),
+        "user_agent_suffix" => config.user_agent_suffix.clone().unwrap_or_default(),
+        "compress" => config.compress().to_string(),
+        "http_timeout_ms" => config.http_timeout().as_millis().to_string(),
+        "emit_timeout_ms" => config.emit_timeout().as_millis().to_string(),
+        "stdin_timeout_ms" => config.stdin_timeout().as_millis().to_string(),
+        "circuit_breaker" => config.circuit_breaker().to_string(),
+        "breaker_failures" => config.breaker_failures().to_string(),
+        "breaker_window_secs" => config.breaker_window().as_secs().to_string(),
+        "breaker_cooldown_secs" => config.breaker_cooldown().as_secs().to_string(),
+        "proxy_url" => config.proxy_url.clone().unwrap_or_default(),
+        "ca_cert_path" => config.ca_cert_path.clone().unwrap_or_default(),
+        "client_cert_path" => config.client_cert_path.clone().unwrap_or_default(),
+        "client_key_path" => config.client_key_path.clone().unwrap_or_default(),
+        "danger_accept_invalid_certs" => config.accept_invalid_certs().to_string(),
+        "export" => config.export().as_str().to_string(),
+        "otlp.endpoint" => config.otlp.endpoint().to_string(),
+        "redaction.enabled" => config.redaction.is_enabled().to_string(),
+        _ => return Err(unknown_key(key)),
+    };
+    Ok(value)
+}
+
+pub(crate) fn set_value(config: &mut PulseConfig, key: &str, value: &str) -> Result<()> {
+    let value = value.trim();
+    match key {
+        "api_url" => config.api_url = parse_http_url(key, value)?,
+        // Each API key source replaces the others; a config may set one.
+        "api_key" => {
+            config.api_key = required(key, value)?;
+            config.api_key_file = None;
+            config.api_key_command = None;
+        }
+        "api_key_file" => {
+            config.api_key_file = Some(required(key, value)?);
+            config.api_key = String::new();
+            config.api_key_command = None;
+        }
+        "api_key_command" => {
+            config.api_key_command = Some(required(key, value)?);
+            config.api_key = String::new();
+            config.api_key_file = None;
+        }
+        "dashboard_url" => config.dashboard_url = Some(parse_http_url(key, value)?),
+        "project_id" => {
+            let id = normalize_project_id(value);
+            validate_project_id(&id).map_err(|err| invalid(key, err))?;
+            config.set_project_id(id);
+        }
+        "local_email" => config.local_email = Some(value.to_string()),
+        "local_password" => config.local_password = Some(value.to_string()),
+        "pulse_bin" => config.pulse_bin = Some(value.to_string()),
+        "sample_rate" => {
+            let rate: f64 = value.parse().map_err(|err| invalid(key, err))?;
+            if !(0.0..=1.0).contains(&rate) {
+                return Err(invalid(key, "must be between 0.0 and 1.0"));
+            }
+            config.sample_rate = Some(rate);
+        }
+        "buffer" => config.buffer = Some(parse_bool(key, value)?),
+        "buffer_max_spans" => config.buffer_max_spans = Some(parse_positive(key, value)?),
+        "async_emit" => config.async_emit = Some(parse_bool(key, value)?),
+        "flush_batch_size" => config.flush_batch_size = Some(parse_positive(key, value)?),
+        "flush_concurrency" => config.flush_concurrency = Some(parse_positive(key, value)?),
+        "heartbeat" => config.heartbeat = Some(parse_bool(key, value)?),
+        "heartbeat_interval_secs" => {
+            config.heartbeat_interval_secs = Some(parse_positive(key, value)? as u64)
+        }
+        "max_field_bytes" => config.max_field_bytes = Some(parse_positive(key, value)?),
+        "capture_git" => config.capture_git = Some(parse_bool(key, value)?),
+        "include_raw" => config.include_raw = Some(parse_bool(key, value)?),
+        "source_autodetect" => config.source_autodetect = Some(parse_bool(key, value)?),
+        "capture_host" => config.capture_host = Some(parse_bool(key, value)?),
+        "capture_emit_errors" => config.capture_emit_errors = Some(parse_bool(key, value)?),
+        "record_spans" => config.record_spans = Some(parse_bool(key, value)?),
+        "spans_path" => config.spans_path = Some(parse_path(key, value)?),
+        "health_path" => config.health_path = Some(parse_path(key, value)?),
+        "auth_header" => {
+            AuthHeader::parse(value).map_err(|err| invalid(key, err))?;
+            config.auth_header = Some(value.to_string());
+        }
+        "user_agent_suffix" => {
+            validate_user_agent_suffix(value).map_err(|err| invalid(key, err))?;
+            config.user_agent_suffix = Some(value.trim().to_string()).filter(|v| !v.is_empty());
+        }
+        "compress" => config.compress = Some(parse_bool(key, value)?),
+        "http_timeout_ms" => config.http_timeout_ms = Some(parse_positive(key, value)? as u64),
+        "emit_timeout_ms" => config.emit_timeout_ms = Some(parse_positive(key, value)? as u64),
+        "stdin_timeout_ms" => config.stdin_timeout_ms = Some(parse_positive(key, value)? as u64),
+        "circuit_breaker" => config.circuit_breaker = Some(parse_bool(key, value)?),
+        "breaker_failures" => config.breaker_failures = Some(parse_positive(key, value)? as u32),
+        "breaker_window_secs" => {
+            config.breaker_window_secs = Some(parse_positive(key, value)? as u64)
+        }
+        "breaker_cooldown_secs" => {
+            config.breaker_cooldown_secs = Some(parse_positive(key, value)? as u64)
+        }
+        "proxy_url" => {
+            parse_proxy(value).map_err(|err| invalid(key, err))?;
+            config.proxy_url = Some(value.to_string());
+        }
+        "ca_cert_path" => {
+            load_ca_certs(value).map_err(|err| invalid(key, err))?;
+            config.ca_cert_path = Some(value.to_string());
+        }
+        // Either may be set first, so the pair is only checked once the key
+        // is set too.
+        "client_cert_path" | "client_key_path" => {
+            std::fs::File::open(value).map_err(|err| invalid(key, err))?;
+            let mut paired = config.clone();
+            match key {
+                "client_cert_path" => paired.client_cert_path = Some(value.to_string()),
+                _ => paired.client_key_path = Some(value.to_string()),
+            }
+            if paired.client_key_path.is_some() {
+                check_client_identity(&paired).map_err(|err| invalid(key, err))?;
+            }
+            *config = paired;
+        }
+        "danger_accept_invalid_certs" => {
+            config.danger_accept_invalid_certs = Some(parse_bool(key, value)?)
+        }
+        "export" => {
+            let target = ExportTarget::ALL
+                .into_iter()
+                .find(|target| target.as_str() == value)
+                .ok_or_else(|| invalid(key, "must be `pulse`, `otlp`, or `both`"))?;
+            config.export = Some(target);
+        }
+        "otlp.endpoint" => config.otlp.endpoint = Some(parse_http_url(key, value)?),
+        "redaction.enabled" => config.redaction.enabled = Some(parse_bool(key, value)?),
+        _ => return Err(unknown_key(key)),
+    }
+    Ok(())
+}
+
+fn required(key: &str, value: &str) -> Result<String> {
+    if value.is_empty() {
+        return Err(invalid(key, "must not be empty"));
+    }
+    Ok(value.to_string())
+}
+
+fn parse_bool(key: &str, value: &str) -> Result<bool> {
+    value
+        .parse()
+        .map_err(|_| invalid(key, "must be `true` or `false`"))
+}
+
+fn parse_positive(key: &str, value: &str) -> Result<usize> {
+    match value.parse::<usize>() {
+        Ok(number) if number > 0 => Ok(number),
+        _ => Err(invalid(key, "must be a positive integer")),
+    }
+}
+
+fn parse_http_url(key: &str, value: &str) -> Result<String> {
+    let url = Url::parse(value).map_err(|err| invalid(key, err))?;
+    if !matches!(url.scheme(), "http" | "https") {
+        return Err(invalid(key, "must be an http or https URL"));
+    }
+    Ok(value.to_string())
+}
+
+fn parse_path(key: &str, value: &str) -> Result<String> {
+    validate_endpoint_path(value)
+        .map(str::to_string)
+        .map_err(|err| invalid(key, err))
+}
+
+fn invalid(key: &str, reason: impl std::fmt::Display) -> PulseError {
+    PulseError::message(format!("invalid value for `{key}`: {reason}"))
+}
+
+fn unknown_key(key: &str) -> PulseError {
+    PulseError::message(format!(
+        "unknown config key `{key}`; valid keys: {}",
+        KEYS.join(", ")
+    ))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_every_key_round_trips() {
+        let mut config = PulseConfig::default();
+        let samples = [
+            ("api_url", "https://pulse.example.com"),
+            ("api_key", "key_123"),
+            ("project_id", "proj_1"),
+            ("api_key_file", "/run/secrets/pulse_key"),
+            ("api_key_command", "op read op://dev/pulse/key"),
+            ("local_email", "me@example.com"),
+            ("local_password", "hunter2"),
+            ("pulse_bin", "/usr/local/bin/pulse"),
+            ("dashboard_url", "https://trace.example.com/dashboard"),
+            ("sample_rate", "0.25"),
+            ("buffer", "true"),
+            ("buffer_max_spans", "20"),
+            ("async_emit", "true"),
+            ("flush_batch_size", "50"),
+            ("flush_concurrency", "8"),
+            ("heartbeat", "true"),
+            ("heartbeat_interval_secs", "60"),
+            ("max_field_bytes", "1024"),
+            ("capture_git", "false"),
+            ("include_raw", "false"),
+            ("source_autodetect", "false"),
+            ("capture_host", "false"),
+            ("capture_emit_errors", "true"),
+            ("record_spans", "true"),
+            ("spans_path", "/pulse/v1/spans/async"),
+            ("health_path", "/pulse/health"),
+            ("auth_header", "x-api-key"),
+            ("user_agent_suffix", "team A/ci"),
+            ("compress", "true"),
+            ("http_timeout_ms", "10000"),
+            ("emit_timeout_ms", "500"),
+            ("stdin_timeout_ms", "250"),
+            ("circuit_breaker", "false"),
+            ("breaker_failures", "5"),
+            ("breaker_window_secs", "120"),
+            ("breaker_cooldown_secs", "10"),
+            ("proxy_url", "http://proxy.corp:3128"),
+            ("danger_accept_invalid_certs", "true"),
+            ("export", "both"),
+            ("otlp.endpoint", "http://collector:4318/v1/traces"),
+            ("redaction.enabled", "false"),
+        ];
+        // The certificate and key paths need real files; see
+        // `test_ca_cert_path_is_validated` and `test_client_cert_paths_must_exist_and_match`.
+        assert_eq!(samples.len() + 3, KEYS.len());
+        for (key, value) in samples {
+            set_value(&mut config, key, value).unwrap();
+            assert_eq!(get_value(&config, key).unwrap(), value, "{key}");
+        }
+    }
+
+    #[test]
+    fn test_invalid_values_are_rejected() {
+        let mut config = PulseConfig::default();
+        assert!(set_value(&mut config, "api_url", "not a url").is_err());
+        assert!(set_value(&mut config, "api_url", "ftp://example.com").is_err());
+        assert!(set_value(&mut config, "api_key", "  ").is_err());
+        assert!(set_value(&mut config, "sample_rate", "1.5").is_err());
+        assert!(set_value(&mut config, "buffer", "yes").is_err());
+        assert!(set_value(&mut config, "export", "jaeger").is_err());
+        assert!(set_value(&mut config, "buffer_max_spans", "0").is_err());
+        assert!(set_value(&mut config, "emit_timeout_ms", "0").is_err());
+        assert!(set_value(&mut config, "spans_path", "https://evil.example.com/v1").is_err());
+        assert!(set_value(&mut config, "spans_path", "//evil.example.com/v1").is_err());
+        assert!(set_value(&mut config, "auth_header", "X API Key").is_err());
+        assert!(set_value(&mut config, "user_agent_suffix", "team (A)").is_err());
+        assert!(set_value(&mut config, "user_agent_suffix", "team\nA").is_err());
+        assert_eq!(config.api_url, "");
+    }
+
+    #[test]
+    fn test_ca_cert_path_is_validated() {
+        let tmp = tempfile::TempDir::new().unwrap();
+        let path = tmp.path().join("ca.pem");
+        std::fs::write(&path, "not a certificate").unwrap();
+
+        let mut config = PulseConfig::default();
+        let err = set_value(&mut config, "ca_cert_path", path.to_str().unwrap()).unwrap_err();
+        assert!(err.to_string().contains("no PEM certificates"), "{err}");
+        assert!(set_value(&mut config, "ca_cert_path", "/nonexistent/ca.pem").is_err());
+        assert_eq!(config.ca_cert_path, None);
+    }
+
+    #[test]
+    fn test_client_cert_paths_must_exist_and_match() {
+        let cert = concat!(env!("CARGO_MANIFEST_DIR"), "/tests/fixtures/client.pem");
+        let key = concat!(env!("CARGO_MANIFEST_DIR"), "/tests/fixtures/client.key");
+
+        let mut config = PulseConfig::default();
+        set_value(&mut config, "client_cert_path", cert).unwrap();
+        set_value(&mut config, "client_key_path", key).unwrap();
+        assert_eq!(get_value(&config, "client_key_path").unwrap(), key);
+        assert!(set_value(&mut config, "client_cert_path", "/nonexistent/client.pem").is_err());
+        assert!(set_value(&mut config, "client_key_path", "/nonexistent/client.key").is_err());
+        assert_eq!(config.client_cert_path.as_deref(), Some(cert));
+
+        let other_key = concat!(env!("CARGO_MANIFEST_DIR"), "/tests/fixtures/other.key");
+        let err = set_value(&mut config, "client_key_path", other_key).unwrap_err();
+        assert!(err.to_string().contains("do not match"), "{err}");
+        assert_eq!(config.client_key_path.as_deref(), Some(key));
+    }
+
+    #[test]
+    fn test_unknown_key_lists_valid_keys() {
+        let mut config = PulseConfig::default();
+        let err = set_value(&mut config, "api_token", "x").unwrap_err();
+        assert!(err.to_string().contains("api_url, api_key, project_id"));
+        assert!(get_value(&config, "api_token").is_err());
+    }
+
+    #[test]
+    fn test_defaults_are_reported() {
+        let config = PulseConfig::default();
+        assert_eq!(get_value(&config, "sample_rate").unwrap(), "1");
+        assert_eq!(get_value(&config, "capture_git").unwrap(), "true");
+        assert_eq!(get_value(&config, "pulse_bin").unwrap(), "");
+    }
+}
@@ -1,22 +1,126 @@
-use crate::{commands::registered_hooks, config::ConfigStore, error::Result, hooks::HookStatus};
+use std::{
+    env,
+    io::{self, IsTerminal, Write},
+    path::PathBuf,
+};
 
-pub fn run_connect() -> Result<()> {
+use clap::Args;
+use regex::Regex;
+
+use crate::{
+    commands::{ClaudeTargets, HookOptions, find_executable, print_json, registered_hooks_with},
+    config::ConfigStore,
+    error::{PulseError, Result},
+    hooks::{ConnectOptions, HookStatus, ProjectSettings, ToolHook},
+    info,
+    output::{Tone, bullet, paint},
+    verbose,
+};
+
+#[derive(Debug, Default, Args)]
+pub struct ConnectArgs {
+    /// Write the absolute path of this `pulse` binary into hook commands,
+    /// and keep doing so on later connects
+    #[arg(long, visible_alias = "self-exe")]
+    pub absolute_path: bool,
+    /// Do not back up settings files before editing them
+    #[arg(long)]
+    pub no_backup: bool,
+    /// Write Claude Code settings backups to this directory instead of next to the file
+    #[arg(long, value_name = "DIR", conflicts_with = "no_backup")]
+    pub backup_dir: Option<PathBuf>,
+    /// Show what would change without writing any files
+    #[arg(long)]
+    pub dry_run: bool,
+    /// Install Claude Code hooks into ./.claude/settings.json (or
+    /// settings.local.json with `--project local`) instead of the home settings
+    #[arg(long, value_enum, num_args = 0..=1, default_missing_value = "shared")]
+    pub project: Option<ProjectSettings>,
+    /// Only these tools, comma-separated (claude_code, opencode, openclaw, cursor, gemini, codex)
+    #[arg(long, value_delimiter = ',')]
+    pub tools: Vec<String>,
+    /// Config directory of the one tool given with `--tools` (opencode or openclaw)
+    #[arg(long)]
+    pub config_dir: Option<PathBuf>,
+    /// Only trace Claude Code tools whose name matches this regex (e.g. "Bash|Edit")
+    #[arg(long, value_name = "PATTERN", value_parser = parse_matcher)]
+    pub matcher: Option<String>,
+    /// Remove duplicate and outdated pulse entries from Claude Code settings
+    #[arg(long)]
+    pub repair: bool,
+    /// Connect every detected tool without asking which ones
+    #[arg(long, short = 'y')]
+    pub yes: bool,
+}
+
+pub fn run_connect(args: ConnectArgs, json: bool) -> Result<()> {
     // Ensure configuration exists before wiring hooks.
-    ConfigStore::load()?;
+    let mut config = ConfigStore::load()?;
+    verbose!("Config: {}", ConfigStore::config_path()?.display());
 
-    println!("Detecting supported tools...");
-    let hooks = registered_hooks()?;
-    let mut any_connected = false;
+    if args.dry_run && !json {
+        println!("Dry run: no files will be changed.");
+    }
 
-    for hook in hooks {
-        let status = hook.connect()?;
-        print_connect_summary(&status);
-        if status.detected && status.connected {
-            any_connected = true;
+    if args.absolute_path {
+        let exe = env::current_exe().map_err(|err| {
+            PulseError::message(format!("could not resolve the pulse binary path: {err}"))
+        })?;
+        config.pulse_bin = Some(exe.display().to_string());
+        if !args.dry_run {
+            // Save to the file as written, without env overrides.
+            let mut stored = ConfigStore::load_file().unwrap_or_default();
+            stored.pulse_bin = config.pulse_bin.clone();
+            ConfigStore::save(&stored)?;
+        }
+        if !json {
+            let verb = if args.dry_run { "Would pin" } else { "Pinning" };
+            info!("{verb} hook commands to {}", exe.display());
         }
+    } else if config.pulse_bin.is_none() && !json {
+        warn_if_not_on_path();
     }
 
-    if any_connected {
+    if !json {
+        info!("Detecting supported tools...");
+    }
+    let hooks = registered_hooks_with(HookOptions {
+        backup: !args.no_backup,
+        backup_dir: args.backup_dir.clone(),
+        pulse_bin: config.pulse_bin.clone(),
+        claude: match args.project {
+            Some(settings) => ClaudeTargets::Project(settings),
+            None => ClaudeTargets::Home,
+        },
+        tools: args.tools.clone(),
+        config_dir: args.config_dir.clone(),
+        matcher: args.matcher.clone(),
+    })?;
+    // `--tools` already says which tools to connect.
+    let hooks = if args.yes || json || !args.tools.is_empty() {
+        hooks
+    } else {
+        pick_hooks(hooks)?
+    };
+    let options = ConnectOptions {
+        dry_run: args.dry_run,
+        repair: args.repair,
+    };
+    let statuses = connect_all(&hooks, &options);
+
+    if json {
+        return print_json(&statuses);
+    }
+
+    let mut any_detected = false;
+    for status in &statuses {
+        print_connect_summary(status, args.dry_run);
+        if status.detected {
+            any_detected = true;
+        }
+    }
+
+    if any_detected {
         Ok(())
     } else {
         println!(
@@ -26,7 +130,117 @@ pub fn run_connect() -> Result<()> {
     }
 }
 
-fn print_connect_summary(status: &HookStatus) {
+/// Connects each tool in turn. A tool that fails, say over a corrupt
+/// settings file, gets a status carrying the error and the rest still
+/// connect.
+fn connect_all(hooks: &[Box<dyn ToolHook>], options: &ConnectOptions) -> Vec<HookStatus> {
+    hooks
+        .iter()
+        .map(|hook| {
+            hook.connect_opts(options)
+                .unwrap_or_else(|err| HookStatus::failed(hook.tool_name(), &err))
+        })
+        .collect()
+}
+
+/// Claude Code treats the matcher as a regex, so reject one that would
+/// never match instead of writing it.
+fn parse_matcher(value: &str) -> std::result::Result<String, String> {
+    Regex::new(value)
+        .map(|_| value.to_string())
+        .map_err(|err| format!("not a valid regex: {err}"))
+}
+
+/// Asks which detected tools to connect when there is more than one and
+/// someone is at the terminal to answer. Otherwise connects them all, so
+/// scripts and CI never block on the prompt.
+fn pick_hooks(hooks: Vec<Box<dyn ToolHook>>) -> Result<Vec<Box<dyn ToolHook>>> {
+    if !io::stdin().is_terminal() || !io::stdout().is_terminal() {
+        return Ok(hooks);
+    }
+    // A tool whose status cannot be read is offered too, so connecting it
+    // reports the error.
+    let detected: Vec<bool> = hooks
+        .iter()
+        .map(|hook| hook.status().map_or(true, |status| status.detected))
+        .collect();
+    let choices: Vec<usize> = (0..hooks.len()).filter(|&i| detected[i]).collect();
+    if choices.len() < 2 {
+        return Ok(hooks);
+    }
+
+    println!("Detected tools:");
+    for (number, &index) in choices.iter().enumerate() {
+        println!("  {}) {}", number + 1, hooks[index].tool_name());
+    }
+    let selected = loop {
+        print!("Connect which? (e.g. 1,3; Enter for all): ");
+        io::stdout().flush()?;
+        let mut input = String::new();
+        if io::stdin().read_line(&mut input)? == 0 {
+            return Err(PulseError::message("no tools selected"));
+        }
+        match parse_selection(&input, choices.len()) {
+            Ok(selected) => break selected,
+            Err(err) => println!("{err}"),
+        }
+    };
+
+    let keep: Vec<bool> = (0..hooks.len())
+        .map(|i| {
+            !detected[i]
+                || selected
+                    .as_ref()
+                    .is_none_or(|selected| selected.iter().any(|&number| choices[number - 1] == i))
+        })
+        .collect();
+    Ok(hooks
+        .into_iter()
+        .zip(keep)
+        .filter_map(|(hook, keep)| keep.then_some(hook))
+        .collect())
+}
+
+/// Parses a comma- or space-separated list of 1-based choices. `None`
+/// means all of them.
+fn parse_selection(input: &str, count: usize) -> std::result::Result<Option<Vec<usize>>, String> {
+    let input = input.trim();
+    if input.is_empty() || input.eq_ignore_ascii_case("all") {
+        return Ok(None);
+    }
+    let mut selected = Vec::new();
+    for part in input.split([',', ' ']).filter(|part| !part.is_empty()) {
+        match part.parse::<usize>() {
+            Ok(number) if (1..=count).contains(&number) => {
+                if !selected.contains(&number) {
+                    selected.push(number);
+                }
+            }
+            _ => {
+                return Err(format!(
+                    "Enter numbers from 1 to {count}, or press Enter for all."
+                ));
+            }
+        }
+    }
+    Ok(Some(selected))
+}
+
+fn warn_if_not_on_path() {
+    if find_executable("pulse").is_some() {
+        return;
+    }
+    let exe = env::current_exe()
+        .map(|path| path.display().to_string())
+        .unwrap_or_else(|_| "the pulse binary".to_string());
+    println!("Warning: `pulse` was not found on PATH.");
+    println!(
+        "  Hooks call `pulse emit` by name, so they will fail silently and no spans will be sent."
+    );
+    println!("  Add {exe} to PATH, or run `pulse connect --absolute-path` to pin hooks to it.");
+}
+
+fn print_connect_summary(status: &HookStatus, dry_run: bool) {
     if !status.detected {
         println!(
             "- {}: {}",
@@ -40,23 +254,47 @@ fn print_connect_summary(status: &HookStatus) {
     }
 
     if status.connected {
-        if status.modified {
+        if status.modified && dry_run {
             println!(
-                "- {}: hooks installed{}",
+                "{} {}: {}{}",
+                bullet(Tone::Good),
                 status.tool,
+                paint(Tone::Good, "would install hooks"),
+                format_path_suffix(status)
+            );
+        } else if status.modified {
+            println!(
+                "{} {}: {}{}",
+                bullet(Tone::Good),
+                status.tool,
+                paint(Tone::Good, "hooks installed"),
                 format_path_suffix(status)
             );
         } else {
             println!(
-                "- {}: already connected{}",
+                "{} {}: {}{}",
+                bullet(Tone::Good),
                 status.tool,
+                paint(Tone::Good, "already connected"),
                 format_path_suffix(status)
             );
         }
+    } else if status.total_hooks == 0 {
+        // Reading or writing the tool's settings failed.
+        println!(
+            "{} {}: {}: {}",
+            bullet(Tone::Bad),
+            status.tool,
+            paint(Tone::Bad, "unable to inject hooks"),
+            status.message.as_deref().unwrap_or("unknown error")
+        );
+        return;
     } else {
         println!(
-            "- {}: unable to inject hooks{}",
+            "{} {}: {}{}",
+            bullet(Tone::Bad),
             status.tool,
+            paint(Tone::Bad, "unable to inject hooks"),
             format_path_suffix(status)
         );
     }
@@ -78,6 +316,11 @@ fn print_hook_details(status: &HookStatus) {
     if status.installed_hooks < status.total_hooks {
         println!("    Run `pulse connect` to install missing hooks");
     }
+    if let Some(message) = &status.message {
+        for line in message.lines() {
+            println!("    {line}");
+        }
+    }
 }
 
 fn format_path_suffix(status: &HookStatus) -> String {
@@ -87,3 +330,54 @@ fn format_path_suffix(status: &HookStatus) -> String {
         .map(|path| format!(" ({})", path.display()))
         .unwrap_or_default()
 }
+
+#[cfg(test)]
+mod tests {
+    use std::fs;
+
+    use tempfile::TempDir;
+
+    use super::*;
+    use crate::hooks::{ClaudeCodeHook, GeminiHook};
+
+    #[test]
+    fn test_corrupt_settings_do_not_stop_other_tools() {
+        let tmp = TempDir::new().unwrap();
+        let settings_path = tmp.path().join(".claude").join("settings.json");
+        fs::create_dir_all(settings_path.parent().unwrap()).unwrap();
+        fs::write(&settings_path, "{\"theme\": \"dark\",").unwrap();
+        let gemini_dir = tmp.path().join(".gemini");
+        fs::create_dir_all(&gemini_dir).unwrap();
+        let hooks: Vec<Box<dyn ToolHook>> = vec![
+            Box::new(ClaudeCodeHook::with_settings_path(settings_path.clone())),
+            Box::new(GeminiHook::with_config_dir(gemini_dir)),
+        ];
+
+        let statuses = connect_all(&hooks, &ConnectOptions::default());
+        assert!(statuses[0].detected);
+        assert!(!statuses[0].connected);
+        let message = statuses[0].message.as_deref().unwrap();
+        assert!(
+            message.contains("settings.json is not valid JSON"),
+            "{message}"
+        );
+        assert!(message.contains("fix or delete it"), "{message}");
+        assert!(statuses[1].connected);
+        // The corrupt file is left for the user to fix.
+        assert_eq!(
+            fs::read_to_string(&settings_path).unwrap(),
+            "{\"theme\": \"dark\","
+        );
+    }
+
+    #[test]
+    fn test_parse_selection() {
+        assert_eq!(parse_selection("\n", 3), Ok(None));
+        assert_eq!(parse_selection("all", 3), Ok(None));
+        assert_eq!(parse_selection("1,3\n", 3), Ok(Some(vec![1, 3])));
+        assert_eq!(parse_selection(" 2, 2 1 ", 3), Ok(Some(vec![2, 1])));
+        assert!(parse_selection("0", 3).is_err());
+        assert!(parse_selection("4", 3).is_err());
+        assert!(parse_selection("cursor", 3).is_err());
+    }
+}
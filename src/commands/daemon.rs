@@ -0,0 +1,406 @@
+//! `pulse daemon`: an optional uploader that `pulse emit` hands spans to
+//! over a Unix socket. It keeps one pooled HTTP client and sends spans in
+//! batches, so hooks no longer open a TLS connection per span.
+//!
+//! The protocol is one JSON request per connection, written as a single
+//! line (`{"type":"spans","spans":[...]}` or `{"type":"stop"}`), answered
+//! with `ok` or `error: <reason>` once the daemon has taken it.
+
+use clap::Args;
+use serde::{Deserialize, Serialize};
+
+use crate::{error::Result, http::SpanPayload};
+
+#[cfg(unix)]
+use std::{fs, future::Future, mem, path::Path, time::Duration};
+
+#[cfg(unix)]
+use tokio::{
+    io::{AsyncBufReadExt, AsyncWriteExt, BufReader},
+    net::{UnixListener, UnixStream},
+    sync::mpsc::{self, UnboundedSender},
+    task::JoinSet,
+    time::{self, timeout},
+};
+
+#[cfg(unix)]
+use chrono::Utc;
+
+#[cfg(unix)]
+use crate::{
+    config::{self, ConfigStore, PulseConfig},
+    error::PulseError,
+    export::SpanExporter,
+    heartbeat::{self, ActiveSessions},
+    info, paths,
+    queue::SpanQueue,
+};
+
+#[cfg(unix)]
+const BATCH_SIZE: usize = 100;
+#[cfg(unix)]
+const FLUSH_INTERVAL: Duration = Duration::from_secs(1);
+/// How long an emit waits on the daemon before sending the spans itself.
+#[cfg(unix)]
+const HAND_OFF_TIMEOUT: Duration = Duration::from_millis(500);
+#[cfg(unix)]
+const ACK: &str = "ok";
+
+#[derive(Debug, Args)]
+pub struct DaemonArgs {
+    /// Ask the running daemon to send what it holds and exit
+    #[arg(long)]
+    pub stop: bool,
+}
+
+/// One request line sent to the daemon.
+#[derive(Debug, Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+enum Request {
+    Spans { spans: Vec<SpanPayload> },
+    Stop,
+}
+
+#[cfg(unix)]
+pub async fn run_daemon(args: DaemonArgs) -> Result<()> {
+    let socket = paths::daemon_socket()?;
+    if args.stop {
+        if send_request(&socket, &Request::Stop).await? {
+            println!("Daemon is stopping after it sends the spans it holds.");
+        } else {
+            println!("No daemon is running.");
+        }
+        return Ok(());
+    }
+
+    // Config changes take effect the next time the daemon starts.
+    let config = ConfigStore::load()?;
+    let daemon = Daemon {
+        exporter: SpanExporter::new(&config)?,
+        fallback: SpanQueue::new()?,
+        batch_size: BATCH_SIZE,
+        interval: FLUSH_INTERVAL,
+        heartbeat: heartbeat::enabled(&config).then(|| config.heartbeat_interval()),
+        config,
+    };
+    let listener = bind(&socket).await?;
+    info!("Listening on {}", socket.display());
+    if let Some(interval) = daemon.heartbeat {
+        info!(
+            "Sending a heartbeat for each active session every {}s",
+            interval.as_secs()
+        );
+    }
+    let result = daemon.serve(listener, shutdown_signal()).await;
+    let _ = fs::remove_file(&socket);
+    result
+}
+
+#[cfg(not(unix))]
+pub async fn run_daemon(_args: DaemonArgs) -> Result<()> {
+    Err(crate::error::PulseError::message(
+        "pulse daemon needs Unix domain sockets, which this platform lacks",
+    ))
+}
+
+/// Hands spans to a running daemon. Returns `false` when none is listening
+/// or it does not answer in time, so the caller sends them itself.
+#[cfg(unix)]
+pub(crate) async fn hand_off(spans: &[SpanPayload]) -> bool {
+    let Ok(socket) = paths::daemon_socket() else {
+        return false;
+    };
+    let request = Request::Spans {
+        spans: spans.to_vec(),
+    };
+    matches!(
+        timeout(HAND_OFF_TIMEOUT, send_request(&socket, &request)).await,
+        Ok(Ok(true))
+    )
+}
+
+#[cfg(not(unix))]
+pub(crate) async fn hand_off(_spans: &[SpanPayload]) -> bool {
+    false
+}
+
+/// Sends one request. Returns `false` if nothing is listening on `socket`.
+#[cfg(unix)]
+async fn send_request(socket: &Path, request: &Request) -> Result<bool> {
+    let Ok(stream) = UnixStream::connect(socket).await else {
+        return Ok(false);
+    };
+    let (read, mut write) = stream.into_split();
+    let mut line = serde_json::to_string(request)?;
+    line.push('\n');
+    write.write_all(line.as_bytes()).await?;
+
+    let mut reply = String::new();
+    BufReader::new(read).read_line(&mut reply).await?;
+    match reply.trim() {
+        ACK => Ok(true),
+        reply => Err(PulseError::message(format!(
+            "daemon rejected the request: {reply}"
+        ))),
+    }
+}
+
+/// Binds the socket, replacing one left behind by a daemon that did not
+/// shut down cleanly. Fails if another daemon is still answering on it.
+#[cfg(unix)]
+async fn bind(socket: &Path) -> Result<UnixListener> {
+    if UnixStream::connect(socket).await.is_ok() {
+        return Err(PulseError::message(format!(
+            "a daemon is already listening on {}",
+            socket.display()
+        )));
+    }
+    let _ = fs::remove_file(socket);
+    if let Some(parent) = socket.parent() {
+        fs::create_dir_all(parent)?;
+    }
+    let listener = UnixListener::bind(socket)?;
+    config::restrict_permissions(socket, 0o600)?;
+    Ok(listener)
+}
+
+/// Resolves on Ctrl-C or SIGTERM.
+#[cfg(unix)]
+async fn shutdown_signal() {
+    use tokio::signal::unix::{SignalKind, signal};
+
+    let Ok(mut terminate) = signal(SignalKind::terminate()) else {
+        let _ = tokio::signal::ctrl_c().await;
+        return;
+    };
+    tokio::select! {
+        _ = tokio::signal::ctrl_c() => {}
+        _ = terminate.recv() => {}
+    }
+}
+
+#[cfg(unix)]
+struct Daemon {
+    exporter: SpanExporter,
+    /// Where spans go when an upload fails, for the next `pulse flush`.
+    fallback: SpanQueue,
+    batch_size: usize,
+    interval: Duration,
+    /// Time between heartbeat spans, when `PULSE_HEARTBEAT` or `heartbeat`
+    /// turns them on.
+    heartbeat: Option<Duration>,
+    /// For building heartbeat spans.
+    config: PulseConfig,
+}
+
+#[cfg(unix)]
+impl Daemon {
+    /// Accepts spans until `shutdown` resolves or a client asks it to stop,
+    /// then sends whatever is still held before returning.
+    async fn serve(
+        &self,
+        listener: UnixListener,
+        shutdown: impl Future<Output = ()>,
+    ) -> Result<()> {
+        let (tx, mut rx) = mpsc::unbounded_channel();
+        let mut uploads = JoinSet::new();
+        let mut held = Vec::new();
+        let mut ticker = time::interval(self.interval);
+        let mut sessions = ActiveSessions::default();
+        let mut beats = time::interval(self.heartbeat.unwrap_or(self.interval));
+        beats.reset();
+        tokio::pin!(shutdown);
+
+        loop {
+            tokio::select! {
+                _ = &mut shutdown => break,
+                accepted = listener.accept() => {
+                    if let Ok((stream, _)) = accepted {
+                        tokio::spawn(handle_connection(stream, tx.clone()));
+                    }
+                }
+                Some(request) = rx.recv() => match request {
+                    Request::Spans { spans } => {
+                        if self.heartbeat.is_some() {
+                            sessions.observe(&spans, Utc::now());
+                        }
+                        held.extend(spans);
+                        if held.len() >= self.batch_size {
+                            uploads.spawn(self.upload(mem::take(&mut held)));
+                        }
+                    }
+                    Request::Stop => break,
+                },
+                _ = ticker.tick() => {
+                    if !held.is_empty() {
+                        uploads.spawn(self.upload(mem::take(&mut held)));
+                    }
+                    while uploads.try_join_next().is_some() {}
+                }
+                _ = beats.tick(), if self.heartbeat.is_some() => {
+                    held.extend(sessions.beat(&self.config, Utc::now()));
+                }
+            }
+        }
+
+        // Clients that connect from here on are refused and send the spans
+        // themselves; anything already acknowledged is still sent.
+        drop(listener);
+        rx.close();
+        while let Some(request) = rx.recv().await {
+            if let Request::Spans { spans } = request {
+                held.extend(spans);
+            }
+        }
+        if !held.is_empty() {
+            uploads.spawn(self.upload(held));
+        }
+        while uploads.join_next().await.is_some() {}
+        Ok(())
+    }
+
+    /// Sends one batch, keeping it in the offline queue if that fails.
+    fn upload(&self, spans: Vec<SpanPayload>) -> impl Future<Output = ()> + Send + 'static {
+        let exporter = self.exporter.clone();
+        let fallback = self.fallback.clone();
+        async move {
+            if let Err(err) = exporter.export(&spans).await {
+                eprintln!(
+                    "failed to send {} span(s), kept in the offline queue: {err}",
+                    spans.len()
+                );
+                let _ = fallback.enqueue_all(&spans);
+            }
+        }
+    }
+}
+
+/// Reads one request and acknowledges it once the daemon holds it.
+#[cfg(unix)]
+async fn handle_connection(stream: UnixStream, tx: UnboundedSender<Request>) {
+    let (read, mut write) = stream.into_split();
+    let mut line = String::new();
+    let read = timeout(HAND_OFF_TIMEOUT, BufReader::new(read).read_line(&mut line)).await;
+    if !matches!(read, Ok(Ok(n)) if n > 0) {
+        return;
+    }
+    let reply = match serde_json::from_str::<Request>(&line) {
+        Ok(request) => match tx.send(request) {
+            Ok(()) => ACK.to_string(),
+            Err(_) => "error: daemon is shutting down".to_string(),
+        },
+        Err(err) => format!("error: {err}"),
+    };
+    let _ = write.write_all(format!("{reply}\n").as_bytes()).await;
+}
+
+#[cfg(all(test, unix))]
+mod tests {
+    use std::future;
+
+    use super::*;
+    use crate::config::PulseConfig;
+    use tempfile::TempDir;
+
+    /// A daemon whose uploads fail, so every span it sends ends up in the
+    /// fallback queue where the test can see it.
+    fn unreachable_daemon(tmp: &TempDir) -> Daemon {
+        let port = std::net::TcpListener::bind("127.0.0.1:0")
+            .unwrap()
+            .local_addr()
+            .unwrap()
+            .port();
+        let config = PulseConfig {
+            api_url: format!("http://127.0.0.1:{port}"),
+            ..PulseConfig::default()
+        };
+        Daemon {
+            exporter: SpanExporter::new(&config).unwrap(),
+            fallback: SpanQueue::at(tmp.path().join(paths::QUEUE_FILE)),
+            batch_size: 2,
+            interval: Duration::from_secs(60),
+            heartbeat: None,
+            config,
+        }
+    }
+
+    #[tokio::test]
+    async fn test_daemon_sends_held_spans_on_stop() {
+        let tmp = TempDir::new().unwrap();
+        let socket = tmp.path().join(paths::DAEMON_SOCKET_FILE);
+        let daemon = unreachable_daemon(&tmp);
+        let listener = bind(&socket).await.unwrap();
+
+        let client = async {
+            let batch = Request::Spans {
+                spans: vec![
+                    SpanPayload::test("a"),
+                    SpanPayload::test("b"),
+                    SpanPayload::test("c"),
+                ],
+            };
+            assert!(send_request(&socket, &batch).await.unwrap());
+            assert!(send_request(&socket, &Request::Stop).await.unwrap());
+        };
+        let (served, ()) = tokio::join!(daemon.serve(listener, future::pending()), client);
+        served.unwrap();
+
+        let mut ids: Vec<_> = daemon
+            .fallback
+            .drain()
+            .unwrap()
+            .into_iter()
+            .map(|span| span.span_id)
+            .collect();
+        ids.sort();
+        assert_eq!(ids, ["a", "b", "c"]);
+    }
+
+    #[tokio::test]
+    async fn test_daemon_sends_heartbeats_for_active_sessions() {
+        let tmp = TempDir::new().unwrap();
+        let socket = tmp.path().join(paths::DAEMON_SOCKET_FILE);
+        let daemon = Daemon {
+            heartbeat: Some(Duration::from_millis(20)),
+            ..unreachable_daemon(&tmp)
+        };
+        let listener = bind(&socket).await.unwrap();
+
+        let client = async {
+            let batch = Request::Spans {
+                spans: vec![SpanPayload::test("a")],
+            };
+            assert!(send_request(&socket, &batch).await.unwrap());
+            time::sleep(Duration::from_millis(100)).await;
+            assert!(send_request(&socket, &Request::Stop).await.unwrap());
+        };
+        let (served, ()) = tokio::join!(daemon.serve(listener, future::pending()), client);
+        served.unwrap();
+
+        let spans = daemon.fallback.drain().unwrap();
+        assert!(
+            spans
+                .iter()
+                .any(|span| span.event_type == heartbeat::HEARTBEAT_EVENT_TYPE
+                    && span.session_id == "sess_1")
+        );
+    }
+
+    #[tokio::test]
+    async fn test_only_one_daemon_binds_the_socket() {
+        let tmp = TempDir::new().unwrap();
+        let socket = tmp.path().join(paths::DAEMON_SOCKET_FILE);
+        let _listener = bind(&socket).await.unwrap();
+        assert!(bind(&socket).await.is_err());
+    }
+
+    #[tokio::test]
+    async fn test_stale_socket_is_replaced() {
+        let tmp = TempDir::new().unwrap();
+        let socket = tmp.path().join(paths::DAEMON_SOCKET_FILE);
+        drop(bind(&socket).await.unwrap());
+        assert!(socket.exists());
+        assert!(!send_request(&socket, &Request::Stop).await.unwrap());
+        assert!(bind(&socket).await.is_ok());
+    }
+}
@@ -1,28 +1,38 @@
-use std::process::Command;
-use std::time::Duration;
+use std::{process::Command, time::Duration};
 
 use clap::Args;
-use reqwest::{Client, Url};
+use reqwest::Url;
 use serde::{Deserialize, Serialize};
+use serde_json::json;
 
+use crate::commands::print_json;
 use crate::config::ConfigStore;
 use crate::error::{PulseError, Result};
+use crate::health::{self, POLL_INTERVAL};
+use crate::http::client_builder;
+use crate::net::is_local_host;
+use crate::{info, verbose};
 
 const DEFAULT_DASHBOARD_URL: &str = "http://localhost:5173";
-const HTTP_TIMEOUT: Duration = Duration::from_secs(5);
-const USER_AGENT: &str = concat!("pulse-cli/", env!("CARGO_PKG_VERSION"));
 
 #[derive(Debug, Args)]
 pub struct DashboardArgs {
     /// Trace service URL (defaults to configured value)
     #[arg(long)]
     pub api_url: Option<String>,
-    /// Dashboard frontend URL to open after local login handoff
+    /// Dashboard URL (defaults to `dashboard_url` in the config, then the
+    /// local frontend or, for a hosted API, the API URL)
     #[arg(long)]
     pub dashboard_url: Option<String>,
     /// Print the login URL instead of opening a browser
     #[arg(long)]
     pub no_open: bool,
+    /// Print only the login URL, for scripts; implies --no-open
+    #[arg(long)]
+    pub print_url_only: bool,
+    /// Seconds to wait for a starting server to become ready
+    #[arg(long, value_name = "SECS", default_value_t = 10)]
+    pub timeout: u64,
 }
 
 #[derive(Debug, Serialize)]
@@ -38,27 +48,35 @@ struct LocalLoginTokenResponse {
     expires_at: String,
 }
 
-pub async fn run_dashboard(args: DashboardArgs) -> Result<()> {
+/// `--json` prints the URL as JSON, and `--print-url-only` just the URL, so
+/// either can be piped into another tool.
+pub async fn run_dashboard(args: DashboardArgs, json: bool) -> Result<()> {
     let config = ConfigStore::load()?;
-    let api_url = args.api_url.unwrap_or_else(|| config.api_url.clone());
-    let dashboard_url = args
-        .dashboard_url
-        .unwrap_or_else(|| DEFAULT_DASHBOARD_URL.to_string());
-
+    let api_url = args
+        .api_url
+        .clone()
+        .unwrap_or_else(|| config.api_url.clone());
     let base_url = normalize_base_url(&api_url)?;
-    let dashboard_url = normalize_base_url(&dashboard_url)?;
+    let dashboard_url = match args
+        .dashboard_url
+        .clone()
+        .or_else(|| config.dashboard_url.clone())
+    {
+        Some(url) => normalize_base_url(&url)?,
+        None if is_local_host(&base_url) => normalize_base_url(DEFAULT_DASHBOARD_URL)?,
+        None => base_url.clone(),
+    };
 
-    if !is_local_host(&base_url) {
-        return Err(PulseError::message(format!(
-            "pulse dashboard requires a local API URL. Got: {base_url}"
-        )));
-    }
-    if !is_local_host(&dashboard_url) {
-        return Err(PulseError::message(format!(
-            "pulse dashboard requires a local dashboard URL. Got: {dashboard_url}"
-        )));
+    // Only a local server mints login tokens; a hosted dashboard has its own
+    // sign-in, so it is simply opened.
+    if !is_local_host(&base_url) || !is_local_host(&dashboard_url) {
+        let url = with_project(dashboard_url, &config.project_id);
+        verbose!("Hosted dashboard: {url}");
+        return show_url(&args, json, url.as_str(), None);
     }
 
+    let client = client_builder(&config)?.build()?;
+
     let local_email = config.local_email.ok_or_else(|| {
         PulseError::message(
             "Local dashboard auto-login is not configured. Run `pulse setup --local` first.",
@@ -70,22 +88,37 @@ pub async fn run_dashboard(args: DashboardArgs) -> Result<()> {
         )
     })?;
 
-    let client = Client::builder()
-        .user_agent(USER_AGENT)
-        .timeout(HTTP_TIMEOUT)
-        .build()?;
-
+    // A server started a moment ago may still be booting or migrating, so
+    // wait for it before asking for a token, and retry the token request on
+    // server errors within the same budget.
+    let timeout = Duration::from_secs(args.timeout);
     let health_url = make_url(&base_url, "/health")?;
-    client.get(health_url).send().await?.error_for_status()?;
+    verbose!("Waiting up to {}s for {health_url}", args.timeout);
+    health::wait_until_healthy(&client, &health_url, timeout)
+        .await
+        .map_err(|err| {
+            PulseError::message(format!(
+                "Trace service at {base_url} is not ready after {}s: {err}",
+                args.timeout
+            ))
+        })?;
 
     let token_url = make_url(&base_url, "/dashboard/api/local-login-token")?;
+    verbose!("Requesting a login token from {token_url}");
     let payload = LocalLoginTokenRequest {
         email: local_email.trim(),
         password: local_password.trim(),
         redirect_url: dashboard_url.as_str(),
     };
 
-    let response = client.post(token_url).json(&payload).send().await?;
+    let response = health::retry_until(timeout, POLL_INTERVAL, || async {
+        let response = client.post(token_url.clone()).json(&payload).send().await?;
+        match response.status() {
+            status if status.is_server_error() => Err(PulseError::ServerError { status }),
+            _ => Ok(response),
+        }
+    })
+    .await?;
     if !response.status().is_success() {
         let status = response.status();
         let body = response.text().await.unwrap_or_default();
@@ -96,33 +129,70 @@ pub async fn run_dashboard(args: DashboardArgs) -> Result<()> {
     }
 
     let token_response: LocalLoginTokenResponse = response.json().await?;
-    println!(
-        "Local dashboard login token created (expires: {}).",
-        token_response.expires_at
-    );
+    if !json && !args.print_url_only {
+        info!(
+            "Local dashboard login token created (expires: {}).",
+            token_response.expires_at
+        );
+    }
+    show_url(
+        &args,
+        json,
+        &token_response.login_url,
+        Some(&token_response.expires_at),
+    )
+}
+
+/// Opens `url` unless told not to, and prints it in the requested form.
+/// `expires_at` is set for a one-time login URL.
+fn show_url(args: &DashboardArgs, json: bool, url: &str, expires_at: Option<&str>) -> Result<()> {
+    if args.print_url_only {
+        println!("{url}");
+        return Ok(());
+    }
+    if json {
+        let opened = !args.no_open && open_in_browser(url).is_ok();
+        return print_json(&match expires_at {
+            Some(expires_at) => json!({
+                "login_url": url,
+                "expires_at": expires_at,
+                "opened": opened,
+            }),
+            None => json!({ "dashboard_url": url, "opened": opened }),
+        });
+    }
 
     if args.no_open {
-        println!("Open this URL in your browser:");
-        println!("{}", token_response.login_url);
+        info!("Open this URL in your browser:");
+        println!("{url}");
         return Ok(());
     }
 
-    match open_in_browser(&token_response.login_url) {
+    match open_in_browser(url) {
         Ok(()) => {
             println!("Opened dashboard in your browser.");
-            println!("If it did not open, use:");
-            println!("{}", token_response.login_url);
+            info!("If it did not open, use:");
+            println!("{url}");
             Ok(())
         }
         Err(err) => {
             println!("Could not open a browser automatically: {err}");
             println!("Open this URL manually:");
-            println!("{}", token_response.login_url);
+            println!("{url}");
             Ok(())
         }
     }
 }
 
+/// Adds the configured project to a hosted dashboard URL, so it opens on
+/// that project.
+fn with_project(mut url: Url, project_id: &str) -> Url {
+    if !project_id.is_empty() {
+        url.query_pairs_mut().append_pair("project_id", project_id);
+    }
+    url
+}
+
 fn open_in_browser(url: &str) -> Result<()> {
     #[cfg(target_os = "macos")]
     let mut command = {
@@ -159,11 +229,7 @@ fn make_url(base_url: &Url, path: &str) -> Result<Url> {
 
 fn normalize_base_url(raw: &str) -> Result<Url> {
     let trimmed = raw.trim().trim_end_matches('/');
-    Url::parse(trimmed).map_err(|err| PulseError::message(format!("invalid API url: {err}")))
-}
-
-fn is_local_host(url: &Url) -> bool {
-    matches!(url.host_str(), Some("localhost" | "127.0.0.1" | "::1"))
+    Url::parse(trimmed).map_err(|err| PulseError::message(format!("invalid url {trimmed}: {err}")))
 }
 
 fn compact_body(body: &str) -> String {
@@ -174,3 +240,22 @@ fn compact_body(body: &str) -> String {
         format!("{}...", &collapsed[..240])
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_hosted_dashboard_url_carries_project() {
+        let url = normalize_base_url("https://trace.example.com/dashboard/").unwrap();
+        assert!(!is_local_host(&url));
+        assert_eq!(
+            with_project(url.clone(), "proj_1").as_str(),
+            "https://trace.example.com/dashboard?project_id=proj_1"
+        );
+        assert_eq!(
+            with_project(url, "").as_str(),
+            "https://trace.example.com/dashboard"
+        );
+    }
+}
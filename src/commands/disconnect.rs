@@ -1,19 +1,71 @@
-use crate::{commands::registered_hooks, config::ConfigStore, error::Result, hooks::HookStatus};
+use std::path::PathBuf;
 
-pub fn run_disconnect() -> Result<()> {
+use clap::Args;
+
+use crate::{
+    commands::{HookOptions, print_json, registered_hooks_with},
+    config::ConfigStore,
+    error::Result,
+    hooks::{ConnectOptions, HookStatus},
+    info,
+    output::{Tone, bullet, paint},
+};
+
+#[derive(Debug, Default, Args)]
+pub struct DisconnectArgs {
+    /// Do not back up settings files before editing them
+    #[arg(long)]
+    pub no_backup: bool,
+    /// Write Claude Code settings backups to this directory instead of next to the file
+    #[arg(long, value_name = "DIR", conflicts_with = "no_backup")]
+    pub backup_dir: Option<PathBuf>,
+    /// Show what would change without writing any files
+    #[arg(long)]
+    pub dry_run: bool,
+    /// Only these tools, comma-separated (claude_code, opencode, openclaw, cursor, gemini, codex)
+    #[arg(long, value_delimiter = ',')]
+    pub tools: Vec<String>,
+    /// Config directory of the one tool given with `--tools` (opencode or openclaw)
+    #[arg(long)]
+    pub config_dir: Option<PathBuf>,
+}
+
+pub fn run_disconnect(args: DisconnectArgs, json: bool) -> Result<()> {
     ConfigStore::load()?;
 
-    println!("Removing hooks...");
-    let hooks = registered_hooks()?;
-    for hook in hooks {
-        let status = hook.disconnect()?;
-        print_disconnect_summary(&status);
+    if !json {
+        if args.dry_run {
+            println!("Dry run: no files will be changed.");
+        }
+        info!("Removing hooks...");
+    }
+    let hooks = registered_hooks_with(HookOptions {
+        backup: !args.no_backup,
+        backup_dir: args.backup_dir.clone(),
+        tools: args.tools.clone(),
+        config_dir: args.config_dir.clone(),
+        ..HookOptions::default()
+    })?;
+    let options = ConnectOptions {
+        dry_run: args.dry_run,
+        ..ConnectOptions::default()
+    };
+    let statuses = hooks
+        .iter()
+        .map(|hook| hook.disconnect_opts(&options))
+        .collect::<Result<Vec<HookStatus>>>()?;
+
+    if json {
+        return print_json(&statuses);
+    }
+    for status in &statuses {
+        print_disconnect_summary(status, args.dry_run);
     }
 
     Ok(())
 }
 
-fn print_disconnect_summary(status: &HookStatus) {
+fn print_disconnect_summary(status: &HookStatus, dry_run: bool) {
     if !status.detected {
         println!(
             "- {}: {}",
@@ -28,16 +80,32 @@ fn print_disconnect_summary(status: &HookStatus) {
 
     if status.connected {
         println!(
-            "- {}: hooks still present{}",
+            "{} {}: {}{}",
+            bullet(Tone::Bad),
             status.tool,
+            paint(Tone::Bad, "hooks still present"),
             format_path_suffix(status)
         );
     } else if status.modified {
         println!(
-            "- {}: hooks removed{}",
+            "{} {}: {}{}",
+            bullet(Tone::Good),
             status.tool,
+            paint(
+                Tone::Good,
+                if dry_run {
+                    "would remove hooks"
+                } else {
+                    "hooks removed"
+                }
+            ),
             format_path_suffix(status)
         );
+        if let Some(message) = &status.message {
+            for line in message.lines() {
+                println!("    {line}");
+            }
+        }
     } else {
         println!(
             "- {}: no hooks to remove{}",
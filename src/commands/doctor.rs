@@ -0,0 +1,324 @@
+use std::{
+    fs::OpenOptions,
+    path::{Path, PathBuf},
+};
+
+use reqwest::StatusCode;
+
+use crate::{
+    commands::{find_executable, registered_hooks},
+    config::{ConfigStore, PulseConfig, validate_project_id},
+    error::{PulseError, Result},
+    hooks::HookStatus,
+    http::{TraceHttpClient, check_client_identity},
+    info,
+};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Outcome {
+    Pass,
+    Warn,
+    Fail,
+}
+
+struct Check {
+    name: String,
+    outcome: Outcome,
+    detail: String,
+    hint: Option<String>,
+}
+
+impl Check {
+    fn pass(name: impl Into<String>, detail: impl Into<String>) -> Self {
+        Self {
+            name: name.into(),
+            outcome: Outcome::Pass,
+            detail: detail.into(),
+            hint: None,
+        }
+    }
+
+    fn warn(name: impl Into<String>, detail: impl Into<String>, hint: impl Into<String>) -> Self {
+        Self {
+            name: name.into(),
+            outcome: Outcome::Warn,
+            detail: detail.into(),
+            hint: Some(hint.into()),
+        }
+    }
+
+    fn fail(name: impl Into<String>, detail: impl Into<String>, hint: impl Into<String>) -> Self {
+        Self {
+            name: name.into(),
+            outcome: Outcome::Fail,
+            detail: detail.into(),
+            hint: Some(hint.into()),
+        }
+    }
+}
+
+pub async fn run_doctor() -> Result<()> {
+    info!("Pulse doctor");
+    info!("------------");
+
+    let mut checks = Vec::new();
+
+    let config = check_config(&mut checks);
+    if let Some(config) = &config {
+        check_service(config, &mut checks).await;
+    }
+    check_binary(&mut checks);
+    check_hooks(&mut checks)?;
+
+    for check in &checks {
+        print_check(check);
+    }
+
+    let failed = checks
+        .iter()
+        .filter(|check| check.outcome == Outcome::Fail)
+        .count();
+    if failed > 0 {
+        return Err(PulseError::message(format!(
+            "{failed} critical check{} failed",
+            if failed == 1 { "" } else { "s" }
+        )));
+    }
+
+    println!("\nAll critical checks passed.");
+    Ok(())
+}
+
+fn check_config(checks: &mut Vec<Check>) -> Option<PulseConfig> {
+    let path = ConfigStore::config_path()
+        .map(|path| path.display().to_string())
+        .unwrap_or_else(|_| "~/.pulse/config.toml".to_string());
+    match ConfigStore::load() {
+        Ok(config) => {
+            checks.push(Check::pass("Config file", path));
+            if let Err(err) = validate_project_id(&config.project_id) {
+                checks.push(Check::fail(
+                    "Project id",
+                    err.to_string(),
+                    "Copy the id from the dashboard and run `pulse config set project_id <id>`.",
+                ));
+            }
+            Some(config)
+        }
+        Err(PulseError::ConfigMissing) => {
+            checks.push(Check::fail(
+                "Config file",
+                format!("not found at {path}"),
+                "Run `pulse setup` or `pulse init` to create it.",
+            ));
+            None
+        }
+        Err(err) => {
+            checks.push(Check::fail(
+                "Config file",
+                format!("{path} could not be read: {err}"),
+                "Fix the file by hand or re-run `pulse init`.",
+            ));
+            None
+        }
+    }
+}
+
+async fn check_service(config: &PulseConfig, checks: &mut Vec<Check>) {
+    if let Some(cert_path) = &config.client_cert_path {
+        match check_client_identity(config) {
+            Ok(()) => checks.push(Check::pass("Client certificate", cert_path)),
+            Err(err) => {
+                checks.push(Check::fail(
+                    "Client certificate",
+                    err.to_string(),
+                    "Point `client_cert_path` and `client_key_path` at a matching PEM certificate and key.",
+                ));
+                return;
+            }
+        }
+    }
+
+    let client = match TraceHttpClient::new(config) {
+        Ok(client) => client,
+        Err(err) => {
+            checks.push(Check::fail(
+                "API URL",
+                format!("`{}` is invalid: {err}", config.api_url),
+                "Set a full URL such as https://pulse.example.com with `pulse init`.",
+            ));
+            return;
+        }
+    };
+
+    if config.accept_invalid_certs() {
+        checks.push(Check::warn(
+            "TLS verification",
+            "disabled by danger_accept_invalid_certs",
+            "Set `ca_cert_path` to your CA certificate instead.",
+        ));
+    }
+
+    match client.health_check().await {
+        Ok(()) => checks.push(Check::pass("Trace service reachable", &config.api_url)),
+        Err(err) => {
+            checks.push(Check::fail(
+                "Trace service reachable",
+                format!("{}: {err}", config.api_url),
+                err.remediation()
+                    .unwrap_or("Start the trace service or correct the URL with `pulse init`."),
+            ));
+            return;
+        }
+    }
+
+    checks.push(match client.probe_spans_endpoint().await {
+        Ok(status) => classify_auth_status(status),
+        Err(err) => Check::fail(
+            "API key and project",
+            format!("request failed: {err}"),
+            err.remediation()
+                .unwrap_or("Check network access to the trace service."),
+        ),
+    });
+}
+
+fn classify_auth_status(status: StatusCode) -> Check {
+    const NAME: &str = "API key and project";
+    // An empty batch may be rejected as invalid, but only after auth passed.
+    match PulseError::from_status(status, "spans endpoint") {
+        Some(
+            err @ (PulseError::Unauthorized | PulseError::Forbidden | PulseError::NotFound { .. }),
+        ) => Check::fail(NAME, err.to_string(), err.remediation().unwrap_or_default()),
+        Some(err @ PulseError::ServerError { .. }) => {
+            Check::warn(NAME, err.to_string(), err.remediation().unwrap_or_default())
+        }
+        _ => Check::pass(NAME, format!("accepted ({status})")),
+    }
+}
+
+fn check_binary(checks: &mut Vec<Check>) {
+    match find_executable("pulse") {
+        Some(path) => checks.push(Check::pass("`pulse` on PATH", path.display().to_string())),
+        None => checks.push(Check::fail(
+            "`pulse` on PATH",
+            "not found",
+            "Hooks run `pulse emit` by name. Add the install directory to PATH.",
+        )),
+    }
+}
+
+fn check_hooks(checks: &mut Vec<Check>) -> Result<()> {
+    for hook in registered_hooks()? {
+        let status = hook.status()?;
+        if let Some(check) = check_hook(&status) {
+            checks.push(check);
+        }
+    }
+    Ok(())
+}
+
+fn check_hook(status: &HookStatus) -> Option<Check> {
+    if !status.detected {
+        return None;
+    }
+    let name = format!("{} hooks", status.tool);
+    let path = status.path.as_ref()?;
+
+    if !is_writable(path) {
+        return Some(Check::fail(
+            name,
+            format!("{} is not writable", path.display()),
+            "Fix the file permissions so `pulse connect` can update it.",
+        ));
+    }
+    if !status.connected || status.installed_hooks < status.total_hooks {
+        return Some(Check::warn(
+            name,
+            format!(
+                "{}/{} hooks installed",
+                status.installed_hooks, status.total_hooks
+            ),
+            "Run `pulse connect` to install missing hooks.",
+        ));
+    }
+    if let Some(message) = &status.message {
+        return Some(Check::warn(
+            name,
+            message.clone(),
+            "Run `pulse connect` to update it.",
+        ));
+    }
+    Some(Check::pass(name, path.display().to_string()))
+}
+
+/// Whether `path` (or the closest existing parent, if it does not exist yet)
+/// can be written without changing anything on disk.
+fn is_writable(path: &Path) -> bool {
+    if path.is_file() {
+        return OpenOptions::new().append(true).open(path).is_ok();
+    }
+    let mut current: Option<PathBuf> = Some(path.to_path_buf());
+    while let Some(dir) = current {
+        if dir.exists() {
+            return dir
+                .metadata()
+                .map(|meta| !meta.permissions().readonly())
+                .unwrap_or(false);
+        }
+        current = dir.parent().map(Path::to_path_buf);
+    }
+    false
+}
+
+fn print_check(check: &Check) {
+    let label = match check.outcome {
+        Outcome::Pass => "ok  ",
+        Outcome::Warn => "warn",
+        Outcome::Fail => "fail",
+    };
+    println!("  [{label}] {}: {}", check.name, check.detail);
+    if let Some(hint) = &check.hint {
+        println!("         {hint}");
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_auth_status_classification() {
+        assert_eq!(
+            classify_auth_status(StatusCode::UNAUTHORIZED).outcome,
+            Outcome::Fail
+        );
+        assert_eq!(
+            classify_auth_status(StatusCode::FORBIDDEN).outcome,
+            Outcome::Fail
+        );
+        assert_eq!(
+            classify_auth_status(StatusCode::NOT_FOUND).outcome,
+            Outcome::Fail
+        );
+        assert_eq!(
+            classify_auth_status(StatusCode::BAD_GATEWAY).outcome,
+            Outcome::Warn
+        );
+        assert_eq!(
+            classify_auth_status(StatusCode::ACCEPTED).outcome,
+            Outcome::Pass
+        );
+        // An empty batch may be rejected as invalid, but only after auth passed.
+        assert_eq!(
+            classify_auth_status(StatusCode::BAD_REQUEST).outcome,
+            Outcome::Pass
+        );
+    }
+
+    #[test]
+    fn test_hook_not_detected_is_skipped() {
+        let status = HookStatus::not_detected("Tool", PathBuf::from("/nonexistent"));
+        assert!(check_hook(&status).is_none());
+    }
+}
@@ -1,125 +1,1280 @@
-use std::io::{self, Read};
+use std::{
+    fs,
+    io::{self, Read},
+    path::{Path, PathBuf},
+    sync::{OnceLock, mpsc},
+    thread,
+    time::{Duration, Instant},
+};
 
-use chrono::Utc;
-use clap::Args;
-use serde_json::{Value, json};
+use chrono::{DateTime, Utc};
+use clap::{Args, builder::PossibleValuesParser};
+use regex::Regex;
+use serde_json::{Map, Value, json};
 use uuid::Uuid;
 
 use crate::{
-    config::ConfigStore,
-    error::Result,
-    hooks::{CLAUDE_SOURCE, span},
-    http::TraceHttpClient,
+    breaker::{CircuitBreaker, Decision},
+    commands::{TOOL_IDS, daemon, flush::start_detached_flush},
+    config::{ConfigStore, PulseConfig},
+    correlation::CorrelationStore,
+    debug,
+    emit_log::{self, EmitLogEntry, Outcome},
+    error::{PulseError, Result},
+    export::SpanExporter,
+    git,
+    history::SpanHistory,
+    hooks::{
+        CLAUDE_SOURCE,
+        span::{self, SpanFields},
+    },
+    http::SpanPayload,
+    queue::{SpanBuffer, SpanQueue},
+    redact::Redactor,
+    sampling, source,
 };
 
-fn debug_enabled() -> bool {
-    std::env::var("PULSE_DEBUG")
+/// Event type of the diagnostic span sent, with `capture_emit_errors`, for
+/// an event that could not be parsed or turned into a span.
+pub const CLI_ERROR_EVENT_TYPE: &str = "cli_error";
+/// Session of a `cli_error` span when none can be read from the input.
+const CLI_ERROR_SESSION_ID: &str = "pulse-cli-error";
+/// How much of the raw input a `cli_error` span keeps, in bytes.
+const CLI_ERROR_INPUT_BYTES: usize = 2048;
+
+#[derive(Debug, Args)]
+pub struct EmitArgs {
+    /// Event type (e.g. post_tool_use, stop)
+    #[arg(required_unless_present = "batch", conflicts_with = "batch")]
+    pub event_type: Option<String>,
+    /// Read one event per line (NDJSON) and send them all as one batch
+    #[arg(long)]
+    pub batch: bool,
+    /// Event type for `--batch` lines without an `event_type` field
+    #[arg(long = "event-type", value_name = "TYPE", requires = "batch")]
+    pub default_event_type: Option<String>,
+    /// Event JSON to use instead of reading stdin
+    #[arg(long, conflicts_with = "payload_file")]
+    pub payload: Option<String>,
+    /// Read the event JSON from a file instead of stdin
+    #[arg(long)]
+    pub payload_file: Option<PathBuf>,
+    /// Send this session's buffered spans now (when `buffer = true`)
+    #[arg(long)]
+    pub flush: bool,
+    /// Source to record; wins over the payload's `source` and `PULSE_SOURCE`
+    #[arg(long, value_parser = PossibleValuesParser::new(TOOL_IDS))]
+    pub source: Option<String>,
+    /// Work out the source from the calling agent when nothing names it
+    /// (default `source_autodetect`, on; also `PULSE_SOURCE_AUTODETECT`)
+    #[arg(long, value_name = "BOOL", num_args = 0..=1, default_missing_value = "true")]
+    pub source_autodetect: Option<bool>,
+    /// Report failures on stderr and exit non-zero (also `PULSE_STRICT=1`)
+    #[arg(long)]
+    pub strict: bool,
+    /// Milliseconds to wait for the event on stdin (default `stdin_timeout_ms`, 1000)
+    #[arg(long, value_name = "MS", value_parser = clap::value_parser!(u64).range(1..))]
+    pub stdin_timeout: Option<u64>,
+}
+
+/// Hooks must never break the agent, so failures are swallowed unless
+/// `--strict` or `PULSE_STRICT=1` asks for them to be reported.
+pub async fn run_emit(args: EmitArgs) -> Result<()> {
+    let strict = args.strict || strict_enabled();
+    let result = if args.batch {
+        emit_batch(args).await
+    } else {
+        emit_inner(args).await
+    };
+    if strict { result } else { Ok(()) }
+}
+
+fn strict_enabled() -> bool {
+    std::env::var("PULSE_STRICT")
         .map(|v| v == "1" || v == "true")
         .unwrap_or(false)
 }
 
-fn debug_log(event_type: &str, payload: &Value) {
-    use std::fs::OpenOptions;
-    use std::io::Write;
+/// `PULSE_ASYNC` wins over `async_emit` in the config, in either direction.
+fn async_enabled(config: &PulseConfig) -> bool {
+    match std::env::var("PULSE_ASYNC") {
+        Ok(v) => v == "1" || v == "true",
+        Err(_) => config.async_emit(),
+    }
+}
 
-    let path = std::env::var("PULSE_DEBUG_LOG").unwrap_or_else(|_| {
-        dirs::home_dir()
-            .map(|h| h.join(".pulse/debug.log").to_string_lossy().to_string())
-            .unwrap_or_else(|| "/tmp/pulse-debug.log".to_string())
-    });
+/// Adds `cli_version`, `project_id`, the raw event payload (unless
+/// `include_raw` is off), any configured labels, and the optional host and
+/// git context to the span metadata. The raw copy stays complete even where
+/// fields were promoted to the span, because replaying a recorded span
+/// re-extracts it from `raw`.
+fn merge_metadata(fields: &mut SpanFields, config: &PulseConfig, payload: &Value) -> Result<()> {
+    let meta = fields.metadata.get_or_insert_with(|| json!({}));
+    if !meta.is_object() {
+        *meta = json!({});
+    }
+    if let Some(obj) = meta.as_object_mut() {
+        obj.insert(
+            "cli_version".to_string(),
+            Value::String(env!("CARGO_PKG_VERSION").to_string()),
+        );
+        obj.insert(
+            "project_id".to_string(),
+            Value::String(config.project_id.clone()),
+        );
+        if config.include_raw() {
+            obj.insert("raw".to_string(), payload.clone());
+        }
+
+        let labels = config.labels();
+        if !labels.is_empty() {
+            obj.insert("labels".to_string(), serde_json::to_value(labels)?);
+        }
+
+        if config.capture_host() {
+            obj.extend(host_metadata());
+        }
 
-    if let Ok(mut file) = OpenOptions::new().create(true).append(true).open(&path) {
-        let ts = Utc::now().to_rfc3339();
-        let pretty = serde_json::to_string_pretty(payload).unwrap_or_default();
-        let _ = writeln!(file, "── [{ts}] {event_type} ──");
-        let _ = writeln!(file, "{pretty}");
-        let _ = writeln!(file);
+        if config.capture_git() {
+            let git = fields.cwd.as_deref().map(Path::new).and_then(git::detect);
+            if let Some(git) = git {
+                obj.insert("git".to_string(), serde_json::to_value(git)?);
+            }
+        }
     }
+    Ok(())
 }
 
-#[derive(Debug, Args)]
-pub struct EmitArgs {
-    /// Event type (e.g. post_tool_use, stop)
-    pub event_type: String,
+/// `host`, `os`, and `user` for the machine running the hook. Each one is
+/// left out if it cannot be determined.
+fn host_metadata() -> Map<String, Value> {
+    let mut meta = Map::new();
+    let host = gethostname::gethostname()
+        .to_string_lossy()
+        .trim()
+        .to_string();
+    if !host.is_empty() {
+        meta.insert("host".to_string(), Value::String(host));
+    }
+    meta.insert(
+        "os".to_string(),
+        Value::String(format!(
+            "{}/{}",
+            std::env::consts::OS,
+            std::env::consts::ARCH
+        )),
+    );
+    let user = ["USER", "USERNAME", "LOGNAME"]
+        .iter()
+        .find_map(|name| std::env::var(name).ok().filter(|value| !value.is_empty()));
+    if let Some(user) = user {
+        meta.insert("user".to_string(), Value::String(user));
+    }
+    meta
 }
 
-pub async fn run_emit(args: EmitArgs) {
-    let _ = emit_inner(args).await;
+/// The first known source from `--source`, the payload's `source`, and
+/// `PULSE_SOURCE`, in that order. When none of them is set, `detect` gets a
+/// say, and Claude Code is assumed after that.
+fn normalized_source(
+    flag: Option<String>,
+    payload: Option<String>,
+    env: Option<String>,
+    detect: impl FnOnce() -> Option<&'static str>,
+) -> String {
+    [flag, payload, env]
+        .into_iter()
+        .flatten()
+        .find(|source| TOOL_IDS.contains(&source.as_str()))
+        .or_else(|| detect().map(str::to_string))
+        .unwrap_or_else(|| CLAUDE_SOURCE.to_string())
+}
+
+/// Returns the raw event JSON from `--payload`, `--payload-file`, or stdin.
+fn read_payload(args: &EmitArgs, timeout: Duration) -> Result<String> {
+    if let Some(payload) = &args.payload {
+        return Ok(payload.clone());
+    }
+    if let Some(path) = &args.payload_file {
+        return Ok(fs::read_to_string(path)?);
+    }
+    with_timeout(timeout, || {
+        let mut stdin = String::new();
+        io::stdin().read_to_string(&mut stdin).map(|_| stdin)
+    })?
+    .ok_or_else(|| stdin_timed_out(timeout))
 }
 
-fn normalized_source(source: Option<String>) -> String {
-    match source.as_deref() {
-        Some("claude_code" | "opencode" | "openclaw") => source.unwrap(),
-        _ => CLAUDE_SOURCE.to_string(),
+/// Runs a blocking stdin read on its own thread and waits at most `timeout`
+/// for it. A hook run with nothing piped in (or a terminal) would otherwise
+/// block forever, and the agent with it. `None` means the time ran out; the
+/// reader stays blocked until the process exits.
+fn with_timeout<T: Send + 'static>(
+    timeout: Duration,
+    read: impl FnOnce() -> io::Result<T> + Send + 'static,
+) -> Result<Option<T>> {
+    let (sender, receiver) = mpsc::channel();
+    thread::spawn(move || {
+        let _ = sender.send(read());
+    });
+    match receiver.recv_timeout(timeout) {
+        Ok(result) => Ok(Some(result?)),
+        Err(_) => Ok(None),
     }
 }
 
+fn stdin_timed_out(timeout: Duration) -> PulseError {
+    PulseError::message(format!(
+        "no event on stdin after {} ms; pipe the event JSON in, or use --payload",
+        timeout.as_millis()
+    ))
+}
+
+/// How `build_span` treats one event beyond the payload itself.
+pub struct BuildOptions<'a> {
+    /// Source from `--source`; wins over the payload's own `source` and
+    /// `PULSE_SOURCE`.
+    pub source: Option<String>,
+    /// Correlation state for tool durations and session parents. Without it
+    /// the span is built without touching anything on disk.
+    pub store: Option<&'a CorrelationStore>,
+    /// Apply `sample_rate`.
+    pub sample: bool,
+    /// Work out the source from the calling agent's processes and
+    /// environment when nothing names it. Only right for live hook events.
+    pub autodetect: bool,
+}
+
+/// Turns one raw hook event into the span `emit` would send: extraction,
+/// metadata, redaction, truncation, and correlation. Returns `None` when the
+/// event has no session, is sampled out, or does not map onto a span.
+pub fn build_span(
+    event_type: &str,
+    payload: &Value,
+    config: &PulseConfig,
+    options: &BuildOptions<'_>,
+) -> Result<Option<SpanPayload>> {
+    Ok(match build_event(event_type, payload, config, options)? {
+        Built::Span(span) => Some(*span),
+        Built::NoSession | Built::Dropped => None,
+    })
+}
+
+/// What `build_event` made of one event.
+enum Built {
+    Span(Box<SpanPayload>),
+    /// No `session_id` once the source's key renames were applied.
+    NoSession,
+    /// Sampled out, or not something that maps onto a span.
+    Dropped,
+}
+
+/// `build_span`, telling a missing session apart from an event dropped on
+/// purpose.
+fn build_event(
+    event_type: &str,
+    payload: &Value,
+    config: &PulseConfig,
+    options: &BuildOptions<'_>,
+) -> Result<Built> {
+    // The source picks the key renames, so it is settled before extraction.
+    let source = normalized_source(
+        options.source.clone(),
+        payload
+            .get("source")
+            .and_then(Value::as_str)
+            .filter(|source| !source.is_empty())
+            .map(str::to_string),
+        std::env::var("PULSE_SOURCE").ok(),
+        || options.autodetect.then(source::detect).flatten(),
+    );
+    let canonical = span::canonical_payload(&source, payload, config.field_map(&source));
+    let mut fields = span::extract(event_type, &canonical);
+
+    merge_metadata(&mut fields, config, payload)?;
+
+    if config.redaction.is_enabled() {
+        let redactor = Redactor::lenient(&config.redaction.patterns);
+        for value in [
+            fields.tool_input.as_mut(),
+            fields.tool_response.as_mut(),
+            fields.error.as_mut(),
+            fields.metadata.as_mut(),
+        ]
+        .into_iter()
+        .flatten()
+        {
+            redactor.redact_value(value);
+        }
+    }
+
+    // After redaction, so a secret is never cut in half and missed.
+    fields.truncate_large_fields(config.max_field_bytes());
+
+    let now = payload_timestamp(payload).unwrap_or_else(Utc::now);
+    let duration_ms = options.store.and_then(|store| {
+        span::correlate_tool_duration(store, event_type, fields.tool_use_id.as_deref(), now)
+    });
+
+    let Some(session_id) = fields.session_id.clone() else {
+        return Ok(Built::NoSession);
+    };
+    if options.sample
+        && !sampling::should_keep(event_type, &session_id, sampling::sample_rate(config))
+    {
+        return Ok(Built::Dropped);
+    }
+
+    let span_id = match (event_type, fields.agent_id.as_deref()) {
+        ("session_start", _) => span::session_span_id(&session_id),
+        ("subagent_start", Some(agent_id)) => span::agent_span_id(&session_id, agent_id),
+        _ => Uuid::new_v4().to_string(),
+    };
+    let parent_span_id = options
+        .store
+        .and_then(|store| span::link_session(store, event_type, &session_id));
+
+    let Some(mut span) = fields.into_span(
+        span_id,
+        now.to_rfc3339(),
+        event_type.to_string(),
+        source,
+        parent_span_id,
+        config.mapping_overrides(),
+    ) else {
+        return Ok(Built::Dropped);
+    };
+    span.duration_ms = duration_ms.or_else(|| {
+        options
+            .store
+            .and_then(|store| span::correlate_session_duration(store, event_type, &session_id, now))
+    });
+    Ok(Built::Span(Box::new(span)))
+}
+
+/// The payload's own `timestamp` (RFC 3339), so replayed or imported events
+/// keep their original time. A value that does not parse is noted in the
+/// debug log and ignored.
+fn payload_timestamp(payload: &Value) -> Option<DateTime<Utc>> {
+    let raw = payload.get("timestamp")?;
+    let parsed = raw
+        .as_str()
+        .and_then(|text| DateTime::parse_from_rfc3339(text).ok());
+    if parsed.is_none() {
+        debug::log_note(&format!(
+            "ignoring payload timestamp {raw}: not RFC 3339; using the current time"
+        ));
+    }
+    parsed.map(|time| time.with_timezone(&Utc))
+}
+
 async fn emit_inner(args: EmitArgs) -> Result<()> {
-    let event_type = args.event_type.trim().to_string();
+    let started = Instant::now();
+    let mut entry = EmitLogEntry::new(args.event_type.as_deref().unwrap_or_default().trim());
+    let result = emit_event(args, &mut entry).await;
+    entry.finish(&result, started.elapsed());
+    emit_log::append(&entry);
+    result.map(drop)
+}
+
+/// Turns one hook event into a span and sends or queues it, noting the
+/// span and any HTTP status in `entry` on the way.
+async fn emit_event(args: EmitArgs, entry: &mut EmitLogEntry) -> Result<Outcome> {
+    let event_type = entry.event_type.clone();
     if event_type.is_empty() {
-        return Ok(());
+        return Err(PulseError::message("event type is empty"));
+    }
+
+    let config = ConfigStore::load()?;
+    let stdin_timeout = args
+        .stdin_timeout
+        .map(Duration::from_millis)
+        .unwrap_or_else(|| config.stdin_timeout());
+    if !config.events.allows(&event_type) {
+        // Skip before parsing anything, but still drain stdin so the
+        // agent writing the payload never sees a broken pipe.
+        if args.payload.is_none() && args.payload_file.is_none() {
+            with_timeout(stdin_timeout, || {
+                io::copy(&mut io::stdin(), &mut io::sink())
+            })?;
+        }
+        return Ok(Outcome::Filtered);
+    }
+    let input = read_payload(&args, stdin_timeout)?;
+
+    let span = match span_from_input(&event_type, &input, &config, &args) {
+        Ok(Some(span)) => span,
+        Ok(None) => return Ok(Outcome::Dropped),
+        Err(err) => {
+            // Dropping the event silently would hide a broken hook, so
+            // report it as a span of its own when asked to.
+            if config.capture_emit_errors() {
+                let span = cli_error_span(&event_type, &input, &err, &config, args.source.clone());
+                let _ = deliver(&config, vec![span]).await;
+            }
+            return Err(err);
+        }
+    };
+    let session_id = span.session_id.clone();
+    entry.source = Some(span.source.clone());
+    entry.span_id = Some(span.span_id.clone());
+    // Local history is a convenience for `pulse sessions`; never fail on it.
+    if let Ok(history) = SpanHistory::new() {
+        let _ = history.record(std::slice::from_ref(&span));
     }
 
-    let config = match ConfigStore::load() {
-        Ok(cfg) => cfg,
-        Err(_) => return Ok(()),
+    let flush_event = matches!(event_type.as_str(), "stop" | "session_end");
+    let spans = if config.buffer_enabled() {
+        let buffer = SpanBuffer::new()?.for_session(&session_id);
+        buffer.enqueue(&span)?;
+        let full = buffer.len()? >= config.buffer_max_spans();
+        if !(args.flush || flush_event || full) {
+            return Ok(Outcome::Queued);
+        }
+        // The session is over on a flush event, so its buffer goes too.
+        if flush_event {
+            buffer.take()?
+        } else {
+            buffer.drain()?
+        }
+    } else {
+        vec![span]
     };
 
-    let mut stdin = String::new();
-    if io::stdin().read_to_string(&mut stdin).is_err() {
-        return Ok(());
+    let delivery = deliver(&config, spans).await?;
+    if let Delivery::Failed { error, .. } = &delivery {
+        entry.http_status = error.http_status().map(|status| status.as_u16());
     }
+    delivery.into_result()
+}
+
+/// `pulse emit --batch`: builds a span for every line of NDJSON and sends
+/// them in one request. Session buffering does not apply, since the spans
+/// already go out together. Lines that cannot be turned into a span are
+/// skipped, and reported as an error after the rest are sent. Every line
+/// gets its own entry in the emit log.
+async fn emit_batch(args: EmitArgs) -> Result<()> {
+    let started = Instant::now();
+    let config = ConfigStore::load()?;
+    // Meant for importing a recorded backlog by hand, so there is no stdin
+    // timeout: the input may take a while to arrive.
+    let input = match (&args.payload, &args.payload_file) {
+        (Some(payload), _) => payload.clone(),
+        (None, Some(path)) => fs::read_to_string(path)?,
+        (None, None) => {
+            let mut stdin = String::new();
+            io::stdin().read_to_string(&mut stdin)?;
+            stdin
+        }
+    };
+
+    let store = CorrelationStore::new().ok();
+    let options = BuildOptions {
+        source: args.source.clone(),
+        store: store.as_ref(),
+        sample: true,
+        // Imported events were usually not sent by the process that runs
+        // pulse, so only guess when asked to.
+        autodetect: args.source_autodetect.unwrap_or(false),
+    };
+    let batch = spans_from_ndjson(
+        &input,
+        args.default_event_type.as_deref(),
+        &config,
+        &options,
+    );
+    let mut entries = batch.entries;
+    let result = if batch.spans.is_empty() {
+        Ok(Outcome::Sent)
+    } else {
+        if let Ok(history) = SpanHistory::new() {
+            let _ = history.record(&batch.spans);
+        }
+        let delivery = deliver(&config, batch.spans).await;
+        let http_status = match &delivery {
+            Ok(Delivery::Failed { error, .. }) => error.http_status().map(|s| s.as_u16()),
+            _ => None,
+        };
+        let result = delivery.and_then(Delivery::into_result);
+        for entry in entries.iter_mut().filter(|entry| entry.span_id.is_some()) {
+            entry.http_status = http_status;
+            entry.finish(&result, Duration::ZERO);
+        }
+        result
+    };
+    let elapsed = started.elapsed().as_millis() as u64;
+    for entry in &mut entries {
+        entry.duration_ms = elapsed;
+        emit_log::append(entry);
+    }
+    result?;
+    match batch.malformed.as_slice() {
+        [] => Ok(()),
+        [first, ..] => Err(PulseError::message(format!(
+            "skipped {} of {} lines ({first}{})",
+            batch.malformed.len(),
+            batch.lines,
+            if batch.malformed.len() > 1 {
+                "; ..."
+            } else {
+                ""
+            }
+        ))),
+    }
+}
+
+/// The spans built from an NDJSON batch.
+#[derive(Debug, Default)]
+struct Batch {
+    spans: Vec<SpanPayload>,
+    /// Non-blank lines read.
+    lines: usize,
+    /// Why each skipped line was skipped, with its line number.
+    malformed: Vec<String>,
+    /// One emit log entry per line. Those with a `span_id` get their
+    /// outcome once the spans are delivered.
+    entries: Vec<EmitLogEntry>,
+}
+
+impl Batch {
+    fn skip(&mut self, mut entry: EmitLogEntry, reason: String) {
+        entry.finish(&Err(PulseError::message(&reason)), Duration::ZERO);
+        self.entries.push(entry);
+        self.malformed.push(reason);
+    }
+
+    fn leave_out(&mut self, mut entry: EmitLogEntry, outcome: Outcome) {
+        entry.finish(&Ok(outcome), Duration::ZERO);
+        self.entries.push(entry);
+    }
+}
+
+/// Builds one span per line of `input`. Each line is an event object whose
+/// `event_type` field, or else `default_event_type`, says what it is.
+/// Events filtered out by `[events]` or sampling are dropped without
+/// counting as malformed.
+fn spans_from_ndjson(
+    input: &str,
+    default_event_type: Option<&str>,
+    config: &PulseConfig,
+    options: &BuildOptions<'_>,
+) -> Batch {
+    let mut batch = Batch::default();
+    for (index, line) in input.lines().enumerate() {
+        if line.trim().is_empty() {
+            continue;
+        }
+        batch.lines += 1;
+        let number = index + 1;
+        let mut entry = EmitLogEntry::new("");
+        let payload: Value = match serde_json::from_str(line) {
+            Ok(payload @ Value::Object(_)) => payload,
+            Ok(_) => {
+                batch.skip(entry, format!("line {number}: not a JSON object"));
+                continue;
+            }
+            Err(err) => {
+                batch.skip(entry, format!("line {number}: not valid JSON: {err}"));
+                continue;
+            }
+        };
+        entry.event_type = payload
+            .get("event_type")
+            .and_then(Value::as_str)
+            .or(default_event_type)
+            .map(str::trim)
+            .unwrap_or_default()
+            .to_string();
+        let event_type = entry.event_type.clone();
+        if event_type.is_empty() {
+            batch.skip(
+                entry,
+                format!("line {number}: no `event_type` field and no --event-type"),
+            );
+            continue;
+        }
+        if !config.events.allows(&event_type) {
+            batch.leave_out(entry, Outcome::Filtered);
+            continue;
+        }
+        debug::log_event(&event_type, &payload);
+        match build_event(&event_type, &payload, config, options) {
+            Ok(Built::Span(span)) => {
+                entry.source = Some(span.source.clone());
+                entry.span_id = Some(span.span_id.clone());
+                batch.entries.push(entry);
+                batch.spans.push(*span);
+            }
+            Ok(Built::NoSession) => batch.skip(entry, format!("line {number}: no session_id")),
+            Ok(Built::Dropped) => batch.leave_out(entry, Outcome::Dropped),
+            Err(err) => batch.skip(entry, format!("line {number}: {err}")),
+        }
+    }
+    batch
+}
 
-    if stdin.trim().is_empty() {
-        return Ok(());
+/// Parses the raw event and builds its span. `None` means the event was
+/// dropped on purpose, e.g. by sampling.
+fn span_from_input(
+    event_type: &str,
+    input: &str,
+    config: &PulseConfig,
+    args: &EmitArgs,
+) -> Result<Option<SpanPayload>> {
+    if input.trim().is_empty() {
+        return Err(PulseError::message("no event payload on stdin"));
     }
+    let payload: Value = serde_json::from_str(input)
+        .map_err(|err| PulseError::message(format!("event payload is not valid JSON: {err}")))?;
+
+    debug::log_event(event_type, &payload);
 
-    let payload: Value = match serde_json::from_str(&stdin) {
-        Ok(value) => value,
-        Err(_) => return Ok(()),
+    let store = CorrelationStore::new().ok();
+    let options = BuildOptions {
+        source: args.source.clone(),
+        store: store.as_ref(),
+        sample: true,
+        autodetect: args
+            .source_autodetect
+            .unwrap_or_else(|| source::enabled(config)),
     };
+    // Sampled-out events are dropped on purpose; a missing session is not.
+    match build_event(event_type, &payload, config, &options)? {
+        Built::Span(span) => Ok(Some(*span)),
+        Built::NoSession => Err(PulseError::message(
+            "event has no session_id (or conversation_id); nothing sent",
+        )),
+        Built::Dropped => Ok(None),
+    }
+}
+
+/// What became of the spans given to `deliver`.
+enum Delivery {
+    Sent,
+    /// Handed to a running daemon, or queued for the detached flusher.
+    Queued,
+    /// Kept in the offline queue after the trace service could not take
+    /// them.
+    Failed {
+        spans: usize,
+        error: PulseError,
+    },
+}
 
-    if debug_enabled() {
-        debug_log(&event_type, &payload);
+impl Delivery {
+    fn into_result(self) -> Result<Outcome> {
+        match self {
+            Self::Sent => Ok(Outcome::Sent),
+            Self::Queued => Ok(Outcome::Queued),
+            Self::Failed { spans, error } => Err(PulseError::message(format!(
+                "failed to send {spans} span(s), kept in the offline queue: {error}"
+            ))),
+        }
     }
+}
 
-    let mut fields = span::extract(&event_type, &payload);
+/// Sends spans through a running daemon, the async queue, or directly,
+/// keeping them in the offline queue when a direct send fails.
+async fn deliver(config: &PulseConfig, spans: Vec<SpanPayload>) -> Result<Delivery> {
+    // A running `pulse daemon` sends them over its pooled connection.
+    if daemon::hand_off(&spans).await {
+        return Ok(Delivery::Queued);
+    }
 
-    // Merge cli_version, project_id, and raw event payload into metadata.
-    let meta = fields.metadata.get_or_insert_with(|| json!({}));
-    if !meta.is_object() {
-        *meta = json!({});
+    if async_enabled(config) {
+        // The hook only pays for the append; a detached flusher sends it.
+        SpanQueue::new()?.enqueue_all(&spans)?;
+        start_detached_flush()?;
+        return Ok(Delivery::Queued);
     }
-    if let Some(obj) = meta.as_object_mut() {
-        obj.insert(
-            "cli_version".to_string(),
-            Value::String(env!("CARGO_PKG_VERSION").to_string()),
+
+    // A broken breaker file must not stop the send, so its errors are
+    // ignored throughout.
+    let breaker = CircuitBreaker::new(config).ok().flatten();
+    if let Some(breaker) = &breaker
+        && breaker.decide().ok() == Some(Decision::Skip)
+    {
+        SpanQueue::new()?.enqueue_all(&spans)?;
+        return Err(PulseError::message(format!(
+            "the trace service has been failing, kept {} span(s) in the offline queue without trying",
+            spans.len()
+        )));
+    }
+
+    let exporter = SpanExporter::new(config)?;
+    let result = exporter.export(&spans).await;
+    if let Some(breaker) = &breaker {
+        let _ = match &result {
+            Ok(_) => breaker.record_success(),
+            Err(err) if err.is_transient() => breaker.record_failure(),
+            Err(_) => Ok(()),
+        };
+    }
+    if let Err(error) = result {
+        // Keep the spans for a later `pulse flush` instead of losing them.
+        if let Ok(queue) = SpanQueue::new() {
+            let _ = queue.enqueue_all(&spans);
+        }
+        return Ok(Delivery::Failed {
+            spans: spans.len(),
+            error,
+        });
+    }
+
+    Ok(Delivery::Sent)
+}
+
+/// The `cli_error` span for input `emit` could not turn into a span. It
+/// carries the error and the start of the raw input (redacted like any other
+/// span), under the session id salvaged from the input when there is one.
+fn cli_error_span(
+    event_type: &str,
+    input: &str,
+    error: &PulseError,
+    config: &PulseConfig,
+    source: Option<String>,
+) -> SpanPayload {
+    let redacted = config
+        .redaction
+        .is_enabled()
+        .then(|| Redactor::lenient(&config.redaction.patterns).redact_str(input))
+        .flatten();
+    let input = redacted.as_deref().unwrap_or(input);
+    let mut end = input.len().min(CLI_ERROR_INPUT_BYTES);
+    while !input.is_char_boundary(end) {
+        end -= 1;
+    }
+
+    SpanPayload {
+        span_id: Uuid::new_v4().to_string(),
+        session_id: salvage_session_id(input).unwrap_or_else(|| CLI_ERROR_SESSION_ID.to_string()),
+        parent_span_id: None,
+        timestamp: Utc::now().to_rfc3339(),
+        duration_ms: None,
+        source: normalized_source(source, None, std::env::var("PULSE_SOURCE").ok(), || {
+            source::enabled(config).then(source::detect).flatten()
+        }),
+        kind: "session".to_string(),
+        event_type: CLI_ERROR_EVENT_TYPE.to_string(),
+        status: "error".to_string(),
+        tool_use_id: None,
+        tool_name: None,
+        tool_input: None,
+        tool_response: None,
+        error: Some(Value::String(error.to_string())),
+        is_interrupt: None,
+        cwd: None,
+        tool_cwd: None,
+        model: None,
+        agent_name: None,
+        metadata: Some(json!({
+            "cli_version": env!("CARGO_PKG_VERSION"),
+            "project_id": config.project_id,
+            "emit_event_type": event_type,
+            "error": error.to_string(),
+            "raw_input": &input[..end],
+            "raw_input_bytes": input.len(),
+        })),
+    }
+}
+
+/// The `session_id` (or `conversation_id`) of an event, read from the raw
+/// text so it works even when the JSON around it is broken.
+fn salvage_session_id(input: &str) -> Option<String> {
+    static SESSION_ID: OnceLock<Regex> = OnceLock::new();
+    let pattern = SESSION_ID.get_or_init(|| {
+        Regex::new(r#""(?:session_id|conversation_id)"\s*:\s*"([^"\\]+)""#)
+            .expect("session id pattern is valid")
+    });
+    pattern
+        .captures(input)
+        .map(|caps| caps[1].to_string())
+        .filter(|id| !id.trim().is_empty())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::EventsConfig;
+
+    #[test]
+    fn test_normalized_source_accepts_known_tools() {
+        for source in TOOL_IDS {
+            assert_eq!(
+                normalized_source(Some(source.to_string()), None, None, || None),
+                *source
+            );
+        }
+        assert_eq!(
+            normalized_source(Some("vim".to_string()), None, None, || None),
+            CLAUDE_SOURCE
         );
-        obj.insert(
-            "project_id".to_string(),
-            Value::String(config.project_id.clone()),
+        assert_eq!(normalized_source(None, None, None, || None), CLAUDE_SOURCE);
+        assert_eq!(
+            normalized_source(None, None, None, || Some("gemini")),
+            "gemini"
         );
-        obj.insert("raw".to_string(), payload.clone());
     }
 
-    let source = normalized_source(fields.source.take());
+    #[test]
+    fn test_normalized_source_precedence() {
+        let some = |source: &str| Some(source.to_string());
+        assert_eq!(
+            normalized_source(some("cursor"), some("opencode"), some("gemini"), || None),
+            "cursor"
+        );
+        assert_eq!(
+            normalized_source(None, some("opencode"), some("gemini"), || None),
+            "opencode"
+        );
+        assert_eq!(
+            normalized_source(None, None, some("gemini"), || Some("codex")),
+            "gemini"
+        );
+        // An unknown value is skipped rather than ending the chain.
+        assert_eq!(
+            normalized_source(None, some("vim"), some("openclaw"), || None),
+            "openclaw"
+        );
+        assert_eq!(
+            normalized_source(None, None, some("vim"), || None),
+            CLAUDE_SOURCE
+        );
+    }
 
-    let span = match fields.into_span(
-        Uuid::new_v4().to_string(),
-        Utc::now().to_rfc3339(),
-        event_type,
-        source.clone(),
-    ) {
-        Some(s) => s,
-        None => return Ok(()),
-    };
+    fn args(event_type: &str, strict: bool) -> EmitArgs {
+        EmitArgs {
+            event_type: Some(event_type.to_string()),
+            batch: false,
+            default_event_type: None,
+            payload: Some("{}".to_string()),
+            payload_file: None,
+            flush: false,
+            source: None,
+            strict,
+            source_autodetect: None,
+            stdin_timeout: None,
+        }
+    }
 
-    let client = match TraceHttpClient::new(&config) {
-        Ok(client) => client,
-        Err(_) => return Ok(()),
-    };
+    #[test]
+    fn test_read_gives_up_after_timeout() {
+        let fast = with_timeout(Duration::from_secs(5), || Ok("{}".to_string())).unwrap();
+        assert_eq!(fast.as_deref(), Some("{}"));
+
+        // A pipe whose writer stays open never reaches end of file.
+        let (mut reader, writer) = io::pipe().unwrap();
+        let started = std::time::Instant::now();
+        let stuck = with_timeout(Duration::from_millis(50), move || {
+            let mut input = String::new();
+            reader.read_to_string(&mut input).map(|_| input)
+        })
+        .unwrap();
+        assert!(stuck.is_none());
+        assert!(started.elapsed() < Duration::from_secs(2));
+        drop(writer);
+    }
 
-    let _ = client.post_spans(&[span]).await;
+    #[tokio::test]
+    async fn test_strict_mode_reports_failures() {
+        assert!(run_emit(args(" ", false)).await.is_ok());
+        let err = run_emit(args(" ", true)).await.unwrap_err();
+        assert!(err.to_string().contains("event type is empty"));
+    }
 
-    Ok(())
+    #[test]
+    fn test_batch_builds_a_span_per_line() {
+        let config = PulseConfig::default();
+        let options = BuildOptions {
+            source: Some("claude_code".to_string()),
+            store: None,
+            sample: false,
+            autodetect: false,
+        };
+        let input = [
+            r#"{"event_type": "session_start", "session_id": "s1"}"#,
+            r#"{"session_id": "s1", "tool_name": "Bash", "tool_use_id": "t1"}"#,
+            "",
+            r#"{"event_type": "stop", "session_id": "s1"}"#,
+        ]
+        .join("\n");
+
+        let batch = spans_from_ndjson(&input, Some("post_tool_use"), &config, &options);
+        assert!(batch.malformed.is_empty(), "{:?}", batch.malformed);
+        assert_eq!(batch.lines, 3);
+        let events: Vec<&str> = batch
+            .spans
+            .iter()
+            .map(|span| span.event_type.as_str())
+            .collect();
+        assert_eq!(events, ["session_start", "post_tool_use", "stop"]);
+        assert_eq!(batch.spans[1].tool_name.as_deref(), Some("Bash"));
+    }
+
+    #[test]
+    fn test_batch_skips_malformed_lines() {
+        let config = PulseConfig::default();
+        let options = BuildOptions {
+            source: None,
+            store: None,
+            sample: false,
+            autodetect: false,
+        };
+        let input = [
+            r#"{"event_type": "stop", "session_id": "s1"}"#,
+            r#"{"event_type": "stop", "session_id": "#,
+            r#"["stop"]"#,
+            r#"{"session_id": "s2"}"#,
+            r#"{"event_type": "stop"}"#,
+        ]
+        .join("\n");
+
+        let batch = spans_from_ndjson(&input, None, &config, &options);
+        assert_eq!(batch.spans.len(), 1);
+        assert_eq!(batch.lines, 5);
+        assert_eq!(batch.malformed.len(), 4);
+        assert!(batch.malformed[0].starts_with("line 2: not valid JSON"));
+        assert_eq!(batch.malformed[1], "line 3: not a JSON object");
+        assert!(batch.malformed[2].contains("no `event_type`"));
+        assert_eq!(batch.malformed[3], "line 5: no session_id");
+
+        let logged: Vec<(&str, Outcome, bool)> = batch
+            .entries
+            .iter()
+            .map(|entry| {
+                (
+                    entry.event_type.as_str(),
+                    entry.outcome,
+                    entry.span_id.is_some(),
+                )
+            })
+            .collect();
+        assert_eq!(
+            logged,
+            [
+                ("stop", Outcome::Error, true),
+                ("", Outcome::Error, false),
+                ("", Outcome::Error, false),
+                ("", Outcome::Error, false),
+                ("stop", Outcome::Error, false),
+            ]
+        );
+        assert_eq!(
+            batch.entries[4].error.as_deref(),
+            Some("line 5: no session_id")
+        );
+    }
+
+    #[test]
+    fn test_batch_logs_filtered_and_sampled_out_lines() {
+        let config = PulseConfig {
+            sample_rate: Some(0.0),
+            events: EventsConfig {
+                disabled: vec!["notification".to_string()],
+                ..EventsConfig::default()
+            },
+            ..PulseConfig::default()
+        };
+        let options = BuildOptions {
+            source: None,
+            store: None,
+            sample: true,
+            autodetect: false,
+        };
+        let input = [
+            r#"{"event_type": "notification", "session_id": "s1"}"#,
+            r#"{"event_type": "post_tool_use", "session_id": "s1"}"#,
+        ]
+        .join("\n");
+
+        let batch = spans_from_ndjson(&input, None, &config, &options);
+        let outcomes: Vec<Outcome> = batch.entries.iter().map(|entry| entry.outcome).collect();
+        assert_eq!(outcomes, [Outcome::Filtered, Outcome::Dropped]);
+        assert!(batch.malformed.is_empty());
+    }
+
+    #[test]
+    fn test_sampled_out_events_are_not_missing_a_session() {
+        let config = PulseConfig {
+            sample_rate: Some(0.0),
+            ..PulseConfig::default()
+        };
+        let options = BuildOptions {
+            source: Some("opencode".to_string()),
+            store: None,
+            sample: true,
+            autodetect: false,
+        };
+        // OpenCode names the session `sessionID`; only the source's key
+        // renames turn it into `session_id`.
+        let input = r#"{"tool": "bash", "sessionID": "ses_1", "callID": "c1"}"#;
+
+        let batch = spans_from_ndjson(input, Some("post_tool_use"), &config, &options);
+        assert!(batch.malformed.is_empty(), "{:?}", batch.malformed);
+        assert!(batch.spans.is_empty());
+    }
+
+    #[test]
+    fn test_cli_error_span_salvages_invalid_json() {
+        let config = PulseConfig {
+            project_id: "proj_1".to_string(),
+            ..PulseConfig::default()
+        };
+        let input = r#"{"session_id": "sess_9", "tool_input": {"command": "ls"#;
+        let err = serde_json::from_str::<Value>(input)
+            .map_err(|err| PulseError::message(format!("event payload is not valid JSON: {err}")))
+            .unwrap_err();
+
+        let span = cli_error_span("post_tool_use", input, &err, &config, None);
+        assert_eq!(span.event_type, CLI_ERROR_EVENT_TYPE);
+        assert_eq!(span.session_id, "sess_9");
+        assert_eq!(span.status, "error");
+        let metadata = span.metadata.unwrap();
+        assert_eq!(metadata["emit_event_type"], "post_tool_use");
+        assert_eq!(metadata["raw_input"], input);
+        assert_eq!(metadata["project_id"], "proj_1");
+        assert!(
+            metadata["error"]
+                .as_str()
+                .unwrap()
+                .starts_with("event payload is not valid JSON")
+        );
+    }
+
+    #[test]
+    fn test_cli_error_span_truncates_input_and_falls_back_to_placeholder() {
+        let config = PulseConfig::default();
+        let input = format!("not json {}", "é".repeat(CLI_ERROR_INPUT_BYTES));
+        let err = PulseError::message("event payload is not valid JSON");
+
+        let span = cli_error_span("stop", &input, &err, &config, Some("cursor".to_string()));
+        assert_eq!(span.session_id, CLI_ERROR_SESSION_ID);
+        assert_eq!(span.source, "cursor");
+        let metadata = span.metadata.unwrap();
+        let kept = metadata["raw_input"].as_str().unwrap();
+        assert!(kept.len() <= CLI_ERROR_INPUT_BYTES);
+        assert!(input.starts_with(kept));
+        assert_eq!(metadata["raw_input_bytes"], input.len());
+    }
+
+    #[test]
+    fn test_salvage_session_id() {
+        assert_eq!(
+            salvage_session_id(r#"{"conversation_id" : "c1", "#).as_deref(),
+            Some("c1")
+        );
+        assert_eq!(salvage_session_id(r#"{"session_id": ""}"#), None);
+        assert_eq!(salvage_session_id("garbage"), None);
+    }
+
+    #[test]
+    fn test_host_metadata_has_os() {
+        let meta = host_metadata();
+        let os = meta["os"].as_str().unwrap();
+        assert!(os.starts_with(std::env::consts::OS));
+        assert!(
+            meta.keys()
+                .all(|key| ["host", "os", "user"].contains(&key.as_str()))
+        );
+    }
+
+    #[test]
+    fn test_subagent_start_is_the_parent_of_its_tools() {
+        let config = PulseConfig::default();
+        let options = BuildOptions {
+            source: None,
+            store: None,
+            sample: false,
+            autodetect: false,
+        };
+        let build = |event_type: &str, payload: Value| {
+            build_span(event_type, &payload, &config, &options)
+                .unwrap()
+                .unwrap()
+        };
+
+        let agent = build(
+            "subagent_start",
+            json!({ "session_id": "s1", "agent_id": "a1", "agent_type": "Explore" }),
+        );
+        let tool = build(
+            "post_tool_use",
+            json!({ "session_id": "s1", "agent_id": "a1", "tool_name": "Grep" }),
+        );
+        assert_eq!(tool.parent_span_id, Some(agent.span_id));
+
+        let top_level = build(
+            "post_tool_use",
+            json!({ "session_id": "s1", "tool_name": "Grep" }),
+        );
+        assert_eq!(top_level.parent_span_id, None);
+    }
+
+    #[test]
+    fn test_payload_timestamp_overrides_now() {
+        let config = PulseConfig::default();
+        let options = BuildOptions {
+            source: None,
+            store: None,
+            sample: false,
+            autodetect: false,
+        };
+        let payload = json!({ "session_id": "s1", "timestamp": "2024-03-05T10:20:30.5+02:00" });
+        let span = build_span("stop", &payload, &config, &options)
+            .unwrap()
+            .unwrap();
+        assert_eq!(span.timestamp, "2024-03-05T08:20:30.500+00:00");
+
+        for bad in [json!("yesterday"), json!(1709634030)] {
+            let before = Utc::now();
+            let payload = json!({ "session_id": "s1", "timestamp": bad });
+            let span = build_span("stop", &payload, &config, &options)
+                .unwrap()
+                .unwrap();
+            let stamped = DateTime::parse_from_rfc3339(&span.timestamp).unwrap();
+            assert!(stamped >= before, "{bad}");
+        }
+    }
+
+    #[test]
+    fn test_include_raw_off_omits_the_payload_copy() {
+        let options = BuildOptions {
+            source: None,
+            store: None,
+            sample: false,
+            autodetect: false,
+        };
+        let payload = json!({
+            "session_id": "s1",
+            "tool_name": "Bash",
+            "tool_response": { "stdout": "ok" },
+        });
+
+        let config = PulseConfig::default();
+        let span = build_span("post_tool_use", &payload, &config, &options)
+            .unwrap()
+            .unwrap();
+        assert_eq!(span.metadata.unwrap()["raw"], payload);
+
+        let config = PulseConfig {
+            include_raw: Some(false),
+            ..PulseConfig::default()
+        };
+        let span = build_span("post_tool_use", &payload, &config, &options)
+            .unwrap()
+            .unwrap();
+        assert_eq!(span.tool_response, Some(json!({ "stdout": "ok" })));
+        let metadata = span.metadata.unwrap();
+        assert!(metadata.get("raw").is_none());
+        assert_eq!(metadata["cli_version"], env!("CARGO_PKG_VERSION"));
+    }
+
+    #[test]
+    fn test_plugin_assistant_message_round_trips() {
+        // The payload the OpenCode plugin sends for an assistant turn.
+        let payload = json!({
+            "session_id": "ses_1",
+            "cwd": "/work",
+            "model": "claude-sonnet-4",
+            "tokens": { "input": 120, "output": 40, "cache": { "read": 7, "write": 0 } },
+            "cost": 0.003,
+            "source": "opencode",
+        });
+        let mut fields = span::extract("assistant_message", &payload);
+        let source = normalized_source(None, fields.source.take(), None, || None);
+        let span = fields
+            .into_span(
+                "span_1".to_string(),
+                "2025-01-01T00:00:00+00:00".to_string(),
+                "assistant_message".to_string(),
+                source,
+                None,
+                span::MappingOverrides::default(),
+            )
+            .unwrap();
+
+        assert_eq!(span.source, "opencode");
+        assert_eq!(span.kind, "llm_response");
+        assert_eq!(span.model.as_deref(), Some("claude-sonnet-4"));
+        let usage = &span.metadata.unwrap()["usage"];
+        assert_eq!(usage["input_tokens"], 120);
+        assert_eq!(usage["output_tokens"], 40);
+        assert_eq!(usage["cache_read_tokens"], 7);
+        assert_eq!(usage["cost"], 0.003);
+    }
+
+    #[test]
+    fn test_field_map_from_config_renames_payload_keys() {
+        let mut config = PulseConfig::default();
+        config.field_map.insert(
+            "cursor".to_string(),
+            [("toolLabel".to_string(), "tool_name".to_string())].into(),
+        );
+        let payload = json!({
+            "conversation_id": "conv_1",
+            "toolLabel": "Shell",
+            "sessionID": "ignored for cursor",
+        });
+        let options = BuildOptions {
+            source: Some("cursor".to_string()),
+            store: None,
+            sample: false,
+            autodetect: false,
+        };
+
+        let span = build_span("pre_tool_use", &payload, &config, &options)
+            .unwrap()
+            .unwrap();
+        assert_eq!(span.session_id, "conv_1");
+        assert_eq!(span.tool_name.as_deref(), Some("Shell"));
+        // The raw copy keeps the payload as it was sent.
+        assert_eq!(span.metadata.unwrap()["raw"]["toolLabel"], "Shell");
+    }
+
+    #[test]
+    fn test_kind_and_status_overrides_from_config() {
+        let tmp = tempfile::TempDir::new().unwrap();
+        let path = tmp.path().join("config.toml");
+        std::fs::write(
+            &path,
+            "api_url = \"http://x\"\napi_key = \"k\"\nproject_id = \"p\"\n\n\
+             [kinds]\ncli_ping = \"diagnostic\"\nnotification = \"alert\"\n\n\
+             [statuses]\ncli_ping = \"unknown\"\n",
+        )
+        .unwrap();
+        let config = ConfigStore::load_from(&path, |_| None).unwrap();
+        let options = BuildOptions {
+            source: Some("claude_code".to_string()),
+            store: None,
+            sample: false,
+            autodetect: false,
+        };
+        let build = |event_type: &str| {
+            build_span(event_type, &json!({"session_id": "s1"}), &config, &options)
+                .unwrap()
+                .unwrap()
+        };
+
+        let ping = build("cli_ping");
+        assert_eq!(
+            (ping.kind.as_str(), ping.status.as_str()),
+            ("diagnostic", "unknown")
+        );
+        assert_eq!(build("notification").kind, "alert");
+        // Event types the config leaves out keep the built-in mapping.
+        let failure = build("post_tool_use_failure");
+        assert_eq!(
+            (failure.kind.as_str(), failure.status.as_str()),
+            ("tool_use", "error")
+        );
+        assert_eq!(build("some_new_event").kind, "session");
+
+        std::fs::write(
+            &path,
+            "api_url = \"x\"\napi_key = \"k\"\nproject_id = \"p\"\n[kinds]\nstop = \" \"\n",
+        )
+        .unwrap();
+        let err = ConfigStore::load_from(&path, |_| None).unwrap_err();
+        assert!(err.to_string().contains("`stop`"), "{err}");
+    }
 }
@@ -0,0 +1,200 @@
+use std::{
+    fs::File,
+    io::{self, BufWriter, Write},
+    path::PathBuf,
+};
+
+use chrono::{DateTime, FixedOffset};
+use clap::{Args, ValueEnum};
+use serde_json::Value;
+
+use crate::{
+    error::Result,
+    http::SpanPayload,
+    queue::{SpanBuffer, SpanQueue},
+};
+
+const CSV_COLUMNS: &[&str] = &[
+    "span_id",
+    "session_id",
+    "parent_span_id",
+    "timestamp",
+    "duration_ms",
+    "source",
+    "kind",
+    "event_type",
+    "status",
+    "tool_use_id",
+    "tool_name",
+    "tool_input",
+    "tool_response",
+    "error",
+    "is_interrupt",
+    "cwd",
+    "model",
+    "agent_name",
+    "metadata",
+];
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, ValueEnum)]
+pub enum ExportFormat {
+    #[default]
+    Ndjson,
+    Csv,
+}
+
+#[derive(Debug, Args)]
+pub struct ExportArgs {
+    /// Output format
+    #[arg(long, value_enum, default_value_t)]
+    pub format: ExportFormat,
+    /// Write to this file instead of stdout
+    #[arg(long)]
+    pub out: Option<PathBuf>,
+    /// Only spans at or after this time (RFC 3339, e.g. 2025-01-01T00:00:00Z)
+    #[arg(long, value_parser = DateTime::parse_from_rfc3339)]
+    pub since: Option<DateTime<FixedOffset>>,
+}
+
+/// Writes the spans stored on this machine (the offline queue and session
+/// buffers) without removing them. Spans that were delivered are not kept
+/// locally; `pulse sessions list` summarizes those.
+pub fn run_export(args: ExportArgs) -> Result<()> {
+    let mut spans = SpanQueue::new()?.peek()?;
+    for buffer in SpanBuffer::new()?.sessions()? {
+        spans.extend(buffer.peek()?);
+    }
+    if let Some(since) = args.since {
+        spans.retain(|span| {
+            DateTime::parse_from_rfc3339(&span.timestamp).is_ok_and(|time| time >= since)
+        });
+    }
+    spans.sort_by(|a, b| a.timestamp.cmp(&b.timestamp));
+
+    let mut out: Box<dyn Write> = match &args.out {
+        Some(path) => Box::new(BufWriter::new(File::create(path)?)),
+        None => Box::new(io::stdout().lock()),
+    };
+    match args.format {
+        ExportFormat::Ndjson => {
+            for span in &spans {
+                writeln!(out, "{}", serde_json::to_string(span)?)?;
+            }
+        }
+        ExportFormat::Csv => write_csv(&mut out, &spans)?,
+    }
+    out.flush()?;
+
+    if let Some(path) = &args.out {
+        eprintln!("Exported {} span(s) to {}", spans.len(), path.display());
+    }
+    Ok(())
+}
+
+fn write_csv(out: &mut dyn Write, spans: &[SpanPayload]) -> Result<()> {
+    writeln!(out, "{}", CSV_COLUMNS.join(","))?;
+    for span in spans {
+        let row = csv_row(span)
+            .iter()
+            .map(|field| csv_escape(field))
+            .collect::<Vec<_>>();
+        writeln!(out, "{}", row.join(","))?;
+    }
+    Ok(())
+}
+
+/// One CSV row in `CSV_COLUMNS` order. Nested JSON fields are written as
+/// compact JSON (strings as-is), and missing values as empty cells.
+fn csv_row(span: &SpanPayload) -> Vec<String> {
+    let text = |value: &Option<String>| value.clone().unwrap_or_default();
+    let json = |value: &Option<Value>| match value {
+        Some(Value::String(text)) => text.clone(),
+        Some(value) => value.to_string(),
+        None => String::new(),
+    };
+    vec![
+        span.span_id.clone(),
+        span.session_id.clone(),
+        text(&span.parent_span_id),
+        span.timestamp.clone(),
+        span.duration_ms
+            .map(|ms| ms.to_string())
+            .unwrap_or_default(),
+        span.source.clone(),
+        span.kind.clone(),
+        span.event_type.clone(),
+        span.status.clone(),
+        text(&span.tool_use_id),
+        text(&span.tool_name),
+        json(&span.tool_input),
+        json(&span.tool_response),
+        json(&span.error),
+        span.is_interrupt.map(|b| b.to_string()).unwrap_or_default(),
+        text(&span.cwd),
+        text(&span.model),
+        text(&span.agent_name),
+        json(&span.metadata),
+    ]
+}
+
+/// Quotes a field when it holds a comma, quote, or line break (RFC 4180).
+fn csv_escape(field: &str) -> String {
+    if field.contains([',', '"', '\n', '\r']) {
+        format!("\"{}\"", field.replace('"', "\"\""))
+    } else {
+        field.to_string()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    fn span(id: &str) -> SpanPayload {
+        SpanPayload {
+            kind: "session".to_string(),
+            event_type: "stop".to_string(),
+            ..SpanPayload::test(id)
+        }
+    }
+
+    #[test]
+    fn test_csv_flattens_nested_fields() {
+        let mut tool = span("a");
+        tool.kind = "tool_use".to_string();
+        tool.event_type = "post_tool_use_failure".to_string();
+        tool.status = "error".to_string();
+        tool.tool_name = Some("Bash".to_string());
+        tool.tool_input = Some(json!({ "command": "echo \"hi\", there" }));
+        tool.error = Some(json!("exit 1"));
+        tool.duration_ms = Some(12.5);
+        tool.metadata = Some(json!({ "exit_code": 1 }));
+
+        let mut out = Vec::new();
+        write_csv(&mut out, &[tool, span("b")]).unwrap();
+        let csv = String::from_utf8(out).unwrap();
+        let lines: Vec<&str> = csv.lines().collect();
+
+        assert_eq!(lines.len(), 3);
+        assert_eq!(lines[0], CSV_COLUMNS.join(","));
+        assert_eq!(
+            lines[1],
+            concat!(
+                "a,sess_1,,2025-01-01T00:00:00+00:00,12.5,claude_code,tool_use,",
+                "post_tool_use_failure,error,,Bash,",
+                r#""{""command"":""echo \""hi\"", there""}",,exit 1,,,,,"#,
+                r#""{""exit_code"":1}""#,
+            )
+        );
+        assert_eq!(
+            lines[2],
+            "b,sess_1,,2025-01-01T00:00:00+00:00,,claude_code,session,stop,success,,,,,,,,,,"
+        );
+    }
+
+    #[test]
+    fn test_csv_row_matches_columns() {
+        assert_eq!(csv_row(&span("a")).len(), CSV_COLUMNS.len());
+    }
+}
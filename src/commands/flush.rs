@@ -0,0 +1,556 @@
+use std::{
+    collections::HashMap,
+    env,
+    fs::{self, File, OpenOptions, TryLockError},
+    path::Path,
+    process::{Command, Stdio},
+    sync::Arc,
+    time::Duration,
+};
+
+use chrono::{DateTime, Utc};
+use clap::Args;
+use tokio::task::JoinSet;
+
+use crate::{
+    config::{ConfigStore, PulseConfig},
+    error::{PulseError, Result},
+    export::SpanExporter,
+    http::SpanPayload,
+    info, paths,
+    queue::{MAX_QUEUE_ENTRIES, SpanBuffer, SpanQueue},
+    verbose,
+};
+
+#[derive(Debug, Args)]
+pub struct FlushArgs {
+    /// Run as the detached flusher started by `PULSE_ASYNC` emits
+    #[arg(long, hide = true)]
+    pub background: bool,
+    /// Start a detached flusher and exit right away
+    #[arg(long, hide = true, conflicts_with = "background")]
+    pub detach: bool,
+    /// Drop queued spans older than this (e.g. 90m, 24h, 7d) instead of sending them
+    #[arg(long, value_name = "DURATION", value_parser = parse_max_age)]
+    pub max_age: Option<Duration>,
+    /// Send at most this many spans; the rest stay queued for the next flush
+    #[arg(long, value_name = "N", value_parser = clap::value_parser!(u64).range(1..))]
+    pub limit: Option<u64>,
+    /// Seconds to wait for another flush to finish instead of failing right away
+    #[arg(long, value_name = "SECS", default_value_t = 0)]
+    pub wait: u64,
+}
+
+/// Session buffers written to more recently than this belong to sessions
+/// that are probably still running, and are left for their `stop`.
+const IDLE_BUFFER_AGE: Duration = Duration::from_secs(60 * 60);
+
+/// How often a waiting `pulse flush --wait` checks the flusher lock again.
+const LOCK_POLL_INTERVAL: Duration = Duration::from_millis(100);
+
+pub async fn run_flush(args: FlushArgs) -> Result<()> {
+    if args.detach {
+        return spawn_flusher();
+    }
+    if args.background {
+        return run_background_flush().await;
+    }
+
+    let config = ConfigStore::load()?;
+    let exporter = SpanExporter::new(&config)?;
+    let queue = SpanQueue::new()?;
+    verbose!("Offline queue: {}", queue.path().display());
+
+    // Held until the end, so no other flush sends the same spans twice.
+    let lock_path = paths::flush_lock()?;
+    let Some(_lock) = wait_for_lock(&lock_path, Duration::from_secs(args.wait)).await? else {
+        return Err(PulseError::message(format!(
+            "another flush is already sending the offline queue ({} is locked); try again once it finishes, or pass --wait <SECS>",
+            lock_path.display()
+        )));
+    };
+
+    // Session buffers left behind by sessions that never reached `stop` are
+    // sent along with the offline queue.
+    let mut overflow = 0;
+    for buffer in SpanBuffer::new()?.sessions()? {
+        if !is_idle(buffer.path(), IDLE_BUFFER_AGE) {
+            verbose!(
+                "Session buffer in use, skipped: {}",
+                buffer.path().display()
+            );
+            continue;
+        }
+        verbose!("Session buffer: {}", buffer.path().display());
+        overflow += buffer.move_to(&queue)?;
+    }
+    if overflow > 0 {
+        println!("- dropped as the offline queue was full ({MAX_QUEUE_ENTRIES} spans): {overflow}");
+    }
+    // Spans stay queued until they are sent, so a flush that is killed part
+    // way through loses nothing.
+    let spans = queue.peek()?;
+    if spans.is_empty() {
+        println!("Offline queue is empty.");
+        return Ok(());
+    }
+
+    let limit = args
+        .limit
+        .map(|limit| usize::try_from(limit).unwrap_or(usize::MAX));
+    let selection = select_spans(spans, args.max_age, limit, Utc::now());
+    queue.remove(&selection.dropped)?;
+    if args.max_age.is_some() {
+        println!("- dropped as too old: {}", selection.dropped.len());
+    }
+    if selection.send.is_empty() {
+        println!("- sent: 0");
+    } else {
+        info!("Flushing {} queued spans...", selection.send.len());
+        let outcome = send_batches(
+            &exporter,
+            &queue,
+            &selection.send,
+            FlushLimits::from_config(&config),
+        )
+        .await?;
+
+        println!("- sent: {}", outcome.sent);
+        if let Some(err) = outcome.error {
+            println!("- kept in queue: {} ({err})", outcome.kept);
+        }
+    }
+    if !selection.held.is_empty() {
+        println!("- left for the next flush: {}", selection.held.len());
+    }
+    Ok(())
+}
+
+/// Whether `path` has gone unmodified for at least `age`. A file whose time
+/// cannot be read counts as idle, so it is not stuck forever.
+fn is_idle(path: &Path, age: Duration) -> bool {
+    match fs::metadata(path).and_then(|metadata| metadata.modified()) {
+        Ok(modified) => modified.elapsed().is_ok_and(|elapsed| elapsed >= age),
+        Err(_) => true,
+    }
+}
+
+/// What `pulse flush --max-age/--limit` does with the queued spans.
+#[derive(Debug, Default)]
+struct Selection {
+    /// Spans to send now, in queue order.
+    send: Vec<SpanPayload>,
+    /// Spans too old to send.
+    dropped: Vec<SpanPayload>,
+    /// Spans beyond `--limit`, left in the queue.
+    held: Vec<SpanPayload>,
+}
+
+/// Drops spans older than `max_age` and holds back everything past the
+/// first `limit` of the rest. A span whose timestamp cannot be read is
+/// kept, since its age is unknown.
+fn select_spans(
+    spans: Vec<SpanPayload>,
+    max_age: Option<Duration>,
+    limit: Option<usize>,
+    now: DateTime<Utc>,
+) -> Selection {
+    let cutoff = max_age.and_then(|age| {
+        chrono::Duration::from_std(age)
+            .ok()
+            .and_then(|age| now.checked_sub_signed(age))
+    });
+    let mut selection = Selection::default();
+    for span in spans {
+        let too_old = cutoff.is_some_and(|cutoff| {
+            DateTime::parse_from_rfc3339(&span.timestamp).is_ok_and(|time| time < cutoff)
+        });
+        if too_old {
+            selection.dropped.push(span);
+        } else if limit.is_some_and(|limit| selection.send.len() >= limit) {
+            selection.held.push(span);
+        } else {
+            selection.send.push(span);
+        }
+    }
+    selection
+}
+
+const AGE_UNITS: &[(char, u64)] = &[
+    ('s', 1),
+    ('m', 60),
+    ('h', 60 * 60),
+    ('d', 24 * 60 * 60),
+    ('w', 7 * 24 * 60 * 60),
+];
+
+/// Parses `--max-age`: a whole number followed by `s`, `m`, `h`, `d`, or `w`.
+fn parse_max_age(value: &str) -> std::result::Result<Duration, String> {
+    let value = value.trim();
+    let invalid = || format!("`{value}` is not a duration; use e.g. 30m, 24h, or 7d");
+    let unit = value.chars().last().ok_or_else(invalid)?;
+    let (_, secs_per_unit) = AGE_UNITS
+        .iter()
+        .find(|(name, _)| *name == unit.to_ascii_lowercase())
+        .ok_or_else(invalid)?;
+    let count: u64 = value[..value.len() - unit.len_utf8()]
+        .trim()
+        .parse()
+        .map_err(|_| invalid())?;
+    if count == 0 {
+        return Err("the age must be greater than zero".to_string());
+    }
+    count
+        .checked_mul(*secs_per_unit)
+        .map(Duration::from_secs)
+        .ok_or_else(invalid)
+}
+
+struct FlushOutcome {
+    sent: usize,
+    kept: usize,
+    error: Option<PulseError>,
+}
+
+/// How `send_batches` splits up the spans it sends.
+#[derive(Debug, Clone, Copy)]
+struct FlushLimits {
+    batch_size: usize,
+    concurrency: usize,
+}
+
+impl FlushLimits {
+    fn from_config(config: &PulseConfig) -> Self {
+        Self {
+            batch_size: config.flush_batch_size(),
+            concurrency: config.flush_concurrency(),
+        }
+    }
+}
+
+/// Sends `spans` in batches, with up to `limits.concurrency` requests in
+/// flight. The tasks share the exporter's connection pool and interleave on
+/// the current-thread runtime while they wait on the network.
+async fn send_batches(
+    exporter: &SpanExporter,
+    queue: &SpanQueue,
+    spans: &[SpanPayload],
+    limits: FlushLimits,
+) -> Result<FlushOutcome> {
+    let mut batches = spans.chunks(limits.batch_size.max(1));
+    let mut uploads = JoinSet::new();
+    let mut in_flight = HashMap::new();
+    let mut delivered = Vec::new();
+    let mut failed = 0;
+    let mut error = None;
+
+    loop {
+        while uploads.len() < limits.concurrency.max(1)
+            && let Some(batch) = batches.next()
+        {
+            let batch: Arc<[SpanPayload]> = batch.into();
+            let exporter = exporter.clone();
+            let task = Arc::clone(&batch);
+            let handle = uploads.spawn(async move { exporter.export(&task).await });
+            in_flight.insert(handle.id(), batch);
+        }
+        let Some(joined) = uploads.join_next_with_id().await else {
+            break;
+        };
+        let (id, result) = match joined {
+            Ok((id, result)) => (id, result),
+            Err(err) => (
+                err.id(),
+                Err(PulseError::message(format!("upload task failed: {err}"))),
+            ),
+        };
+        let batch = in_flight.remove(&id).unwrap_or_else(|| Arc::from([]));
+        match result {
+            Ok(()) => delivered.extend_from_slice(&batch),
+            Err(err) => {
+                failed += batch.len();
+                error = Some(err);
+            }
+        }
+    }
+
+    // Only delivered spans leave the queue; the rest wait for the next flush.
+    queue.remove(&delivered)?;
+    Ok(FlushOutcome {
+        sent: delivered.len(),
+        kept: failed,
+        error,
+    })
+}
+
+/// Sends the offline queue for `PULSE_ASYNC` emits. Only one flusher runs
+/// at a time; session buffers are left alone since their sessions are live.
+async fn run_background_flush() -> Result<()> {
+    let config = ConfigStore::load()?;
+    let exporter = SpanExporter::new(&config)?;
+    let queue = SpanQueue::new()?;
+    let lock_path = paths::flush_lock()?;
+    let limits = FlushLimits::from_config(&config);
+
+    loop {
+        // Whoever holds the lock picks up spans queued while it runs.
+        let Some(lock) = try_lock(&lock_path)? else {
+            return Ok(());
+        };
+        let spans = queue.peek()?;
+        let outcome = send_batches(&exporter, &queue, &spans, limits).await?;
+        drop(lock);
+
+        // An emit that queued after our read may have started a flusher
+        // that found the lock taken, so check again once it is released.
+        // After a failure the spans wait for the next emit instead.
+        if outcome.error.is_some() || queue.is_empty()? {
+            return Ok(());
+        }
+    }
+}
+
+/// Takes the flusher lock, or returns `None` if another process holds it.
+fn try_lock(path: &Path) -> Result<Option<File>> {
+    if let Some(parent) = path.parent() {
+        fs::create_dir_all(parent)?;
+    }
+    let file = OpenOptions::new()
+        .write(true)
+        .create(true)
+        .truncate(false)
+        .open(path)?;
+    match file.try_lock() {
+        Ok(()) => Ok(Some(file)),
+        Err(TryLockError::WouldBlock) => Ok(None),
+        Err(TryLockError::Error(err)) => Err(err.into()),
+    }
+}
+
+/// Takes the flusher lock, trying again until `timeout` has passed. Returns
+/// `None` if another process still holds it then.
+async fn wait_for_lock(path: &Path, timeout: Duration) -> Result<Option<File>> {
+    let deadline = tokio::time::Instant::now() + timeout;
+    loop {
+        if let Some(lock) = try_lock(path)? {
+            return Ok(Some(lock));
+        }
+        if tokio::time::Instant::now() >= deadline {
+            return Ok(None);
+        }
+        tokio::time::sleep(LOCK_POLL_INTERVAL).await;
+    }
+}
+
+/// Starts a flusher that outlives the calling hook. On Unix this goes
+/// through `pulse flush --detach`, which starts the flusher in its own
+/// process group and exits, so the flusher is reparented to init and the
+/// agent never waits on it (the double fork).
+pub(crate) fn start_detached_flush() -> Result<()> {
+    if cfg!(windows) {
+        // `DETACHED_PROCESS` already cuts the flusher loose on Windows.
+        return spawn_flusher();
+    }
+    pulse_command("--detach").status()?;
+    Ok(())
+}
+
+/// Spawns `pulse flush --background` detached from this process and its
+/// terminal, without waiting for it.
+fn spawn_flusher() -> Result<()> {
+    let mut command = pulse_command("--background");
+    #[cfg(unix)]
+    {
+        use std::os::unix::process::CommandExt;
+        command.process_group(0);
+    }
+    #[cfg(windows)]
+    {
+        use std::os::windows::process::CommandExt;
+        const DETACHED_PROCESS: u32 = 0x0000_0008;
+        const CREATE_NEW_PROCESS_GROUP: u32 = 0x0000_0200;
+        command.creation_flags(DETACHED_PROCESS | CREATE_NEW_PROCESS_GROUP);
+    }
+    command.spawn()?;
+    Ok(())
+}
+
+fn pulse_command(flag: &str) -> Command {
+    let exe = env::current_exe().unwrap_or_else(|_| "pulse".into());
+    let mut command = Command::new(exe);
+    command
+        .args(["flush", flag])
+        .stdin(Stdio::null())
+        .stdout(Stdio::null())
+        .stderr(Stdio::null());
+    command
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_support::serve;
+    use tempfile::TempDir;
+
+    /// A trace service that answers 500 to any batch holding `fail_id` and
+    /// 202 to the rest. Returns its URL.
+    fn serve_failing(fail_id: &'static str) -> String {
+        let needle = format!("\"span_id\":\"{fail_id}\"");
+        serve(move |body| {
+            if body.contains(&needle) {
+                "500 Internal Server Error"
+            } else {
+                "202 Accepted"
+            }
+        })
+    }
+
+    #[tokio::test]
+    async fn test_only_failed_batch_is_kept() {
+        let tmp = TempDir::new().unwrap();
+        let queue = SpanQueue::at(tmp.path().join(paths::QUEUE_FILE));
+        let config = PulseConfig {
+            api_url: serve_failing("s2"),
+            ..PulseConfig::default()
+        };
+        let exporter = SpanExporter::new(&config).unwrap();
+        let spans: Vec<_> = (0..7)
+            .map(|i| SpanPayload::test(&format!("s{i}")))
+            .collect();
+        queue.enqueue_all(&spans).unwrap();
+        let limits = FlushLimits {
+            batch_size: 2,
+            concurrency: 2,
+        };
+
+        let outcome = send_batches(&exporter, &queue, &spans, limits)
+            .await
+            .unwrap();
+        assert_eq!(outcome.sent, 5);
+        assert_eq!(outcome.kept, 2);
+        assert!(outcome.error.is_some());
+        let kept: Vec<_> = queue
+            .peek()
+            .unwrap()
+            .into_iter()
+            .map(|span| span.span_id)
+            .collect();
+        assert_eq!(kept, ["s2", "s3"]);
+    }
+
+    #[test]
+    fn test_max_age_drops_old_spans_and_limit_holds_the_rest() {
+        let now = DateTime::parse_from_rfc3339("2025-01-08T12:00:00+00:00")
+            .unwrap()
+            .with_timezone(&Utc);
+        let at = |id: &str, timestamp: &str| SpanPayload {
+            timestamp: timestamp.to_string(),
+            ..SpanPayload::test(id)
+        };
+        let spans = vec![
+            at("ancient", "2024-06-01T00:00:00+00:00"),
+            at("week_old", "2025-01-01T11:59:59+00:00"),
+            at("yesterday", "2025-01-07T12:00:00+00:00"),
+            at("unknown", "not a time"),
+            at("offset", "2025-01-08T10:00:00-01:00"),
+            at("recent", "2025-01-08T11:00:00Z"),
+        ];
+        let ids = |spans: &[SpanPayload]| -> Vec<String> {
+            spans.iter().map(|span| span.span_id.clone()).collect()
+        };
+
+        let all = select_spans(spans.clone(), None, None, now);
+        assert_eq!(all.send.len(), 6);
+        assert_eq!(all.dropped.len(), 0);
+
+        let week = select_spans(spans.clone(), Some(parse_max_age("7d").unwrap()), None, now);
+        assert_eq!(week.dropped.len(), 2);
+        assert_eq!(
+            ids(&week.send),
+            ["yesterday", "unknown", "offset", "recent"]
+        );
+
+        let day = select_spans(spans, Some(parse_max_age("24h").unwrap()), Some(2), now);
+        assert_eq!(day.dropped.len(), 2);
+        assert_eq!(ids(&day.send), ["yesterday", "unknown"]);
+        assert_eq!(ids(&day.held), ["offset", "recent"]);
+    }
+
+    #[test]
+    fn test_parse_max_age() {
+        assert_eq!(parse_max_age("90s"), Ok(Duration::from_secs(90)));
+        assert_eq!(parse_max_age("30m"), Ok(Duration::from_secs(30 * 60)));
+        assert_eq!(parse_max_age("24h"), Ok(Duration::from_secs(24 * 3600)));
+        assert_eq!(parse_max_age("7D"), Ok(Duration::from_secs(7 * 86400)));
+        assert_eq!(parse_max_age("2w"), Ok(Duration::from_secs(14 * 86400)));
+        for bad in ["", "7", "d", "0h", "-1d", "1.5h", "3y", "1 day"] {
+            assert!(parse_max_age(bad).is_err(), "{bad}");
+        }
+    }
+
+    #[test]
+    fn test_recently_written_buffers_are_not_idle() {
+        let tmp = TempDir::new().unwrap();
+        let path = tmp.path().join("sess.ndjson");
+        fs::write(&path, "").unwrap();
+        assert!(!is_idle(&path, IDLE_BUFFER_AGE));
+
+        let two_hours_ago = std::time::SystemTime::now() - Duration::from_secs(2 * 60 * 60);
+        File::options()
+            .write(true)
+            .open(&path)
+            .unwrap()
+            .set_modified(two_hours_ago)
+            .unwrap();
+        assert!(is_idle(&path, IDLE_BUFFER_AGE));
+    }
+
+    #[test]
+    fn test_only_one_flusher_holds_the_lock() {
+        let tmp = TempDir::new().unwrap();
+        let path = tmp.path().join(paths::FLUSH_LOCK_FILE);
+
+        let first = try_lock(&path).unwrap();
+        assert!(first.is_some());
+        assert!(try_lock(&path).unwrap().is_none());
+
+        drop(first);
+        assert!(try_lock(&path).unwrap().is_some());
+    }
+
+    #[tokio::test]
+    async fn test_flush_backs_off_while_another_holds_the_lock() {
+        let tmp = TempDir::new().unwrap();
+        let path = tmp.path().join(paths::FLUSH_LOCK_FILE);
+        let (locked, wait_for_locked) = std::sync::mpsc::channel();
+        let (release, wait_for_release) = std::sync::mpsc::channel::<()>();
+        let holder = {
+            let path = path.clone();
+            std::thread::spawn(move || {
+                let lock = try_lock(&path).unwrap().unwrap();
+                locked.send(()).unwrap();
+                wait_for_release.recv().unwrap();
+                drop(lock);
+            })
+        };
+        wait_for_locked.recv().unwrap();
+
+        // Without a wait it fails fast; with one it gives up at the deadline.
+        assert!(
+            wait_for_lock(&path, Duration::ZERO)
+                .await
+                .unwrap()
+                .is_none()
+        );
+        let started = std::time::Instant::now();
+        let waited = wait_for_lock(&path, Duration::from_millis(300)).await;
+        assert!(waited.unwrap().is_none());
+        assert!(started.elapsed() >= Duration::from_millis(300));
+
+        // A waiter gets the lock once the holder lets go.
+        release.send(()).unwrap();
+        let lock = wait_for_lock(&path, Duration::from_secs(5)).await.unwrap();
+        assert!(lock.is_some());
+        holder.join().unwrap();
+    }
+}
@@ -0,0 +1,86 @@
+use std::path::PathBuf;
+
+use clap::{Args, Subcommand};
+use serde_json::json;
+
+use crate::{
+    commands::{HookOptions, print_json, registered_hooks_with},
+    error::Result,
+    hooks::ToolHook,
+};
+
+#[derive(Debug, Args)]
+pub struct HooksArgs {
+    #[command(subcommand)]
+    pub command: HooksCommand,
+}
+
+#[derive(Debug, Subcommand)]
+pub enum HooksCommand {
+    /// List the hook events and files `pulse connect` installs for each tool
+    List {
+        /// Only these tools, comma-separated (claude_code, opencode, openclaw, cursor, gemini, codex)
+        #[arg(long, value_delimiter = ',')]
+        tools: Vec<String>,
+        /// Config directory of the one tool given with `--tools` (opencode or openclaw)
+        #[arg(long)]
+        config_dir: Option<PathBuf>,
+    },
+}
+
+pub fn run_hooks(args: HooksArgs, json: bool) -> Result<()> {
+    match args.command {
+        HooksCommand::List { tools, config_dir } => {
+            let hooks = registered_hooks_with(HookOptions {
+                tools,
+                config_dir,
+                ..HookOptions::default()
+            })?;
+            list_hooks(&hooks, json)
+        }
+    }
+}
+
+fn list_hooks(hooks: &[Box<dyn ToolHook>], json: bool) -> Result<()> {
+    if json {
+        return print_json(
+            &hooks
+                .iter()
+                .map(|hook| {
+                    json!({
+                        "id": hook.id(),
+                        "tool": hook.tool_name(),
+                        "hooks": hook.managed_hooks(),
+                    })
+                })
+                .collect::<Vec<_>>(),
+        );
+    }
+    for (index, hook) in hooks.iter().enumerate() {
+        if index > 0 {
+            println!();
+        }
+        let managed = hook.managed_hooks();
+        let events: Vec<_> = managed
+            .iter()
+            .filter_map(|managed| Some((managed.event.as_deref()?, managed)))
+            .collect();
+        match events.first() {
+            Some((_, first)) => println!("{} ({})", hook.tool_name(), first.path.display()),
+            None => println!("{}", hook.tool_name()),
+        }
+        let event_width = events
+            .iter()
+            .map(|(event, _)| event.len())
+            .max()
+            .unwrap_or(0);
+        for (event, managed) in &events {
+            let command = managed.command.as_deref().unwrap_or_default();
+            println!("  {event:<event_width$}  {command}");
+        }
+        for file in managed.iter().filter(|managed| managed.event.is_none()) {
+            println!("  {}", file.path.display());
+        }
+    }
+    Ok(())
+}
@@ -3,9 +3,11 @@ use std::io::{self, Write};
 use clap::Args;
 
 use crate::{
-    config::{ConfigStore, PulseConfig},
+    commands::confirm_overwrite,
+    config::{ConfigStore, PulseConfig, validate_project_id},
     error::{PulseError, Result},
-    http::TraceHttpClient,
+    http::{INSECURE_WARNING, TraceHttpClient, check_client_identity},
+    info,
 };
 
 #[derive(Debug, Args)]
@@ -22,14 +24,34 @@ pub struct InitArgs {
     /// Skip health check validation
     #[arg(long)]
     pub no_validate: bool,
+    /// PEM file with the CA certificate that signed the service's TLS certificate
+    #[arg(long, value_name = "PATH")]
+    pub ca_cert: Option<String>,
+    /// PEM client certificate, for a service that requires mutual TLS
+    #[arg(long, value_name = "PATH")]
+    pub client_cert: Option<String>,
+    /// PEM private key for `--client-cert`, if it is not in the same file
+    #[arg(long, value_name = "PATH", requires = "client_cert")]
+    pub client_key: Option<String>,
+    /// Disable TLS certificate verification (UNSAFE, local development only)
+    #[arg(long)]
+    pub insecure: bool,
+    /// Overwrite an existing config without asking
+    #[arg(long)]
+    pub force: bool,
 }
 
 pub async fn run_init(args: InitArgs) -> Result<()> {
+    if !confirm_overwrite(args.force)? {
+        println!("Aborted.");
+        return Ok(());
+    }
+
     let api_url = match args.api_url {
         Some(v) => v,
         None => {
-            println!("Pulse CLI setup");
-            println!("----------------");
+            info!("Pulse CLI setup");
+            info!("----------------");
             prompt_required("Trace service URL (e.g. https://pulse.example.com)", false)?
         }
     };
@@ -50,15 +72,30 @@ pub async fn run_init(args: InitArgs) -> Result<()> {
         project_id,
         local_email: None,
         local_password: None,
+        ca_cert_path: args.ca_cert,
+        client_cert_path: args.client_cert,
+        client_key_path: args.client_key,
+        danger_accept_invalid_certs: args.insecure.then_some(true),
+        ..PulseConfig::default()
     }
     .sanitized();
+    validate_project_id(&config.project_id)?;
+    check_client_identity(&config)?;
+
+    if config.accept_invalid_certs() {
+        println!("{INSECURE_WARNING}");
+    }
 
     if !args.no_validate {
-        println!("Validating credentials...");
+        info!("Validating credentials...");
         let client = TraceHttpClient::new(&config)?;
         client.health_check().await.map_err(|err| {
+            let hint = err
+                .remediation()
+                .map(|hint| format!("\n{hint}"))
+                .unwrap_or_default();
             PulseError::message(format!(
-                "Failed to contact trace service at {}: {err}",
+                "Failed to contact trace service at {}: {err}{hint}",
                 config.api_url
             ))
         })?;
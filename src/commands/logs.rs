@@ -0,0 +1,179 @@
+use std::{
+    fs,
+    io::ErrorKind,
+    path::{Path, PathBuf},
+};
+
+use clap::{Args, Subcommand};
+use serde::Serialize;
+
+use crate::{commands::print_json, debug, error::Result, paths};
+
+#[derive(Debug, Args)]
+pub struct LogsArgs {
+    #[command(subcommand)]
+    pub command: LogsCommand,
+}
+
+#[derive(Debug, Subcommand)]
+pub enum LogsCommand {
+    /// Delete the debug log (and its rotated copies)
+    Clear {
+        /// Also delete spans waiting in the offline queue (they are lost)
+        #[arg(long)]
+        queue: bool,
+        /// Also delete correlation state: tool start times and session parents
+        #[arg(long)]
+        pending: bool,
+        /// Only report what would be deleted
+        #[arg(long)]
+        dry_run: bool,
+    },
+}
+
+/// Size of one kind of local state before it was cleared.
+#[derive(Debug, Default, PartialEq, Serialize)]
+struct Usage {
+    name: &'static str,
+    files: usize,
+    bytes: u64,
+}
+
+pub fn run_logs(args: LogsArgs, json: bool) -> Result<()> {
+    let LogsCommand::Clear {
+        queue,
+        pending,
+        dry_run,
+    } = args.command;
+
+    let mut targets = vec![("debug log", debug::log_files(&paths::debug_log()))];
+    if queue {
+        targets.push(("offline queue", vec![paths::queue_file()?]));
+    }
+    if pending {
+        targets.push(("correlation store", vec![paths::pending_dir()?]));
+    }
+
+    let mut cleared = Vec::new();
+    for (name, target) in &targets {
+        let usage = measure(name, target);
+        if !dry_run {
+            remove(target)?;
+        }
+        cleared.push(usage);
+    }
+
+    if json {
+        return print_json(&cleared);
+    }
+    for usage in &cleared {
+        println!(
+            "- {}: {} file(s), {}",
+            usage.name,
+            usage.files,
+            format_bytes(usage.bytes)
+        );
+    }
+    let files: usize = cleared.iter().map(|usage| usage.files).sum();
+    let bytes = format_bytes(cleared.iter().map(|usage| usage.bytes).sum());
+    if dry_run {
+        println!("Would free {bytes} in {files} file(s).");
+    } else {
+        println!("Freed {bytes} in {files} file(s).");
+    }
+    Ok(())
+}
+
+/// Counts the files at `paths`, looking one level into directories.
+fn measure(name: &'static str, paths: &[PathBuf]) -> Usage {
+    let mut usage = Usage {
+        name,
+        ..Usage::default()
+    };
+    let mut count = |path: &Path| {
+        if let Ok(meta) = fs::metadata(path)
+            && meta.is_file()
+        {
+            usage.files += 1;
+            usage.bytes += meta.len();
+        }
+    };
+    for path in paths {
+        match fs::read_dir(path) {
+            Ok(entries) => entries.flatten().for_each(|entry| count(&entry.path())),
+            Err(_) => count(path),
+        }
+    }
+    usage
+}
+
+fn remove(paths: &[PathBuf]) -> Result<()> {
+    for path in paths {
+        let result = if path.is_dir() {
+            fs::remove_dir_all(path)
+        } else {
+            fs::remove_file(path)
+        };
+        match result {
+            Err(err) if err.kind() != ErrorKind::NotFound => return Err(err.into()),
+            _ => {}
+        }
+    }
+    Ok(())
+}
+
+fn format_bytes(bytes: u64) -> String {
+    const UNITS: [&str; 3] = ["KB", "MB", "GB"];
+    if bytes < 1024 {
+        return format!("{bytes} B");
+    }
+    let mut value = bytes as f64 / 1024.0;
+    let mut unit = 0;
+    while value >= 1024.0 && unit < UNITS.len() - 1 {
+        value /= 1024.0;
+        unit += 1;
+    }
+    format!("{value:.1} {}", UNITS[unit])
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::TempDir;
+
+    #[test]
+    fn test_measure_and_remove() {
+        let tmp = TempDir::new().unwrap();
+        let log = tmp.path().join("debug.log");
+        let pending = tmp.path().join("pending");
+        fs::write(&log, "x".repeat(100)).unwrap();
+        fs::create_dir_all(&pending).unwrap();
+        fs::write(pending.join("tool-a"), "1").unwrap();
+        fs::write(pending.join("tool-b"), "22").unwrap();
+
+        let targets = [log.clone(), tmp.path().join("debug.log.1"), pending.clone()];
+        assert_eq!(
+            measure("all", &targets),
+            Usage {
+                name: "all",
+                files: 3,
+                bytes: 103,
+            }
+        );
+
+        remove(&targets).unwrap();
+        assert!(!log.exists());
+        assert!(!pending.exists());
+        assert_eq!(measure("all", &targets).files, 0);
+        // Clearing again is not an error.
+        remove(&targets).unwrap();
+    }
+
+    #[test]
+    fn test_format_bytes() {
+        assert_eq!(format_bytes(0), "0 B");
+        assert_eq!(format_bytes(1023), "1023 B");
+        assert_eq!(format_bytes(1536), "1.5 KB");
+        assert_eq!(format_bytes(10 * 1024 * 1024), "10.0 MB");
+    }
+}
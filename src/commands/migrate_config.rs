@@ -0,0 +1,305 @@
+use std::{
+    env, fs,
+    path::{Path, PathBuf},
+};
+
+use clap::Args;
+use dirs::home_dir;
+use serde_json::json;
+
+use crate::{
+    commands::{config::set_value, confirm_overwrite, print_json},
+    config::{
+        API_KEY_ENV, API_URL_ENV, CONFIG_FILE, ConfigStore, PROJECT_ID_ENV, PulseConfig,
+        merge_labels, restrict_permissions,
+    },
+    error::{PulseError, Result},
+    info,
+};
+
+/// Variables with a config key of their own. Flags are on for `1` or
+/// `true`, as when pulse reads them.
+const ENV_KEYS: &[(&str, &str, bool)] = &[
+    (API_URL_ENV, "api_url", false),
+    (API_KEY_ENV, "api_key", false),
+    (PROJECT_ID_ENV, "project_id", false),
+    ("PULSE_SAMPLE_RATE", "sample_rate", false),
+    ("PULSE_ASYNC", "async_emit", true),
+    ("PULSE_HEARTBEAT", "heartbeat", true),
+    ("PULSE_SOURCE_AUTODETECT", "source_autodetect", true),
+];
+
+#[derive(Debug, Default, Args)]
+pub struct MigrateConfigArgs {
+    /// Write the `PULSE_*` variables set in this shell into the config file
+    /// instead of looking for an old one
+    #[arg(long)]
+    pub from_env: bool,
+    /// Overwrite an existing config without asking
+    #[arg(long)]
+    pub force: bool,
+}
+
+pub fn run_migrate_config(args: MigrateConfigArgs, json: bool) -> Result<()> {
+    let target = ConfigStore::config_path()?;
+    if args.from_env {
+        return import_env(&target, json);
+    }
+
+    let home = home_dir().ok_or(PulseError::HomeDirNotFound)?;
+    let searched = legacy_config_paths(&home, env::var_os("XDG_CONFIG_HOME").map(PathBuf::from));
+    let Some(source) = searched.iter().find(|path| path.is_file()) else {
+        if json {
+            return print_json(&json!({ "migrated": false, "searched": searched }));
+        }
+        let searched: Vec<String> = searched
+            .iter()
+            .map(|path| path.display().to_string())
+            .collect();
+        println!(
+            "No config found at {}; nothing to migrate.",
+            searched.join(" or ")
+        );
+        if ENV_KEYS
+            .iter()
+            .any(|(var, _, _)| env::var_os(var).is_some())
+        {
+            info!(
+                "PULSE_* variables are set; `pulse migrate-config --from-env` writes them to the config file."
+            );
+        }
+        return Ok(());
+    };
+
+    if !confirm_overwrite(args.force)? {
+        println!("Aborted.");
+        return Ok(());
+    }
+    copy_config(source, &target)?;
+    if json {
+        return print_json(&json!({ "migrated": true, "from": source, "to": target }));
+    }
+    println!("Copied {} to {}.", source.display(), target.display());
+    info!("The original was left in place; delete it once pulse works with the new file.");
+    Ok(())
+}
+
+/// Where older pulse versions kept the config: `$XDG_CONFIG_HOME/pulse` and
+/// `~/.config/pulse`.
+fn legacy_config_paths(home: &Path, xdg_config_home: Option<PathBuf>) -> Vec<PathBuf> {
+    let mut paths: Vec<PathBuf> = xdg_config_home
+        .filter(|dir| dir.is_absolute())
+        .map(|dir| dir.join("pulse").join(CONFIG_FILE))
+        .into_iter()
+        .collect();
+    let default = home.join(".config").join("pulse").join(CONFIG_FILE);
+    if !paths.contains(&default) {
+        paths.push(default);
+    }
+    paths
+}
+
+/// Copies `source` next to `target` and loads the copy, which validates it
+/// and upgrades an older schema, before moving it into place. `source` is
+/// only read.
+fn copy_config(source: &Path, target: &Path) -> Result<PulseConfig> {
+    if let Some(dir) = target.parent() {
+        fs::create_dir_all(dir)?;
+        restrict_permissions(dir, 0o700)?;
+    }
+    let staged = target.with_extension("toml.migrating");
+    fs::copy(source, &staged)?;
+    restrict_permissions(&staged, 0o600)?;
+    let config = match ConfigStore::load_from(&staged, |_| None) {
+        Ok(config) => config,
+        Err(err) => {
+            let _ = fs::remove_file(&staged);
+            return Err(PulseError::message(format!(
+                "{} is not a usable pulse config ({err}); nothing was copied",
+                source.display()
+            )));
+        }
+    };
+    fs::rename(&staged, target)?;
+    Ok(config)
+}
+
+fn import_env(target: &Path, json: bool) -> Result<()> {
+    let config = match ConfigStore::load_from(target, |_| None) {
+        Ok(config) => config,
+        Err(PulseError::ConfigMissing) => PulseConfig::default(),
+        Err(err) => return Err(err),
+    };
+    let (config, imported) = config_from_env(config, |name| env::var(name).ok(), env::vars())?;
+    if imported.is_empty() {
+        if json {
+            return print_json(&json!({ "migrated": false, "variables": imported }));
+        }
+        println!("No PULSE_* settings are set in this shell; nothing to import.");
+        return Ok(());
+    }
+    ConfigStore::save_to(target, &config)?;
+    if json {
+        return print_json(
+            &json!({ "migrated": true, "from": "environment", "to": target, "variables": imported }),
+        );
+    }
+    println!("Wrote {} to {}.", imported.join(", "), target.display());
+    info!(
+        "While they stay set, the variables still win over the file; unset them once pulse works without them."
+    );
+    Ok(())
+}
+
+/// `config` with the settings from `PULSE_*` variables on top, and the
+/// names of the variables that were used. The result must name a trace
+/// service, an API key, and a project, from the variables or the file.
+fn config_from_env(
+    mut config: PulseConfig,
+    env: impl Fn(&str) -> Option<String>,
+    vars: impl IntoIterator<Item = (String, String)>,
+) -> Result<(PulseConfig, Vec<String>)> {
+    let mut imported = Vec::new();
+    for (var, key, flag) in ENV_KEYS {
+        let Some(value) = env(var).filter(|value| !value.trim().is_empty()) else {
+            continue;
+        };
+        let value = if *flag {
+            (value == "1" || value == "true").to_string()
+        } else {
+            value
+        };
+        set_value(&mut config, key, &value)
+            .map_err(|err| PulseError::message(format!("{var}: {err}")))?;
+        imported.push(var.to_string());
+    }
+    let labels = merge_labels(&config.labels, vars);
+    if labels != config.labels {
+        config.labels = labels;
+        imported.push("PULSE_LABEL_*".to_string());
+    }
+    if imported.is_empty() {
+        return Ok((config, imported));
+    }
+
+    let missing: Vec<&str> = [
+        (API_URL_ENV, config.api_url.is_empty()),
+        (
+            API_KEY_ENV,
+            config.api_key.is_empty()
+                && config.api_key_file.is_none()
+                && config.api_key_command.is_none(),
+        ),
+        (PROJECT_ID_ENV, config.project_id.is_empty()),
+    ]
+    .into_iter()
+    .filter_map(|(var, missing)| missing.then_some(var))
+    .collect();
+    if !missing.is_empty() {
+        return Err(PulseError::message(format!(
+            "{} must also be set, or already be in the config file, to write a usable config",
+            missing.join(", ")
+        )));
+    }
+    Ok((config, imported))
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::HashMap;
+
+    use tempfile::TempDir;
+
+    use super::*;
+
+    const LEGACY_CONFIG: &str = "api_url = \"http://localhost:3000\"\napi_key = \"pk_legacy\"\nproject_id = \"proj_1\"\n# kept from the old install\n";
+
+    #[test]
+    fn test_copies_legacy_config_and_leaves_the_original() {
+        let home = TempDir::new().unwrap();
+        let paths = legacy_config_paths(home.path(), None);
+        assert_eq!(paths, [home.path().join(".config/pulse/config.toml")]);
+        assert!(!paths[0].exists());
+
+        fs::create_dir_all(paths[0].parent().unwrap()).unwrap();
+        fs::write(&paths[0], LEGACY_CONFIG).unwrap();
+        let target = home.path().join(".pulse").join(CONFIG_FILE);
+        let config = copy_config(&paths[0], &target).unwrap();
+        assert_eq!(config.api_key, "pk_legacy");
+
+        // Validated, upgraded to the current schema, and readable as usual.
+        let copied = ConfigStore::load_from(&target, |_| None).unwrap();
+        assert_eq!(copied.project_id, "proj_1");
+        assert!(fs::read_to_string(&target).unwrap().contains("version"));
+        assert_eq!(fs::read_to_string(&paths[0]).unwrap(), LEGACY_CONFIG);
+        assert_eq!(fs::read_dir(target.parent().unwrap()).unwrap().count(), 1);
+    }
+
+    #[test]
+    fn test_unusable_legacy_config_is_not_copied() {
+        let home = TempDir::new().unwrap();
+        let xdg = home.path().join("xdg");
+        let paths = legacy_config_paths(home.path(), Some(xdg.clone()));
+        assert_eq!(paths[0], xdg.join("pulse/config.toml"));
+        assert_eq!(paths.len(), 2);
+
+        fs::create_dir_all(paths[0].parent().unwrap()).unwrap();
+        fs::write(&paths[0], "api_url = \"http://x\"\n").unwrap();
+        let target = home.path().join(".pulse").join(CONFIG_FILE);
+        let err = copy_config(&paths[0], &target).unwrap_err().to_string();
+        assert!(err.contains("not a usable pulse config"), "{err}");
+        assert!(!target.exists());
+        assert_eq!(fs::read_dir(target.parent().unwrap()).unwrap().count(), 0);
+    }
+
+    #[test]
+    fn test_config_from_env() {
+        let vars: HashMap<&str, &str> = [
+            ("PULSE_API_URL", "https://pulse.example.com"),
+            ("PULSE_API_KEY", "pk_env"),
+            ("PULSE_PROJECT_ID", "proj_env"),
+            ("PULSE_ASYNC", "1"),
+            ("PULSE_SAMPLE_RATE", ""),
+            ("PULSE_LABEL_TEAM", "infra"),
+        ]
+        .into();
+        let env = |name: &str| vars.get(name).map(|value| value.to_string());
+        let listed = || {
+            vars.iter()
+                .map(|(name, value)| (name.to_string(), value.to_string()))
+                .collect::<Vec<_>>()
+        };
+
+        let (config, imported) = config_from_env(PulseConfig::default(), env, listed()).unwrap();
+        assert_eq!(config.api_url, "https://pulse.example.com");
+        assert_eq!(config.project_id, "proj_env");
+        assert_eq!(config.async_emit, Some(true));
+        assert_eq!(config.sample_rate, None);
+        assert_eq!(config.labels["team"], "infra");
+        assert_eq!(
+            imported,
+            [
+                "PULSE_API_URL",
+                "PULSE_API_KEY",
+                "PULSE_PROJECT_ID",
+                "PULSE_ASYNC",
+                "PULSE_LABEL_*"
+            ]
+        );
+
+        // Nothing set is not an error; a partial setup is.
+        let (_, imported) = config_from_env(PulseConfig::default(), |_| None, Vec::new()).unwrap();
+        assert!(imported.is_empty());
+        let err = config_from_env(
+            PulseConfig::default(),
+            |name| (name == "PULSE_HEARTBEAT").then(|| "true".to_string()),
+            Vec::new(),
+        )
+        .unwrap_err()
+        .to_string();
+        assert!(
+            err.starts_with("PULSE_API_URL, PULSE_API_KEY, PULSE_PROJECT_ID must"),
+            "{err}"
+        );
+    }
+}
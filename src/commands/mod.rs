@@ -1,26 +1,407 @@
+pub mod config;
 pub mod connect;
+pub mod daemon;
 pub mod dashboard;
 pub mod disconnect;
+pub mod doctor;
 pub mod emit;
+pub mod export;
+pub mod flush;
+pub mod hooks;
 pub mod init;
+pub mod logs;
+pub mod migrate_config;
+pub mod ping;
+pub mod project;
+pub mod reconnect;
+pub mod replay;
+pub mod restore;
+pub mod sessions;
 pub mod setup;
 pub mod status;
+pub mod tail;
+pub mod uninstall;
+pub mod version;
 
-use crate::error::Result;
-use crate::hooks::{ClaudeCodeHook, OpenClawHook, OpenCodeHook, ToolHook};
+use std::{
+    env,
+    io::{self, IsTerminal, Write},
+    path::{Path, PathBuf},
+};
 
-pub use connect::run_connect;
+use serde::Serialize;
+
+use crate::config::ConfigStore;
+use crate::error::{PulseError, Result};
+use crate::hooks::{
+    ClaudeCodeHook, CodexHook, CursorHook, GeminiHook, OpenClawHook, OpenCodeHook, ProjectSettings,
+    ToolHook,
+};
+
+pub use config::{ConfigArgs, run_config};
+pub use connect::{ConnectArgs, run_connect};
+pub use daemon::{DaemonArgs, run_daemon};
 pub use dashboard::{DashboardArgs, run_dashboard};
-pub use disconnect::run_disconnect;
+pub use disconnect::{DisconnectArgs, run_disconnect};
+pub use doctor::run_doctor;
 pub use emit::{EmitArgs, run_emit};
+pub use export::{ExportArgs, run_export};
+pub use flush::{FlushArgs, run_flush};
+pub use hooks::{HooksArgs, run_hooks};
 pub use init::{InitArgs, run_init};
+pub use logs::{LogsArgs, run_logs};
+pub use migrate_config::{MigrateConfigArgs, run_migrate_config};
+pub use ping::{PingArgs, run_ping};
+pub use project::{ProjectArgs, run_project};
+pub use reconnect::{ReconnectArgs, run_reconnect};
+pub use replay::{ReplayArgs, run_replay};
+pub use restore::{RestoreArgs, run_restore};
+pub use sessions::{SessionsArgs, run_sessions};
 pub use setup::{SetupArgs, run_setup};
-pub use status::run_status;
+pub use status::{StatusArgs, run_status};
+pub use tail::{TailArgs, run_tail};
+pub use uninstall::{UninstallArgs, run_uninstall};
+pub use version::run_version;
+
+/// Ids accepted by `--tools`, one per supported agent.
+pub const TOOL_IDS: &[&str] = &[
+    "claude_code",
+    "opencode",
+    "openclaw",
+    "cursor",
+    "gemini",
+    "codex",
+];
+
+/// Per-run settings applied to the hooks returned by `registered_hooks_with`.
+#[derive(Debug, Clone)]
+pub struct HookOptions {
+    /// Back up settings files before changing them.
+    pub backup: bool,
+    /// Where Claude Code settings backups go, instead of next to the file.
+    pub backup_dir: Option<PathBuf>,
+    /// Use this `pulse` path instead of `pulse_bin` from the config.
+    pub pulse_bin: Option<String>,
+    /// Which Claude Code settings files to include.
+    pub claude: ClaudeTargets,
+    /// Only return hooks with these ids (`ToolHook::id`); empty means all.
+    pub tools: Vec<String>,
+    /// Config directory for the one tool in `tools` (OpenCode or OpenClaw).
+    pub config_dir: Option<PathBuf>,
+    /// Tool-name matcher for Claude Code's tool-use hooks.
+    pub matcher: Option<String>,
+}
+
+impl Default for HookOptions {
+    fn default() -> Self {
+        Self {
+            backup: true,
+            backup_dir: None,
+            pulse_bin: None,
+            claude: ClaudeTargets::default(),
+            tools: Vec::new(),
+            config_dir: None,
+            matcher: None,
+        }
+    }
+}
+
+/// Claude Code settings files `registered_hooks_with` returns hooks for.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum ClaudeTargets {
+    /// `~/.claude/settings.json` plus any project settings files that exist
+    /// in the current directory.
+    #[default]
+    Detected,
+    /// `~/.claude/settings.json` plus the project settings files in the
+    /// current directory that already have pulse hooks, so refreshing them
+    /// does not add hooks to a project that never had them.
+    Connected,
+    /// Only `~/.claude/settings.json`.
+    Home,
+    /// Only this project settings file in the current directory.
+    Project(ProjectSettings),
+}
+
+/// Every supported tool's hook with default options: the home Claude Code
+/// settings plus any project settings in the current directory, then the
+/// other agents in their default locations.
+pub fn registered_hooks() -> Result<Vec<Box<dyn ToolHook>>> {
+    registered_hooks_with(HookOptions::default())
+}
 
-pub(crate) fn registered_hooks() -> Result<Vec<Box<dyn ToolHook>>> {
+/// The hooks `connect`, `disconnect`, and `status` work on, shaped by
+/// `options`. Fails on an unknown id in `options.tools`.
+pub fn registered_hooks_with(options: HookOptions) -> Result<Vec<Box<dyn ToolHook>>> {
+    let pulse_bin = options
+        .pulse_bin
+        .or_else(|| ConfigStore::load().ok().and_then(|config| config.pulse_bin));
     let mut hooks: Vec<Box<dyn ToolHook>> = Vec::new();
-    hooks.push(Box::new(ClaudeCodeHook::new()?));
-    hooks.push(Box::new(OpenCodeHook::new()?));
-    hooks.push(Box::new(OpenClawHook::new()?));
-    Ok(hooks)
+    let claude = claude_hooks(options.claude, ClaudeCodeHook::new()?, &env::current_dir()?);
+    for hook in claude {
+        hooks.push(Box::new(
+            hook.with_pulse_bin(pulse_bin.clone())
+                .with_matcher(options.matcher.clone())
+                .with_backup(options.backup)
+                .with_backup_dir(options.backup_dir.clone()),
+        ));
+    }
+    if !matches!(options.claude, ClaudeTargets::Project(_)) {
+        let config_dir = config_dir_tool(&options.tools, options.config_dir.as_ref())?;
+        let opencode = match config_dir {
+            Some(("opencode", dir)) => OpenCodeHook::with_config_dir(dir.clone()),
+            _ => OpenCodeHook::new()?,
+        };
+        let openclaw = match config_dir {
+            Some(("openclaw", dir)) => OpenClawHook::with_config_dir(dir.clone()),
+            _ => OpenClawHook::new()?,
+        };
+        hooks.extend::<[Box<dyn ToolHook>; 5]>([
+            Box::new(opencode.with_pulse_bin(pulse_bin.clone())),
+            Box::new(openclaw.with_pulse_bin(pulse_bin.clone())),
+            Box::new(CursorHook::new()?.with_pulse_bin(pulse_bin.clone())),
+            Box::new(GeminiHook::new()?.with_pulse_bin(pulse_bin.clone())),
+            Box::new(CodexHook::new()?.with_pulse_bin(pulse_bin)),
+        ]);
+    }
+    filter_hooks(hooks, &options.tools)
+}
+
+/// The tool `--config-dir` applies to. The flag only makes sense for a
+/// single tool whose location is a directory, so anything else is an error.
+fn config_dir_tool<'a>(
+    tools: &'a [String],
+    config_dir: Option<&'a PathBuf>,
+) -> Result<Option<(&'a str, &'a PathBuf)>> {
+    let Some(dir) = config_dir else {
+        return Ok(None);
+    };
+    match tools {
+        [tool] if tool == "opencode" || tool == "openclaw" => Ok(Some((tool, dir))),
+        _ => Err(PulseError::message(
+            "--config-dir needs exactly one of `--tools opencode` or `--tools openclaw`",
+        )),
+    }
+}
+
+/// Keeps the hooks whose id is in `tools`, or all of them when it is empty.
+/// Unknown names are an error listing the valid ids.
+fn filter_hooks(hooks: Vec<Box<dyn ToolHook>>, tools: &[String]) -> Result<Vec<Box<dyn ToolHook>>> {
+    if tools.is_empty() {
+        return Ok(hooks);
+    }
+    if let Some(unknown) = tools.iter().find(|tool| !TOOL_IDS.contains(&tool.as_str())) {
+        return Err(PulseError::message(format!(
+            "unknown tool `{unknown}`; valid tools: {}",
+            TOOL_IDS.join(", ")
+        )));
+    }
+    Ok(hooks
+        .into_iter()
+        .filter(|hook| tools.iter().any(|tool| tool == hook.id()))
+        .collect())
+}
+
+/// The Claude Code hooks `targets` selects: `home` and/or the project
+/// settings files under `cwd`.
+fn claude_hooks(targets: ClaudeTargets, home: ClaudeCodeHook, cwd: &Path) -> Vec<ClaudeCodeHook> {
+    match targets {
+        ClaudeTargets::Home => vec![home],
+        ClaudeTargets::Project(settings) => vec![ClaudeCodeHook::for_project(cwd, settings)],
+        ClaudeTargets::Detected | ClaudeTargets::Connected => {
+            let project = ProjectSettings::ALL
+                .into_iter()
+                .map(|settings| ClaudeCodeHook::for_project(cwd, settings))
+                // Run from the home directory, the shared project file is
+                // the home settings file.
+                .filter(|hook| {
+                    hook.settings_exist() && hook.settings_path() != home.settings_path()
+                })
+                // A file that cannot be read is kept, so its error is
+                // reported rather than the file silently skipped.
+                .filter(|hook| {
+                    targets != ClaudeTargets::Connected
+                        || hook
+                            .status()
+                            .map_or(true, |status| status.installed_hooks > 0)
+                })
+                .collect::<Vec<_>>();
+            std::iter::once(home).chain(project).collect()
+        }
+    }
+}
+
+/// Prints `value` as pretty JSON for `--json` output.
+pub(crate) fn print_json(value: &impl Serialize) -> Result<()> {
+    println!("{}", serde_json::to_string_pretty(value)?);
+    Ok(())
+}
+
+/// Asks before `init` or `setup` replaces an existing config file. With no
+/// terminal to ask on, refuses unless `force` is set.
+pub(crate) fn confirm_overwrite(force: bool) -> Result<bool> {
+    let path = ConfigStore::config_path()?;
+    if force || !path.exists() {
+        return Ok(true);
+    }
+    let target = ConfigStore::load_file()
+        .ok()
+        .map(|config| config.api_url)
+        .filter(|api_url| !api_url.is_empty())
+        .unwrap_or_else(|| path.display().to_string());
+    if !io::stdin().is_terminal() {
+        return Err(PulseError::message(format!(
+            "Config already exists for {target}. Pass --force to overwrite it."
+        )));
+    }
+    confirm(&format!("Config already exists for {target}. Overwrite?"))
+}
+
+pub(crate) fn confirm(prompt: &str) -> Result<bool> {
+    print!("{prompt} [y/N]: ");
+    io::stdout().flush()?;
+    let mut input = String::new();
+    io::stdin().read_line(&mut input)?;
+    Ok(is_yes(&input))
+}
+
+fn is_yes(input: &str) -> bool {
+    matches!(input.trim().to_lowercase().as_str(), "y" | "yes")
+}
+
+/// Resolves `name` against `PATH` the way a shell would.
+pub(crate) fn find_executable(name: &str) -> Option<PathBuf> {
+    let paths = env::var_os("PATH")?;
+    env::split_paths(&paths).find_map(|dir| {
+        let candidate = dir.join(name);
+        if is_executable(&candidate) {
+            return Some(candidate);
+        }
+        if cfg!(windows) {
+            let exe = dir.join(format!("{name}.exe"));
+            if exe.is_file() {
+                return Some(exe);
+            }
+        }
+        None
+    })
+}
+
+#[cfg(unix)]
+fn is_executable(path: &std::path::Path) -> bool {
+    use std::os::unix::fs::PermissionsExt;
+    path.metadata()
+        .map(|meta| meta.is_file() && meta.permissions().mode() & 0o111 != 0)
+        .unwrap_or(false)
+}
+
+#[cfg(not(unix))]
+fn is_executable(path: &std::path::Path) -> bool {
+    path.is_file()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn all_hooks() -> Vec<Box<dyn ToolHook>> {
+        vec![
+            Box::new(OpenCodeHook::new().unwrap()),
+            Box::new(OpenClawHook::new().unwrap()),
+            Box::new(CursorHook::new().unwrap()),
+            Box::new(GeminiHook::new().unwrap()),
+            Box::new(CodexHook::new().unwrap()),
+        ]
+    }
+
+    #[test]
+    fn test_filter_hooks_keeps_named_tools() {
+        let tools = vec!["cursor".to_string(), "opencode".to_string()];
+        let ids: Vec<&str> = filter_hooks(all_hooks(), &tools)
+            .unwrap()
+            .iter()
+            .map(|hook| hook.id())
+            .collect();
+        assert_eq!(ids, ["opencode", "cursor"]);
+        assert_eq!(filter_hooks(all_hooks(), &[]).unwrap().len(), 5);
+    }
+
+    #[test]
+    fn test_filter_hooks_rejects_unknown_tools() {
+        let err = filter_hooks(all_hooks(), &["vscode".to_string()])
+            .err()
+            .unwrap();
+        let message = err.to_string();
+        assert!(message.contains("unknown tool `vscode`"), "{message}");
+        assert!(message.contains(&TOOL_IDS.join(", ")), "{message}");
+    }
+
+    #[test]
+    fn test_config_dir_needs_one_directory_tool() {
+        let dir = PathBuf::from("/custom/opencode");
+        let tools = |names: &[&str]| {
+            names
+                .iter()
+                .map(|name| name.to_string())
+                .collect::<Vec<_>>()
+        };
+
+        assert!(config_dir_tool(&tools(&[]), None).unwrap().is_none());
+        let opencode = tools(&["opencode"]);
+        assert_eq!(
+            config_dir_tool(&opencode, Some(&dir)).unwrap(),
+            Some(("opencode", &dir))
+        );
+        assert!(config_dir_tool(&tools(&[]), Some(&dir)).is_err());
+        assert!(config_dir_tool(&tools(&["cursor"]), Some(&dir)).is_err());
+        assert!(config_dir_tool(&tools(&["opencode", "openclaw"]), Some(&dir)).is_err());
+    }
+
+    #[test]
+    fn test_connected_targets_skip_projects_without_hooks() {
+        let tmp = tempfile::TempDir::new().unwrap();
+        let home = ClaudeCodeHook::with_settings_path(tmp.path().join("home/settings.json"));
+        let project = tmp.path().join("project");
+        std::fs::create_dir_all(project.join(".claude")).unwrap();
+        ClaudeCodeHook::for_project(&project, ProjectSettings::Shared)
+            .connect()
+            .unwrap();
+        std::fs::write(project.join(".claude/settings.local.json"), "{}").unwrap();
+
+        let names = |targets| {
+            claude_hooks(targets, home.clone(), &project)
+                .iter()
+                .map(|hook| hook.tool_name())
+                .collect::<Vec<_>>()
+        };
+        assert_eq!(
+            names(ClaudeTargets::Detected),
+            [
+                "Claude Code",
+                "Claude Code (project)",
+                "Claude Code (project, local)"
+            ]
+        );
+        assert_eq!(
+            names(ClaudeTargets::Connected),
+            ["Claude Code", "Claude Code (project)"]
+        );
+    }
+
+    #[test]
+    fn test_confirmation_defaults_to_no() {
+        assert!(is_yes("y\n"));
+        assert!(is_yes(" YES "));
+        assert!(!is_yes("\n"));
+        assert!(!is_yes("nope"));
+    }
+
+    #[test]
+    fn test_tool_ids_match_hooks() {
+        let mut ids: Vec<&str> = all_hooks().iter().map(|hook| hook.id()).collect();
+        ids.push(ClaudeCodeHook::new().unwrap().id());
+        for id in TOOL_IDS {
+            assert!(ids.contains(id), "{id}");
+        }
+    }
 }
@@ -0,0 +1,115 @@
+use std::time::{Duration, Instant};
+
+use chrono::Utc;
+use clap::Args;
+use serde_json::json;
+use uuid::Uuid;
+
+use crate::{
+    commands::print_json,
+    config::{ConfigStore, PulseConfig},
+    error::{PulseError, Result},
+    health::{self, POLL_INTERVAL},
+    hooks::CLAUDE_SOURCE,
+    http::{SpanPayload, TraceHttpClient},
+};
+
+/// Event type of the heartbeat span, so the server and dashboard can drop it.
+pub const PING_EVENT_TYPE: &str = "cli_ping";
+const PING_SESSION_ID: &str = "pulse-cli-ping";
+
+#[derive(Debug, Default, Args)]
+pub struct PingArgs {
+    /// Seconds to keep retrying while the trace service is unreachable or failing
+    #[arg(long, value_name = "SECS", default_value_t = 0)]
+    pub timeout: u64,
+}
+
+/// Sends one heartbeat span through the authenticated spans endpoint. Unlike
+/// the `/health` check in `status`, this fails on a bad API key, project id,
+/// or spans path.
+pub async fn run_ping(args: PingArgs, json: bool) -> Result<()> {
+    let config = ConfigStore::load()?;
+    let client = TraceHttpClient::new(&config)?;
+
+    // The latency reported is that of the attempt that got through.
+    let started = Instant::now();
+    let timeout = Duration::from_secs(args.timeout);
+    let result = health::retry_until(timeout, POLL_INTERVAL, || async {
+        let attempt = Instant::now();
+        client.post_spans(&[ping_span(&config)]).await?;
+        Ok(attempt.elapsed().as_millis())
+    })
+    .await;
+
+    let latency_ms = match result {
+        Ok(latency_ms) => latency_ms,
+        Err(err) => {
+            let elapsed_ms = started.elapsed().as_millis();
+            let hint = err
+                .remediation()
+                .map(|hint| format!("\n{hint}"))
+                .unwrap_or_default();
+            return Err(PulseError::message(format!(
+                "ping failed after {elapsed_ms} ms: {err}{hint}"
+            )));
+        }
+    };
+
+    if json {
+        return print_json(&json!({ "accepted": true, "latency_ms": latency_ms }));
+    }
+    println!(
+        "Ping accepted by {} in {latency_ms} ms",
+        config.api_url.trim_end_matches('/')
+    );
+    Ok(())
+}
+
+fn ping_span(config: &PulseConfig) -> SpanPayload {
+    SpanPayload {
+        span_id: Uuid::new_v4().to_string(),
+        session_id: PING_SESSION_ID.to_string(),
+        parent_span_id: None,
+        timestamp: Utc::now().to_rfc3339(),
+        duration_ms: None,
+        source: CLAUDE_SOURCE.to_string(),
+        kind: "session".to_string(),
+        event_type: PING_EVENT_TYPE.to_string(),
+        status: "success".to_string(),
+        tool_use_id: None,
+        tool_name: None,
+        tool_input: None,
+        tool_response: None,
+        error: None,
+        is_interrupt: None,
+        cwd: None,
+        tool_cwd: None,
+        model: None,
+        agent_name: None,
+        metadata: Some(json!({
+            "synthetic": true,
+            "cli_version": env!("CARGO_PKG_VERSION"),
+            "project_id": config.project_id,
+        })),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_ping_span_is_marked_synthetic() {
+        let config = PulseConfig {
+            project_id: "proj_1".to_string(),
+            ..PulseConfig::default()
+        };
+        let span = ping_span(&config);
+        assert_eq!(span.event_type, PING_EVENT_TYPE);
+        assert_eq!(span.session_id, PING_SESSION_ID);
+        let metadata = span.metadata.unwrap();
+        assert_eq!(metadata["synthetic"], true);
+        assert_eq!(metadata["project_id"], "proj_1");
+    }
+}
@@ -0,0 +1,174 @@
+use clap::{Args, Subcommand};
+use serde_json::json;
+
+use crate::{
+    commands::print_json,
+    config::{ConfigStore, PulseConfig},
+    error::{PulseError, Result},
+    info,
+};
+
+#[derive(Debug, Args)]
+pub struct ProjectArgs {
+    #[command(subcommand)]
+    pub command: ProjectCommand,
+}
+
+#[derive(Debug, Subcommand)]
+pub enum ProjectCommand {
+    /// List the configured projects; the active one is marked with `*`
+    List,
+    /// Add a project on the same trace service
+    Add {
+        /// Name to switch to it by
+        name: String,
+        /// Project id from the dashboard
+        id: String,
+        /// Also make it the active project
+        #[arg(long = "use")]
+        use_it: bool,
+    },
+    /// Send spans to another configured project
+    Use { name: String },
+}
+
+pub fn run_project(args: ProjectArgs, json: bool) -> Result<()> {
+    match args.command {
+        ProjectCommand::List => list_projects(&ConfigStore::load()?, json),
+        ProjectCommand::Add { name, id, use_it } => {
+            // Edit the file as written so env overrides are not persisted.
+            let mut config = match ConfigStore::load_file() {
+                Ok(config) => config,
+                Err(PulseError::ConfigMissing) => PulseConfig::default(),
+                Err(err) => return Err(err),
+            };
+            config.add_project(&name, &id)?;
+            if use_it {
+                config.use_project(name.trim())?;
+            }
+            ConfigStore::save(&config)?;
+            info!("Added project {}", name.trim());
+            print_active(&config);
+            Ok(())
+        }
+        ProjectCommand::Use { name } => {
+            let mut config = ConfigStore::load_file()?;
+            config.use_project(&name)?;
+            ConfigStore::save(&config)?;
+            print_active(&config);
+            Ok(())
+        }
+    }
+}
+
+fn list_projects(config: &PulseConfig, json: bool) -> Result<()> {
+    let active = config.active_project();
+    let projects = config.projects();
+    if json {
+        return print_json(
+            &projects
+                .iter()
+                .map(|project| {
+                    json!({
+                        "name": project.name,
+                        "id": project.id,
+                        "active": active.as_ref() == Some(&project.name),
+                    })
+                })
+                .collect::<Vec<_>>(),
+        );
+    }
+    if projects.is_empty() {
+        println!("No projects configured. Add one with `pulse project add <name> <id>`.");
+        return Ok(());
+    }
+    let name_width = projects
+        .iter()
+        .map(|project| project.name.len())
+        .max()
+        .unwrap_or(0);
+    for project in &projects {
+        let marker = if active.as_ref() == Some(&project.name) {
+            '*'
+        } else {
+            ' '
+        };
+        println!("{marker} {:<name_width$}  {}", project.name, project.id);
+    }
+    if active.is_none() {
+        println!(
+            "Spans go to project {}, which is not in the list.",
+            config.project_id
+        );
+    }
+    Ok(())
+}
+
+fn print_active(config: &PulseConfig) {
+    match config.active_project() {
+        Some(name) => println!("Active project: {name} ({})", config.project_id),
+        None => println!("Active project id: {}", config.project_id),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::fs;
+
+    use serde_json::json;
+    use tempfile::TempDir;
+
+    use super::*;
+    use crate::commands::emit::{BuildOptions, build_span};
+
+    fn sent_project_id(config: &PulseConfig) -> String {
+        let options = BuildOptions {
+            source: Some("claude_code".to_string()),
+            store: None,
+            sample: false,
+            autodetect: false,
+        };
+        let span = build_span("stop", &json!({"session_id": "s1"}), config, &options)
+            .unwrap()
+            .unwrap();
+        span.metadata.unwrap()["project_id"]
+            .as_str()
+            .unwrap()
+            .to_string()
+    }
+
+    #[test]
+    fn test_switching_project_changes_the_id_emit_sends() {
+        let tmp = TempDir::new().unwrap();
+        let path = tmp.path().join("config.toml");
+        fs::write(
+            &path,
+            "api_url = \"http://x\"\napi_key = \"k\"\nproject_id = \"proj_web\"\n",
+        )
+        .unwrap();
+
+        let mut config = ConfigStore::load_from(&path, |_| None).unwrap();
+        assert_eq!(config.active_project().as_deref(), Some("default"));
+        config.add_project("api", "proj_api").unwrap();
+        ConfigStore::save_to(&path, &config).unwrap();
+
+        // Adding keeps the old id active, now as the `default` entry.
+        let mut config = ConfigStore::load_from(&path, |_| None).unwrap();
+        assert_eq!(sent_project_id(&config), "proj_web");
+        assert_eq!(config.projects().len(), 2);
+
+        config.use_project("api").unwrap();
+        ConfigStore::save_to(&path, &config).unwrap();
+        let config = ConfigStore::load_from(&path, |_| None).unwrap();
+        assert_eq!(config.active_project().as_deref(), Some("api"));
+        assert_eq!(sent_project_id(&config), "proj_api");
+
+        // PULSE_PROJECT_ID still wins over the active project.
+        let config = ConfigStore::load_from(&path, |name| {
+            (name == "PULSE_PROJECT_ID").then(|| "proj_env".to_string())
+        })
+        .unwrap();
+        assert_eq!(sent_project_id(&config), "proj_env");
+        assert_eq!(config.active_project(), None);
+    }
+}
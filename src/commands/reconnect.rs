@@ -0,0 +1,227 @@
+use std::path::PathBuf;
+
+use clap::Args;
+use serde::Serialize;
+
+use crate::{
+    commands::{ClaudeTargets, HookOptions, print_json, registered_hooks_with},
+    config::ConfigStore,
+    error::Result,
+    hooks::{HookStatus, ToolHook},
+};
+
+#[derive(Debug, Default, Args)]
+pub struct ReconnectArgs {
+    /// Do not back up settings files before editing them
+    #[arg(long)]
+    pub no_backup: bool,
+    /// Write Claude Code settings backups to this directory instead of next to the file
+    #[arg(long, value_name = "DIR", conflicts_with = "no_backup")]
+    pub backup_dir: Option<PathBuf>,
+    /// Only these tools, comma-separated (claude_code, opencode, openclaw, cursor, gemini, codex)
+    #[arg(long, value_delimiter = ',')]
+    pub tools: Vec<String>,
+    /// Config directory of the one tool given with `--tools` (opencode or openclaw)
+    #[arg(long)]
+    pub config_dir: Option<PathBuf>,
+}
+
+/// Net change to one tool's hooks after a reconnect.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ReconnectOutcome {
+    NotDetected,
+    /// Nothing of pulse was installed before.
+    NewlyInstalled,
+    /// Hooks were missing or plugin files outdated, and are now current.
+    Refreshed,
+    /// The same complete, current hooks are installed as before.
+    Unchanged,
+    Failed,
+}
+
+impl ReconnectOutcome {
+    fn describe(self) -> &'static str {
+        match self {
+            Self::NotDetected => "not detected",
+            Self::NewlyInstalled => "newly installed",
+            Self::Refreshed => "refreshed",
+            Self::Unchanged => "unchanged",
+            Self::Failed => "unable to inject hooks",
+        }
+    }
+}
+
+#[derive(Debug, Serialize)]
+struct Reconnected {
+    outcome: ReconnectOutcome,
+    #[serde(flatten)]
+    status: HookStatus,
+}
+
+/// Removes and reinstalls the hooks of every detected tool, so an upgrade
+/// picks up new hook definitions and plugin files in one step. Claude Code
+/// project settings are only refreshed when they already have pulse hooks.
+pub fn run_reconnect(args: ReconnectArgs, json: bool) -> Result<()> {
+    let config = ConfigStore::load()?;
+
+    let hooks = registered_hooks_with(HookOptions {
+        backup: !args.no_backup,
+        backup_dir: args.backup_dir,
+        pulse_bin: config.pulse_bin.clone(),
+        tools: args.tools,
+        claude: ClaudeTargets::Connected,
+        config_dir: args.config_dir,
+        ..HookOptions::default()
+    })?;
+    let results = reconnect_all(&hooks);
+
+    if json {
+        return print_json(&results);
+    }
+    for result in &results {
+        let path = result
+            .status
+            .path
+            .as_ref()
+            .filter(|_| result.status.detected)
+            .map(|path| format!(" ({})", path.display()))
+            .unwrap_or_default();
+        println!(
+            "- {}: {}{path}",
+            result.status.tool,
+            result.outcome.describe()
+        );
+        if result.status.total_hooks > 0 {
+            println!(
+                "    {}/{} hooks installed",
+                result.status.installed_hooks, result.status.total_hooks
+            );
+        }
+    }
+    Ok(())
+}
+
+/// Reconnects each tool in turn. A tool that fails, say over a corrupt
+/// settings file, is reported as `Failed` with the error and the rest
+/// still reconnect.
+fn reconnect_all(hooks: &[Box<dyn ToolHook>]) -> Vec<Reconnected> {
+    hooks
+        .iter()
+        .map(|hook| {
+            reconnect(hook.as_ref()).unwrap_or_else(|err| Reconnected {
+                outcome: ReconnectOutcome::Failed,
+                status: HookStatus::failed(hook.tool_name(), &err),
+            })
+        })
+        .collect()
+}
+
+fn reconnect(hook: &dyn ToolHook) -> Result<Reconnected> {
+    let before = hook.status()?;
+    if before.detected {
+        hook.disconnect()?;
+    }
+    let after = hook.connect()?;
+    Ok(Reconnected {
+        outcome: outcome(&before, &after),
+        status: after,
+    })
+}
+
+fn outcome(before: &HookStatus, after: &HookStatus) -> ReconnectOutcome {
+    if !after.detected {
+        ReconnectOutcome::NotDetected
+    } else if !after.connected {
+        ReconnectOutcome::Failed
+    } else if before.installed_hooks == 0 {
+        ReconnectOutcome::NewlyInstalled
+    } else if before.connected
+        && before.message.is_none()
+        && before.installed_hook_names == after.installed_hook_names
+    {
+        ReconnectOutcome::Unchanged
+    } else {
+        ReconnectOutcome::Refreshed
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::fs;
+
+    use tempfile::TempDir;
+
+    use super::*;
+    use crate::hooks::{ClaudeCodeHook, OpenCodeHook};
+
+    #[test]
+    fn test_reconnect_reports_net_change() {
+        let tmp = TempDir::new().unwrap();
+        let config_dir = tmp.path().join("opencode");
+        let hook = OpenCodeHook::with_config_dir(config_dir.clone());
+
+        assert_eq!(
+            reconnect(&hook).unwrap().outcome,
+            ReconnectOutcome::NotDetected
+        );
+
+        fs::create_dir_all(&config_dir).unwrap();
+        assert_eq!(
+            reconnect(&hook).unwrap().outcome,
+            ReconnectOutcome::NewlyInstalled
+        );
+        assert_eq!(
+            reconnect(&hook).unwrap().outcome,
+            ReconnectOutcome::Unchanged
+        );
+
+        let plugin = config_dir.join("plugin/pulse-plugin.ts");
+        fs::write(&plugin, "// an older plugin").unwrap();
+        assert_eq!(
+            reconnect(&hook).unwrap().outcome,
+            ReconnectOutcome::Refreshed
+        );
+        assert!(hook.status().unwrap().message.is_none());
+    }
+
+    #[test]
+    fn test_failing_tool_does_not_stop_the_others() {
+        let tmp = TempDir::new().unwrap();
+        let settings = tmp.path().join("settings.json");
+        fs::write(&settings, "{ not json").unwrap();
+        let config_dir = tmp.path().join("opencode");
+        fs::create_dir_all(&config_dir).unwrap();
+        let hooks: Vec<Box<dyn ToolHook>> = vec![
+            Box::new(ClaudeCodeHook::with_settings_path(settings)),
+            Box::new(OpenCodeHook::with_config_dir(config_dir)),
+        ];
+
+        let results = reconnect_all(&hooks);
+        assert_eq!(results[0].outcome, ReconnectOutcome::Failed);
+        assert!(results[0].status.message.is_some());
+        assert_eq!(results[1].outcome, ReconnectOutcome::NewlyInstalled);
+    }
+
+    #[test]
+    fn test_partial_install_is_refreshed() {
+        let status = |installed: usize| HookStatus {
+            tool: "Claude Code",
+            detected: true,
+            connected: installed == 3,
+            modified: false,
+            path: None,
+            message: None,
+            installed_hooks: installed,
+            total_hooks: 3,
+            installed_hook_names: (0..installed).map(|i| format!("hook{i}")).collect(),
+        };
+        assert_eq!(outcome(&status(2), &status(3)), ReconnectOutcome::Refreshed);
+        assert_eq!(outcome(&status(3), &status(3)), ReconnectOutcome::Unchanged);
+        assert_eq!(
+            outcome(&status(0), &status(3)),
+            ReconnectOutcome::NewlyInstalled
+        );
+        assert_eq!(outcome(&status(0), &status(1)), ReconnectOutcome::Failed);
+    }
+}
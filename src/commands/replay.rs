@@ -0,0 +1,241 @@
+use std::{fs, path::PathBuf};
+
+use clap::{Args, builder::PossibleValuesParser};
+use serde_json::Value;
+
+use crate::{
+    commands::{
+        TOOL_IDS,
+        emit::{BuildOptions, build_span},
+    },
+    config::ConfigStore,
+    debug::{EVENT_HEADER, HTTP_HEADER, NOTE_HEADER},
+    error::{PulseError, Result},
+    export::SpanExporter,
+    output::JsonFormat,
+};
+
+#[derive(Debug, Args)]
+pub struct ReplayArgs {
+    /// Event JSON (object or array), a queue/NDJSON file, or a PULSE_DEBUG log
+    pub path: PathBuf,
+    /// Event type for payloads that do not record one
+    #[arg(long)]
+    pub event_type: Option<String>,
+    /// Source to record, as with `pulse emit --source`
+    #[arg(long, value_parser = PossibleValuesParser::new(TOOL_IDS))]
+    pub source: Option<String>,
+    /// Print the resulting spans as JSON instead of sending them
+    #[arg(long)]
+    pub dry_run: bool,
+    /// Layout of the `--dry-run` output: an indented array, or one span per line
+    #[arg(long, value_enum, default_value_t = JsonFormat::Pretty)]
+    pub format: JsonFormat,
+}
+
+/// One raw hook event read back from a file.
+#[derive(Debug, PartialEq)]
+struct RecordedEvent {
+    event_type: String,
+    payload: Value,
+    source: Option<String>,
+    /// When the event was recorded, for payloads without a `timestamp`.
+    recorded_at: Option<String>,
+}
+
+pub async fn run_replay(args: ReplayArgs) -> Result<()> {
+    let config = ConfigStore::load()?;
+    let contents = fs::read_to_string(&args.path)?;
+    let events = parse_events(&contents, args.event_type.as_deref())?;
+
+    // Replays never touch the correlation store or sampling, so the spans
+    // match what the event maps to rather than what the live session did.
+    let mut spans = Vec::new();
+    for mut event in events {
+        if let (Some(recorded_at), Some(payload)) =
+            (event.recorded_at.take(), event.payload.as_object_mut())
+        {
+            payload
+                .entry("timestamp")
+                .or_insert(Value::String(recorded_at));
+        }
+        let options = BuildOptions {
+            source: args.source.clone().or(event.source),
+            store: None,
+            sample: false,
+            autodetect: false,
+        };
+        match build_span(&event.event_type, &event.payload, &config, &options)? {
+            Some(span) => spans.push(span),
+            None => eprintln!("Skipped {}: no span produced", event.event_type),
+        }
+    }
+
+    if args.dry_run {
+        match args.format {
+            JsonFormat::Pretty => println!("{}", args.format.to_string(&spans)?),
+            JsonFormat::Compact => {
+                for span in &spans {
+                    println!("{}", args.format.to_string(span)?);
+                }
+            }
+        }
+        return Ok(());
+    }
+
+    SpanExporter::new(&config)?.export(&spans).await?;
+    println!("Sent {} span(s).", spans.len());
+    Ok(())
+}
+
+/// Reads events from any of the formats pulse writes or accepts:
+///
+/// - a `PULSE_DEBUG` log, where each event follows a `── [time] type ──` line
+/// - queued spans (`queue.ndjson`), replayed from `metadata.raw`
+/// - a raw payload object, or an array or NDJSON of them, with `event_type`
+fn parse_events(contents: &str, event_type: Option<&str>) -> Result<Vec<RecordedEvent>> {
+    let start = contents.trim_start();
+    if [EVENT_HEADER, HTTP_HEADER, NOTE_HEADER]
+        .iter()
+        .any(|header| start.starts_with(header))
+    {
+        return parse_debug_log(contents);
+    }
+
+    let values = match serde_json::from_str::<Value>(contents) {
+        Ok(Value::Array(values)) => values,
+        Ok(value) => vec![value],
+        Err(_) => contents
+            .lines()
+            .filter(|line| !line.trim().is_empty())
+            .map(serde_json::from_str)
+            .collect::<std::result::Result<_, _>>()?,
+    };
+    values
+        .into_iter()
+        .map(|value| recorded_event(value, event_type))
+        .collect()
+}
+
+fn recorded_event(value: Value, event_type: Option<&str>) -> Result<RecordedEvent> {
+    // A span from the offline queue carries its event type and raw payload.
+    if let (Some(recorded), Some(raw)) = (
+        value.get("event_type").and_then(Value::as_str),
+        value.pointer("/metadata/raw"),
+    ) {
+        return Ok(RecordedEvent {
+            event_type: event_type.unwrap_or(recorded).to_string(),
+            payload: raw.clone(),
+            source: value
+                .get("source")
+                .and_then(Value::as_str)
+                .map(str::to_string),
+            recorded_at: value
+                .get("timestamp")
+                .and_then(Value::as_str)
+                .map(str::to_string),
+        });
+    }
+
+    let event_type = event_type.ok_or_else(|| {
+        PulseError::message("the file does not record event types; pass --event-type")
+    })?;
+    Ok(RecordedEvent {
+        event_type: event_type.to_string(),
+        payload: value,
+        source: None,
+        recorded_at: None,
+    })
+}
+
+fn parse_debug_log(contents: &str) -> Result<Vec<RecordedEvent>> {
+    let mut events = Vec::new();
+    for entry in contents.split(EVENT_HEADER).skip(1) {
+        let (header, body) = entry.split_once('\n').unwrap_or((entry, ""));
+        let (recorded_at, rest) = header
+            .split_once("] ")
+            .ok_or_else(|| PulseError::message(format!("malformed debug log entry: {header}")))?;
+        let event_type = Some(rest.trim_end_matches('─').trim())
+            .filter(|event_type| !event_type.is_empty())
+            .ok_or_else(|| PulseError::message(format!("malformed debug log entry: {header}")))?;
+        // HTTP lines logged while handling the event follow its payload.
+        let payload = serde_json::Deserializer::from_str(body)
+            .into_iter::<Value>()
+            .next()
+            .ok_or_else(|| {
+                PulseError::message(format!("debug log entry has no payload: {header}"))
+            })??;
+        events.push(RecordedEvent {
+            event_type: event_type.to_string(),
+            payload,
+            source: None,
+            recorded_at: Some(recorded_at.to_string()),
+        });
+    }
+    Ok(events)
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json::json;
+
+    use super::*;
+
+    #[test]
+    fn test_parse_debug_log() {
+        let log = "── [2025-01-01T00:00:00+00:00] post_tool_use ──\n{\n  \"session_id\": \"s1\"\n}\n\n── [2025-01-01T00:00:01+00:00] stop ──\n{\"session_id\": \"s1\"}\n\n";
+        let events = parse_events(log, None).unwrap();
+        assert_eq!(events.len(), 2);
+        assert_eq!(events[0].event_type, "post_tool_use");
+        assert_eq!(events[0].payload, json!({"session_id": "s1"}));
+        assert_eq!(
+            events[0].recorded_at.as_deref(),
+            Some("2025-01-01T00:00:00+00:00")
+        );
+        assert_eq!(events[1].event_type, "stop");
+    }
+
+    #[test]
+    fn test_parse_debug_log_skips_http_lines() {
+        let log = concat!(
+            ">> [2025-01-01T00:00:00+00:00] GET http://localhost:3000/health -> 200 OK\n\n",
+            "── [2025-01-01T00:00:01+00:00] stop ──\n{\"session_id\": \"s1\"}\n\n",
+            ">> [2025-01-01T00:00:02+00:00] POST http://localhost:3000/v1/spans/async -> 401 Unauthorized\n",
+            "   response: {\"error\": \"bad key\"}\n\n",
+        );
+        let events = parse_events(log, None).unwrap();
+        assert_eq!(events.len(), 1);
+        assert_eq!(events[0].payload, json!({"session_id": "s1"}));
+    }
+
+    #[test]
+    fn test_parse_queued_spans() {
+        let queue = concat!(
+            r#"{"event_type":"stop","source":"cursor","timestamp":"2025-01-01T00:00:00+00:00","metadata":{"raw":{"conversation_id":"c1"}}}"#,
+            "\n",
+            r#"{"event_type":"session_start","source":"opencode","metadata":{"raw":{"session_id":"s2"}}}"#,
+            "\n",
+        );
+        let events = parse_events(queue, None).unwrap();
+        assert_eq!(
+            events[0],
+            RecordedEvent {
+                event_type: "stop".to_string(),
+                payload: json!({"conversation_id": "c1"}),
+                source: Some("cursor".to_string()),
+                recorded_at: Some("2025-01-01T00:00:00+00:00".to_string()),
+            }
+        );
+        assert_eq!(events[1].event_type, "session_start");
+    }
+
+    #[test]
+    fn test_raw_payloads_need_event_type() {
+        let raw = r#"[{"session_id": "s1"}, {"session_id": "s2"}]"#;
+        assert!(parse_events(raw, None).is_err());
+
+        let events = parse_events(raw, Some("stop")).unwrap();
+        assert_eq!(events.len(), 2);
+        assert!(events.iter().all(|event| event.event_type == "stop"));
+    }
+}
@@ -0,0 +1,158 @@
+use std::{
+    env,
+    io::{self, IsTerminal, Write},
+    path::PathBuf,
+};
+
+use chrono::{DateTime, Local};
+use clap::Args;
+use serde_json::json;
+
+use crate::{
+    commands::{TOOL_IDS, print_json},
+    error::{PulseError, Result},
+    hooks::{ClaudeCodeHook, ProjectSettings, SettingsBackup},
+    info,
+};
+
+#[derive(Debug, Default, Args)]
+pub struct RestoreArgs {
+    /// Tool whose settings to restore (only claude_code settings are backed up)
+    pub tool: String,
+    /// Backup to restore, by its number in the list (1 is the newest); lists them when left out
+    pub number: Option<usize>,
+    /// Restore ./.claude/settings.json (or settings.local.json with `--project local`)
+    #[arg(long, value_enum, num_args = 0..=1, default_missing_value = "shared")]
+    pub project: Option<ProjectSettings>,
+    /// Directory the backups were written to with `--backup-dir`
+    #[arg(long, value_name = "DIR")]
+    pub backup_dir: Option<PathBuf>,
+}
+
+/// Lists the backups pulse made of a tool's settings, or puts one back.
+/// Whatever the restore replaces is backed up first, so a restore can be
+/// undone the same way.
+pub fn run_restore(args: RestoreArgs, json: bool) -> Result<()> {
+    let hook = claude_hook(&args)?;
+    // Newest first, the order they are numbered in.
+    let mut backups = hook.backups()?;
+    backups.reverse();
+    if backups.is_empty() {
+        return Err(PulseError::message(format!(
+            "no backups of {} found{}",
+            hook.settings_path().display(),
+            match &args.backup_dir {
+                Some(dir) => format!(" in {}", dir.display()),
+                None => String::new(),
+            }
+        )));
+    }
+
+    let number = match args.number {
+        Some(number) => number,
+        None if json => return print_json(&backups),
+        None => {
+            print_backups(&hook, &backups);
+            if !io::stdin().is_terminal() || !io::stdout().is_terminal() {
+                println!("Run `pulse restore {} <number>` to restore one.", args.tool);
+                return Ok(());
+            }
+            match pick_backup(backups.len())? {
+                Some(number) => number,
+                None => return Ok(()),
+            }
+        }
+    };
+    let backup = number
+        .checked_sub(1)
+        .and_then(|index| backups.get(index))
+        .ok_or_else(|| {
+            PulseError::message(format!(
+                "no backup number {number}; choose from 1 to {}",
+                backups.len()
+            ))
+        })?;
+
+    let saved = hook.restore(backup)?;
+    if json {
+        return print_json(&json!({
+            "settings": hook.settings_path(),
+            "restored": backup,
+            "saved": saved,
+        }));
+    }
+    info!(
+        "Restored {} from {}",
+        hook.settings_path().display(),
+        backup.path.display()
+    );
+    if let Some(saved) = saved {
+        info!("The replaced settings were saved to {}", saved.display());
+    }
+    Ok(())
+}
+
+fn claude_hook(args: &RestoreArgs) -> Result<ClaudeCodeHook> {
+    if !TOOL_IDS.contains(&args.tool.as_str()) {
+        return Err(PulseError::message(format!(
+            "unknown tool `{}`; valid tools: {}",
+            args.tool,
+            TOOL_IDS.join(", ")
+        )));
+    }
+    if args.tool != "claude_code" {
+        return Err(PulseError::message(format!(
+            "pulse keeps backups of Claude Code settings only; `{}` has none to restore",
+            args.tool
+        )));
+    }
+    let hook = match args.project {
+        Some(settings) => ClaudeCodeHook::for_project(&env::current_dir()?, settings),
+        None => ClaudeCodeHook::new()?,
+    };
+    Ok(hook.with_backup_dir(args.backup_dir.clone()))
+}
+
+fn print_backups(hook: &ClaudeCodeHook, backups: &[SettingsBackup]) {
+    println!("Backups of {}:", hook.settings_path().display());
+    for (index, backup) in backups.iter().enumerate() {
+        println!(
+            "  {:>2}) {}  {}",
+            index + 1,
+            local_time(backup.timestamp),
+            backup.path.display()
+        );
+    }
+}
+
+/// Asks which backup to restore; `None` when the user just presses Enter.
+fn pick_backup(count: usize) -> Result<Option<usize>> {
+    loop {
+        print!("Restore which? (1-{count}, Enter to cancel): ");
+        io::stdout().flush()?;
+        let mut input = String::new();
+        if io::stdin().read_line(&mut input)? == 0 {
+            return Ok(None);
+        }
+        let input = input.trim();
+        if input.is_empty() {
+            return Ok(None);
+        }
+        match input.parse::<usize>() {
+            Ok(number) if (1..=count).contains(&number) => return Ok(Some(number)),
+            _ => println!("Enter a number from 1 to {count}, or press Enter to cancel."),
+        }
+    }
+}
+
+fn local_time(timestamp: u64) -> String {
+    i64::try_from(timestamp)
+        .ok()
+        .and_then(|secs| DateTime::from_timestamp(secs, 0))
+        .map(|time| {
+            time.with_timezone(&Local)
+                .format("%Y-%m-%d %H:%M:%S")
+                .to_string()
+        })
+        .unwrap_or_else(|| timestamp.to_string())
+}
@@ -0,0 +1,205 @@
+use chrono::{DateTime, Local, Utc};
+use clap::{Args, Subcommand};
+
+use crate::{
+    commands::print_json,
+    error::{PulseError, Result},
+    history::{
+        HistoryEntry, SessionDetail, SessionSummary, SpanHistory, Usage, session_detail, summarize,
+    },
+};
+
+const NO_HISTORY: &str = "No local span history yet. `pulse emit` records one line per span.";
+
+#[derive(Debug, Args)]
+pub struct SessionsArgs {
+    #[command(subcommand)]
+    pub command: SessionsCommand,
+}
+
+#[derive(Debug, Subcommand)]
+pub enum SessionsCommand {
+    /// Summarize recent sessions from the local span history
+    List {
+        /// Show at most this many sessions
+        #[arg(long, default_value_t = 20)]
+        limit: usize,
+    },
+    /// Show one session's spans in order, with token usage and cost
+    Show {
+        /// Session id, or a prefix of it that matches one session
+        session_id: String,
+    },
+}
+
+pub fn run_sessions(args: SessionsArgs, json: bool) -> Result<()> {
+    match args.command {
+        SessionsCommand::List { limit } => list_sessions(limit, json),
+        SessionsCommand::Show { session_id } => show_session(&session_id, json),
+    }
+}
+
+fn list_sessions(limit: usize, json: bool) -> Result<()> {
+    let Some(entries) = SpanHistory::new()?.entries()? else {
+        if json {
+            return print_json(&Vec::<SessionSummary>::new());
+        }
+        println!("{NO_HISTORY}");
+        return Ok(());
+    };
+
+    let mut sessions = summarize(&entries);
+    sessions.truncate(limit);
+    if json {
+        return print_json(&sessions);
+    }
+    if sessions.is_empty() {
+        println!("{NO_HISTORY}");
+        return Ok(());
+    }
+
+    let id_width = sessions
+        .iter()
+        .map(|session| session.session_id.len())
+        .max()
+        .unwrap_or(0)
+        .max("SESSION".len());
+    println!(
+        "{:<id_width$}  {:<11}  {:<19}  {:<19}  {:>5}  {:>6}",
+        "SESSION", "SOURCE", "FIRST", "LAST", "SPANS", "ERRORS"
+    );
+    for session in &sessions {
+        println!(
+            "{:<id_width$}  {:<11}  {:<19}  {:<19}  {:>5}  {:>6}",
+            session.session_id,
+            session.source,
+            local_time(session.first),
+            local_time(session.last),
+            session.spans,
+            session.errors
+        );
+    }
+    Ok(())
+}
+
+fn local_time(time: DateTime<Utc>) -> String {
+    time.with_timezone(&Local)
+        .format("%Y-%m-%d %H:%M:%S")
+        .to_string()
+}
+
+fn show_session(session_id: &str, json: bool) -> Result<()> {
+    let detail = SpanHistory::new()?
+        .entries()?
+        .and_then(|entries| session_detail(&entries, session_id))
+        .ok_or_else(|| {
+            PulseError::message(format!(
+                "no session `{session_id}` in the local span history; `pulse sessions list` shows the recorded ones"
+            ))
+        })?;
+    if json {
+        return print_json(&detail);
+    }
+    print_detail(&detail);
+    Ok(())
+}
+
+fn print_detail(detail: &SessionDetail) {
+    let summary = &detail.summary;
+    println!("Session {} ({})", summary.session_id, summary.source);
+    println!(
+        "  {} to {}, {} spans, {} errors",
+        local_time(summary.first),
+        local_time(summary.last),
+        summary.spans,
+        summary.errors
+    );
+    println!();
+    println!("{:<19}  {:<12}  {:<22}  DETAIL", "TIME", "KIND", "EVENT");
+    for span in &detail.spans {
+        let line = format!(
+            "{:<19}  {:<12}  {:<22}  {}",
+            DateTime::parse_from_rfc3339(&span.timestamp)
+                .map(|time| local_time(time.with_timezone(&Utc)))
+                .unwrap_or_else(|_| span.timestamp.clone()),
+            span.kind.as_deref().unwrap_or("-"),
+            span.event_type,
+            span_detail(span)
+        );
+        println!("{}", line.trim_end());
+    }
+    println!();
+    if detail.turns == 0 {
+        println!("No token usage recorded for this session.");
+    } else {
+        println!(
+            "Total over {} turns: {}",
+            detail.turns,
+            usage_line(&detail.usage)
+        );
+    }
+}
+
+/// Tool, status, duration, and usage, whichever the span has.
+fn span_detail(span: &HistoryEntry) -> String {
+    let mut parts = Vec::new();
+    if let Some(tool) = &span.tool_name {
+        parts.push(tool.clone());
+    }
+    if span.status != "success" {
+        parts.push(span.status.clone());
+    }
+    if let Some(ms) = span.duration_ms {
+        parts.push(format!("{ms:.0} ms"));
+    }
+    if let Some(usage) = &span.usage {
+        parts.push(usage_line(usage));
+    }
+    parts.join(", ")
+}
+
+/// `1200 in / 400 out tokens (30 reasoning, cache 7 read / 0 written), $0.0123`
+fn usage_line(usage: &Usage) -> String {
+    let mut line = format!(
+        "{} in / {} out tokens",
+        usage.input_tokens, usage.output_tokens
+    );
+    let mut extra = Vec::new();
+    if usage.reasoning_tokens > 0 {
+        extra.push(format!("{} reasoning", usage.reasoning_tokens));
+    }
+    if usage.cache_read_tokens > 0 || usage.cache_write_tokens > 0 {
+        extra.push(format!(
+            "cache {} read / {} written",
+            usage.cache_read_tokens, usage.cache_write_tokens
+        ));
+    }
+    if !extra.is_empty() {
+        line.push_str(&format!(" ({})", extra.join(", ")));
+    }
+    if let Some(cost) = usage.cost {
+        line.push_str(&format!(", ${cost:.4}"));
+    }
+    line
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_usage_line() {
+        let usage = Usage {
+            input_tokens: 1200,
+            output_tokens: 400,
+            cache_read_tokens: 7,
+            cost: Some(0.01234),
+            ..Usage::default()
+        };
+        assert_eq!(
+            usage_line(&usage),
+            "1200 in / 400 out tokens (cache 7 read / 0 written), $0.0123"
+        );
+        assert_eq!(usage_line(&Usage::default()), "0 in / 0 out tokens");
+    }
+}
@@ -1,34 +1,41 @@
 use std::{
+    fs,
     io::{self, Write},
-    process::{Command, Stdio},
+    path::Path,
+    process::{Child, Command, Stdio},
     time::Duration,
 };
 
+use chrono::{DateTime, TimeDelta, Utc};
+
 use clap::Args;
 use reqwest::{
     Client, Url,
     header::{COOKIE, HeaderMap, HeaderValue, SET_COOKIE},
 };
-use serde::Deserialize;
+use serde::{Deserialize, Serialize};
 use serde_json::json;
-use tokio::time::sleep;
 use uuid::Uuid;
 
 use crate::{
-    config::{ConfigStore, PulseConfig},
+    config::{ConfigStore, PulseConfig, validate_project_id, write_private},
     error::{PulseError, Result},
+    health,
+    http::{INSECURE_WARNING, check_client_identity, client_builder},
+    info,
+    net::is_local_host,
+    paths, verbose,
 };
 
-use super::run_connect;
+use super::{ConnectArgs, confirm_overwrite, run_connect};
 
 const DEFAULT_API_URL: &str = "http://localhost:3000";
 const DEFAULT_SERVER_COMMAND: &str = "pulse-server";
 const DEFAULT_PROJECT_NAME: &str = "Pulse Project";
 const DEFAULT_LOCAL_ACCOUNT_NAME: &str = "Local User";
 const HEALTH_TIMEOUT: Duration = Duration::from_secs(30);
-const HEALTH_INTERVAL: Duration = Duration::from_millis(500);
-const HTTP_TIMEOUT: Duration = Duration::from_secs(5);
-const USER_AGENT: &str = concat!("pulse-cli/", env!("CARGO_PKG_VERSION"));
+/// Lines of server output shown when the server exits during setup.
+const SERVER_LOG_TAIL_LINES: usize = 20;
 
 #[derive(Debug, Args)]
 pub struct SetupArgs {
@@ -62,6 +69,25 @@ pub struct SetupArgs {
     /// Skip automatic `pulse connect` at the end
     #[arg(long)]
     pub no_connect: bool,
+    /// PEM file with the CA certificate that signed the service's TLS certificate
+    #[arg(long, value_name = "PATH")]
+    pub ca_cert: Option<String>,
+    /// PEM client certificate, for a service that requires mutual TLS
+    #[arg(long, value_name = "PATH")]
+    pub client_cert: Option<String>,
+    /// PEM private key for `--client-cert`, if it is not in the same file
+    #[arg(long, value_name = "PATH", requires = "client_cert")]
+    pub client_key: Option<String>,
+    /// Disable TLS certificate verification (UNSAFE, local development only).
+    /// Running setup again without it turns verification back on
+    #[arg(long)]
+    pub insecure: bool,
+    /// Sign in again instead of reusing the cached session
+    #[arg(long)]
+    pub fresh_login: bool,
+    /// Overwrite an existing config without asking
+    #[arg(long)]
+    pub force: bool,
 }
 
 #[derive(Debug, Deserialize)]
@@ -100,8 +126,8 @@ struct CreateApiKeyResponse {
 }
 
 pub async fn run_setup(args: SetupArgs) -> Result<()> {
-    println!("Pulse setup");
-    println!("-----------");
+    info!("Pulse setup");
+    info!("-----------");
 
     let SetupArgs {
         api_url,
@@ -114,8 +140,19 @@ pub async fn run_setup(args: SetupArgs) -> Result<()> {
         server_command,
         no_start_server,
         no_connect,
+        ca_cert,
+        client_cert,
+        client_key,
+        insecure,
+        fresh_login,
+        force,
     } = args;
 
+    if !confirm_overwrite(force)? {
+        println!("Aborted.");
+        return Ok(());
+    }
+
     let existing_config = ConfigStore::load().ok();
 
     let api_url = match (api_url, local) {
@@ -154,7 +191,7 @@ pub async fn run_setup(args: SetupArgs) -> Result<()> {
         let local_password = password
             .or_else(|| persisted_pair.as_ref().map(|(_, value)| value.clone()))
             .unwrap_or_else(random_secret);
-        println!("Using local setup mode with managed local credentials.");
+        info!("Using local setup mode with managed local credentials.");
         (local_email, local_password)
     } else {
         let account_email = match email {
@@ -168,27 +205,62 @@ pub async fn run_setup(args: SetupArgs) -> Result<()> {
         (account_email, account_password)
     };
 
-    let client = Client::builder()
-        .user_agent(USER_AGENT)
-        .timeout(HTTP_TIMEOUT)
-        .build()?;
+    let transport = transport_config(
+        existing_config.unwrap_or_default(),
+        ca_cert,
+        client_cert,
+        client_key,
+        insecure,
+    );
+    if transport.accept_invalid_certs() {
+        println!("{INSECURE_WARNING}");
+    }
+    check_client_identity(&transport)?;
+    let client = client_builder(&transport)?.build()?;
 
     ensure_trace_service(&client, &base_url, &server_command, no_start_server).await?;
 
-    let session_cookie =
-        ensure_session_cookie(&client, &base_url, &name, &email, &password, &project_name).await?;
+    let cache_path = paths::session_cache()?;
+    verbose!("Session cache: {}", cache_path.display());
+    let cached = if fresh_login {
+        None
+    } else {
+        cached_session_cookie(&client, &base_url, &email, &cache_path).await
+    };
+    let session_cookie = match cached {
+        Some(cookie) => cookie,
+        None => {
+            let session =
+                ensure_session_cookie(&client, &base_url, &name, &email, &password, &project_name)
+                    .await?;
+            let cached = CachedSession {
+                api_url: base_url.to_string(),
+                email: email.trim().to_lowercase(),
+                cookie: session.value.clone(),
+                expires_at: session.expires_at,
+            };
+            // A cache that cannot be written only costs a sign-in next time.
+            let _ = cached.save(&cache_path);
+            session.value
+        }
+    };
 
     let (project_id, api_key) =
         resolve_project_and_api_key(&client, &base_url, &session_cookie, &project_name).await?;
 
-    let config = PulseConfig {
-        api_url: base_url.to_string(),
+    let local_credentials = local.then(|| (email.clone(), password.clone()));
+    let config = setup_config(
+        transport,
+        base_url.to_string(),
         api_key,
         project_id,
-        local_email: local.then(|| email.clone()),
-        local_password: local.then(|| password.clone()),
-    }
-    .sanitized();
+        local_credentials,
+    );
+    validate_project_id(&config.project_id).map_err(|err| {
+        PulseError::message(format!(
+            "the trace service returned an unusable project: {err}"
+        ))
+    })?;
 
     ConfigStore::save(&config)?;
     let config_path = ConfigStore::config_path()?;
@@ -200,30 +272,79 @@ pub async fn run_setup(args: SetupArgs) -> Result<()> {
         format_api_key_for_display(&config.api_key, show_api_key)
     );
     if local && !show_api_key {
-        println!("Use `pulse setup --local --show-api-key` to print the full API key.");
+        info!("Use `pulse setup --local --show-api-key` to print the full API key.");
     }
 
     if no_connect {
-        println!("Skipped agent integration setup (--no-connect).");
+        info!("Skipped agent integration setup (--no-connect).");
     } else {
-        println!("Installing agent integrations...");
-        run_connect()?;
+        info!("Installing agent integrations...");
+        run_connect(ConnectArgs::default(), false)?;
     }
 
     println!("Setup complete.");
-    println!("Run `pulse status` to verify connectivity and hooks.");
+    info!("Run `pulse status` to verify connectivity and hooks.");
 
     Ok(())
 }
 
+/// The existing config with the TLS flags given this time applied; every
+/// other setting carries over. `--insecure` is not: leaving it off turns
+/// certificate verification back on.
+fn transport_config(
+    existing: PulseConfig,
+    ca_cert: Option<String>,
+    client_cert: Option<String>,
+    client_key: Option<String>,
+    insecure: bool,
+) -> PulseConfig {
+    PulseConfig {
+        ca_cert_path: ca_cert.or(existing.ca_cert_path),
+        client_key_path: match client_cert {
+            Some(_) => client_key,
+            None => existing.client_key_path,
+        },
+        client_cert_path: client_cert.or(existing.client_cert_path),
+        danger_accept_invalid_certs: insecure.then_some(true),
+        ..existing
+    }
+}
+
+/// The config setup saves: `transport` with the service, key, and project
+/// setup just resolved. The new inline key replaces any `api_key_file` or
+/// `api_key_command`, and the project replaces any `active_project`.
+fn setup_config(
+    transport: PulseConfig,
+    api_url: String,
+    api_key: String,
+    project_id: String,
+    local_credentials: Option<(String, String)>,
+) -> PulseConfig {
+    let (local_email, local_password) = local_credentials.unzip();
+    PulseConfig {
+        api_url,
+        api_key,
+        api_key_file: None,
+        api_key_command: None,
+        project_id,
+        active_project: None,
+        local_email,
+        local_password,
+        ..transport
+    }
+    .sanitized()
+}
+
 async fn ensure_trace_service(
     client: &Client,
     base_url: &Url,
     server_command: &str,
     no_start_server: bool,
 ) -> Result<()> {
-    if is_healthy(client, base_url).await {
-        println!("Trace service reachable at {}", base_url);
+    let health_url = make_url(base_url, "/health")?;
+    verbose!("Checking {health_url}");
+    if health::check(client, &health_url).await.is_ok() {
+        info!("Trace service reachable at {}", base_url);
         return Ok(());
     }
 
@@ -242,43 +363,121 @@ async fn ensure_trace_service(
         )));
     }
 
-    println!(
+    info!(
         "Trace service is not reachable. Starting `{}` in the background...",
         server_command
     );
 
+    // The server's output goes to a log, so a server that fails to come up
+    // can say why. Without one it is discarded as before.
+    let log_path = paths::server_log()?;
+    let log = fs::create_dir_all(ConfigStore::config_dir()?)
+        .and_then(|_| fs::File::create(&log_path))
+        .and_then(|stdout| Ok((stdout.try_clone()?, stdout)))
+        .ok();
+    let log_path = log.is_some().then_some(log_path);
     let mut command = Command::new(server_command.trim());
-    command
-        .stdout(Stdio::null())
-        .stderr(Stdio::null())
-        .stdin(Stdio::null());
+    match log {
+        Some((stdout, stderr)) => command.stdout(stdout).stderr(stderr),
+        None => command.stdout(Stdio::null()).stderr(Stdio::null()),
+    };
+    command.stdin(Stdio::null());
 
     let used_defaults = apply_server_env_defaults(&mut command, base_url);
-    let child = command.spawn().map_err(|err| {
+    let mut child = command.spawn().map_err(|err| {
         PulseError::message(format!(
             "Failed to start `{}`: {err}",
             server_command.trim()
         ))
     })?;
 
-    println!("Started `{}` (pid={}).", server_command.trim(), child.id());
+    info!("Started `{}` (pid={}).", server_command.trim(), child.id());
     if used_defaults {
-        println!("Using generated local auth/encryption secrets for this server process.");
+        info!("Using generated local auth/encryption secrets for this server process.");
+    }
+    if let Some(log_path) = &log_path {
+        verbose!("Server output: {}", log_path.display());
     }
 
-    if wait_until_healthy(client, base_url, HEALTH_TIMEOUT, HEALTH_INTERVAL).await {
-        println!("Trace service is ready at {}", base_url);
+    wait_for_server(
+        client,
+        &health_url,
+        &mut child,
+        server_command.trim(),
+        log_path.as_deref(),
+        HEALTH_TIMEOUT,
+    )
+    .await?;
+    info!("Trace service is ready at {}", base_url);
+    Ok(())
+}
+
+/// Polls the health endpoint of the server `pulse setup` just started. A
+/// server that exits with an error (a port already in use, a bad
+/// environment) fails the wait right away, with the end of its output,
+/// instead of after `timeout`. A clean exit may be a server that put itself
+/// in the background, so polling goes on.
+async fn wait_for_server(
+    client: &Client,
+    health_url: &Url,
+    child: &mut Child,
+    name: &str,
+    log_path: Option<&Path>,
+    timeout: Duration,
+) -> Result<()> {
+    let mut exit = None;
+    let result = health::retry_until(timeout, health::POLL_INTERVAL, || {
+        if exit.is_none() {
+            exit = child
+                .try_wait()
+                .ok()
+                .flatten()
+                .filter(|status| !status.success());
+        }
+        let exited = exit;
+        async move {
+            match exited {
+                Some(status) => Err(PulseError::message(format!("exited with {status}"))),
+                None => health::check(client, health_url).await,
+            }
+        }
+    })
+    .await;
+    let Err(err) = result else {
         return Ok(());
-    }
+    };
 
+    let log_hint = log_path
+        .map(|path| format!("\nFull output: {}", path.display()))
+        .unwrap_or_default();
+    if let Some(status) = exit {
+        let output = log_path
+            .and_then(|path| fs::read_to_string(path).ok())
+            .map(|log| log_tail(&log, SERVER_LOG_TAIL_LINES))
+            .filter(|tail| !tail.is_empty())
+            .map(|tail| format!("\nLast output:\n{tail}"))
+            .unwrap_or_default();
+        return Err(PulseError::message(format!(
+            "`{name}` exited before the trace service came up ({status}).{output}{log_hint}"
+        )));
+    }
     Err(PulseError::message(format!(
-        "Trace service did not become healthy within {}s. \
-         Check server logs or start `{}` manually.",
-        HEALTH_TIMEOUT.as_secs(),
-        server_command.trim()
+        "Trace service did not become healthy within {}s ({err}). \
+         Check the server output or start `{name}` manually.{log_hint}",
+        timeout.as_secs(),
     )))
 }
 
+/// The last `lines` lines of `log`, indented.
+fn log_tail(log: &str, lines: usize) -> String {
+    let all: Vec<&str> = log.lines().filter(|line| !line.trim().is_empty()).collect();
+    all[all.len().saturating_sub(lines)..]
+        .iter()
+        .map(|line| format!("  {line}"))
+        .collect::<Vec<_>>()
+        .join("\n")
+}
+
 fn apply_server_env_defaults(command: &mut Command, base_url: &Url) -> bool {
     let mut used_defaults = false;
 
@@ -335,31 +534,71 @@ fn format_api_key_for_display(api_key: &str, show_full: bool) -> String {
     )
 }
 
-async fn wait_until_healthy(
-    client: &Client,
-    base_url: &Url,
-    timeout: Duration,
-    interval: Duration,
-) -> bool {
-    let mut elapsed = Duration::from_secs(0);
-    while elapsed <= timeout {
-        if is_healthy(client, base_url).await {
-            return true;
+/// Session cookie from an earlier `pulse setup`, kept in `~/.pulse/session.json`
+/// so repeated runs do not sign in every time and trip the sign-in rate limit.
+#[derive(Debug, Serialize, Deserialize)]
+struct CachedSession {
+    api_url: String,
+    email: String,
+    cookie: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    expires_at: Option<DateTime<Utc>>,
+}
+
+impl CachedSession {
+    fn load(path: &Path) -> Option<Self> {
+        serde_json::from_str(&fs::read_to_string(path).ok()?).ok()
+    }
+
+    fn save(&self, path: &Path) -> Result<()> {
+        if let Some(dir) = path.parent() {
+            fs::create_dir_all(dir)?;
         }
-        sleep(interval).await;
-        elapsed = elapsed.saturating_add(interval);
+        write_private(path, serde_json::to_string_pretty(self)?.as_bytes())
+    }
+
+    /// Whether the cookie was issued for this account on this server and
+    /// has at least a minute left.
+    fn usable_for(&self, base_url: &Url, email: &str, now: DateTime<Utc>) -> bool {
+        self.api_url == base_url.as_str()
+            && self.email == email.trim().to_lowercase()
+            && self
+                .expires_at
+                .is_none_or(|expires_at| expires_at > now + TimeDelta::minutes(1))
     }
-    false
 }
 
-async fn is_healthy(client: &Client, base_url: &Url) -> bool {
-    match make_url(base_url, "/health") {
-        Ok(url) => match client.get(url).send().await {
-            Ok(response) => response.status().is_success(),
-            Err(_) => false,
-        },
-        Err(_) => false,
+/// The cached session cookie, if it is still valid for this account. The
+/// server has the final word, so the cookie is checked against an
+/// authenticated endpoint before use.
+async fn cached_session_cookie(
+    client: &Client,
+    base_url: &Url,
+    email: &str,
+    path: &Path,
+) -> Option<String> {
+    let cached = CachedSession::load(path)?;
+    if !cached.usable_for(base_url, email, Utc::now()) {
+        return None;
     }
+    let url = make_url(base_url, "/dashboard/api/projects").ok()?;
+    let response = client
+        .get(url)
+        .header(COOKIE, cookie_header_value(&cached.cookie).ok()?)
+        .send()
+        .await
+        .ok()?;
+    if !response.status().is_success() {
+        let _ = fs::remove_file(path);
+        return None;
+    }
+    info!("Reusing the session from the last setup.");
+    Some(cached.cookie)
+}
+
+struct SessionCookie {
+    value: String,
+    expires_at: Option<DateTime<Utc>>,
 }
 
 async fn ensure_session_cookie(
@@ -369,18 +608,18 @@ async fn ensure_session_cookie(
     email: &str,
     password: &str,
     project_name: &str,
-) -> Result<String> {
+) -> Result<SessionCookie> {
     if let Some(cookie) = sign_in(client, base_url, email, password).await? {
-        println!("Signed in existing account.");
+        info!("Signed in existing account.");
         return Ok(cookie);
     }
 
-    println!("Creating account and first project...");
+    info!("Creating account and first project...");
     sign_up_with_project(client, base_url, name, email, password, project_name).await?;
 
     match sign_in(client, base_url, email, password).await? {
         Some(cookie) => {
-            println!("Signed in.");
+            info!("Signed in.");
             Ok(cookie)
         }
         None => Err(PulseError::message(
@@ -394,7 +633,7 @@ async fn sign_in(
     base_url: &Url,
     email: &str,
     password: &str,
-) -> Result<Option<String>> {
+) -> Result<Option<SessionCookie>> {
     let url = make_url(base_url, "/api/auth/sign-in/email")?;
     let response = client
         .post(url)
@@ -409,7 +648,7 @@ async fn sign_in(
         return Ok(None);
     }
 
-    let cookie = extract_session_cookie(response.headers()).ok_or_else(|| {
+    let cookie = extract_session_cookie(response.headers(), Utc::now()).ok_or_else(|| {
         PulseError::message("Sign-in succeeded but no session cookie was returned by the server")
     })?;
 
@@ -459,12 +698,12 @@ async fn resolve_project_and_api_key(
         .iter()
         .find(|project| project.name.trim() == project_name.trim())
     {
-        println!("Using existing project `{}`.", project.name);
+        info!("Using existing project `{}`.", project.name);
         let api_key = get_or_create_api_key(client, base_url, session_cookie, &project.id).await?;
         return Ok((project.id.clone(), api_key));
     }
 
-    println!("Creating project `{}`...", project_name.trim());
+    info!("Creating project `{}`...", project_name.trim());
     let created = create_project(client, base_url, session_cookie, project_name).await?;
     Ok((created.project_id, created.api_key))
 }
@@ -597,12 +836,37 @@ fn cookie_header_value(session_cookie: &str) -> Result<HeaderValue> {
         .map_err(|err| PulseError::message(format!("invalid session cookie: {err}")))
 }
 
-fn extract_session_cookie(headers: &HeaderMap) -> Option<String> {
+fn extract_session_cookie(headers: &HeaderMap, now: DateTime<Utc>) -> Option<SessionCookie> {
     headers
         .get_all(SET_COOKIE)
         .iter()
         .filter_map(|value| value.to_str().ok())
-        .find_map(extract_cookie_pair)
+        .find_map(|set_cookie| {
+            Some(SessionCookie {
+                value: extract_cookie_pair(set_cookie)?,
+                expires_at: cookie_expiry(set_cookie, now),
+            })
+        })
+}
+
+/// When a `Set-Cookie` header says the cookie expires, from `Max-Age`
+/// (which wins, as in browsers) or `Expires`.
+fn cookie_expiry(set_cookie: &str, now: DateTime<Utc>) -> Option<DateTime<Utc>> {
+    let attribute = |name: &str| {
+        set_cookie.split(';').skip(1).find_map(|part| {
+            let (key, value) = part.split_once('=')?;
+            key.trim()
+                .eq_ignore_ascii_case(name)
+                .then(|| value.trim().to_string())
+        })
+    };
+    if let Some(seconds) = attribute("Max-Age").and_then(|value| value.parse::<i64>().ok()) {
+        return Some(now + TimeDelta::seconds(seconds));
+    }
+    let expires = attribute("Expires")?;
+    DateTime::parse_from_rfc2822(&expires)
+        .ok()
+        .map(|time| time.with_timezone(&Utc))
 }
 
 fn extract_cookie_pair(set_cookie: &str) -> Option<String> {
@@ -628,10 +892,6 @@ fn normalize_base_url(raw: &str) -> Result<Url> {
     Url::parse(trimmed).map_err(|err| PulseError::message(format!("invalid API url: {err}")))
 }
 
-fn is_local_host(url: &Url) -> bool {
-    matches!(url.host_str(), Some("localhost" | "127.0.0.1" | "::1"))
-}
-
 fn compact_body(body: &str) -> String {
     let collapsed = body.split_whitespace().collect::<Vec<_>>().join(" ");
     if collapsed.len() <= 240 {
@@ -672,3 +932,130 @@ fn prompt_with_default(prompt: &str, default: &str) -> Result<String> {
         Ok(value.to_string())
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::TempDir;
+
+    #[test]
+    fn test_cookie_expiry_attributes() {
+        let now = DateTime::parse_from_rfc3339("2025-01-01T00:00:00Z")
+            .unwrap()
+            .with_timezone(&Utc);
+        let max_age = "better-auth.session_token=abc; Max-Age=3600; Path=/; HttpOnly";
+        assert_eq!(cookie_expiry(max_age, now), Some(now + TimeDelta::hours(1)));
+
+        let expires =
+            "better-auth.session_token=abc; Path=/; Expires=Wed, 08 Jan 2025 00:00:00 GMT";
+        assert_eq!(cookie_expiry(expires, now), Some(now + TimeDelta::days(7)));
+
+        assert_eq!(
+            cookie_expiry("better-auth.session_token=abc; Path=/", now),
+            None
+        );
+    }
+
+    #[test]
+    fn test_cached_session_matches_account_and_expiry() {
+        let tmp = TempDir::new().unwrap();
+        let path = tmp.path().join(paths::SESSION_CACHE_FILE);
+        let base_url = normalize_base_url("http://localhost:3000").unwrap();
+        let now = Utc::now();
+        CachedSession {
+            api_url: base_url.to_string(),
+            email: "me@example.com".to_string(),
+            cookie: "better-auth.session_token=abc".to_string(),
+            expires_at: Some(now + TimeDelta::hours(1)),
+        }
+        .save(&path)
+        .unwrap();
+
+        let cached = CachedSession::load(&path).unwrap();
+        assert!(cached.usable_for(&base_url, " Me@Example.com", now));
+        assert!(!cached.usable_for(&base_url, "other@example.com", now));
+        let other_server = normalize_base_url("http://localhost:4000").unwrap();
+        assert!(!cached.usable_for(&other_server, "me@example.com", now));
+        assert!(!cached.usable_for(&base_url, "me@example.com", now + TimeDelta::hours(2)));
+
+        #[cfg(unix)]
+        {
+            use std::os::unix::fs::PermissionsExt;
+            let mode = fs::metadata(&path).unwrap().permissions().mode();
+            assert_eq!(mode & 0o777, 0o600);
+        }
+    }
+
+    #[test]
+    fn test_setup_keeps_other_settings() {
+        let existing = PulseConfig {
+            api_url: "http://old".to_string(),
+            api_key_file: Some("/secrets/pulse.key".to_string()),
+            project_id: "proj_old".to_string(),
+            sample_rate: Some(0.5),
+            labels: [("env".to_string(), "prod".to_string())].into(),
+            ca_cert_path: Some("/etc/ca.pem".to_string()),
+            ..PulseConfig::default()
+        };
+        let transport = transport_config(existing, None, None, None, false);
+        let config = setup_config(
+            transport,
+            "http://new".to_string(),
+            "key_new".to_string(),
+            "proj_new".to_string(),
+            None,
+        );
+
+        assert_eq!(config.api_url, "http://new");
+        assert_eq!(config.api_key, "key_new");
+        assert_eq!(config.api_key_file, None);
+        assert_eq!(config.project_id, "proj_new");
+        assert_eq!(config.sample_rate, Some(0.5));
+        assert_eq!(config.labels["env"], "prod");
+        assert_eq!(config.ca_cert_path.as_deref(), Some("/etc/ca.pem"));
+    }
+
+    #[test]
+    fn test_setup_without_insecure_turns_verification_back_on() {
+        let insecure = transport_config(PulseConfig::default(), None, None, None, true);
+        assert!(insecure.accept_invalid_certs());
+
+        let again = transport_config(insecure, None, None, None, false);
+        assert!(!again.accept_invalid_certs());
+        assert_eq!(again.danger_accept_invalid_certs, None);
+    }
+
+    #[cfg(unix)]
+    #[tokio::test]
+    async fn test_server_that_exits_fails_fast() {
+        let tmp = TempDir::new().unwrap();
+        let log_path = tmp.path().join(paths::SERVER_LOG_FILE);
+        let log = fs::File::create(&log_path).unwrap();
+        let mut child = Command::new("sh")
+            .args(["-c", "echo 'port 3000 already in use' >&2; exit 1"])
+            .stdout(Stdio::null())
+            .stderr(log)
+            .spawn()
+            .unwrap();
+
+        // Nothing listens on port 9, so only the exit can end the wait early.
+        let health_url = Url::parse("http://127.0.0.1:9/api/health").unwrap();
+        let started = std::time::Instant::now();
+        let err = wait_for_server(
+            &Client::new(),
+            &health_url,
+            &mut child,
+            "pulse-server",
+            Some(&log_path),
+            Duration::from_secs(30),
+        )
+        .await
+        .unwrap_err()
+        .to_string();
+
+        assert!(started.elapsed() < Duration::from_secs(10), "{err}");
+        assert!(err.contains("exit status: 1"), "{err}");
+        assert!(err.contains("  port 3000 already in use"), "{err}");
+        assert!(err.contains(&log_path.display().to_string()), "{err}");
+    }
+}
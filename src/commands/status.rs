@@ -1,14 +1,50 @@
+use std::{
+    env, fs,
+    path::{Path, PathBuf},
+    process::Command,
+    time::Duration,
+};
+
+use clap::Args;
+use serde::Serialize;
+use serde_json::json;
+
 use crate::{
-    commands::registered_hooks,
-    config::ConfigStore,
+    commands::{HookOptions, find_executable, print_json, registered_hooks_with},
+    config::{ConfigStore, PulseConfig},
     error::{PulseError, Result},
+    health::{self, POLL_INTERVAL},
     hooks::HookStatus,
     http::TraceHttpClient,
+    output::{Tone, bullet, mark, paint},
 };
 
-pub async fn run_status() -> Result<()> {
+#[derive(Debug, Default, Args)]
+pub struct StatusArgs {
+    /// Only these tools, comma-separated (claude_code, opencode, openclaw, cursor, gemini, codex)
+    #[arg(long, value_delimiter = ',')]
+    pub tools: Vec<String>,
+    /// Config directory of the one tool given with `--tools` (opencode or openclaw)
+    #[arg(long)]
+    pub config_dir: Option<PathBuf>,
+    /// Seconds to keep retrying an unreachable trace service
+    #[arg(long, value_name = "SECS", default_value_t = 0)]
+    pub timeout: u64,
+}
+
+pub async fn run_status(args: StatusArgs, json: bool) -> Result<()> {
+    let hook_options = HookOptions {
+        tools: args.tools,
+        config_dir: args.config_dir,
+        ..HookOptions::default()
+    };
+    let timeout = Duration::from_secs(args.timeout);
+
     let config = match ConfigStore::load() {
         Ok(cfg) => cfg,
+        Err(PulseError::ConfigMissing) if json => {
+            return print_json(&json!({ "initialized": false }));
+        }
         Err(PulseError::ConfigMissing) => {
             println!("Pulse is not initialized. Run `pulse init` first.");
             return Ok(());
@@ -16,24 +52,53 @@ pub async fn run_status() -> Result<()> {
         Err(err) => return Err(err),
     };
 
+    if json {
+        return print_status_json(&config, hook_options, timeout).await;
+    }
+
     println!("Configuration");
     println!("  API URL     : {}", config.api_url);
     println!("  Project ID  : {}", config.project_id);
+    if let Some(name) = config.active_project() {
+        println!("  Project     : {name}");
+    }
     let config_path = ConfigStore::config_path()?;
     println!("  Config file : {}", config_path.display());
-    println!("  API key     : {}", mask_key(&config.api_key));
+    println!("  API key     : {}", describe_key(&config));
+
+    println!("\nEnvironment");
+    let environment = Environment::detect(&config);
+    for (label, binary) in [
+        ("Running   ", environment.running.as_ref()),
+        ("On PATH   ", environment.on_path.as_ref()),
+        ("pulse_bin ", environment.pulse_bin.as_ref()),
+    ] {
+        if let Some(binary) = binary {
+            println!("  {label}  : {binary}");
+        }
+    }
+    for warning in &environment.warnings {
+        println!("  {}: {warning}", paint(Tone::Warning, "Warning"));
+    }
 
     println!("\nConnectivity");
     match TraceHttpClient::new(&config) {
-        Ok(client) => match client.health_check().await {
-            Ok(_) => println!("  Trace service reachable"),
-            Err(err) => println!("  Unable to reach trace service: {err}"),
-        },
-        Err(err) => println!("  Invalid configuration: {err}"),
+        Ok(client) => {
+            match health::retry_until(timeout, POLL_INTERVAL, || client.health_check()).await {
+                Ok(_) => println!("  {}Trace service reachable", mark(Tone::Good)),
+                Err(err) => {
+                    println!("  {}Unable to reach trace service: {err}", mark(Tone::Bad));
+                    if let Some(hint) = err.remediation() {
+                        println!("  {hint}");
+                    }
+                }
+            }
+        }
+        Err(err) => println!("  {}Invalid configuration: {err}", mark(Tone::Bad)),
     }
 
     println!("\nHooks");
-    for hook in registered_hooks()? {
+    for hook in registered_hooks_with(hook_options)? {
         let status = hook.status()?;
         print_hook_status(&status);
     }
@@ -41,7 +106,54 @@ pub async fn run_status() -> Result<()> {
     Ok(())
 }
 
-fn mask_key(key: &str) -> String {
+async fn print_status_json(
+    config: &PulseConfig,
+    hook_options: HookOptions,
+    timeout: Duration,
+) -> Result<()> {
+    let connectivity = match TraceHttpClient::new(config) {
+        Ok(client) => {
+            match health::retry_until(timeout, POLL_INTERVAL, || client.health_check()).await {
+                Ok(()) => json!({ "reachable": true }),
+                Err(err) => json!({
+                    "reachable": false,
+                    "error": err.to_string(),
+                    "hint": err.remediation(),
+                }),
+            }
+        }
+        Err(err) => json!({ "reachable": false, "error": err.to_string() }),
+    };
+    let hooks = registered_hooks_with(hook_options)?
+        .iter()
+        .map(|hook| hook.status())
+        .collect::<Result<Vec<HookStatus>>>()?;
+
+    print_json(&json!({
+        "initialized": true,
+        "config": {
+            "api_url": config.api_url,
+            "project_id": config.project_id,
+            "project": config.active_project(),
+            "config_file": ConfigStore::config_path()?,
+            "api_key": describe_key(config),
+        },
+        "environment": Environment::detect(config),
+        "connectivity": connectivity,
+        "hooks": hooks,
+    }))
+}
+
+/// The masked key, or where it is read from when it is not inline.
+fn describe_key(config: &PulseConfig) -> String {
+    match (&config.api_key_file, &config.api_key_command) {
+        (Some(path), _) if config.api_key.is_empty() => format!("(from file {path})"),
+        (_, Some(command)) if config.api_key.is_empty() => format!("(from `{command}`)"),
+        _ => mask_key(&config.api_key),
+    }
+}
+
+pub(crate) fn mask_key(key: &str) -> String {
     if key.is_empty() {
         return "(empty)".to_string();
     }
@@ -68,11 +180,18 @@ fn print_hook_status(status: &HookStatus) {
         .map(|path| format!(" ({})", path.display()))
         .unwrap_or_default();
 
-    if status.connected {
-        println!("  - {}: connected{}", status.tool, suffix);
+    let (tone, state) = if status.connected {
+        (Tone::Good, "connected")
     } else {
-        println!("  - {}: disconnected{}", status.tool, suffix);
-    }
+        (Tone::Bad, "disconnected")
+    };
+    println!(
+        "  {} {}: {}{}",
+        bullet(tone),
+        status.tool,
+        paint(tone, state),
+        suffix
+    );
 
     if status.total_hooks > 0 {
         println!(
@@ -86,4 +205,157 @@ fn print_hook_status(status: &HookStatus) {
             println!("    Run `pulse connect` to install missing hooks");
         }
     }
+    if let Some(message) = &status.message {
+        println!("    {message}");
+    }
+}
+
+/// A `pulse` executable and the version it reports.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+struct Binary {
+    path: PathBuf,
+    version: Option<String>,
+}
+
+impl Binary {
+    /// The binary at `path`. Asks it for its version unless it is `running`,
+    /// whose version is already known.
+    fn at(path: &Path, running: Option<&Binary>) -> Self {
+        let path = fs::canonicalize(path).unwrap_or_else(|_| path.to_path_buf());
+        let version = match running {
+            Some(running) if running.path == path => running.version.clone(),
+            _ => Command::new(&path)
+                .arg("--version")
+                .output()
+                .ok()
+                .filter(|output| output.status.success())
+                .and_then(|output| parse_version(&String::from_utf8_lossy(&output.stdout))),
+        };
+        Self { path, version }
+    }
+}
+
+impl std::fmt::Display for Binary {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let version = self.version.as_deref().unwrap_or("unknown version");
+        write!(f, "{} ({version})", self.path.display())
+    }
+}
+
+/// Which `pulse` the hooks will run, next to the one running now. Hooks
+/// call `pulse` by name (or `pulse_bin` for Claude Code after `connect
+/// --absolute-path`), so after installing a new version somewhere else they
+/// can keep running the old one.
+#[derive(Debug, Serialize)]
+struct Environment {
+    running: Option<Binary>,
+    on_path: Option<Binary>,
+    pulse_bin: Option<Binary>,
+    warnings: Vec<String>,
+}
+
+impl Environment {
+    fn detect(config: &PulseConfig) -> Self {
+        let running = env::current_exe().ok().map(|path| Binary {
+            path: fs::canonicalize(&path).unwrap_or(path),
+            version: Some(env!("CARGO_PKG_VERSION").to_string()),
+        });
+        let on_path = find_executable("pulse").map(|path| Binary::at(&path, running.as_ref()));
+        let pulse_bin = config
+            .pulse_bin
+            .as_deref()
+            .filter(|bin| !bin.trim().is_empty())
+            .map(|bin| Binary::at(Path::new(bin), running.as_ref()));
+
+        let mut warnings = Vec::new();
+        if on_path.is_none() {
+            warnings.push(
+                "`pulse` is not on PATH, so hooks that call it by name will fail".to_string(),
+            );
+        }
+        if let Some(running) = &running {
+            for (binary, fix) in [
+                (
+                    on_path.as_ref(),
+                    "put this one first on PATH, or run `pulse connect --absolute-path`",
+                ),
+                (
+                    pulse_bin.as_ref(),
+                    "run `pulse connect --absolute-path` with this one",
+                ),
+            ] {
+                if let Some(warning) = binary.and_then(|binary| mismatch(binary, running, fix)) {
+                    warnings.push(warning);
+                }
+            }
+        }
+        Self {
+            running,
+            on_path,
+            pulse_bin,
+            warnings,
+        }
+    }
+}
+
+/// A warning when hooks run `hooks` instead of `running` and it is not
+/// known to be the same version. Another copy of the same version is fine.
+fn mismatch(hooks: &Binary, running: &Binary, fix: &str) -> Option<String> {
+    if hooks.path == running.path || (hooks.version.is_some() && hooks.version == running.version) {
+        return None;
+    }
+    Some(format!(
+        "hooks run {hooks}, not this pulse {running}; {fix}"
+    ))
+}
+
+/// The version in `pulse --version` output (`pulse 0.2.5`).
+fn parse_version(output: &str) -> Option<String> {
+    output
+        .trim()
+        .strip_prefix("pulse ")
+        .map(|version| version.trim().to_string())
+        .filter(|version| !version.is_empty())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn binary(path: &str, version: Option<&str>) -> Binary {
+        Binary {
+            path: PathBuf::from(path),
+            version: version.map(str::to_string),
+        }
+    }
+
+    #[test]
+    fn test_mismatch_needs_another_version() {
+        let running = binary("/home/me/.cargo/bin/pulse", Some("0.3.0"));
+        assert!(mismatch(&running.clone(), &running, "fix").is_none());
+        assert!(
+            mismatch(
+                &binary("/usr/local/bin/pulse", Some("0.3.0")),
+                &running,
+                "fix"
+            )
+            .is_none()
+        );
+
+        let old = binary("/usr/local/bin/pulse", Some("0.2.5"));
+        let warning = mismatch(&old, &running, "fix").unwrap();
+        assert!(
+            warning.contains("/usr/local/bin/pulse (0.2.5)"),
+            "{warning}"
+        );
+        assert!(warning.contains("(0.3.0)"), "{warning}");
+        assert!(mismatch(&binary("/usr/local/bin/pulse", None), &running, "fix").is_some());
+    }
+
+    #[test]
+    fn test_parse_version() {
+        assert_eq!(parse_version("pulse 0.2.5\n").as_deref(), Some("0.2.5"));
+        assert_eq!(parse_version("something else"), None);
+        assert_eq!(parse_version("pulse "), None);
+    }
 }
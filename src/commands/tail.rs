@@ -0,0 +1,225 @@
+use std::{
+    fs::{self, File},
+    io::{self, ErrorKind, Read, Seek, SeekFrom, Write},
+    path::{Path, PathBuf},
+    time::Duration,
+};
+
+use clap::Args;
+
+use crate::{
+    debug::{self, EVENT_HEADER, HTTP_HEADER},
+    error::Result,
+    paths,
+};
+
+const POLL_INTERVAL: Duration = Duration::from_millis(250);
+
+#[derive(Debug, Args)]
+pub struct TailArgs {
+    /// Print the last N lines before following
+    #[arg(long, short = 'n', default_value_t = 10)]
+    pub lines: usize,
+    /// Only show entries for this event type
+    #[arg(long)]
+    pub event_type: Option<String>,
+}
+
+pub async fn run_tail(args: TailArgs) -> Result<()> {
+    let path = paths::debug_log();
+    let mut filter = EntryFilter::new(args.event_type);
+    let mut follower = Follower::new(path.clone());
+
+    let existing = follower.poll()?;
+    if follower.file.is_none() {
+        eprintln!("Waiting for {} (is PULSE_DEBUG=1 set?)", path.display());
+    }
+    let kept: Vec<String> = existing
+        .into_iter()
+        .filter(|line| filter.keep(line))
+        .collect();
+    let skip = kept.len().saturating_sub(args.lines);
+    print_lines(kept.into_iter().skip(skip))?;
+
+    loop {
+        tokio::time::sleep(POLL_INTERVAL).await;
+        let lines = follower.poll()?;
+        print_lines(lines.into_iter().filter(|line| filter.keep(line)))?;
+    }
+}
+
+fn print_lines(lines: impl IntoIterator<Item = String>) -> Result<()> {
+    let mut stdout = io::stdout().lock();
+    for line in lines {
+        writeln!(stdout, "{line}")?;
+    }
+    stdout.flush()?;
+    Ok(())
+}
+
+/// Decides line by line whether the entry it belongs to is shown. An event
+/// entry runs from its `── [time] type ──` header to the next header; HTTP
+/// lines are not tied to an event type, so a filter hides them.
+struct EntryFilter {
+    event_type: Option<String>,
+    showing: bool,
+}
+
+impl EntryFilter {
+    fn new(event_type: Option<String>) -> Self {
+        Self {
+            showing: event_type.is_none(),
+            event_type,
+        }
+    }
+
+    fn keep(&mut self, line: &str) -> bool {
+        let Some(wanted) = &self.event_type else {
+            return true;
+        };
+        if line.starts_with(EVENT_HEADER) {
+            self.showing = debug::event_type(line) == Some(wanted.as_str());
+        } else if line.starts_with(HTTP_HEADER) {
+            self.showing = false;
+        }
+        self.showing
+    }
+}
+
+/// Reads the lines appended to a file since the last poll. Starts over from
+/// the top when the file is truncated or replaced (rotated), and waits for
+/// it to appear if it does not exist yet.
+struct Follower {
+    path: PathBuf,
+    file: Option<File>,
+    id: Option<u64>,
+    pos: u64,
+    partial: Vec<u8>,
+}
+
+impl Follower {
+    fn new(path: PathBuf) -> Self {
+        Self {
+            path,
+            file: None,
+            id: None,
+            pos: 0,
+            partial: Vec::new(),
+        }
+    }
+
+    /// Complete lines written since the last call. A line still being
+    /// written is held back until its newline arrives.
+    fn poll(&mut self) -> Result<Vec<String>> {
+        let meta = match fs::metadata(&self.path) {
+            Ok(meta) => meta,
+            Err(err) if err.kind() == ErrorKind::NotFound => return Ok(Vec::new()),
+            Err(err) => return Err(err.into()),
+        };
+        let replaced = self.file.is_some() && file_id(&self.path) != self.id;
+        if self.file.is_none() || replaced || meta.len() < self.pos {
+            self.file = Some(File::open(&self.path)?);
+            self.id = file_id(&self.path);
+            self.pos = 0;
+            self.partial.clear();
+        }
+
+        let Some(file) = self.file.as_mut() else {
+            return Ok(Vec::new());
+        };
+        file.seek(SeekFrom::Start(self.pos))?;
+        let read = file.read_to_end(&mut self.partial)?;
+        self.pos += read as u64;
+
+        let Some(end) = self.partial.iter().rposition(|byte| *byte == b'\n') else {
+            return Ok(Vec::new());
+        };
+        let rest = self.partial.split_off(end + 1);
+        let complete = std::mem::replace(&mut self.partial, rest);
+        Ok(String::from_utf8_lossy(&complete)
+            .lines()
+            .map(str::to_string)
+            .collect())
+    }
+}
+
+/// Identifies the file behind `path`, so a rotated log is noticed even when
+/// the new file has already grown past the old read position.
+#[cfg(unix)]
+fn file_id(path: &Path) -> Option<u64> {
+    use std::os::unix::fs::MetadataExt;
+    fs::metadata(path).ok().map(|meta| meta.ino())
+}
+
+#[cfg(not(unix))]
+fn file_id(_path: &Path) -> Option<u64> {
+    None
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::fs::OpenOptions;
+    use tempfile::TempDir;
+
+    fn append(path: &Path, text: &str) {
+        let mut file = OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(path)
+            .unwrap();
+        file.write_all(text.as_bytes()).unwrap();
+    }
+
+    #[test]
+    fn test_follower_reads_appended_lines() {
+        let tmp = TempDir::new().unwrap();
+        let path = tmp.path().join("debug.log");
+        let mut follower = Follower::new(path.clone());
+        assert!(follower.poll().unwrap().is_empty());
+
+        append(&path, "one\ntwo\nthr");
+        assert_eq!(follower.poll().unwrap(), vec!["one", "two"]);
+        append(&path, "ee\n");
+        assert_eq!(follower.poll().unwrap(), vec!["three"]);
+        assert!(follower.poll().unwrap().is_empty());
+    }
+
+    #[test]
+    fn test_follower_restarts_after_truncation_and_rotation() {
+        let tmp = TempDir::new().unwrap();
+        let path = tmp.path().join("debug.log");
+        let mut follower = Follower::new(path.clone());
+        append(&path, "old line\n");
+        follower.poll().unwrap();
+
+        fs::write(&path, "").unwrap();
+        append(&path, "new\n");
+        assert_eq!(follower.poll().unwrap(), vec!["new"]);
+
+        fs::rename(&path, tmp.path().join("debug.log.1")).unwrap();
+        append(&path, "rotated file, longer than before\n");
+        assert_eq!(
+            follower.poll().unwrap(),
+            vec!["rotated file, longer than before"]
+        );
+    }
+
+    #[test]
+    fn test_filter_keeps_matching_entries() {
+        let log = [
+            "── [2025-01-01T00:00:00+00:00] stop ──",
+            "{\"session_id\": \"s1\"}",
+            "",
+            ">> [2025-01-01T00:00:01+00:00] POST http://x/v1/spans/async -> 202 Accepted",
+            "── [2025-01-01T00:00:02+00:00] post_tool_use ──",
+            "{\"session_id\": \"s1\"}",
+        ];
+        let mut filter = EntryFilter::new(Some("stop".to_string()));
+        let kept: Vec<&str> = log.into_iter().filter(|line| filter.keep(line)).collect();
+        assert_eq!(kept, &log[..3]);
+
+        let mut filter = EntryFilter::new(None);
+        assert!(log.iter().all(|line| filter.keep(line)));
+    }
+}
@@ -0,0 +1,84 @@
+use std::{fs, io::ErrorKind, path::Path};
+
+use clap::Args;
+
+use crate::{
+    commands::{confirm, registered_hooks},
+    config::ConfigStore,
+    error::Result,
+    info,
+};
+
+#[derive(Debug, Args)]
+pub struct UninstallArgs {
+    /// Do not ask for confirmation
+    #[arg(long, short = 'y')]
+    pub yes: bool,
+}
+
+pub fn run_uninstall(args: UninstallArgs) -> Result<()> {
+    let pulse_dir = ConfigStore::config_dir()?;
+
+    if !args.yes {
+        println!("This removes Pulse hooks from every agent and deletes");
+        println!(
+            "{} (config, offline queue, buffers, debug log).",
+            pulse_dir.display()
+        );
+        if !confirm("Continue?")? {
+            println!("Aborted.");
+            return Ok(());
+        }
+    }
+
+    // Unlike `disconnect`, keep going past a hook that fails so one broken
+    // settings file does not leave pulse half-installed.
+    info!("Removing hooks...");
+    for hook in registered_hooks()? {
+        let line = match hook.disconnect() {
+            Ok(status) if !status.detected => "not detected".to_string(),
+            Ok(status) if status.modified => match &status.path {
+                Some(path) => format!("hooks removed ({})", path.display()),
+                None => "hooks removed".to_string(),
+            },
+            Ok(_) => "no hooks to remove".to_string(),
+            Err(err) => format!("failed to remove hooks: {err}"),
+        };
+        println!("- {}: {line}", hook.tool_name());
+    }
+
+    if remove_dir(&pulse_dir)? {
+        println!("Deleted {}", pulse_dir.display());
+    } else {
+        println!("{} does not exist", pulse_dir.display());
+    }
+    println!("Pulse is uninstalled. Remove the `pulse` binary to finish.");
+    Ok(())
+}
+
+/// Deletes `dir` and everything in it. Returns `false` if it was not there.
+fn remove_dir(dir: &Path) -> Result<bool> {
+    match fs::remove_dir_all(dir) {
+        Ok(()) => Ok(true),
+        Err(err) if err.kind() == ErrorKind::NotFound => Ok(false),
+        Err(err) => Err(err.into()),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::TempDir;
+
+    #[test]
+    fn test_remove_dir_handles_missing_directory() {
+        let tmp = TempDir::new().unwrap();
+        let dir = tmp.path().join(".pulse");
+        fs::create_dir_all(dir.join("pending")).unwrap();
+        fs::write(dir.join("config.toml"), "api_url = \"x\"").unwrap();
+
+        assert!(remove_dir(&dir).unwrap());
+        assert!(!dir.exists());
+        assert!(!remove_dir(&dir).unwrap());
+    }
+}
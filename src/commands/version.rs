@@ -0,0 +1,130 @@
+use std::env;
+
+use chrono::DateTime;
+use serde_json::json;
+
+use crate::{
+    commands::{print_json, registered_hooks},
+    config::ConfigStore,
+    error::Result,
+    hooks::HookStatus,
+    output::{self, Verbosity},
+};
+
+const VERSION: &str = env!("CARGO_PKG_VERSION");
+const GIT_COMMIT: &str = env!("PULSE_GIT_COMMIT");
+const BUILD_TIMESTAMP: &str = env!("PULSE_BUILD_TIMESTAMP");
+const BUILD_PROFILE: &str = env!("PULSE_BUILD_PROFILE");
+const BUILD_TARGET: &str = env!("PULSE_BUILD_TARGET");
+
+/// `pulse version` prints what `--version` does. With `--verbose` (or
+/// `--json`) it adds the build, the config file, and the detected tools:
+/// what a bug report needs, in one paste.
+pub fn run_version(json: bool) -> Result<()> {
+    if !json && output::verbosity() != Verbosity::Verbose {
+        println!("pulse {VERSION}");
+        return Ok(());
+    }
+
+    let config_path = ConfigStore::config_path()?;
+    let config_found = config_path.exists();
+    let hooks = registered_hooks()?;
+    let tools = hooks
+        .iter()
+        .map(|hook| hook.status())
+        .collect::<Result<Vec<HookStatus>>>()?;
+
+    if json {
+        let tools: Vec<_> = hooks
+            .iter()
+            .zip(&tools)
+            .map(|(hook, status)| {
+                json!({
+                    "id": hook.id(),
+                    "tool": status.tool,
+                    "detected": status.detected,
+                    "connected": status.connected,
+                })
+            })
+            .collect();
+        return print_json(&json!({
+            "version": VERSION,
+            "commit": non_empty(GIT_COMMIT),
+            "built_at": built_at(),
+            "profile": BUILD_PROFILE,
+            "target": BUILD_TARGET,
+            "os": env::consts::OS,
+            "arch": env::consts::ARCH,
+            "config_file": config_path,
+            "config_found": config_found,
+            "tools": tools,
+        }));
+    }
+
+    println!("pulse {VERSION}");
+    println!("  commit  : {}", non_empty(GIT_COMMIT).unwrap_or("unknown"));
+    println!("  built   : {}", built_at().as_deref().unwrap_or("unknown"));
+    println!("  build   : {BUILD_PROFILE} ({BUILD_TARGET})");
+    println!("  os      : {}/{}", env::consts::OS, env::consts::ARCH);
+    println!(
+        "  config  : {}{}",
+        config_path.display(),
+        if config_found { "" } else { " (missing)" }
+    );
+    println!("  tools   : {}", tool_summary(&tools));
+    Ok(())
+}
+
+/// `claude_code (connected), cursor (detected)`; tools that are not
+/// installed are left out.
+fn tool_summary(tools: &[HookStatus]) -> String {
+    let found: Vec<String> = tools
+        .iter()
+        .filter(|status| status.detected)
+        .map(|status| {
+            let state = if status.connected {
+                "connected"
+            } else {
+                "detected"
+            };
+            format!("{} ({state})", status.tool)
+        })
+        .collect();
+    if found.is_empty() {
+        "none detected".to_string()
+    } else {
+        found.join(", ")
+    }
+}
+
+fn built_at() -> Option<String> {
+    let secs = BUILD_TIMESTAMP.parse().ok()?;
+    DateTime::from_timestamp(secs, 0).map(|time| time.to_rfc3339())
+}
+
+fn non_empty(value: &str) -> Option<&str> {
+    (!value.is_empty()).then_some(value)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::path::PathBuf;
+
+    use super::*;
+
+    #[test]
+    fn test_tool_summary_lists_detected_tools() {
+        let mut claude = HookStatus::not_detected("claude_code", PathBuf::from("settings.json"));
+        claude.detected = true;
+        claude.connected = true;
+        let mut cursor = HookStatus::not_detected("cursor", PathBuf::from("hooks.json"));
+        cursor.detected = true;
+        let gemini = HookStatus::not_detected("gemini", PathBuf::from("hooks.json"));
+
+        assert_eq!(
+            tool_summary(&[claude, cursor, gemini.clone()]),
+            "claude_code (connected), cursor (detected)"
+        );
+        assert_eq!(tool_summary(&[gemini]), "none detected");
+    }
+}
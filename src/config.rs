@@ -1,29 +1,680 @@
-use std::{fs, io::ErrorKind, path::PathBuf};
+use std::{
+    collections::BTreeMap,
+    env, fs,
+    io::{ErrorKind, Write},
+    path::{Path, PathBuf},
+    process::{Command, Stdio},
+    time::Duration,
+};
 
 use dirs::home_dir;
 use serde::{Deserialize, Serialize};
 
-use crate::error::{PulseError, Result};
+use crate::{
+    error::{PulseError, Result},
+    hooks::span::MappingOverrides,
+};
 
 const CONFIG_DIR: &str = ".pulse";
-const CONFIG_FILE: &str = "config.toml";
+pub(crate) const CONFIG_FILE: &str = "config.toml";
+pub const API_URL_ENV: &str = "PULSE_API_URL";
+pub const API_KEY_ENV: &str = "PULSE_API_KEY";
+pub const PROJECT_ID_ENV: &str = "PULSE_PROJECT_ID";
+/// `PULSE_LABEL_<key>=value` sets one span label.
+pub const LABEL_ENV_PREFIX: &str = "PULSE_LABEL_";
+/// Name given to the `project_id` of a config without `[[projects]]`.
+pub const DEFAULT_PROJECT_NAME: &str = "default";
+const DEFAULT_BUFFER_MAX_SPANS: usize = 50;
+const DEFAULT_MAX_FIELD_BYTES: usize = 64 * 1024;
+const DEFAULT_FLUSH_BATCH_SIZE: usize = 100;
+const DEFAULT_FLUSH_CONCURRENCY: usize = 4;
+const DEFAULT_HEARTBEAT_INTERVAL_SECS: u64 = 30;
+pub const DEFAULT_SPANS_PATH: &str = "/v1/spans/async";
+pub const DEFAULT_HEALTH_PATH: &str = "/health";
+pub const DEFAULT_AUTH_HEADER: &str = "bearer";
+const DEFAULT_HTTP_TIMEOUT_MS: u64 = 5_000;
+const DEFAULT_EMIT_TIMEOUT_MS: u64 = 2_000;
+const DEFAULT_STDIN_TIMEOUT_MS: u64 = 1_000;
+const DEFAULT_BREAKER_FAILURES: u32 = 3;
+const DEFAULT_BREAKER_WINDOW_SECS: u64 = 60;
+const DEFAULT_BREAKER_COOLDOWN_SECS: u64 = 30;
+pub const DEFAULT_OTLP_ENDPOINT: &str = "http://localhost:4318/v1/traces";
 
-#[derive(Debug, Clone, Serialize, Deserialize)]
+/// Upgrades a config file one schema version at a time: entry `n` turns a
+/// version `n` file into version `n + 1`.
+const MIGRATIONS: &[fn(&mut toml::Table)] = &[
+    // v1 only adds `version`; every v0 key still parses as-is.
+    |_| {},
+];
+/// Schema version written by this build.
+pub const CONFIG_VERSION: u32 = MIGRATIONS.len() as u32;
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
 pub struct PulseConfig {
+    /// Schema version of the file; missing means 0, the shape before
+    /// versioning. Older files are migrated and rewritten on load.
+    #[serde(default)]
+    pub version: u32,
     pub api_url: String,
+    /// API key, inline. Left empty when the key comes from `api_key_file`
+    /// or `api_key_command`.
+    #[serde(default)]
     pub api_key: String,
+    /// File holding the API key, such as a 0600 file or a mounted secret,
+    /// so the key stays out of this file.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub api_key_file: Option<String>,
+    /// Command that prints the API key, e.g. `op read op://dev/pulse/key`.
+    /// Runs through the shell every time a client is created.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub api_key_command: Option<String>,
+    /// Id spans are sent under. With `[[projects]]` it follows
+    /// `active_project`.
     pub project_id: String,
+    /// Name of the `[[projects]]` entry in use.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub active_project: Option<String>,
     #[serde(default, skip_serializing_if = "Option::is_none")]
     pub local_email: Option<String>,
     #[serde(default, skip_serializing_if = "Option::is_none")]
     pub local_password: Option<String>,
+    /// Absolute path to `pulse` written into hook commands by
+    /// `pulse connect --absolute-path`.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub pulse_bin: Option<String>,
+    /// Dashboard `pulse dashboard` opens. Defaults to the local frontend for
+    /// a local `api_url`, and to `api_url` itself for a hosted one.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub dashboard_url: Option<String>,
+    /// Fraction of sessions to send, between 0.0 and 1.0 (default 1.0).
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub sample_rate: Option<f64>,
+    /// Buffer spans per session and send them as one batch.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub buffer: Option<bool>,
+    /// Flush a session buffer once it holds this many spans (default 50).
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub buffer_max_spans: Option<usize>,
+    /// Queue spans and let a detached `pulse flush` send them, so hooks
+    /// only pay for a local file append.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub async_emit: Option<bool>,
+    /// Spans per request when `pulse flush` sends the offline queue
+    /// (default 100).
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub flush_batch_size: Option<usize>,
+    /// Requests `pulse flush` keeps in flight at once (default 4).
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub flush_concurrency: Option<usize>,
+    /// Have `pulse daemon` send a `heartbeat` span for each active session
+    /// (default false).
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub heartbeat: Option<bool>,
+    /// Seconds between heartbeats (default 30).
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub heartbeat_interval_secs: Option<u64>,
+    /// Largest `tool_input`/`tool_response` sent as-is, in bytes (default 64KB).
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub max_field_bytes: Option<usize>,
+    /// Record the hostname, OS, and user name on each span (default true).
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub capture_host: Option<bool>,
+    /// Record the git branch, commit, and dirty state of `cwd` (default true).
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub capture_git: Option<bool>,
+    /// Copy the whole hook payload into `metadata.raw` (default true).
+    /// `pulse replay` of recorded spans needs it.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub include_raw: Option<bool>,
+    /// Work out which agent ran `pulse emit` from its processes and
+    /// environment when nothing names the source (default true).
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub source_autodetect: Option<bool>,
+    /// Send a `cli_error` span when `pulse emit` cannot parse an event,
+    /// instead of dropping it silently (default false).
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub capture_emit_errors: Option<bool>,
+    /// Append every span the trace service accepts to `~/.pulse/sent.ndjson`
+    /// (or `PULSE_RECORD_LOG`), as an audit trail (default false).
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub record_spans: Option<bool>,
+    /// Spans endpoint, relative to `api_url` (default `/v1/spans/async`).
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub spans_path: Option<String>,
+    /// Health endpoint, relative to `api_url` (default `/health`).
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub health_path: Option<String>,
+    /// Header that carries the API key: `bearer` (default) for
+    /// `Authorization: Bearer <key>`, or a header name such as `x-api-key`
+    /// that is sent the bare key.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub auth_header: Option<String>,
+    /// Appended to the `User-Agent` of every request, as
+    /// `pulse-cli/<version> (<suffix>)`, to tell deployments apart behind a
+    /// shared proxy.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub user_agent_suffix: Option<String>,
+    /// gzip span batches above a small size before POSTing them. Off by
+    /// default since the server has to accept `Content-Encoding: gzip`.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub compress: Option<bool>,
+    /// Timeout for requests from interactive commands, in milliseconds
+    /// (default 5000).
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub http_timeout_ms: Option<u64>,
+    /// Timeout for sending spans from `pulse emit`, in milliseconds
+    /// (default 2000). Every hook waits on it, so keep it short.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub emit_timeout_ms: Option<u64>,
+    /// How long `pulse emit` waits for the event on stdin before giving up,
+    /// in milliseconds (default 1000), so a hook with nothing piped in
+    /// cannot hang the agent.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub stdin_timeout_ms: Option<u64>,
+    /// Stop trying to send from `pulse emit` for a while after repeated
+    /// connection failures or server errors (default true).
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub circuit_breaker: Option<bool>,
+    /// Failures that open the circuit (default 3)...
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub breaker_failures: Option<u32>,
+    /// ...within this many seconds of the first (default 60).
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub breaker_window_secs: Option<u64>,
+    /// Seconds before the first retry once open, doubled after each failed
+    /// retry up to 10 minutes (default 30).
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub breaker_cooldown_secs: Option<u64>,
+    /// Proxy for all requests to the trace service. When unset, the
+    /// `HTTPS_PROXY`/`HTTP_PROXY`/`NO_PROXY` environment variables apply.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub proxy_url: Option<String>,
+    /// PEM file with extra root certificates, for a service behind a
+    /// private CA.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub ca_cert_path: Option<String>,
+    /// PEM client certificate for services that require mutual TLS. It may
+    /// hold the private key too; otherwise set `client_key_path`.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub client_cert_path: Option<String>,
+    /// PEM private key for `client_cert_path`.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub client_key_path: Option<String>,
+    /// Skip TLS certificate verification entirely. Unsafe: only for local
+    /// development against self-signed certificates.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub danger_accept_invalid_certs: Option<bool>,
+    /// Where spans go: the pulse trace service, an OTLP collector, or both
+    /// (default `pulse`).
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub export: Option<ExportTarget>,
+    #[serde(default, skip_serializing_if = "OtlpConfig::is_default")]
+    pub otlp: OtlpConfig,
+    #[serde(default, skip_serializing_if = "RedactionConfig::is_default")]
+    pub redaction: RedactionConfig,
+    #[serde(default, skip_serializing_if = "EventsConfig::is_default")]
+    pub events: EventsConfig,
+    /// Static labels added to every span under `metadata.labels`, e.g.
+    /// `env = "prod"`, for slicing data by deployment or team.
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub labels: BTreeMap<String, String>,
+    /// Extra payload key renames per source, on top of the built-in ones in
+    /// `span::SOURCE_FIELD_MAPS`: `[field_map.opencode]` with
+    /// `toolName = "tool_name"`.
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub field_map: BTreeMap<String, BTreeMap<String, String>>,
+    /// Span `kind` per event type, on top of the built-in
+    /// `span::DEFAULT_KINDS`: `[kinds]` with `cli_ping = "diagnostic"`.
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub kinds: BTreeMap<String, String>,
+    /// Span `status` per event type, on top of `span::DEFAULT_STATUSES`.
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub statuses: BTreeMap<String, String>,
+    /// Projects on the same trace service to switch between with
+    /// `pulse project use`. Empty for a config with just `project_id`.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub projects: Vec<ProjectEntry>,
+}
+
+/// One `[[projects]]` entry.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ProjectEntry {
+    pub name: String,
+    pub id: String,
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ExportTarget {
+    #[default]
+    Pulse,
+    Otlp,
+    Both,
+}
+
+impl ExportTarget {
+    pub const ALL: [Self; 3] = [Self::Pulse, Self::Otlp, Self::Both];
+
+    pub fn as_str(self) -> &'static str {
+        match self {
+            Self::Pulse => "pulse",
+            Self::Otlp => "otlp",
+            Self::Both => "both",
+        }
+    }
+
+    pub fn to_pulse(self) -> bool {
+        matches!(self, Self::Pulse | Self::Both)
+    }
+
+    pub fn to_otlp(self) -> bool {
+        matches!(self, Self::Otlp | Self::Both)
+    }
+}
+
+/// `[otlp]` section, used when `export` is `otlp` or `both`.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct OtlpConfig {
+    /// OTLP/HTTP traces URL (default `http://localhost:4318/v1/traces`).
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub endpoint: Option<String>,
+    /// Extra request headers, e.g. collector credentials.
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub headers: BTreeMap<String, String>,
+}
+
+impl OtlpConfig {
+    pub fn endpoint(&self) -> &str {
+        self.endpoint.as_deref().unwrap_or(DEFAULT_OTLP_ENDPOINT)
+    }
+
+    fn is_default(&self) -> bool {
+        *self == Self::default()
+    }
+}
+
+/// `[redaction]` section. Redaction is on unless `enabled = false`;
+/// `patterns` adds regexes on top of the built-in secret patterns.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct RedactionConfig {
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub enabled: Option<bool>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub patterns: Vec<String>,
+}
+
+impl RedactionConfig {
+    pub fn is_enabled(&self) -> bool {
+        self.enabled.unwrap_or(true)
+    }
+
+    fn is_default(&self) -> bool {
+        *self == Self::default()
+    }
+}
+
+/// `[events]` section. `disabled` drops the listed event types; `enabled`
+/// instead sends only the listed ones. At most one of them may be set.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct EventsConfig {
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub enabled: Vec<String>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub disabled: Vec<String>,
+}
+
+impl EventsConfig {
+    /// Whether spans for `event_type` should be sent at all.
+    pub fn allows(&self, event_type: &str) -> bool {
+        if !self.enabled.is_empty() {
+            return self.enabled.iter().any(|enabled| enabled == event_type);
+        }
+        !self.disabled.iter().any(|disabled| disabled == event_type)
+    }
+
+    fn validate(&self) -> Result<()> {
+        if !self.enabled.is_empty() && !self.disabled.is_empty() {
+            return Err(PulseError::message(
+                "`[events]` sets both `enabled` and `disabled`; use only one",
+            ));
+        }
+        Ok(())
+    }
+
+    fn is_default(&self) -> bool {
+        *self == Self::default()
+    }
 }
 
 impl PulseConfig {
+    pub fn buffer_enabled(&self) -> bool {
+        self.buffer.unwrap_or(false)
+    }
+
+    pub fn buffer_max_spans(&self) -> usize {
+        self.buffer_max_spans
+            .filter(|max| *max > 0)
+            .unwrap_or(DEFAULT_BUFFER_MAX_SPANS)
+    }
+
+    pub fn async_emit(&self) -> bool {
+        self.async_emit.unwrap_or(false)
+    }
+
+    pub fn flush_batch_size(&self) -> usize {
+        self.flush_batch_size
+            .filter(|size| *size > 0)
+            .unwrap_or(DEFAULT_FLUSH_BATCH_SIZE)
+    }
+
+    pub fn flush_concurrency(&self) -> usize {
+        self.flush_concurrency
+            .filter(|limit| *limit > 0)
+            .unwrap_or(DEFAULT_FLUSH_CONCURRENCY)
+    }
+
+    pub fn heartbeat(&self) -> bool {
+        self.heartbeat.unwrap_or(false)
+    }
+
+    pub fn heartbeat_interval(&self) -> Duration {
+        Duration::from_secs(
+            self.heartbeat_interval_secs
+                .filter(|secs| *secs > 0)
+                .unwrap_or(DEFAULT_HEARTBEAT_INTERVAL_SECS),
+        )
+    }
+
+    pub fn max_field_bytes(&self) -> usize {
+        self.max_field_bytes
+            .filter(|max| *max > 0)
+            .unwrap_or(DEFAULT_MAX_FIELD_BYTES)
+    }
+
+    pub fn capture_host(&self) -> bool {
+        self.capture_host.unwrap_or(true)
+    }
+
+    pub fn capture_git(&self) -> bool {
+        self.capture_git.unwrap_or(true)
+    }
+
+    pub fn include_raw(&self) -> bool {
+        self.include_raw.unwrap_or(true)
+    }
+
+    pub fn source_autodetect(&self) -> bool {
+        self.source_autodetect.unwrap_or(true)
+    }
+
+    /// `labels` with `PULSE_LABEL_<key>` variables on top.
+    pub fn labels(&self) -> BTreeMap<String, String> {
+        merge_labels(&self.labels, env::vars())
+    }
+
+    /// The configured key renames for payloads from `source`.
+    pub fn field_map(&self, source: &str) -> Option<&BTreeMap<String, String>> {
+        self.field_map.get(source)
+    }
+
+    /// The configured `[kinds]` and `[statuses]`.
+    pub fn mapping_overrides(&self) -> MappingOverrides<'_> {
+        MappingOverrides {
+            kinds: &self.kinds,
+            statuses: &self.statuses,
+        }
+    }
+
+    pub fn capture_emit_errors(&self) -> bool {
+        self.capture_emit_errors.unwrap_or(false)
+    }
+
+    pub fn record_spans(&self) -> bool {
+        self.record_spans.unwrap_or(false)
+    }
+
+    pub fn spans_path(&self) -> &str {
+        self.spans_path.as_deref().unwrap_or(DEFAULT_SPANS_PATH)
+    }
+
+    pub fn health_path(&self) -> &str {
+        self.health_path.as_deref().unwrap_or(DEFAULT_HEALTH_PATH)
+    }
+
+    pub fn auth_header(&self) -> &str {
+        self.auth_header.as_deref().unwrap_or(DEFAULT_AUTH_HEADER)
+    }
+
+    pub fn compress(&self) -> bool {
+        self.compress.unwrap_or(false)
+    }
+
+    pub fn http_timeout(&self) -> Duration {
+        Duration::from_millis(
+            self.http_timeout_ms
+                .filter(|ms| *ms > 0)
+                .unwrap_or(DEFAULT_HTTP_TIMEOUT_MS),
+        )
+    }
+
+    pub fn circuit_breaker(&self) -> bool {
+        self.circuit_breaker.unwrap_or(true)
+    }
+
+    pub fn breaker_failures(&self) -> u32 {
+        self.breaker_failures
+            .filter(|failures| *failures > 0)
+            .unwrap_or(DEFAULT_BREAKER_FAILURES)
+    }
+
+    pub fn breaker_window(&self) -> Duration {
+        Duration::from_secs(
+            self.breaker_window_secs
+                .filter(|secs| *secs > 0)
+                .unwrap_or(DEFAULT_BREAKER_WINDOW_SECS),
+        )
+    }
+
+    pub fn breaker_cooldown(&self) -> Duration {
+        Duration::from_secs(
+            self.breaker_cooldown_secs
+                .filter(|secs| *secs > 0)
+                .unwrap_or(DEFAULT_BREAKER_COOLDOWN_SECS),
+        )
+    }
+
+    pub fn emit_timeout(&self) -> Duration {
+        Duration::from_millis(
+            self.emit_timeout_ms
+                .filter(|ms| *ms > 0)
+                .unwrap_or(DEFAULT_EMIT_TIMEOUT_MS),
+        )
+    }
+
+    pub fn stdin_timeout(&self) -> Duration {
+        Duration::from_millis(
+            self.stdin_timeout_ms
+                .filter(|ms| *ms > 0)
+                .unwrap_or(DEFAULT_STDIN_TIMEOUT_MS),
+        )
+    }
+
+    pub fn export(&self) -> ExportTarget {
+        self.export.unwrap_or_default()
+    }
+
+    pub fn accept_invalid_certs(&self) -> bool {
+        self.danger_accept_invalid_certs.unwrap_or(false)
+    }
+
+    /// The configured projects. A config with only `project_id` has one,
+    /// named `default`.
+    pub fn projects(&self) -> Vec<ProjectEntry> {
+        if self.projects.is_empty() && !self.project_id.is_empty() {
+            return vec![ProjectEntry {
+                name: DEFAULT_PROJECT_NAME.to_string(),
+                id: self.project_id.clone(),
+            }];
+        }
+        self.projects.clone()
+    }
+
+    /// Name of the project spans go to, or `None` when `project_id` does not
+    /// belong to a listed project (e.g. `PULSE_PROJECT_ID` overrides it).
+    pub fn active_project(&self) -> Option<String> {
+        let projects = self.projects();
+        let active = match &self.active_project {
+            Some(name) => projects.iter().find(|project| project.name == *name),
+            None if self.projects.is_empty() => projects.first(),
+            None => None,
+        }?;
+        (active.id == self.project_id).then(|| active.name.clone())
+    }
+
+    /// Adds a project, turning a single `project_id` into a `default` entry
+    /// first. The first project of an empty config becomes the active one.
+    pub fn add_project(&mut self, name: &str, id: &str) -> Result<()> {
+        let name = name.trim();
+        if name.is_empty() {
+            return Err(PulseError::message("project name is empty"));
+        }
+        let id = normalize_project_id(id);
+        validate_project_id(&id)?;
+        if self.projects().iter().any(|project| project.name == name) {
+            return Err(PulseError::message(format!(
+                "a project named `{name}` already exists"
+            )));
+        }
+        if self.projects.is_empty() && !self.project_id.is_empty() {
+            self.projects = self.projects();
+            self.active_project = Some(DEFAULT_PROJECT_NAME.to_string());
+        }
+        self.projects.push(ProjectEntry {
+            name: name.to_string(),
+            id: id.clone(),
+        });
+        if self.project_id.is_empty() {
+            self.active_project = Some(name.to_string());
+            self.project_id = id;
+        }
+        Ok(())
+    }
+
+    /// Makes `name` the active project, so spans are sent with its id.
+    pub fn use_project(&mut self, name: &str) -> Result<()> {
+        let projects = self.projects();
+        let project = projects
+            .iter()
+            .find(|project| project.name == name)
+            .ok_or_else(|| unknown_project(name, &projects))?;
+        self.project_id = project.id.clone();
+        if !self.projects.is_empty() {
+            self.active_project = Some(project.name.clone());
+        }
+        Ok(())
+    }
+
+    /// Sets `project_id`, and the id of the active project along with it so
+    /// the next load does not switch it back.
+    pub fn set_project_id(&mut self, id: String) {
+        let active = self.active_project.as_deref();
+        if let Some(project) = self
+            .projects
+            .iter_mut()
+            .find(|project| Some(project.name.as_str()) == active)
+        {
+            project.id = id.clone();
+        }
+        self.project_id = id;
+    }
+
+    /// Points `project_id` at the `active_project` entry.
+    fn resolve_active_project(&mut self) -> Result<()> {
+        let Some(name) = &self.active_project else {
+            return Ok(());
+        };
+        let project = self
+            .projects
+            .iter()
+            .find(|project| project.name == *name)
+            .ok_or_else(|| unknown_project(name, &self.projects))?;
+        self.project_id = project.id.clone();
+        Ok(())
+    }
+
+    /// The API key from whichever source is set, preferring an inline
+    /// `api_key`, then `api_key_file`, then `api_key_command`. Empty when
+    /// none is.
+    pub fn resolve_api_key(&self) -> Result<String> {
+        if !self.api_key.is_empty() {
+            return Ok(self.api_key.clone());
+        }
+        if let Some(path) = &self.api_key_file {
+            let key = fs::read_to_string(path).map_err(|err| {
+                PulseError::message(format!("cannot read api_key_file {path}: {err}"))
+            })?;
+            return non_empty_key(key, || format!("api_key_file {path} is empty"));
+        }
+        if let Some(command) = &self.api_key_command {
+            let output = shell(command)
+                .stdin(Stdio::null())
+                .stderr(Stdio::piped())
+                .output()
+                .map_err(|err| {
+                    PulseError::message(format!("cannot run api_key_command `{command}`: {err}"))
+                })?;
+            if !output.status.success() {
+                return Err(PulseError::message(format!(
+                    "api_key_command `{command}` failed ({}): {}",
+                    output.status,
+                    String::from_utf8_lossy(&output.stderr).trim()
+                )));
+            }
+            return non_empty_key(String::from_utf8_lossy(&output.stdout).into_owned(), || {
+                format!("api_key_command `{command}` printed nothing")
+            });
+        }
+        Ok(String::new())
+    }
+
+    /// At most one of `api_key`, `api_key_file`, and `api_key_command` may
+    /// be set, so it is never a guess which key is sent.
+    fn validate_api_key_source(&self) -> Result<()> {
+        let sources: Vec<&str> = [
+            ("api_key", !self.api_key.is_empty()),
+            ("api_key_file", self.api_key_file.is_some()),
+            ("api_key_command", self.api_key_command.is_some()),
+        ]
+        .into_iter()
+        .filter_map(|(name, set)| set.then_some(name))
+        .collect();
+        if sources.len() > 1 {
+            return Err(PulseError::message(format!(
+                "the config sets {}; keep only one source for the API key",
+                sources.join(" and ")
+            )));
+        }
+        Ok(())
+    }
+
+    /// A `[kinds]` or `[statuses]` entry with an empty value would send
+    /// spans the trace service rejects.
+    fn validate_span_mappings(&self) -> Result<()> {
+        for (table, mapping) in [("kinds", &self.kinds), ("statuses", &self.statuses)] {
+            if let Some((event_type, _)) = mapping.iter().find(|(_, value)| value.trim().is_empty())
+            {
+                return Err(PulseError::message(format!(
+                    "[{table}] maps `{event_type}` to an empty value"
+                )));
+            }
+        }
+        Ok(())
+    }
+
     pub fn sanitized(mut self) -> Self {
         self.api_url = self.api_url.trim_end_matches('/').trim().to_string();
         self.api_key = self.api_key.trim().to_string();
-        self.project_id = self.project_id.trim().to_string();
+        self.project_id = normalize_project_id(&self.project_id);
         self.local_email = self
             .local_email
             .as_ref()
@@ -38,6 +689,83 @@ impl PulseConfig {
     }
 }
 
+fn unknown_project(name: &str, projects: &[ProjectEntry]) -> PulseError {
+    let names: Vec<&str> = projects.iter().map(|p| p.name.as_str()).collect();
+    PulseError::message(format!(
+        "no project named `{name}`; configured projects: {}",
+        if names.is_empty() {
+            "(none)".to_string()
+        } else {
+            names.join(", ")
+        }
+    ))
+}
+
+fn non_empty_key(key: String, empty: impl FnOnce() -> String) -> Result<String> {
+    match key.trim() {
+        "" => Err(PulseError::message(empty())),
+        key => Ok(key.to_string()),
+    }
+}
+
+#[cfg(unix)]
+fn shell(command: &str) -> Command {
+    let mut shell = Command::new("sh");
+    shell.args(["-c", command]);
+    shell
+}
+
+#[cfg(windows)]
+fn shell(command: &str) -> Command {
+    let mut shell = Command::new("cmd");
+    shell.args(["/C", command]);
+    shell
+}
+
+/// Longest project id accepted. Ids are UUIDs or short slugs.
+const MAX_PROJECT_ID_LEN: usize = 128;
+
+/// Trims a pasted project id, including quotes copied along with it.
+pub fn normalize_project_id(id: &str) -> String {
+    let id = id.trim();
+    let unquoted = ['"', '\'']
+        .iter()
+        .find_map(|quote| id.strip_prefix(*quote)?.strip_suffix(*quote))
+        .unwrap_or(id);
+    unquoted.trim().to_string()
+}
+
+/// Checks that `id` looks like a project id rather than, say, a project name
+/// pasted in its place: non-empty, and only ASCII letters, digits, `-`, `_`,
+/// and `.`. Whether the server knows the id is checked by `doctor` and
+/// `ping`, which send through the authenticated spans endpoint.
+pub fn validate_project_id(id: &str) -> Result<()> {
+    if id.is_empty() {
+        return Err(PulseError::message("project id is empty"));
+    }
+    if id.chars().any(char::is_whitespace) {
+        return Err(PulseError::message(format!(
+            "project id `{id}` contains spaces, so it looks like a project name; \
+             copy the project id from the dashboard instead"
+        )));
+    }
+    if let Some(bad) = id
+        .chars()
+        .find(|c| !(c.is_ascii_alphanumeric() || matches!(c, '-' | '_' | '.')))
+    {
+        return Err(PulseError::message(format!(
+            "project id `{id}` contains `{bad}`; expected letters, digits, `-`, `_`, or `.`"
+        )));
+    }
+    if id.len() > MAX_PROJECT_ID_LEN {
+        return Err(PulseError::message(format!(
+            "project id is {} characters long; expected at most {MAX_PROJECT_ID_LEN}",
+            id.len()
+        )));
+    }
+    Ok(())
+}
+
 pub struct ConfigStore;
 
 impl ConfigStore {
@@ -50,24 +778,507 @@ impl ConfigStore {
         Ok(Self::config_dir()?.join(CONFIG_FILE))
     }
 
+    /// Loads the config file with `PULSE_API_URL`, `PULSE_API_KEY`, and
+    /// `PULSE_PROJECT_ID` layered on top (env > file). When all three are
+    /// set the file is optional. `PULSE_API_KEY` also wins over
+    /// `api_key_file` and `api_key_command`.
     pub fn load() -> Result<PulseConfig> {
-        let path = Self::config_path()?;
-        let contents = fs::read_to_string(path).map_err(|err| {
-            if err.kind() == ErrorKind::NotFound {
-                PulseError::ConfigMissing
-            } else {
-                err.into()
+        Self::load_from(&Self::config_path()?, |name| env::var(name).ok())
+    }
+
+    /// The config file alone, ignoring environment overrides.
+    pub fn load_file() -> Result<PulseConfig> {
+        Self::load_from(&Self::config_path()?, |_| None)
+    }
+
+    pub fn load_from(path: &Path, env: impl Fn(&str) -> Option<String>) -> Result<PulseConfig> {
+        let read = |name: &str| {
+            env(name)
+                .map(|value| value.trim().to_string())
+                .filter(|value| !value.is_empty())
+        };
+        let api_url = read(API_URL_ENV);
+        let api_key = read(API_KEY_ENV);
+        let project_id = read(PROJECT_ID_ENV);
+
+        let mut config = match fs::read_to_string(path) {
+            Ok(contents) => {
+                let (config, migrated) = Self::migrate(&contents)?;
+                if migrated {
+                    // Best effort: a read-only file still loads.
+                    let _ = Self::save_to(path, &config);
+                }
+                config
+            }
+            Err(err) if err.kind() == ErrorKind::NotFound => {
+                if api_url.is_none() || api_key.is_none() || project_id.is_none() {
+                    return Err(PulseError::ConfigMissing);
+                }
+                PulseConfig::default()
             }
-        })?;
-        let config: PulseConfig = toml::from_str(&contents)?;
+            Err(err) => return Err(err.into()),
+        };
+        config.events.validate()?;
+        config.validate_span_mappings()?;
+        config.resolve_active_project()?;
+
+        if let Some(api_url) = api_url {
+            config.api_url = api_url;
+        }
+        if let Some(api_key) = api_key {
+            // The variable replaces the key's source, whichever it is.
+            config.api_key = api_key;
+            config.api_key_file = None;
+            config.api_key_command = None;
+        }
+        config.validate_api_key_source()?;
+        if let Some(project_id) = project_id {
+            config.project_id = project_id;
+        }
         Ok(config)
     }
 
+    /// Parses a config file, upgrading it from an older schema version.
+    /// Returns whether anything changed. Unknown keys, such as ones from a
+    /// newer pulse, are ignored rather than rejected.
+    pub fn migrate(contents: &str) -> Result<(PulseConfig, bool)> {
+        let mut table: toml::Table = toml::from_str(contents)?;
+        let version = table
+            .get("version")
+            .and_then(toml::Value::as_integer)
+            .unwrap_or(0)
+            .max(0) as usize;
+        for migration in MIGRATIONS.iter().skip(version) {
+            migration(&mut table);
+        }
+        let migrated = version < MIGRATIONS.len();
+        if migrated {
+            table.insert("version".to_string(), i64::from(CONFIG_VERSION).into());
+        }
+        Ok((table.try_into()?, migrated))
+    }
+
     pub fn save(config: &PulseConfig) -> Result<()> {
-        let dir = Self::config_dir()?;
-        fs::create_dir_all(&dir)?;
-        let body = toml::to_string_pretty(config)?;
-        fs::write(dir.join(CONFIG_FILE), body)?;
-        Ok(())
+        Self::save_to(&Self::config_path()?, config)
+    }
+
+    /// Writes the config readable only by the current user, since it holds
+    /// the API key and, in local mode, a plaintext password.
+    pub fn save_to(path: &Path, config: &PulseConfig) -> Result<()> {
+        if let Some(dir) = path.parent() {
+            fs::create_dir_all(dir)?;
+            restrict_permissions(dir, 0o700)?;
+        }
+        let config = PulseConfig {
+            version: config.version.max(CONFIG_VERSION),
+            ..config.clone()
+        };
+        let body = toml::to_string_pretty(&config)?;
+        write_private(path, body.as_bytes())
+    }
+}
+
+#[cfg(unix)]
+pub(crate) fn restrict_permissions(path: &Path, mode: u32) -> Result<()> {
+    use std::os::unix::fs::PermissionsExt;
+    fs::set_permissions(path, fs::Permissions::from_mode(mode))?;
+    Ok(())
+}
+
+#[cfg(not(unix))]
+pub(crate) fn restrict_permissions(_path: &Path, _mode: u32) -> Result<()> {
+    Ok(())
+}
+
+/// Writes a file only its owner can read. The contents go to a temporary
+/// file created with mode 0600 that is then renamed over `path`, so the
+/// secret is never readable by others, not even briefly.
+pub(crate) fn write_private(path: &Path, contents: &[u8]) -> Result<()> {
+    let staged = path.with_extension("tmp");
+    let mut options = fs::OpenOptions::new();
+    options.write(true).create(true).truncate(true);
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::OpenOptionsExt;
+        options.mode(0o600);
+    }
+    let mut file = options.open(&staged)?;
+    // The mode only applies when the file is created; a leftover from a
+    // crash keeps whatever it had.
+    restrict_permissions(&staged, 0o600)?;
+    file.write_all(contents)?;
+    file.sync_all()?;
+    fs::rename(&staged, path)?;
+    Ok(())
+}
+
+/// Config labels overridden by `PULSE_LABEL_<key>` variables. Keys from the
+/// environment are lowercased to match the config's, and an empty value
+/// removes the label.
+pub(crate) fn merge_labels(
+    labels: &BTreeMap<String, String>,
+    vars: impl IntoIterator<Item = (String, String)>,
+) -> BTreeMap<String, String> {
+    let mut merged = labels.clone();
+    for (name, value) in vars {
+        let Some(key) = name
+            .strip_prefix(LABEL_ENV_PREFIX)
+            .filter(|key| !key.is_empty())
+        else {
+            continue;
+        };
+        let key = key.to_lowercase();
+        match value.trim() {
+            "" => merged.remove(&key),
+            value => merged.insert(key, value.to_string()),
+        };
+    }
+    merged
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::collections::HashMap;
+    use tempfile::TempDir;
+
+    #[test]
+    fn test_project_id_validation() {
+        for id in [
+            "proj_1",
+            "my-project",
+            "550e8400-e29b-41d4-a716-446655440000",
+            "team.prod",
+        ] {
+            assert!(validate_project_id(id).is_ok(), "{id}");
+        }
+
+        let err = validate_project_id("My Project").unwrap_err();
+        assert!(
+            err.to_string().contains("looks like a project name"),
+            "{err}"
+        );
+        assert!(validate_project_id("").is_err());
+        assert!(validate_project_id("proj/1").is_err());
+        assert!(validate_project_id(&"a".repeat(MAX_PROJECT_ID_LEN + 1)).is_err());
+    }
+
+    #[test]
+    fn test_project_id_is_normalized() {
+        assert_eq!(normalize_project_id("  proj_1\n"), "proj_1");
+        assert_eq!(normalize_project_id("\"proj_1\""), "proj_1");
+        assert_eq!(normalize_project_id("'proj_1'"), "proj_1");
+        assert_eq!(normalize_project_id("   "), "");
+        let config = PulseConfig {
+            project_id: " 'proj_1' ".to_string(),
+            ..PulseConfig::default()
+        };
+        assert_eq!(config.sanitized().project_id, "proj_1");
+    }
+
+    #[test]
+    fn test_env_labels_win_over_config() {
+        let config: PulseConfig = toml::from_str(
+            "api_url = \"http://file\"\napi_key = \"k\"\nproject_id = \"p\"\n\n[labels]\nenv = \"staging\"\nteam = \"payments\"\nregion = \"eu\"\n",
+        )
+        .unwrap();
+        let vars = [
+            ("PULSE_LABEL_ENV", "prod"),
+            ("PULSE_LABEL_ci_job", "build-42"),
+            ("PULSE_LABEL_REGION", ""),
+            ("PULSE_LABEL_", "ignored"),
+            ("PULSE_API_KEY", "not a label"),
+        ]
+        .map(|(name, value)| (name.to_string(), value.to_string()));
+
+        let labels = merge_labels(&config.labels, vars);
+        assert_eq!(
+            labels.into_iter().collect::<Vec<_>>(),
+            [
+                ("ci_job".to_string(), "build-42".to_string()),
+                ("env".to_string(), "prod".to_string()),
+                ("team".to_string(), "payments".to_string()),
+            ]
+        );
+    }
+
+    fn env_from(vars: &[(&str, &str)]) -> impl Fn(&str) -> Option<String> {
+        let vars: HashMap<String, String> = vars
+            .iter()
+            .map(|(k, v)| (k.to_string(), v.to_string()))
+            .collect();
+        move |name| vars.get(name).cloned()
+    }
+
+    fn write_config(tmp: &TempDir) -> PathBuf {
+        let path = tmp.path().join(CONFIG_DIR).join(CONFIG_FILE);
+        fs::create_dir_all(path.parent().unwrap()).unwrap();
+        fs::write(
+            &path,
+            "api_url = \"http://file\"\napi_key = \"file_key\"\nproject_id = \"file_proj\"\nsample_rate = 0.5\n",
+        )
+        .unwrap();
+        path
+    }
+
+    #[test]
+    fn test_file_only() {
+        let tmp = TempDir::new().unwrap();
+        let path = write_config(&tmp);
+        let config = ConfigStore::load_from(&path, env_from(&[])).unwrap();
+        assert_eq!(config.api_url, "http://file");
+        assert_eq!(config.api_key, "file_key");
+        assert_eq!(config.project_id, "file_proj");
+        assert_eq!(config.spans_path(), DEFAULT_SPANS_PATH);
+        assert_eq!(config.health_path(), DEFAULT_HEALTH_PATH);
+    }
+
+    #[test]
+    fn test_env_overrides_file_values() {
+        let tmp = TempDir::new().unwrap();
+        let path = write_config(&tmp);
+        let env = env_from(&[(API_KEY_ENV, " env_key "), (PROJECT_ID_ENV, "")]);
+        let config = ConfigStore::load_from(&path, env).unwrap();
+        assert_eq!(config.api_url, "http://file");
+        assert_eq!(config.api_key, "env_key");
+        // Empty variables are ignored.
+        assert_eq!(config.project_id, "file_proj");
+        // Settings only the file knows about are kept.
+        assert_eq!(config.sample_rate, Some(0.5));
+    }
+
+    #[test]
+    fn test_env_alone_needs_no_file() {
+        let tmp = TempDir::new().unwrap();
+        let path = tmp.path().join(CONFIG_DIR).join(CONFIG_FILE);
+        let env = env_from(&[
+            (API_URL_ENV, "http://env"),
+            (API_KEY_ENV, "env_key"),
+            (PROJECT_ID_ENV, "env_proj"),
+        ]);
+        let config = ConfigStore::load_from(&path, env).unwrap();
+        assert_eq!(config.api_url, "http://env");
+        assert_eq!(config.api_key, "env_key");
+        assert_eq!(config.project_id, "env_proj");
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_save_restricts_permissions() {
+        use std::os::unix::fs::PermissionsExt;
+
+        let tmp = TempDir::new().unwrap();
+        let path = tmp.path().join(CONFIG_DIR).join(CONFIG_FILE);
+        ConfigStore::save_to(&path, &PulseConfig::default()).unwrap();
+
+        let mode = |path: &Path| fs::metadata(path).unwrap().permissions().mode() & 0o777;
+        assert_eq!(mode(&path), 0o600);
+        assert_eq!(mode(path.parent().unwrap()), 0o700);
+
+        // Loosened permissions are tightened again on the next save.
+        fs::set_permissions(&path, fs::Permissions::from_mode(0o644)).unwrap();
+        ConfigStore::save_to(&path, &PulseConfig::default()).unwrap();
+        assert_eq!(mode(&path), 0o600);
+
+        // So is a temporary file a crash left behind, before it is written.
+        let staged = path.with_extension("tmp");
+        fs::write(&staged, "").unwrap();
+        fs::set_permissions(&staged, fs::Permissions::from_mode(0o644)).unwrap();
+        write_private(&path, b"api_key = \"k\"\n").unwrap();
+        assert_eq!(mode(&path), 0o600);
+        assert!(!staged.exists());
+    }
+
+    #[test]
+    fn test_v0_config_is_migrated_and_rewritten() {
+        let tmp = TempDir::new().unwrap();
+        let path = tmp.path().join(CONFIG_FILE);
+        fs::write(
+            &path,
+            "api_url = \"http://x\"\napi_key = \"k\"\nproject_id = \"p\"\nbuffer = true\n",
+        )
+        .unwrap();
+
+        let config = ConfigStore::load_from(&path, |_| None).unwrap();
+        assert_eq!(config.version, CONFIG_VERSION);
+        assert!(config.buffer_enabled());
+        assert_eq!(config.emit_timeout(), Duration::from_secs(2));
+        assert_eq!(config.spans_path(), DEFAULT_SPANS_PATH);
+
+        let rewritten = fs::read_to_string(&path).unwrap();
+        assert!(rewritten.contains(&format!("version = {CONFIG_VERSION}")));
+        assert!(rewritten.contains("buffer = true"));
+        // A second load has nothing left to migrate.
+        assert!(!ConfigStore::migrate(&rewritten).unwrap().1);
+    }
+
+    #[test]
+    fn test_newer_config_keeps_unknown_keys_loadable() {
+        let contents = "version = 99\napi_url = \"http://x\"\napi_key = \"k\"\nproject_id = \"p\"\nfuture_key = 1\n";
+        let (config, migrated) = ConfigStore::migrate(contents).unwrap();
+        assert!(!migrated);
+        assert_eq!(config.version, 99);
+        assert_eq!(config.api_key, "k");
+    }
+
+    #[test]
+    fn test_events_filter() {
+        let all = EventsConfig::default();
+        assert!(all.allows("notification"));
+
+        let denylist = EventsConfig {
+            disabled: vec!["notification".to_string()],
+            ..EventsConfig::default()
+        };
+        assert!(!denylist.allows("notification"));
+        assert!(denylist.allows("stop"));
+
+        let allowlist = EventsConfig {
+            enabled: vec!["stop".to_string(), "post_tool_use".to_string()],
+            ..EventsConfig::default()
+        };
+        assert!(allowlist.allows("stop"));
+        assert!(!allowlist.allows("notification"));
+    }
+
+    #[test]
+    fn test_events_enabled_and_disabled_conflict() {
+        let tmp = TempDir::new().unwrap();
+        let path = tmp.path().join(CONFIG_FILE);
+        fs::write(
+            &path,
+            "api_url = \"http://x\"\napi_key = \"k\"\nproject_id = \"p\"\n\n[events]\nenabled = [\"stop\"]\ndisabled = [\"notification\"]\n",
+        )
+        .unwrap();
+        let err = ConfigStore::load_from(&path, |_| None).unwrap_err();
+        assert!(err.to_string().contains("use only one"), "{err}");
+    }
+
+    #[test]
+    fn test_projects_list() {
+        let mut config = PulseConfig::default();
+        assert!(config.projects().is_empty());
+        config.add_project("web", "proj_web").unwrap();
+        assert_eq!(config.project_id, "proj_web");
+        assert_eq!(config.active_project().as_deref(), Some("web"));
+
+        assert!(config.add_project("web", "proj_2").is_err());
+        assert!(config.add_project("cli", "My Project").is_err());
+        let err = config.use_project("nope").unwrap_err();
+        assert!(
+            err.to_string().contains("configured projects: web"),
+            "{err}"
+        );
+
+        // `config set project_id` edits the active entry, not just the copy.
+        config.set_project_id("proj_web2".to_string());
+        assert_eq!(config.projects[0].id, "proj_web2");
+    }
+
+    #[test]
+    fn test_active_project_must_exist() {
+        let tmp = TempDir::new().unwrap();
+        let path = tmp.path().join(CONFIG_FILE);
+        fs::write(
+            &path,
+            "api_url = \"http://x\"\napi_key = \"k\"\nproject_id = \"p\"\nactive_project = \"b\"\n\n[[projects]]\nname = \"a\"\nid = \"proj_a\"\n",
+        )
+        .unwrap();
+        let err = ConfigStore::load_from(&path, |_| None).unwrap_err();
+        assert!(err.to_string().contains("no project named `b`"), "{err}");
+    }
+
+    #[test]
+    fn test_api_key_from_file() {
+        let tmp = TempDir::new().unwrap();
+        let key_path = tmp.path().join("pulse.key");
+        fs::write(&key_path, "  key_from_file\n").unwrap();
+        let path = tmp.path().join(CONFIG_FILE);
+        fs::write(
+            &path,
+            format!(
+                "api_url = \"http://x\"\nproject_id = \"p\"\napi_key_file = {:?}\n",
+                key_path.display().to_string()
+            ),
+        )
+        .unwrap();
+
+        let config = ConfigStore::load_from(&path, |_| None).unwrap();
+        assert_eq!(config.api_key, "");
+        assert_eq!(config.resolve_api_key().unwrap(), "key_from_file");
+
+        // PULSE_API_KEY replaces the file reference.
+        let config = ConfigStore::load_from(&path, env_from(&[(API_KEY_ENV, "env_key")])).unwrap();
+        assert_eq!(config.resolve_api_key().unwrap(), "env_key");
+        assert_eq!(config.api_key_file, None);
+
+        fs::write(&key_path, "\n").unwrap();
+        let err = ConfigStore::load_from(&path, |_| None)
+            .unwrap()
+            .resolve_api_key()
+            .unwrap_err();
+        assert!(err.to_string().contains("is empty"), "{err}");
+        fs::remove_file(&key_path).unwrap();
+        let err = ConfigStore::load_from(&path, |_| None)
+            .unwrap()
+            .resolve_api_key()
+            .unwrap_err();
+        assert!(
+            err.to_string().contains("cannot read api_key_file"),
+            "{err}"
+        );
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_api_key_from_command() {
+        let config = PulseConfig {
+            api_key_command: Some("printf 'key_from_command\\n'".to_string()),
+            ..PulseConfig::default()
+        };
+        assert_eq!(config.resolve_api_key().unwrap(), "key_from_command");
+
+        let config = PulseConfig {
+            api_key_command: Some("echo locked >&2; exit 3".to_string()),
+            ..PulseConfig::default()
+        };
+        let err = config.resolve_api_key().unwrap_err();
+        assert!(err.to_string().contains("locked"), "{err}");
+    }
+
+    #[test]
+    fn test_api_key_sources_are_exclusive() {
+        // Inline wins when more than one is set in memory...
+        let config = PulseConfig {
+            api_key: "inline".to_string(),
+            api_key_file: Some("/nonexistent/pulse.key".to_string()),
+            ..PulseConfig::default()
+        };
+        assert_eq!(config.resolve_api_key().unwrap(), "inline");
+        // ...but a config file may set only one.
+        let tmp = TempDir::new().unwrap();
+        let path = tmp.path().join(CONFIG_FILE);
+        fs::write(
+            &path,
+            "api_url = \"http://x\"\napi_key = \"k\"\nproject_id = \"p\"\napi_key_command = \"op read x\"\n",
+        )
+        .unwrap();
+        let err = ConfigStore::load_from(&path, |_| None).unwrap_err();
+        assert!(
+            err.to_string()
+                .contains("sets api_key and api_key_command; keep only one"),
+            "{err}"
+        );
+    }
+
+    #[test]
+    fn test_partial_env_without_file_is_missing() {
+        let tmp = TempDir::new().unwrap();
+        let path = tmp.path().join(CONFIG_DIR).join(CONFIG_FILE);
+        let env = env_from(&[(API_URL_ENV, "http://env"), (API_KEY_ENV, "env_key")]);
+        assert!(matches!(
+            ConfigStore::load_from(&path, env),
+            Err(PulseError::ConfigMissing)
+        ));
     }
 }
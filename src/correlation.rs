@@ -0,0 +1,150 @@
+use std::{
+    fs,
+    io::ErrorKind,
+    path::{Path, PathBuf},
+    time::{Duration, SystemTime},
+};
+
+use crate::{error::Result, paths};
+
+/// Entries not written for this long are deleted on the next `put`: a tool
+/// call whose post event never came, or a session that ended without
+/// `session_end`.
+const ENTRY_TTL: Duration = Duration::from_secs(7 * 24 * 60 * 60);
+
+/// Small key/value store shared between independent `pulse emit` processes.
+///
+/// Each key is a file under `~/.pulse/pending/`, so a value written while
+/// handling one hook can be picked up by the process handling the next.
+#[derive(Debug, Clone)]
+pub struct CorrelationStore {
+    dir: PathBuf,
+}
+
+impl CorrelationStore {
+    pub fn new() -> Result<Self> {
+        Ok(Self::with_dir(paths::pending_dir()?))
+    }
+
+    pub fn with_dir(dir: PathBuf) -> Self {
+        Self { dir }
+    }
+
+    pub fn dir(&self) -> &Path {
+        &self.dir
+    }
+
+    pub fn put(&self, key: &str, value: &str) -> Result<()> {
+        fs::create_dir_all(&self.dir)?;
+        fs::write(self.key_path(key), value)?;
+        self.prune(SystemTime::now());
+        Ok(())
+    }
+
+    pub fn get(&self, key: &str) -> Option<String> {
+        fs::read_to_string(self.key_path(key)).ok()
+    }
+
+    /// Reads and deletes a key. Returns `None` when it was never written.
+    pub fn take(&self, key: &str) -> Option<String> {
+        let path = self.key_path(key);
+        let value = fs::read_to_string(&path).ok()?;
+        match fs::remove_file(&path) {
+            Ok(()) => Some(value),
+            // Another process took it first.
+            Err(err) if err.kind() == ErrorKind::NotFound => None,
+            Err(_) => Some(value),
+        }
+    }
+
+    fn key_path(&self, key: &str) -> PathBuf {
+        self.dir.join(file_safe_key(key))
+    }
+
+    /// Deletes entries last written more than `ENTRY_TTL` before `now`. Best
+    /// effort, since another process may be pruning at the same time.
+    fn prune(&self, now: SystemTime) {
+        let Ok(entries) = fs::read_dir(&self.dir) else {
+            return;
+        };
+        for entry in entries.flatten() {
+            let expired = entry
+                .metadata()
+                .and_then(|metadata| metadata.modified())
+                .is_ok_and(|modified| {
+                    now.duration_since(modified)
+                        .is_ok_and(|age| age > ENTRY_TTL)
+                });
+            if expired {
+                let _ = fs::remove_file(entry.path());
+            }
+        }
+    }
+}
+
+/// Maps an arbitrary id onto a string that is safe to use as a file name.
+/// ASCII letters, digits, and `-` are kept; every other byte becomes `_` and
+/// two hex digits, so different ids never share a name.
+pub(crate) fn file_safe_key(key: &str) -> String {
+    let mut safe = String::with_capacity(key.len());
+    for byte in key.bytes() {
+        if byte.is_ascii_alphanumeric() || byte == b'-' {
+            safe.push(char::from(byte));
+        } else {
+            safe.push_str(&format!("_{byte:02x}"));
+        }
+    }
+    safe
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::TempDir;
+
+    #[test]
+    fn test_take_removes_value() {
+        let tmp = TempDir::new().unwrap();
+        let store = CorrelationStore::with_dir(tmp.path().join(paths::PENDING_DIR));
+        store.put("tool-tu_1", "value").unwrap();
+        assert_eq!(store.take("tool-tu_1").as_deref(), Some("value"));
+        assert!(store.take("tool-tu_1").is_none());
+    }
+
+    #[test]
+    fn test_keys_are_sanitized() {
+        let tmp = TempDir::new().unwrap();
+        let store = CorrelationStore::with_dir(tmp.path().to_path_buf());
+        store.put("../escape/key", "value").unwrap();
+        assert!(!tmp.path().parent().unwrap().join("escape").exists());
+        assert_eq!(store.get("../escape/key").as_deref(), Some("value"));
+    }
+
+    #[test]
+    fn test_distinct_keys_get_distinct_files() {
+        assert_ne!(file_safe_key("a/b"), file_safe_key("a_b"));
+        assert_ne!(file_safe_key("a.b"), file_safe_key("a_2eb"));
+        assert_eq!(file_safe_key("toolu_01"), "toolu_5f01");
+        assert_eq!(file_safe_key("é"), "_c3_a9");
+    }
+
+    #[test]
+    fn test_put_prunes_stale_entries() {
+        let tmp = TempDir::new().unwrap();
+        let store = CorrelationStore::with_dir(tmp.path().to_path_buf());
+        store.put("tool-abandoned", "2025-01-01T00:00:00Z").unwrap();
+        store.put("session-live", "span").unwrap();
+        let week_ago = SystemTime::now() - ENTRY_TTL - Duration::from_secs(60);
+        fs::File::options()
+            .write(true)
+            .open(store.key_path("tool-abandoned"))
+            .unwrap()
+            .set_modified(week_ago)
+            .unwrap();
+
+        store.put("tool-next", "2025-01-02T00:00:00Z").unwrap();
+        assert!(store.get("tool-abandoned").is_none());
+        assert!(store.get("session-live").is_some());
+        assert!(store.get("tool-next").is_some());
+    }
+}
@@ -0,0 +1,187 @@
+use std::{
+    env,
+    fs::{self, File, OpenOptions},
+    io::{self, Write},
+    path::{Path, PathBuf},
+};
+
+use chrono::Utc;
+use serde_json::Value;
+
+use crate::{output::JsonFormat, paths, verbose};
+
+const MAX_BODY_CHARS: usize = 500;
+const DEFAULT_MAX_BYTES: u64 = 10 * 1024 * 1024;
+/// Rotated files kept next to the active log (`debug.log.1`, `debug.log.2`).
+const ROTATED_GENERATIONS: u32 = 2;
+
+/// Start of an event entry's header line.
+pub const EVENT_HEADER: &str = "── [";
+/// Start of an HTTP entry's line.
+pub const HTTP_HEADER: &str = ">> [";
+/// Start of a note about how an event was handled.
+pub const NOTE_HEADER: &str = "!! [";
+
+/// Whether `PULSE_DEBUG` asks for the debug log.
+pub fn enabled() -> bool {
+    env::var("PULSE_DEBUG")
+        .map(|v| v == "1" || v == "true")
+        .unwrap_or(false)
+}
+
+/// `PULSE_DEBUG_FORMAT=compact` writes each payload on one line; the
+/// default is pretty-printed.
+fn payload_format() -> JsonFormat {
+    env::var("PULSE_DEBUG_FORMAT")
+        .ok()
+        .and_then(|v| JsonFormat::parse(&v))
+        .unwrap_or_default()
+}
+
+/// Appends an incoming hook event. `pulse replay` reads these entries back,
+/// so the `── [time] type ──` header format must stay stable.
+pub fn log_event(event_type: &str, payload: &Value) {
+    if !enabled() {
+        return;
+    }
+    let ts = Utc::now().to_rfc3339();
+    let body = payload_format().to_string(payload).unwrap_or_default();
+    append(&format!("{EVENT_HEADER}{ts}] {event_type} ──\n{body}\n\n"));
+}
+
+/// The event type named by a `── [time] type ──` header line.
+pub fn event_type(line: &str) -> Option<&str> {
+    line.strip_prefix(EVENT_HEADER)?
+        .split_once("] ")
+        .map(|(_, rest)| rest.trim_end_matches('─').trim())
+        .filter(|event_type| !event_type.is_empty())
+}
+
+/// Appends one HTTP exchange: the request, its status (or transport error),
+/// and on failure the start of the response body. `--verbose` also prints
+/// the request line on stderr, with or without `PULSE_DEBUG`.
+pub fn log_http(method: &str, url: &str, outcome: &str, body: Option<&str>) {
+    verbose!("{method} {url} -> {outcome}");
+    if !enabled() {
+        return;
+    }
+    let ts = Utc::now().to_rfc3339();
+    let mut entry = format!("{HTTP_HEADER}{ts}] {method} {url} -> {outcome}\n");
+    if let Some(body) = body.map(str::trim).filter(|body| !body.is_empty()) {
+        let mut preview: String = body.chars().take(MAX_BODY_CHARS).collect();
+        if preview.len() < body.len() {
+            preview.push_str("...");
+        }
+        entry.push_str(&format!("   response: {preview}\n"));
+    }
+    entry.push('\n');
+    append(&entry);
+}
+
+/// Appends a one-line note, e.g. about a payload field that was ignored.
+pub fn log_note(message: &str) {
+    if !enabled() {
+        return;
+    }
+    let ts = Utc::now().to_rfc3339();
+    append(&format!("{NOTE_HEADER}{ts}] {message}\n\n"));
+}
+
+fn append(entry: &str) {
+    let _ = append_rotating(&paths::debug_log(), entry.as_bytes(), max_bytes());
+}
+
+/// The debug log at `path` and its rotated generations, newest first.
+pub fn log_files(path: &Path) -> Vec<PathBuf> {
+    let mut files = vec![path.to_path_buf()];
+    files.extend((1..=ROTATED_GENERATIONS).map(|generation| rotated_path(path, generation)));
+    files
+}
+
+/// `PULSE_DEBUG_LOG_MAX_BYTES`, or 10MB.
+fn max_bytes() -> u64 {
+    env::var("PULSE_DEBUG_LOG_MAX_BYTES")
+        .ok()
+        .and_then(|v| v.parse().ok())
+        .filter(|max| *max > 0)
+        .unwrap_or(DEFAULT_MAX_BYTES)
+}
+
+/// Appends under an exclusive lock, first rotating the file to `.1` (and
+/// `.1` to `.2`) once it has reached `max_bytes`. Also used by the span
+/// record.
+pub(crate) fn append_rotating(path: &Path, entry: &[u8], max_bytes: u64) -> io::Result<()> {
+    loop {
+        let mut file = OpenOptions::new().create(true).append(true).open(path)?;
+        file.lock()?;
+        // Another writer may have rotated the file while we waited.
+        if !is_current(&file, path) {
+            continue;
+        }
+        if file.metadata()?.len() >= max_bytes {
+            rotate(path)?;
+            continue;
+        }
+        return file.write_all(entry);
+    }
+}
+
+fn rotate(path: &Path) -> io::Result<()> {
+    for generation in (1..ROTATED_GENERATIONS).rev() {
+        let from = rotated_path(path, generation);
+        if from.exists() {
+            fs::rename(from, rotated_path(path, generation + 1))?;
+        }
+    }
+    fs::rename(path, rotated_path(path, 1))
+}
+
+fn rotated_path(path: &Path, generation: u32) -> PathBuf {
+    let mut name = path.as_os_str().to_owned();
+    name.push(format!(".{generation}"));
+    PathBuf::from(name)
+}
+
+/// Whether `file` is still the file at `path` rather than a rotated one.
+#[cfg(unix)]
+fn is_current(file: &File, path: &Path) -> bool {
+    use std::os::unix::fs::MetadataExt;
+    match (file.metadata(), fs::metadata(path)) {
+        (Ok(open), Ok(current)) => open.dev() == current.dev() && open.ino() == current.ino(),
+        _ => false,
+    }
+}
+
+#[cfg(not(unix))]
+fn is_current(_file: &File, path: &Path) -> bool {
+    path.exists()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::TempDir;
+
+    #[test]
+    fn test_log_rotates_past_the_limit() {
+        let tmp = TempDir::new().unwrap();
+        let path = tmp.path().join("debug.log");
+        let entry = [b'x'; 40];
+
+        for _ in 0..3 {
+            append_rotating(&path, &entry, 100).unwrap();
+        }
+        assert_eq!(fs::metadata(&path).unwrap().len(), 120);
+        assert!(!rotated_path(&path, 1).exists());
+
+        append_rotating(&path, &entry, 100).unwrap();
+        assert_eq!(fs::metadata(&path).unwrap().len(), 40);
+        assert_eq!(fs::metadata(rotated_path(&path, 1)).unwrap().len(), 120);
+
+        for _ in 0..6 {
+            append_rotating(&path, &entry, 100).unwrap();
+        }
+        assert!(rotated_path(&path, 2).exists());
+        assert!(!rotated_path(&path, 3).exists());
+    }
+}
@@ -0,0 +1,173 @@
+//! Operational log of `pulse emit`: one JSON object per hook event saying
+//! what became of it, for collecting from many machines with ordinary log
+//! tooling. The debug log keeps raw hook input for a person to read, and the
+//! span record keeps what was sent; this keeps outcomes.
+
+use std::{env, path::Path, time::Duration};
+
+use chrono::Utc;
+use serde::Serialize;
+
+use crate::{debug, error::Result, paths};
+
+pub const LOG_JSON_ENV: &str = "PULSE_LOG_JSON";
+/// Size at which the log is rotated to `.1` (and `.1` to `.2`).
+const MAX_LOG_BYTES: u64 = 10 * 1024 * 1024;
+
+/// Whether `PULSE_LOG_JSON` asks for the log.
+pub fn enabled() -> bool {
+    env::var(LOG_JSON_ENV)
+        .map(|v| v == "1" || v == "true")
+        .unwrap_or(false)
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Outcome {
+    /// The trace service accepted the span.
+    Sent,
+    /// Left for later: in the session buffer, with a running daemon, or in
+    /// the queue for the async flusher.
+    Queued,
+    /// Sampled out.
+    Dropped,
+    /// Skipped by `[events]`.
+    Filtered,
+    /// Not sent: an unusable payload or a failed send. A failed send still
+    /// keeps the span in the offline queue.
+    #[default]
+    Error,
+}
+
+/// One line of the log.
+#[derive(Debug, Clone, Default, Serialize)]
+pub struct EmitLogEntry {
+    pub timestamp: String,
+    pub event_type: String,
+    pub source: Option<String>,
+    pub span_id: Option<String>,
+    pub outcome: Outcome,
+    /// Status of the trace service's answer when it rejected the span.
+    pub http_status: Option<u16>,
+    pub duration_ms: u64,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub error: Option<String>,
+}
+
+impl EmitLogEntry {
+    pub fn new(event_type: &str) -> Self {
+        Self {
+            timestamp: Utc::now().to_rfc3339(),
+            event_type: event_type.to_string(),
+            ..Self::default()
+        }
+    }
+
+    /// Fills in the outcome once the attempt is over.
+    pub fn finish(&mut self, result: &Result<Outcome>, elapsed: Duration) {
+        self.duration_ms = elapsed.as_millis() as u64;
+        match result {
+            Ok(outcome) => self.outcome = *outcome,
+            Err(err) => {
+                self.outcome = Outcome::Error;
+                self.error = Some(err.to_string());
+            }
+        }
+    }
+}
+
+/// Appends `entry` to `PULSE_LOG_FILE` (or `~/.pulse/emit.ndjson`) when
+/// `PULSE_LOG_JSON` is on. Best effort: a hook never fails over its log.
+pub fn append(entry: &EmitLogEntry) {
+    if !enabled() {
+        return;
+    }
+    if let Ok(path) = paths::emit_log() {
+        let _ = append_to(&path, entry);
+    }
+}
+
+pub fn append_to(path: &Path, entry: &EmitLogEntry) -> Result<()> {
+    let mut line = serde_json::to_vec(entry)?;
+    line.push(b'\n');
+    if let Some(dir) = path.parent() {
+        std::fs::create_dir_all(dir)?;
+    }
+    debug::append_rotating(path, &line, MAX_LOG_BYTES)?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use std::fs;
+
+    use serde_json::{Value, json};
+    use tempfile::TempDir;
+
+    use super::*;
+    use crate::error::PulseError;
+
+    fn lines(path: &Path) -> Vec<Value> {
+        fs::read_to_string(path)
+            .unwrap()
+            .lines()
+            .map(|line| serde_json::from_str(line).unwrap())
+            .collect()
+    }
+
+    #[test]
+    fn test_log_lines_for_sent_and_filtered_emits() {
+        let tmp = TempDir::new().unwrap();
+        let path = tmp.path().join(paths::EMIT_LOG_FILE);
+
+        let mut sent = EmitLogEntry::new("post_tool_use");
+        sent.source = Some("claude_code".to_string());
+        sent.span_id = Some("span_1".to_string());
+        sent.finish(&Ok(Outcome::Sent), Duration::from_millis(42));
+        append_to(&path, &sent).unwrap();
+
+        let mut filtered = EmitLogEntry::new("notification");
+        filtered.finish(&Ok(Outcome::Filtered), Duration::from_millis(1));
+        append_to(&path, &filtered).unwrap();
+
+        let logged = lines(&path);
+        assert_eq!(logged.len(), 2);
+        assert!(
+            logged[0]["timestamp"]
+                .as_str()
+                .unwrap()
+                .parse::<chrono::DateTime<Utc>>()
+                .is_ok()
+        );
+        let mut sent_line = logged[0].clone();
+        sent_line.as_object_mut().unwrap().remove("timestamp");
+        assert_eq!(
+            sent_line,
+            json!({
+                "event_type": "post_tool_use",
+                "source": "claude_code",
+                "span_id": "span_1",
+                "outcome": "sent",
+                "http_status": null,
+                "duration_ms": 42,
+            })
+        );
+        assert_eq!(logged[1]["outcome"], "filtered");
+        assert_eq!(logged[1]["span_id"], Value::Null);
+        assert!(logged[1].get("error").is_none());
+    }
+
+    #[test]
+    fn test_failed_emit_records_the_error() {
+        let mut entry = EmitLogEntry::new("stop");
+        entry.http_status = Some(401);
+        entry.finish(
+            &Err(PulseError::message("failed to send 1 span(s)")),
+            Duration::ZERO,
+        );
+        let line = serde_json::to_value(&entry).unwrap();
+        assert_eq!(line["outcome"], "error");
+        assert_eq!(line["http_status"], 401);
+        assert_eq!(line["error"], "failed to send 1 span(s)");
+    }
+}
@@ -1,5 +1,6 @@
-use std::io;
+use std::{error::Error as _, io};
 
+use reqwest::{Response, StatusCode};
 use thiserror::Error;
 
 pub type Result<T, E = PulseError> = std::result::Result<T, E>;
@@ -20,12 +21,196 @@ pub enum PulseError {
     TomlDe(#[from] toml::de::Error),
     #[error(transparent)]
     TomlSer(#[from] toml::ser::Error),
-    #[error(transparent)]
-    Http(#[from] reqwest::Error),
+    #[error("API key rejected (401 Unauthorized)")]
+    Unauthorized,
+    #[error("project not accessible with this API key (403 Forbidden)")]
+    Forbidden,
+    #[error("{path} not found (404 Not Found)")]
+    NotFound { path: String },
+    #[error("request rejected ({status})")]
+    BadRequest { status: StatusCode },
+    #[error("trace service error ({status})")]
+    ServerError { status: StatusCode },
+    #[error("{}", describe_http_error(.source))]
+    Connection { source: reqwest::Error },
+    /// Any other reqwest failure, such as an unreadable response body.
+    #[error("{}", describe_http_error(.0))]
+    Http(reqwest::Error),
 }
 
 impl PulseError {
     pub fn message<T: Into<String>>(msg: T) -> Self {
         Self::Message(msg.into())
     }
+
+    /// The typed error for an HTTP status from `path`, or `None` when the
+    /// status is not a client or server error.
+    pub fn from_status(status: StatusCode, path: &str) -> Option<Self> {
+        match status {
+            StatusCode::UNAUTHORIZED => Some(Self::Unauthorized),
+            StatusCode::FORBIDDEN => Some(Self::Forbidden),
+            StatusCode::NOT_FOUND => Some(Self::NotFound {
+                path: path.to_string(),
+            }),
+            status if status.is_client_error() => Some(Self::BadRequest { status }),
+            status if status.is_server_error() => Some(Self::ServerError { status }),
+            _ => None,
+        }
+    }
+
+    pub fn from_response(response: &Response) -> Option<Self> {
+        Self::from_status(response.status(), response.url().path())
+    }
+
+    /// The HTTP status behind the error, when the trace service answered.
+    pub fn http_status(&self) -> Option<StatusCode> {
+        match self {
+            Self::Unauthorized => Some(StatusCode::UNAUTHORIZED),
+            Self::Forbidden => Some(StatusCode::FORBIDDEN),
+            Self::NotFound { .. } => Some(StatusCode::NOT_FOUND),
+            Self::BadRequest { status } | Self::ServerError { status } => Some(*status),
+            _ => None,
+        }
+    }
+
+    /// Whether the same request may succeed if tried again shortly, as when
+    /// the trace service is still starting.
+    pub fn is_transient(&self) -> bool {
+        matches!(self, Self::ServerError { .. } | Self::Connection { .. })
+    }
+
+    /// What the user can do about an HTTP failure, for commands that print
+    /// advice next to the error.
+    pub fn remediation(&self) -> Option<&'static str> {
+        match self {
+            Self::Unauthorized => {
+                Some("Create a new key in the dashboard and run `pulse init --api-key ...`.")
+            }
+            Self::Forbidden => Some("Check that the project id belongs to the API key's project."),
+            Self::NotFound { .. } => Some(
+                "The API URL may point at the wrong service; check `api_url`, `spans_path`, and `health_path`.",
+            ),
+            Self::BadRequest { .. } => {
+                Some("Run with PULSE_DEBUG=1 to log the response body to the debug log.")
+            }
+            Self::ServerError { .. } => Some("The trace service is unhealthy; check its logs."),
+            Self::Connection { .. } => {
+                Some("Start the trace service or correct the URL with `pulse init`.")
+            }
+            _ => None,
+        }
+    }
+}
+
+impl From<reqwest::Error> for PulseError {
+    /// Sorts a reqwest error into a typed variant: statuses from
+    /// `error_for_status`, and connect, TLS, or timeout failures.
+    fn from(err: reqwest::Error) -> Self {
+        if let Some(status) = err.status() {
+            let path = err
+                .url()
+                .map(|url| url.path().to_string())
+                .unwrap_or_default();
+            if let Some(typed) = Self::from_status(status, &path) {
+                return typed;
+            }
+        }
+        if err.is_connect() || err.is_timeout() {
+            return Self::Connection { source: err };
+        }
+        Self::Http(err)
+    }
+}
+
+/// reqwest reports TLS and connect failures alike as "error sending request",
+/// so look through the error's sources to say which one it was.
+pub(crate) fn describe_http_error(err: &reqwest::Error) -> String {
+    let mut causes = Vec::new();
+    let mut refused = false;
+    let mut source = err.source();
+    while let Some(cause) = source {
+        if let Some(io_err) = cause.downcast_ref::<io::Error>() {
+            refused |= io_err.kind() == io::ErrorKind::ConnectionRefused;
+        }
+        causes.push(cause.to_string());
+        source = cause.source();
+    }
+    let root = causes.last().cloned().unwrap_or_default();
+    let is_tls = causes.iter().any(|cause| {
+        let cause = cause.to_lowercase();
+        cause.contains("certificate") || cause.contains("tls")
+    });
+
+    if is_tls {
+        format!(
+            "TLS error: {root}. If the service uses a private CA, set `ca_cert_path` in ~/.pulse/config.toml"
+        )
+    } else if refused {
+        format!(
+            "connection refused: nothing is listening at {}",
+            host_of(err)
+        )
+    } else if err.is_connect() {
+        format!("could not connect to {}: {root}", host_of(err))
+    } else {
+        err.to_string()
+    }
+}
+
+fn host_of(err: &reqwest::Error) -> String {
+    err.url()
+        .map(|url| {
+            let host = url.host_str().unwrap_or_default();
+            match url.port_or_known_default() {
+                Some(port) => format!("{host}:{port}"),
+                None => host.to_string(),
+            }
+        })
+        .unwrap_or_else(|| "the trace service".to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_statuses_map_to_variants() {
+        let classify =
+            |code: u16| PulseError::from_status(StatusCode::from_u16(code).unwrap(), "/v1/spans");
+
+        assert!(matches!(classify(401), Some(PulseError::Unauthorized)));
+        assert!(matches!(classify(403), Some(PulseError::Forbidden)));
+        assert!(
+            matches!(classify(404), Some(PulseError::NotFound { path }) if path == "/v1/spans")
+        );
+        assert!(matches!(
+            classify(400),
+            Some(PulseError::BadRequest { status }) if status == StatusCode::BAD_REQUEST
+        ));
+        assert!(matches!(classify(429), Some(PulseError::BadRequest { .. })));
+        assert!(matches!(
+            classify(502),
+            Some(PulseError::ServerError { status }) if status == StatusCode::BAD_GATEWAY
+        ));
+        assert!(classify(200).is_none());
+        assert!(classify(202).is_none());
+        assert!(classify(304).is_none());
+    }
+
+    #[test]
+    fn test_http_errors_carry_remediation() {
+        for code in [401, 403, 404, 400, 503] {
+            let err =
+                PulseError::from_status(StatusCode::from_u16(code).unwrap(), "/health").unwrap();
+            assert!(err.remediation().is_some(), "{err}");
+        }
+        assert!(PulseError::message("other").remediation().is_none());
+        assert_eq!(
+            PulseError::NotFound {
+                path: "/health".to_string()
+            }
+            .to_string(),
+            "/health not found (404 Not Found)"
+        );
+    }
 }
@@ -0,0 +1,108 @@
+pub mod otlp;
+
+use crate::{
+    config::PulseConfig,
+    error::Result,
+    http::{SpanPayload, TraceHttpClient},
+    record::SpanRecord,
+};
+
+pub use otlp::OtlpExporter;
+
+/// Sends spans to every backend selected by `export` in the config.
+#[derive(Clone)]
+pub struct SpanExporter {
+    pulse: Option<TraceHttpClient>,
+    otlp: Option<OtlpExporter>,
+    /// Where accepted spans are recorded, with `record_spans`.
+    record: Option<SpanRecord>,
+}
+
+impl SpanExporter {
+    pub fn new(config: &PulseConfig) -> Result<Self> {
+        let target = config.export();
+        Ok(Self {
+            pulse: target
+                .to_pulse()
+                .then(|| TraceHttpClient::new(config))
+                .transpose()?,
+            otlp: target
+                .to_otlp()
+                .then(|| OtlpExporter::new(config))
+                .transpose()?,
+            record: config.record_spans().then(SpanRecord::new).transpose()?,
+        })
+    }
+
+    /// Tries every backend and fails if any of them did. The caller queues
+    /// the batch again, so with `both` a backend that succeeded sees it
+    /// twice when it is retried. Only a batch every backend accepted is
+    /// added to the span record.
+    pub async fn export(&self, spans: &[SpanPayload]) -> Result<()> {
+        let mut result = Ok(());
+        if let Some(pulse) = &self.pulse {
+            result = pulse.post_spans(spans).await;
+        }
+        if let Some(otlp) = &self.otlp {
+            let exported = otlp.export(spans).await;
+            result = result.and(exported);
+        }
+        if result.is_ok()
+            && let Some(record) = &self.record
+        {
+            // The spans were delivered; a record that cannot be written
+            // must not turn that into a failure and a resend.
+            let _ = record.append(spans);
+        }
+        result
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::fs;
+
+    use super::*;
+    use crate::{config::PulseConfig, paths, test_support::serve};
+    use tempfile::TempDir;
+
+    fn recording_exporter(api_url: String, tmp: &TempDir) -> SpanExporter {
+        let config = PulseConfig {
+            api_url,
+            ..PulseConfig::default()
+        };
+        SpanExporter {
+            record: Some(SpanRecord::at(tmp.path().join(paths::RECORD_FILE))),
+            ..SpanExporter::new(&config).unwrap()
+        }
+    }
+
+    #[tokio::test]
+    async fn test_accepted_spans_are_recorded() {
+        let tmp = TempDir::new().unwrap();
+        let exporter = recording_exporter(serve(|_| "202 Accepted"), &tmp);
+        exporter
+            .export(&[SpanPayload::test("span_1")])
+            .await
+            .unwrap();
+
+        let contents = fs::read_to_string(tmp.path().join(paths::RECORD_FILE)).unwrap();
+        let lines: Vec<&str> = contents.lines().collect();
+        assert_eq!(lines.len(), 1);
+        let recorded: SpanPayload = serde_json::from_str(lines[0]).unwrap();
+        assert_eq!(recorded.span_id, "span_1");
+    }
+
+    #[tokio::test]
+    async fn test_rejected_spans_are_not_recorded() {
+        let tmp = TempDir::new().unwrap();
+        let exporter = recording_exporter(serve(|_| "500 Internal Server Error"), &tmp);
+        assert!(
+            exporter
+                .export(&[SpanPayload::test("span_1")])
+                .await
+                .is_err()
+        );
+        assert!(!tmp.path().join(paths::RECORD_FILE).exists());
+    }
+}
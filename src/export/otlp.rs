@@ -0,0 +1,347 @@
+//! OTLP/HTTP export using the JSON encoding of `ExportTraceServiceRequest`.
+//!
+//! Each span keeps its pulse fields under `pulse.*` and maps what it can
+//! onto OpenTelemetry semantic conventions:
+//!
+//! | Span field                | Attribute                                   |
+//! |---------------------------|---------------------------------------------|
+//! | `session_id`              | `gen_ai.conversation.id` (and the trace id) |
+//! | `span_id`                 | `pulse.span_id` (and the span id)           |
+//! | `event_type`, `kind`      | `pulse.event_type`, `pulse.kind`            |
+//! | `source`, `status`        | `pulse.source`, `pulse.status`              |
+//! | `cwd`, `is_interrupt`     | `pulse.cwd`, `pulse.is_interrupt`           |
+//! | `tool_cwd`                | `pulse.tool_cwd`                            |
+//! | `tool_name`               | `gen_ai.tool.name`                          |
+//! | `tool_use_id`             | `gen_ai.tool.call.id`                       |
+//! | `tool_input`              | `gen_ai.tool.call.arguments` (JSON)         |
+//! | `tool_response`           | `gen_ai.tool.call.result` (JSON)            |
+//! | `model`                   | `gen_ai.response.model` on `llm_response`, else `gen_ai.request.model` |
+//! | `metadata.usage`          | `gen_ai.usage.input_tokens`, `gen_ai.usage.output_tokens` |
+//! | `agent_name`              | `gen_ai.agent.name`                         |
+//! | `metadata.host`, `.user`  | `host.name`, `user.name`                    |
+//! | `metadata.git`            | `vcs.ref.head.name`, `vcs.ref.head.revision` |
+//!
+//! `tool_use`, `llm_response`, and `agent_run` spans are named
+//! `execute_tool {tool}`, `chat {model}`, and `invoke_agent {agent}` with a
+//! matching `gen_ai.operation.name`; other spans are named after their event
+//! type. `error` becomes an error status with the error as its message.
+
+use chrono::{DateTime, Utc};
+use reqwest::{
+    Client, Url,
+    header::{HeaderMap, HeaderName, HeaderValue},
+};
+use serde_json::{Value, json};
+use sha2::{Digest, Sha256};
+
+use crate::{
+    config::PulseConfig,
+    error::{PulseError, Result},
+    http::{SpanPayload, client_builder, finish, log_transport_error},
+};
+
+const SERVICE_NAME: &str = "pulse-cli";
+const SCOPE_NAME: &str = "pulse";
+const VERSION: &str = env!("CARGO_PKG_VERSION");
+const SPAN_KIND_INTERNAL: u8 = 1;
+const SPAN_KIND_CLIENT: u8 = 3;
+const STATUS_CODE_UNSET: u8 = 0;
+const STATUS_CODE_ERROR: u8 = 2;
+
+#[derive(Clone)]
+pub struct OtlpExporter {
+    client: Client,
+    endpoint: Url,
+    headers: HeaderMap,
+}
+
+impl OtlpExporter {
+    pub fn new(config: &PulseConfig) -> Result<Self> {
+        let endpoint = Url::parse(config.otlp.endpoint())
+            .map_err(|err| PulseError::message(format!("invalid otlp.endpoint: {err}")))?;
+        let mut headers = HeaderMap::new();
+        for (name, value) in &config.otlp.headers {
+            let header = HeaderName::from_bytes(name.as_bytes()).map_err(|err| {
+                PulseError::message(format!("invalid otlp header `{name}`: {err}"))
+            })?;
+            let value = HeaderValue::from_str(value).map_err(|err| {
+                PulseError::message(format!("invalid value for otlp header `{name}`: {err}"))
+            })?;
+            headers.insert(header, value);
+        }
+        let client = client_builder(config)?
+            .timeout(config.emit_timeout())
+            .build()?;
+        Ok(Self {
+            client,
+            endpoint,
+            headers,
+        })
+    }
+
+    pub async fn export(&self, spans: &[SpanPayload]) -> Result<()> {
+        if spans.is_empty() {
+            return Ok(());
+        }
+        let response = self
+            .client
+            .post(self.endpoint.clone())
+            .headers(self.headers.clone())
+            .json(&export_request(spans))
+            .send()
+            .await
+            .inspect_err(|err| log_transport_error("POST", &self.endpoint, err))?;
+        finish("POST", response).await
+    }
+}
+
+/// All `spans` as one OTLP resource and scope.
+pub fn export_request(spans: &[SpanPayload]) -> Value {
+    json!({
+        "resourceSpans": [{
+            "resource": {
+                "attributes": [
+                    attribute("service.name", string(SERVICE_NAME)),
+                    attribute("service.version", string(VERSION)),
+                ],
+            },
+            "scopeSpans": [{
+                "scope": { "name": SCOPE_NAME, "version": VERSION },
+                "spans": spans.iter().map(otlp_span).collect::<Vec<_>>(),
+            }],
+        }],
+    })
+}
+
+fn otlp_span(span: &SpanPayload) -> Value {
+    // A span is recorded when its event fires, so `timestamp` is the end.
+    let end = DateTime::parse_from_rfc3339(&span.timestamp)
+        .map(|time| time.with_timezone(&Utc))
+        .unwrap_or_else(|_| Utc::now());
+    let end_ns = end.timestamp_nanos_opt().unwrap_or_default();
+    let start_ns = end_ns - (span.duration_ms.unwrap_or(0.0) * 1_000_000.0) as i64;
+
+    let (operation, target) = match span.kind.as_str() {
+        "tool_use" => (Some("execute_tool"), span.tool_name.as_deref()),
+        "llm_response" => (Some("chat"), span.model.as_deref()),
+        "agent_run" => (Some("invoke_agent"), span.agent_name.as_deref()),
+        _ => (None, None),
+    };
+    let name = match (operation, target) {
+        (Some(operation), Some(target)) => format!("{operation} {target}"),
+        (Some(operation), None) => operation.to_string(),
+        (None, _) => span.event_type.clone(),
+    };
+    let kind = if span.kind == "llm_response" {
+        SPAN_KIND_CLIENT
+    } else {
+        SPAN_KIND_INTERNAL
+    };
+
+    let mut value = json!({
+        "traceId": hex_id(&span.session_id, 16),
+        "spanId": hex_id(&span.span_id, 8),
+        "name": name,
+        "kind": kind,
+        "startTimeUnixNano": start_ns.to_string(),
+        "endTimeUnixNano": end_ns.to_string(),
+        "attributes": attributes(span, operation),
+        "status": status(span),
+    });
+    if let Some(parent) = &span.parent_span_id {
+        value["parentSpanId"] = Value::String(hex_id(parent, 8));
+    }
+    value
+}
+
+fn attributes(span: &SpanPayload, operation: Option<&str>) -> Vec<Value> {
+    let mut attrs = Vec::new();
+    let mut push = |key: &str, value: Option<Value>| {
+        if let Some(value) = value {
+            attrs.push(attribute(key, value));
+        }
+    };
+    let metadata = span.metadata.as_ref();
+    let meta_str = |pointer: &str| {
+        metadata
+            .and_then(|meta| meta.pointer(pointer))
+            .and_then(Value::as_str)
+            .map(string)
+    };
+    let meta_int = |pointer: &str| {
+        metadata
+            .and_then(|meta| meta.pointer(pointer))
+            .and_then(Value::as_i64)
+            .map(int)
+    };
+    let model_key = if span.kind == "llm_response" {
+        "gen_ai.response.model"
+    } else {
+        "gen_ai.request.model"
+    };
+
+    push("gen_ai.conversation.id", Some(string(&span.session_id)));
+    push("gen_ai.operation.name", operation.map(string));
+    push("pulse.span_id", Some(string(&span.span_id)));
+    push("pulse.event_type", Some(string(&span.event_type)));
+    push("pulse.kind", Some(string(&span.kind)));
+    push("pulse.source", Some(string(&span.source)));
+    push("pulse.status", Some(string(&span.status)));
+    push("pulse.cwd", span.cwd.as_deref().map(string));
+    push("pulse.tool_cwd", span.tool_cwd.as_deref().map(string));
+    push(
+        "pulse.is_interrupt",
+        span.is_interrupt.map(|b| json!({ "boolValue": b })),
+    );
+    push("gen_ai.tool.name", span.tool_name.as_deref().map(string));
+    push(
+        "gen_ai.tool.call.id",
+        span.tool_use_id.as_deref().map(string),
+    );
+    push(
+        "gen_ai.tool.call.arguments",
+        span.tool_input.as_ref().map(json_string),
+    );
+    push(
+        "gen_ai.tool.call.result",
+        span.tool_response.as_ref().map(json_string),
+    );
+    push(model_key, span.model.as_deref().map(string));
+    push("gen_ai.agent.name", span.agent_name.as_deref().map(string));
+    push("gen_ai.usage.input_tokens", meta_int("/usage/input_tokens"));
+    push(
+        "gen_ai.usage.output_tokens",
+        meta_int("/usage/output_tokens"),
+    );
+    push("host.name", meta_str("/host"));
+    push("user.name", meta_str("/user"));
+    push("vcs.ref.head.name", meta_str("/git/branch"));
+    push("vcs.ref.head.revision", meta_str("/git/commit"));
+    attrs
+}
+
+fn status(span: &SpanPayload) -> Value {
+    if span.status != "error" {
+        return json!({ "code": STATUS_CODE_UNSET });
+    }
+    let message = match &span.error {
+        Some(Value::String(message)) => message.clone(),
+        Some(error) => error.to_string(),
+        None => String::new(),
+    };
+    json!({ "code": STATUS_CODE_ERROR, "message": message })
+}
+
+/// Hex id of `bytes` length derived from `id`, so the same pulse id always
+/// maps to the same OTLP id whatever format it had.
+fn hex_id(id: &str, bytes: usize) -> String {
+    Sha256::digest(id.as_bytes())
+        .iter()
+        .take(bytes)
+        .map(|byte| format!("{byte:02x}"))
+        .collect()
+}
+
+fn attribute(key: &str, value: Value) -> Value {
+    json!({ "key": key, "value": value })
+}
+
+fn string(value: &str) -> Value {
+    json!({ "stringValue": value })
+}
+
+fn int(value: i64) -> Value {
+    // int64 values are strings in the OTLP JSON encoding.
+    json!({ "intValue": value.to_string() })
+}
+
+fn json_string(value: &Value) -> Value {
+    match value {
+        Value::String(text) => string(text),
+        other => string(&other.to_string()),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn tool_span() -> SpanPayload {
+        SpanPayload {
+            span_id: "span_1".to_string(),
+            session_id: "sess_1".to_string(),
+            parent_span_id: Some("root".to_string()),
+            timestamp: "2025-01-01T00:00:01+00:00".to_string(),
+            duration_ms: Some(250.0),
+            source: "claude_code".to_string(),
+            kind: "tool_use".to_string(),
+            event_type: "post_tool_use".to_string(),
+            status: "error".to_string(),
+            tool_use_id: Some("toolu_1".to_string()),
+            tool_name: Some("Bash".to_string()),
+            tool_input: Some(json!({ "command": "ls" })),
+            tool_response: None,
+            error: Some(json!("exit code 1")),
+            is_interrupt: None,
+            cwd: Some("/work".to_string()),
+            tool_cwd: None,
+            model: None,
+            agent_name: None,
+            metadata: Some(json!({ "host": "devbox", "git": { "branch": "main" } })),
+        }
+    }
+
+    fn attr<'a>(span: &'a Value, key: &str) -> &'a Value {
+        span["attributes"]
+            .as_array()
+            .unwrap()
+            .iter()
+            .find(|attr| attr["key"] == key)
+            .map(|attr| &attr["value"])
+            .unwrap_or(&Value::Null)
+    }
+
+    #[test]
+    fn test_tool_use_span_mapping() {
+        let request = export_request(&[tool_span()]);
+        let span = &request["resourceSpans"][0]["scopeSpans"][0]["spans"][0];
+
+        assert_eq!(span["name"], "execute_tool Bash");
+        assert_eq!(span["kind"], SPAN_KIND_INTERNAL);
+        assert_eq!(span["traceId"].as_str().unwrap().len(), 32);
+        assert_eq!(span["spanId"].as_str().unwrap().len(), 16);
+        assert_eq!(span["parentSpanId"], hex_id("root", 8));
+        assert_eq!(span["endTimeUnixNano"], "1735689601000000000");
+        assert_eq!(span["startTimeUnixNano"], "1735689600750000000");
+        assert_eq!(
+            span["status"],
+            json!({ "code": STATUS_CODE_ERROR, "message": "exit code 1" })
+        );
+
+        assert_eq!(attr(span, "gen_ai.operation.name"), &string("execute_tool"));
+        assert_eq!(attr(span, "gen_ai.tool.name"), &string("Bash"));
+        assert_eq!(attr(span, "gen_ai.tool.call.id"), &string("toolu_1"));
+        assert_eq!(
+            attr(span, "gen_ai.tool.call.arguments"),
+            &string(r#"{"command":"ls"}"#)
+        );
+        assert_eq!(attr(span, "gen_ai.conversation.id"), &string("sess_1"));
+        assert_eq!(attr(span, "pulse.kind"), &string("tool_use"));
+        assert_eq!(attr(span, "pulse.status"), &string("error"));
+        assert_eq!(attr(span, "pulse.source"), &string("claude_code"));
+        assert_eq!(attr(span, "pulse.cwd"), &string("/work"));
+        assert_eq!(attr(span, "host.name"), &string("devbox"));
+        assert_eq!(attr(span, "vcs.ref.head.name"), &string("main"));
+        assert_eq!(attr(span, "gen_ai.tool.call.result"), &Value::Null);
+    }
+
+    #[test]
+    fn test_ids_are_stable_per_session() {
+        let mut other = tool_span();
+        other.span_id = "span_2".to_string();
+        let request = export_request(&[tool_span(), other]);
+        let spans = &request["resourceSpans"][0]["scopeSpans"][0]["spans"];
+        assert_eq!(spans[0]["traceId"], spans[1]["traceId"]);
+        assert_ne!(spans[0]["spanId"], spans[1]["spanId"]);
+    }
+}
@@ -0,0 +1,205 @@
+use std::{
+    fs,
+    path::{Path, PathBuf},
+    process::{Command, Stdio},
+    thread,
+    time::{Duration, Instant},
+};
+
+use serde::Serialize;
+
+const SHORT_COMMIT_LEN: usize = 7;
+const STATUS_TIMEOUT: Duration = Duration::from_millis(500);
+const STATUS_POLL_INTERVAL: Duration = Duration::from_millis(10);
+
+/// Repository state recorded in span metadata under `git`.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize)]
+pub struct GitContext {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub branch: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub commit: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub dirty: Option<bool>,
+}
+
+/// Branch, short commit and dirty flag for the repository containing `cwd`.
+/// Returns `None` when `cwd` is not inside a git repository.
+///
+/// Branch and commit come straight from the files under `.git`, so no process
+/// is spawned for them. Only `dirty` asks `git status`, and is left out if
+/// git is missing or too slow.
+pub fn detect(cwd: &Path) -> Option<GitContext> {
+    let (work_tree, git_dir) = find_repository(cwd)?;
+    let head = fs::read_to_string(git_dir.join("HEAD")).ok()?;
+    let head = head.trim();
+
+    let (branch, commit) = match head.strip_prefix("ref:") {
+        Some(reference) => {
+            let reference = reference.trim();
+            let branch = reference
+                .strip_prefix("refs/heads/")
+                .unwrap_or(reference)
+                .to_string();
+            (Some(branch), resolve_ref(&git_dir, reference))
+        }
+        None => (None, Some(head.to_string())),
+    };
+
+    Some(GitContext {
+        branch,
+        commit: commit.map(|sha| sha.chars().take(SHORT_COMMIT_LEN).collect()),
+        dirty: is_dirty(&work_tree, &git_dir),
+    })
+}
+
+/// Walks up from `start` to the first directory containing `.git`, returning
+/// the work tree and the git directory (following `gitdir:` files used by
+/// worktrees and submodules).
+fn find_repository(start: &Path) -> Option<(PathBuf, PathBuf)> {
+    for dir in start.ancestors() {
+        let dot_git = dir.join(".git");
+        if dot_git.is_dir() {
+            return Some((dir.to_path_buf(), dot_git));
+        }
+        if dot_git.is_file() {
+            let contents = fs::read_to_string(&dot_git).ok()?;
+            let target = contents.trim().strip_prefix("gitdir:")?.trim();
+            return Some((dir.to_path_buf(), dir.join(target)));
+        }
+    }
+    None
+}
+
+fn resolve_ref(git_dir: &Path, reference: &str) -> Option<String> {
+    // Linked worktrees keep branch refs in the main repository.
+    let common_dir = fs::read_to_string(git_dir.join("commondir"))
+        .ok()
+        .map(|path| git_dir.join(path.trim()))
+        .unwrap_or_else(|| git_dir.to_path_buf());
+
+    for dir in [git_dir, common_dir.as_path()] {
+        if let Ok(sha) = fs::read_to_string(dir.join(reference)) {
+            return Some(sha.trim().to_string());
+        }
+    }
+
+    let packed = fs::read_to_string(common_dir.join("packed-refs")).ok()?;
+    packed.lines().find_map(|line| {
+        let (sha, name) = line.split_once(' ')?;
+        (name.trim() == reference).then(|| sha.to_string())
+    })
+}
+
+fn is_dirty(work_tree: &Path, git_dir: &Path) -> Option<bool> {
+    let mut child = Command::new("git")
+        .arg("--git-dir")
+        .arg(git_dir)
+        .arg("--work-tree")
+        .arg(work_tree)
+        .args(["status", "--porcelain", "--untracked-files=no"])
+        .stdin(Stdio::null())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::null())
+        .spawn()
+        .ok()?;
+
+    let deadline = Instant::now() + STATUS_TIMEOUT;
+    loop {
+        match child.try_wait().ok()? {
+            Some(_) => break,
+            None if Instant::now() >= deadline => {
+                let _ = child.kill();
+                let _ = child.wait();
+                return None;
+            }
+            None => thread::sleep(STATUS_POLL_INTERVAL),
+        }
+    }
+
+    let output = child.wait_with_output().ok()?;
+    if !output.status.success() {
+        return None;
+    }
+    Some(!output.stdout.is_empty())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::TempDir;
+
+    const SHA: &str = "0123456789abcdef0123456789abcdef01234567";
+
+    fn fixture(head: &str) -> TempDir {
+        let tmp = TempDir::new().unwrap();
+        let git_dir = tmp.path().join(".git");
+        fs::create_dir_all(git_dir.join("refs/heads/feature")).unwrap();
+        fs::write(git_dir.join("HEAD"), head).unwrap();
+        tmp
+    }
+
+    #[test]
+    fn test_branch_and_commit_from_loose_ref() {
+        let tmp = fixture("ref: refs/heads/main\n");
+        fs::write(tmp.path().join(".git/refs/heads/main"), format!("{SHA}\n")).unwrap();
+        let nested = tmp.path().join("src/deep");
+        fs::create_dir_all(&nested).unwrap();
+
+        let context = detect(&nested).unwrap();
+        assert_eq!(context.branch.as_deref(), Some("main"));
+        assert_eq!(context.commit.as_deref(), Some("0123456"));
+    }
+
+    #[test]
+    fn test_commit_from_packed_refs() {
+        let tmp = fixture("ref: refs/heads/feature/x\n");
+        fs::write(
+            tmp.path().join(".git/packed-refs"),
+            format!("# pack-refs with: peeled\n{SHA} refs/heads/feature/x\n"),
+        )
+        .unwrap();
+
+        let context = detect(tmp.path()).unwrap();
+        assert_eq!(context.branch.as_deref(), Some("feature/x"));
+        assert_eq!(context.commit.as_deref(), Some("0123456"));
+    }
+
+    #[test]
+    fn test_detached_head_has_no_branch() {
+        let tmp = fixture(&format!("{SHA}\n"));
+        let context = detect(tmp.path()).unwrap();
+        assert_eq!(context.branch, None);
+        assert_eq!(context.commit.as_deref(), Some("0123456"));
+    }
+
+    #[test]
+    fn test_unborn_branch_has_no_commit() {
+        let tmp = fixture("ref: refs/heads/main\n");
+        let context = detect(tmp.path()).unwrap();
+        assert_eq!(context.branch.as_deref(), Some("main"));
+        assert_eq!(context.commit, None);
+    }
+
+    #[test]
+    fn test_gitdir_file_is_followed() {
+        let tmp = fixture("ref: refs/heads/main\n");
+        fs::write(tmp.path().join(".git/refs/heads/main"), SHA).unwrap();
+        let worktree = tmp.path().join("worktree");
+        fs::create_dir_all(&worktree).unwrap();
+        fs::write(
+            worktree.join(".git"),
+            format!("gitdir: {}\n", tmp.path().join(".git").display()),
+        )
+        .unwrap();
+
+        let context = detect(&worktree).unwrap();
+        assert_eq!(context.commit.as_deref(), Some("0123456"));
+    }
+
+    #[test]
+    fn test_not_a_repository() {
+        let tmp = TempDir::new().unwrap();
+        assert!(find_repository(tmp.path()).is_none());
+    }
+}
@@ -0,0 +1,103 @@
+//! Waiting on the trace service, for commands that may run while a local
+//! server is still starting or migrating.
+
+use std::{future::Future, time::Duration};
+
+use reqwest::{Client, Url};
+use tokio::time::{Instant, sleep};
+
+use crate::error::{PulseError, Result};
+
+/// Time between attempts.
+pub const POLL_INTERVAL: Duration = Duration::from_millis(500);
+
+/// Runs `attempt` until it succeeds, fails in a way waiting will not fix, or
+/// the next try would start after `timeout`. Returns the last result, so a
+/// zero timeout tries exactly once.
+pub async fn retry_until<T, F, Fut>(
+    timeout: Duration,
+    interval: Duration,
+    mut attempt: F,
+) -> Result<T>
+where
+    F: FnMut() -> Fut,
+    Fut: Future<Output = Result<T>>,
+{
+    let deadline = Instant::now() + timeout;
+    loop {
+        match attempt().await {
+            Err(err) if err.is_transient() && Instant::now() + interval <= deadline => {
+                sleep(interval).await;
+            }
+            result => return result,
+        }
+    }
+}
+
+/// GETs `url` and fails unless it answers with a success status.
+pub async fn check(client: &Client, url: &Url) -> Result<()> {
+    let response = client.get(url.clone()).send().await?;
+    match PulseError::from_response(&response) {
+        Some(err) => Err(err),
+        None => Ok(()),
+    }
+}
+
+/// Polls the health endpoint at `url` until it answers or `timeout` passes.
+pub async fn wait_until_healthy(client: &Client, url: &Url, timeout: Duration) -> Result<()> {
+    retry_until(timeout, POLL_INTERVAL, || check(client, url)).await
+}
+
+#[cfg(test)]
+mod tests {
+    use std::cell::Cell;
+
+    use reqwest::StatusCode;
+
+    use super::*;
+
+    const SHORT: Duration = Duration::from_millis(1);
+
+    #[tokio::test]
+    async fn test_retries_transient_errors() {
+        let attempts = Cell::new(0);
+        let result = retry_until(Duration::from_secs(5), SHORT, || async {
+            attempts.set(attempts.get() + 1);
+            if attempts.get() < 3 {
+                Err(PulseError::ServerError {
+                    status: StatusCode::SERVICE_UNAVAILABLE,
+                })
+            } else {
+                Ok(attempts.get())
+            }
+        })
+        .await;
+        assert_eq!(result.unwrap(), 3);
+    }
+
+    #[tokio::test]
+    async fn test_stops_on_errors_waiting_cannot_fix() {
+        let attempts = Cell::new(0);
+        let result: Result<()> = retry_until(Duration::from_secs(5), SHORT, || async {
+            attempts.set(attempts.get() + 1);
+            Err(PulseError::Unauthorized)
+        })
+        .await;
+        assert!(matches!(result, Err(PulseError::Unauthorized)));
+        assert_eq!(attempts.get(), 1);
+    }
+
+    #[tokio::test]
+    async fn test_zero_timeout_tries_once() {
+        let attempts = Cell::new(0);
+        let result: Result<()> = retry_until(Duration::ZERO, SHORT, || async {
+            attempts.set(attempts.get() + 1);
+            Err(PulseError::ServerError {
+                status: StatusCode::BAD_GATEWAY,
+            })
+        })
+        .await;
+        assert!(result.is_err());
+        assert_eq!(attempts.get(), 1);
+    }
+}
@@ -0,0 +1,187 @@
+//! Heartbeat spans for sessions the daemon has seen activity from, so a
+//! long-running session that is still working can be told apart from one
+//! whose agent died without sending `session_end`.
+
+use std::{collections::HashMap, env, time::Duration};
+
+use chrono::{DateTime, Utc};
+use serde_json::json;
+
+use crate::{
+    commands::emit::{BuildOptions, CLI_ERROR_EVENT_TYPE, build_span},
+    config::PulseConfig,
+    http::SpanPayload,
+};
+
+pub const HEARTBEAT_EVENT_TYPE: &str = "heartbeat";
+/// A session with no events for this long gets no more heartbeats, so an
+/// agent that crashed does not beat forever.
+const MAX_IDLE: Duration = Duration::from_secs(60 * 60);
+
+/// `PULSE_HEARTBEAT` wins over `heartbeat` in the config, in either
+/// direction.
+pub fn enabled(config: &PulseConfig) -> bool {
+    match env::var("PULSE_HEARTBEAT") {
+        Ok(v) => v == "1" || v == "true",
+        Err(_) => config.heartbeat(),
+    }
+}
+
+#[derive(Debug, Clone)]
+struct ActiveSession {
+    source: String,
+    parent_span_id: Option<String>,
+    last_event: DateTime<Utc>,
+}
+
+/// Sessions between their first event and `stop`/`session_end`. A session
+/// that stops and then gets a new prompt becomes active again.
+#[derive(Debug, Default)]
+pub struct ActiveSessions {
+    sessions: HashMap<String, ActiveSession>,
+}
+
+impl ActiveSessions {
+    pub fn len(&self) -> usize {
+        self.sessions.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.sessions.is_empty()
+    }
+
+    /// Updates the sessions from spans on their way to the trace service.
+    pub fn observe(&mut self, spans: &[SpanPayload], now: DateTime<Utc>) {
+        for span in spans {
+            match span.event_type.as_str() {
+                "stop" | "session_end" => {
+                    self.sessions.remove(&span.session_id);
+                }
+                HEARTBEAT_EVENT_TYPE | CLI_ERROR_EVENT_TYPE => {}
+                event_type => {
+                    let parent_span_id = if event_type == "session_start" {
+                        Some(span.span_id.clone())
+                    } else {
+                        span.parent_span_id.clone()
+                    };
+                    let session =
+                        self.sessions
+                            .entry(span.session_id.clone())
+                            .or_insert_with(|| ActiveSession {
+                                source: span.source.clone(),
+                                parent_span_id: None,
+                                last_event: now,
+                            });
+                    session.last_event = now;
+                    if parent_span_id.is_some() {
+                        session.parent_span_id = parent_span_id;
+                    }
+                }
+            }
+        }
+    }
+
+    /// One heartbeat per active session, after dropping sessions idle for
+    /// longer than `MAX_IDLE`. Each carries how long its session has been
+    /// quiet, which is what a dashboard needs to flag a stall.
+    pub fn beat(&mut self, config: &PulseConfig, now: DateTime<Utc>) -> Vec<SpanPayload> {
+        self.sessions
+            .retain(|_, session| idle(session, now) <= MAX_IDLE);
+        self.sessions
+            .iter()
+            .filter_map(|(session_id, session)| {
+                let payload = json!({
+                    "session_id": session_id,
+                    "last_event_at": session.last_event.to_rfc3339(),
+                    "idle_ms": idle(session, now).as_millis() as u64,
+                });
+                let options = BuildOptions {
+                    source: Some(session.source.clone()),
+                    store: None,
+                    sample: false,
+                    autodetect: false,
+                };
+                let mut span = build_span(HEARTBEAT_EVENT_TYPE, &payload, config, &options)
+                    .ok()
+                    .flatten()?;
+                span.parent_span_id = session.parent_span_id.clone();
+                // Also at the top of metadata, where `include_raw = false`
+                // cannot drop them.
+                if let Some(metadata) = span.metadata.as_mut().and_then(|m| m.as_object_mut()) {
+                    for key in ["last_event_at", "idle_ms"] {
+                        metadata.insert(key.to_string(), payload[key].clone());
+                    }
+                }
+                Some(span)
+            })
+            .collect()
+    }
+}
+
+fn idle(session: &ActiveSession, now: DateTime<Utc>) -> Duration {
+    now.signed_duration_since(session.last_event)
+        .to_std()
+        .unwrap_or_default()
+}
+
+#[cfg(test)]
+mod tests {
+    use chrono::TimeDelta;
+
+    use super::*;
+
+    fn span(session_id: &str, event_type: &str) -> SpanPayload {
+        SpanPayload {
+            session_id: session_id.to_string(),
+            kind: "session".to_string(),
+            event_type: event_type.to_string(),
+            ..SpanPayload::test(&format!("{session_id}-{event_type}"))
+        }
+    }
+
+    #[test]
+    fn test_sessions_beat_until_they_stop() {
+        let config = PulseConfig::default();
+        let start = Utc::now();
+        let mut sessions = ActiveSessions::default();
+        sessions.observe(
+            &[
+                span("s1", "session_start"),
+                span("s2", "user_prompt_submit"),
+            ],
+            start,
+        );
+
+        let later = start + TimeDelta::seconds(90);
+        let mut beats = sessions.beat(&config, later);
+        beats.sort_by(|a, b| a.session_id.cmp(&b.session_id));
+        assert_eq!(beats.len(), 2);
+        assert_eq!(beats[0].event_type, HEARTBEAT_EVENT_TYPE);
+        assert_eq!(beats[0].kind, "session");
+        assert_eq!(beats[0].parent_span_id.as_deref(), Some("s1-session_start"));
+        let metadata = beats[0].metadata.as_ref().unwrap();
+        assert_eq!(metadata["idle_ms"], 90_000);
+        assert_eq!(metadata["raw"]["idle_ms"], 90_000);
+
+        sessions.observe(&[span("s1", "stop"), span("s2", "session_end")], later);
+        assert!(sessions.beat(&config, later).is_empty());
+
+        // A new prompt after `stop` makes the session active again.
+        sessions.observe(&[span("s1", "user_prompt_submit")], later);
+        assert_eq!(sessions.len(), 1);
+    }
+
+    #[test]
+    fn test_idle_sessions_stop_beating() {
+        let config = PulseConfig::default();
+        let start = Utc::now();
+        let mut sessions = ActiveSessions::default();
+        sessions.observe(&[span("s1", "pre_tool_use")], start);
+        sessions.observe(&[span("s2", HEARTBEAT_EVENT_TYPE)], start);
+        assert_eq!(sessions.len(), 1);
+
+        let much_later = start + TimeDelta::hours(2);
+        assert!(sessions.beat(&config, much_later).is_empty());
+        assert!(sessions.is_empty());
+    }
+}
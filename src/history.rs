@@ -0,0 +1,417 @@
+use std::{
+    collections::HashMap,
+    fs::{self, File, OpenOptions},
+    io::{BufRead, BufReader, ErrorKind, Seek, SeekFrom, Write},
+    path::{Path, PathBuf},
+};
+
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+
+use crate::{error::Result, http::SpanPayload, paths};
+
+/// Once the file passes this size, the older half of it is dropped.
+const MAX_HISTORY_BYTES: u64 = 1024 * 1024;
+
+/// One line of local span history: enough to summarize a session without
+/// keeping payloads on disk. Fields after `timestamp` were added later and
+/// are missing from older lines.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct HistoryEntry {
+    pub session_id: String,
+    pub source: String,
+    pub event_type: String,
+    pub status: String,
+    pub timestamp: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub kind: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub tool_name: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub duration_ms: Option<f64>,
+    /// `metadata.usage` of `assistant_message` spans.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub usage: Option<Usage>,
+}
+
+impl From<&SpanPayload> for HistoryEntry {
+    fn from(span: &SpanPayload) -> Self {
+        Self {
+            session_id: span.session_id.clone(),
+            source: span.source.clone(),
+            event_type: span.event_type.clone(),
+            status: span.status.clone(),
+            timestamp: span.timestamp.clone(),
+            kind: Some(span.kind.clone()),
+            tool_name: span.tool_name.clone(),
+            duration_ms: span.duration_ms,
+            usage: span
+                .metadata
+                .as_ref()
+                .and_then(|metadata| metadata.get("usage"))
+                .and_then(|usage| serde_json::from_value(usage.clone()).ok()),
+        }
+    }
+}
+
+/// Token counts and cost of one model turn, or the sum over several.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct Usage {
+    #[serde(default)]
+    pub input_tokens: u64,
+    #[serde(default)]
+    pub output_tokens: u64,
+    #[serde(default)]
+    pub reasoning_tokens: u64,
+    #[serde(default)]
+    pub cache_read_tokens: u64,
+    #[serde(default)]
+    pub cache_write_tokens: u64,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub cost: Option<f64>,
+}
+
+impl Usage {
+    fn add(&mut self, other: &Usage) {
+        self.input_tokens += other.input_tokens;
+        self.output_tokens += other.output_tokens;
+        self.reasoning_tokens += other.reasoning_tokens;
+        self.cache_read_tokens += other.cache_read_tokens;
+        self.cache_write_tokens += other.cache_write_tokens;
+        if let Some(cost) = other.cost {
+            *self.cost.get_or_insert(0.0) += cost;
+        }
+    }
+}
+
+/// Append-only record of the spans `pulse emit` has handled, kept under a
+/// size cap so it never needs cleaning up.
+#[derive(Debug, Clone)]
+pub struct SpanHistory {
+    path: PathBuf,
+    max_bytes: u64,
+}
+
+impl SpanHistory {
+    pub fn new() -> Result<Self> {
+        Ok(Self::at(paths::history_file()?))
+    }
+
+    pub fn at(path: PathBuf) -> Self {
+        Self {
+            path,
+            max_bytes: MAX_HISTORY_BYTES,
+        }
+    }
+
+    pub fn path(&self) -> &PathBuf {
+        &self.path
+    }
+
+    pub fn record(&self, spans: &[SpanPayload]) -> Result<()> {
+        if spans.is_empty() {
+            return Ok(());
+        }
+        if let Some(parent) = self.path.parent() {
+            fs::create_dir_all(parent)?;
+        }
+        let mut file = OpenOptions::new()
+            .read(true)
+            .append(true)
+            .create(true)
+            .open(&self.path)?;
+        file.lock()?;
+        let mut lines = String::new();
+        for span in spans {
+            lines.push_str(&serde_json::to_string(&HistoryEntry::from(span))?);
+            lines.push('\n');
+        }
+        file.write_all(lines.as_bytes())?;
+
+        if file.metadata()?.len() > self.max_bytes {
+            trim_older_half(&mut file, &self.path)?;
+        }
+        Ok(())
+    }
+
+    /// Every recorded entry, oldest first, or `None` if nothing was ever
+    /// recorded. Lines that no longer parse are skipped.
+    pub fn entries(&self) -> Result<Option<Vec<HistoryEntry>>> {
+        let file = match File::open(&self.path) {
+            Ok(file) => file,
+            Err(err) if err.kind() == ErrorKind::NotFound => return Ok(None),
+            Err(err) => return Err(err.into()),
+        };
+        file.lock_shared()?;
+        let mut entries = Vec::new();
+        for line in BufReader::new(&file).lines() {
+            if let Ok(entry) = serde_json::from_str(&line?) {
+                entries.push(entry);
+            }
+        }
+        Ok(Some(entries))
+    }
+}
+
+fn trim_older_half(file: &mut File, path: &Path) -> Result<()> {
+    file.seek(SeekFrom::Start(0))?;
+    let lines: Vec<String> = BufReader::new(&*file)
+        .lines()
+        .collect::<std::io::Result<_>>()?;
+    let keep = &lines[lines.len() / 2..];
+    // `append` mode ignores seeks, so rewrite through a second handle while
+    // this one still holds the lock.
+    let mut rewrite = OpenOptions::new().write(true).truncate(true).open(path)?;
+    for line in keep {
+        writeln!(rewrite, "{line}")?;
+    }
+    rewrite.flush()?;
+    Ok(())
+}
+
+/// Per-session rollup of history entries.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct SessionSummary {
+    pub session_id: String,
+    pub source: String,
+    pub first: DateTime<Utc>,
+    pub last: DateTime<Utc>,
+    pub spans: usize,
+    pub errors: usize,
+}
+
+/// Groups entries by session, most recently active session first. Entries
+/// with an unreadable timestamp are left out.
+pub fn summarize(entries: &[HistoryEntry]) -> Vec<SessionSummary> {
+    let mut sessions: HashMap<&str, SessionSummary> = HashMap::new();
+    for entry in entries {
+        let Ok(time) = DateTime::parse_from_rfc3339(&entry.timestamp) else {
+            continue;
+        };
+        let time = time.with_timezone(&Utc);
+        let summary = sessions
+            .entry(&entry.session_id)
+            .or_insert_with(|| SessionSummary {
+                session_id: entry.session_id.clone(),
+                source: entry.source.clone(),
+                first: time,
+                last: time,
+                spans: 0,
+                errors: 0,
+            });
+        summary.first = summary.first.min(time);
+        summary.last = summary.last.max(time);
+        summary.spans += 1;
+        if entry.status == "error" {
+            summary.errors += 1;
+        }
+    }
+    let mut summaries: Vec<_> = sessions.into_values().collect();
+    summaries.sort_by(|a, b| b.last.cmp(&a.last).then(a.session_id.cmp(&b.session_id)));
+    summaries
+}
+
+/// Everything the history holds about one session, for `sessions show`.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct SessionDetail {
+    pub summary: SessionSummary,
+    /// The session's entries by timestamp. Concurrent hooks can record
+    /// spans out of order, so this is not the order they were appended in.
+    pub spans: Vec<HistoryEntry>,
+    /// Turns that reported usage.
+    pub turns: usize,
+    /// Usage summed over those turns.
+    pub usage: Usage,
+}
+
+/// The session `session_id` names, or the only one whose id starts with it.
+/// Entries with an unreadable timestamp are left out, as in `summarize`.
+pub fn session_detail(entries: &[HistoryEntry], session_id: &str) -> Option<SessionDetail> {
+    let matches = |id: &str| -> Vec<(DateTime<Utc>, &HistoryEntry)> {
+        entries
+            .iter()
+            .filter(|entry| entry.session_id == id)
+            .filter_map(|entry| Some((parse_time(&entry.timestamp)?, entry)))
+            .collect()
+    };
+    let mut timed = matches(session_id);
+    if timed.is_empty() {
+        let mut ids: Vec<&str> = entries
+            .iter()
+            .map(|entry| entry.session_id.as_str())
+            .filter(|id| id.starts_with(session_id))
+            .collect();
+        ids.sort_unstable();
+        ids.dedup();
+        let [id] = ids[..] else {
+            return None;
+        };
+        timed = matches(id);
+    }
+    // Stable, so entries with the same timestamp keep their recorded order.
+    timed.sort_by_key(|(time, _)| *time);
+
+    let (first, first_entry) = *timed.first()?;
+    let (last, _) = *timed.last()?;
+    let summary = SessionSummary {
+        session_id: first_entry.session_id.clone(),
+        source: first_entry.source.clone(),
+        first,
+        last,
+        spans: timed.len(),
+        errors: timed
+            .iter()
+            .filter(|(_, entry)| entry.status == "error")
+            .count(),
+    };
+    let spans: Vec<HistoryEntry> = timed.into_iter().map(|(_, entry)| entry.clone()).collect();
+    let mut usage = Usage::default();
+    let mut turns = 0;
+    for entry_usage in spans.iter().filter_map(|entry| entry.usage.as_ref()) {
+        usage.add(entry_usage);
+        turns += 1;
+    }
+    Some(SessionDetail {
+        summary,
+        spans,
+        turns,
+        usage,
+    })
+}
+
+fn parse_time(timestamp: &str) -> Option<DateTime<Utc>> {
+    DateTime::parse_from_rfc3339(timestamp)
+        .ok()
+        .map(|time| time.with_timezone(&Utc))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::TempDir;
+
+    fn entry(session_id: &str, status: &str, timestamp: &str) -> HistoryEntry {
+        HistoryEntry {
+            session_id: session_id.to_string(),
+            source: "claude_code".to_string(),
+            event_type: "post_tool_use".to_string(),
+            status: status.to_string(),
+            timestamp: timestamp.to_string(),
+            kind: None,
+            tool_name: None,
+            duration_ms: None,
+            usage: None,
+        }
+    }
+
+    fn turn(timestamp: &str, input: u64, output: u64, cost: f64) -> HistoryEntry {
+        HistoryEntry {
+            event_type: "assistant_message".to_string(),
+            kind: Some("llm_response".to_string()),
+            usage: Some(Usage {
+                input_tokens: input,
+                output_tokens: output,
+                cost: Some(cost),
+                ..Usage::default()
+            }),
+            ..entry("sess_1", "success", timestamp)
+        }
+    }
+
+    #[test]
+    fn test_session_detail_sorts_and_totals_usage() {
+        let tool = |event_type: &str, timestamp: &str| HistoryEntry {
+            event_type: event_type.to_string(),
+            kind: Some("tool_use".to_string()),
+            tool_name: Some("Bash".to_string()),
+            ..entry("sess_1", "success", timestamp)
+        };
+        let entries = [
+            turn("2025-01-01T10:00:01+00:00", 100, 20, 0.0015),
+            // Recorded after the turn, but it started first.
+            tool("pre_tool_use", "2025-01-01T10:00:00+00:00"),
+            tool("post_tool_use", "2025-01-01T10:00:02+00:00"),
+            entry("sess_2", "success", "2025-01-01T10:00:03+00:00"),
+            turn("2025-01-01T10:00:04+00:00", 300, 60, 0.0045),
+        ];
+
+        let detail = session_detail(&entries, "sess_1").unwrap();
+        let events: Vec<&str> = detail
+            .spans
+            .iter()
+            .map(|span| span.event_type.as_str())
+            .collect();
+        assert_eq!(
+            events,
+            [
+                "pre_tool_use",
+                "assistant_message",
+                "post_tool_use",
+                "assistant_message"
+            ]
+        );
+        assert_eq!(detail.summary.spans, 4);
+        assert_eq!(detail.turns, 2);
+        assert_eq!(detail.usage.input_tokens, 400);
+        assert_eq!(detail.usage.output_tokens, 80);
+        assert!((detail.usage.cost.unwrap() - 0.006).abs() < 1e-9);
+
+        // A unique prefix is enough; an ambiguous one is not.
+        assert_eq!(session_detail(&entries, "sess_2").unwrap().turns, 0);
+        assert!(session_detail(&entries, "sess").is_none());
+        assert!(session_detail(&entries, "other").is_none());
+    }
+
+    #[test]
+    fn test_old_history_lines_still_parse() {
+        let line = r#"{"session_id":"s","source":"claude_code","event_type":"stop","status":"success","timestamp":"2025-01-01T00:00:00+00:00"}"#;
+        let entry: HistoryEntry = serde_json::from_str(line).unwrap();
+        assert_eq!(entry.kind, None);
+        assert_eq!(entry.usage, None);
+    }
+
+    #[test]
+    fn test_summarize_groups_and_sorts_by_last_activity() {
+        let entries = [
+            entry("a", "success", "2025-01-01T10:00:00+00:00"),
+            entry("b", "success", "2025-01-01T11:00:00+00:00"),
+            entry("a", "error", "2025-01-01T12:00:00+00:00"),
+            entry("a", "success", "not a time"),
+        ];
+        let summaries = summarize(&entries);
+        assert_eq!(summaries.len(), 2);
+        assert_eq!(summaries[0].session_id, "a");
+        assert_eq!(summaries[0].spans, 2);
+        assert_eq!(summaries[0].errors, 1);
+        assert_eq!(summaries[0].first.to_rfc3339(), "2025-01-01T10:00:00+00:00");
+        assert_eq!(summaries[1].session_id, "b");
+    }
+
+    #[test]
+    fn test_missing_history_is_none() {
+        let tmp = TempDir::new().unwrap();
+        let history = SpanHistory::at(tmp.path().join(paths::HISTORY_FILE));
+        assert_eq!(history.entries().unwrap(), None);
+    }
+
+    #[test]
+    fn test_history_is_trimmed_past_the_cap() {
+        let tmp = TempDir::new().unwrap();
+        let mut history = SpanHistory::at(tmp.path().join(paths::HISTORY_FILE));
+        history.max_bytes = 2_000;
+        let span = |id: usize| SpanPayload {
+            session_id: format!("sess_{id}"),
+            kind: "session".to_string(),
+            event_type: "stop".to_string(),
+            ..SpanPayload::test(&id.to_string())
+        };
+        for id in 0..100 {
+            history.record(&[span(id)]).unwrap();
+        }
+
+        assert!(fs::metadata(history.path()).unwrap().len() <= 2_000);
+        let entries = history.entries().unwrap().unwrap();
+        assert_eq!(entries.last().unwrap().session_id, "sess_99");
+        assert!(entries.len() < 100);
+    }
+}
@@ -1,15 +1,33 @@
-use std::{fs, io::ErrorKind, path::PathBuf};
+use std::{
+    fs,
+    io::ErrorKind,
+    path::{Path, PathBuf},
+    time::{SystemTime, UNIX_EPOCH},
+};
 
 use dirs::home_dir;
+use serde::Serialize;
 use serde_json::{Map, Value, json};
 
 use crate::error::{PulseError, Result};
 
-use super::{HookStatus, ToolHook};
+use super::{
+    ConnectOptions, HookStatus, ManagedHook, ToolHook, content_hash, hook_command, json_diff,
+    pulse_arguments, same_command,
+};
 
 const CLAUDE_SETTINGS: &str = ".claude/settings.json";
+const CLAUDE_PROJECT_DIR: &str = ".claude";
 const CLAUDE_TOOL_NAME: &str = "Claude Code";
+const BACKUP_MARKER: &str = ".pulse-bak-";
+const MAX_SETTINGS_BACKUPS: usize = 5;
 pub const CLAUDE_SOURCE: &str = "claude_code";
+/// Claude Code hook events and the `pulse emit` command each one runs.
+///
+/// Claude Code has no hook that fires per assistant turn, so nothing here
+/// emits `assistant_message`; that event (model, token usage, cost) comes from
+/// the OpenCode plugin, or from any integration that pipes the documented
+/// payload to `pulse emit assistant_message`.
 pub const HOOK_DEFINITIONS: &[(&str, &str)] = &[
     ("PreToolUse", "pulse emit pre_tool_use"),
     ("PostToolUse", "pulse emit post_tool_use"),
@@ -23,23 +41,139 @@ pub const HOOK_DEFINITIONS: &[(&str, &str)] = &[
     ("Notification", "pulse emit notification"),
 ];
 
+/// Events whose entries honor a `matcher` (a regex over the tool name).
+/// Claude Code ignores the matcher on every other event here.
+pub const TOOL_USE_EVENTS: &[&str] = &["PreToolUse", "PostToolUse", "PostToolUseFailure"];
+
+/// Project-level settings files Claude Code reads from `<repo>/.claude/`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, clap::ValueEnum)]
+pub enum ProjectSettings {
+    /// `settings.json`, usually checked in and shared with the team.
+    Shared,
+    /// `settings.local.json`, personal and usually git-ignored.
+    Local,
+}
+
+impl ProjectSettings {
+    pub const ALL: [ProjectSettings; 2] = [ProjectSettings::Shared, ProjectSettings::Local];
+
+    fn file_name(self) -> &'static str {
+        match self {
+            ProjectSettings::Shared => "settings.json",
+            ProjectSettings::Local => "settings.local.json",
+        }
+    }
+
+    fn tool_name(self) -> &'static str {
+        match self {
+            ProjectSettings::Shared => "Claude Code (project)",
+            ProjectSettings::Local => "Claude Code (project, local)",
+        }
+    }
+}
+
 #[derive(Debug, Clone)]
 pub struct ClaudeCodeHook {
+    tool_name: &'static str,
     settings_path: PathBuf,
+    /// Claude Code counts as present when this exists: the settings file
+    /// itself for `~/.claude`, the `.claude` directory for a project.
+    detect_path: PathBuf,
+    pulse_bin: Option<String>,
+    /// Matcher written on tool-use entries; `None` keeps whatever is there.
+    matcher: Option<String>,
+    backup: bool,
+    /// Where backups go; `None` keeps them next to the settings file.
+    backup_dir: Option<PathBuf>,
+}
+
+/// A copy of the settings file saved before pulse rewrote it.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct SettingsBackup {
+    /// Unix time the copy was taken, from its file name.
+    pub timestamp: u64,
+    pub path: PathBuf,
 }
 
 impl ClaudeCodeHook {
     pub fn new() -> Result<Self> {
         let home = home_dir().ok_or(PulseError::HomeDirNotFound)?;
-        Ok(Self {
-            settings_path: home.join(CLAUDE_SETTINGS),
-        })
+        Ok(Self::with_settings_path(home.join(CLAUDE_SETTINGS)))
+    }
+
+    /// Hook for the settings file at `settings_path`, which counts as
+    /// detected when it exists.
+    pub fn with_settings_path(settings_path: PathBuf) -> Self {
+        Self {
+            tool_name: CLAUDE_TOOL_NAME,
+            detect_path: settings_path.clone(),
+            settings_path,
+            pulse_bin: None,
+            matcher: None,
+            backup: true,
+            backup_dir: None,
+        }
+    }
+
+    /// Hook for a settings file in `<project_dir>/.claude/`. The file is
+    /// created on connect if the `.claude` directory exists.
+    pub fn for_project(project_dir: &Path, settings: ProjectSettings) -> Self {
+        let claude_dir = project_dir.join(CLAUDE_PROJECT_DIR);
+        Self {
+            tool_name: settings.tool_name(),
+            settings_path: claude_dir.join(settings.file_name()),
+            detect_path: claude_dir,
+            pulse_bin: None,
+            matcher: None,
+            backup: true,
+            backup_dir: None,
+        }
+    }
+
+    pub fn settings_path(&self) -> &Path {
+        &self.settings_path
+    }
+
+    /// Whether the settings file this hook edits exists yet.
+    pub fn settings_exist(&self) -> bool {
+        self.settings_path.exists()
+    }
+
+    fn is_detected(&self) -> bool {
+        self.detect_path.exists()
+    }
+
+    /// Writes hook commands with an absolute `pulse` path instead of relying
+    /// on `PATH`. Entries running `pulse` bare or under any other path are
+    /// still recognized.
+    pub fn with_pulse_bin(mut self, pulse_bin: Option<String>) -> Self {
+        self.pulse_bin = pulse_bin.filter(|bin| !bin.trim().is_empty());
+        self
+    }
+
+    /// Limits the tool-use hooks (`TOOL_USE_EVENTS`) to tools whose name
+    /// matches this regex. An empty matcher traces every tool.
+    pub fn with_matcher(mut self, matcher: Option<String>) -> Self {
+        self.matcher = matcher;
+        self
+    }
+
+    /// Whether `connect`/`disconnect` copy settings.json aside before
+    /// rewriting it. On by default.
+    pub fn with_backup(mut self, backup: bool) -> Self {
+        self.backup = backup;
+        self
     }
 
     fn read_settings(&self) -> Result<Option<Value>> {
         match fs::read_to_string(&self.settings_path) {
             Ok(contents) => {
-                let value: Value = serde_json::from_str(&contents)?;
+                let value: Value = serde_json::from_str(&contents).map_err(|err| {
+                    PulseError::message(format!(
+                        "{} is not valid JSON ({err}); fix or delete it",
+                        self.settings_path.display()
+                    ))
+                })?;
                 Ok(Some(value))
             }
             Err(err) => {
@@ -61,7 +195,137 @@ impl ClaudeCodeHook {
         Ok(())
     }
 
-    fn hooks_map<'a>(value: &'a mut Value) -> Result<&'a mut Map<String, Value>> {
+    /// Writes backups to `dir` instead of next to the settings file.
+    pub fn with_backup_dir(mut self, dir: Option<PathBuf>) -> Self {
+        self.backup_dir = dir;
+        self
+    }
+
+    /// The directory backups go to and the file name prefix they share.
+    /// Next to the settings file that is `settings.json.pulse-bak-`; in a
+    /// `backup_dir` shared by several settings files, a hash of the settings
+    /// path keeps them apart.
+    fn backup_location(&self) -> Option<(PathBuf, String)> {
+        let name = self.settings_path.file_name()?.to_str()?;
+        match &self.backup_dir {
+            None => Some((
+                self.settings_path.parent()?.to_path_buf(),
+                format!("{name}{BACKUP_MARKER}"),
+            )),
+            Some(dir) => {
+                let hash = content_hash(&[self.settings_path.as_os_str().as_encoded_bytes()]);
+                Some((dir.clone(), format!("{name}.{hash}{BACKUP_MARKER}")))
+            }
+        }
+    }
+
+    /// Copies the current settings file to `settings.json.pulse-bak-<unixtime>`
+    /// and prunes all but the newest `MAX_SETTINGS_BACKUPS` copies.
+    fn backup_settings(&self) -> Result<PathBuf> {
+        let (dir, prefix) = self.backup_location().ok_or_else(|| {
+            PulseError::message(format!(
+                "cannot back up {}: not a file path",
+                self.settings_path.display()
+            ))
+        })?;
+        fs::create_dir_all(&dir)?;
+        let current = fs::read(&self.settings_path)?;
+        let mut timestamp = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map(|elapsed| elapsed.as_secs())
+            .unwrap_or_default();
+        // A second backup within the same second gets the next free second,
+        // so it neither overwrites the first nor sorts before it.
+        let backup_path = loop {
+            let path = dir.join(format!("{prefix}{timestamp}"));
+            match fs::read(&path) {
+                Ok(existing) if existing == current => break path,
+                Ok(_) => timestamp += 1,
+                Err(err) if err.kind() == ErrorKind::NotFound => {
+                    fs::copy(&self.settings_path, &path)?;
+                    break path;
+                }
+                Err(err) => return Err(err.into()),
+            }
+        };
+        self.prune_backups()?;
+        Ok(backup_path)
+    }
+
+    fn prune_backups(&self) -> Result<()> {
+        let mut backups = self.backups()?;
+        if backups.len() <= MAX_SETTINGS_BACKUPS {
+            return Ok(());
+        }
+        let excess = backups.len() - MAX_SETTINGS_BACKUPS;
+        for backup in backups.drain(..excess) {
+            let _ = fs::remove_file(backup.path);
+        }
+        Ok(())
+    }
+
+    /// Existing backups, oldest first.
+    pub fn backups(&self) -> Result<Vec<SettingsBackup>> {
+        let Some((dir, prefix)) = self.backup_location() else {
+            return Ok(Vec::new());
+        };
+        let entries = match fs::read_dir(&dir) {
+            Ok(entries) => entries,
+            Err(err) if err.kind() == ErrorKind::NotFound => return Ok(Vec::new()),
+            Err(err) => return Err(err.into()),
+        };
+        let mut backups: Vec<SettingsBackup> = entries
+            .filter_map(|entry| entry.ok())
+            .filter_map(|entry| {
+                let file_name = entry.file_name();
+                let timestamp = file_name.to_str()?.strip_prefix(&prefix)?.parse().ok()?;
+                Some(SettingsBackup {
+                    timestamp,
+                    path: entry.path(),
+                })
+            })
+            .collect();
+        backups.sort_by_key(|backup| backup.timestamp);
+        Ok(backups)
+    }
+
+    /// Puts `backup` back as the settings file. The backup must parse as
+    /// JSON, and the file it replaces is backed up first (even with backups
+    /// turned off); returns where that copy went.
+    pub fn restore(&self, backup: &SettingsBackup) -> Result<Option<PathBuf>> {
+        let contents = fs::read_to_string(&backup.path)?;
+        if let Err(err) = serde_json::from_str::<Value>(&contents) {
+            return Err(PulseError::message(format!(
+                "{} is not valid JSON ({err}); settings left unchanged",
+                backup.path.display()
+            )));
+        }
+        let saved = if self.settings_path.exists() {
+            Some(self.backup_settings()?)
+        } else {
+            None
+        };
+        if let Some(parent) = self.settings_path.parent() {
+            fs::create_dir_all(parent)?;
+        }
+        fs::write(&self.settings_path, contents)?;
+        Ok(saved)
+    }
+
+    /// Writes `value`, backing up the previous file first when enabled.
+    /// Returns a note about the backup for `HookStatus.message`.
+    fn save_settings(&self, value: &Value) -> Result<Option<String>> {
+        let note = if self.backup && self.settings_path.exists() {
+            let path = self.backup_settings()?;
+            Some(format!("Backup saved to {}", path.display()))
+        } else {
+            None
+        };
+        self.write_settings(value)?;
+        Ok(note)
+    }
+
+    fn hooks_map(value: &mut Value) -> Result<&mut Map<String, Value>> {
         let obj = value.as_object_mut().ok_or_else(|| {
             PulseError::message("Claude settings file must contain a JSON object")
         })?;
@@ -73,15 +337,40 @@ impl ClaudeCodeHook {
             .ok_or_else(|| PulseError::message("`hooks` field must be a JSON object"))
     }
 
-    fn ensure_command(events: &mut Vec<Value>, command: &str) -> bool {
-        let already_present = events
-            .iter()
-            .any(|entry| entry_contains_command(entry, command));
+    /// Makes sure `events` runs `command`. With a `matcher`, an entry with
+    /// a different matcher is replaced; without one, the existing matcher
+    /// is kept even when the command form changes.
+    fn ensure_command(
+        events: &mut Vec<Value>,
+        command: &str,
+        pulse_bin: Option<&str>,
+        matcher: Option<&str>,
+    ) -> bool {
+        let desired = hook_command(command, pulse_bin);
+        let already_present = events.iter().any(|entry| {
+            pulse_commands(entry).contains(&desired.as_str())
+                && matcher.is_none_or(|matcher| entry_matcher(entry) == matcher)
+        });
         if already_present {
             return false;
         }
+        let matcher = matcher
+            .or_else(|| {
+                events
+                    .iter()
+                    .find(|entry| entry_contains_command(entry, command))
+                    .map(entry_matcher)
+            })
+            .unwrap_or("")
+            .to_string();
+        // Swap out other forms of the same command (bare vs absolute path).
+        for entry in events.iter_mut() {
+            remove_command(entry, command);
+        }
+        events.retain(|entry| !entry_is_empty(entry));
+        let command = desired.as_str();
         let hook_value = json!({
-            "matcher": "",
+            "matcher": matcher,
             "hooks": [{
                 "type": "command",
                 "command": command,
@@ -92,7 +381,11 @@ impl ClaudeCodeHook {
         true
     }
 
-    fn insert_hooks(value: &mut Value) -> Result<bool> {
+    fn insert_hooks(
+        value: &mut Value,
+        pulse_bin: Option<&str>,
+        matcher: Option<&str>,
+    ) -> Result<bool> {
         let hooks_map = Self::hooks_map(value)?;
         let mut changed = false;
         for (event, command) in HOOK_DEFINITIONS {
@@ -102,7 +395,8 @@ impl ClaudeCodeHook {
             let events = entry
                 .as_array_mut()
                 .ok_or_else(|| PulseError::message("Hook event entries must be arrays"))?;
-            if Self::ensure_command(events, command) {
+            let matcher = matcher.filter(|_| TOOL_USE_EVENTS.contains(event));
+            if Self::ensure_command(events, command, pulse_bin, matcher) {
                 changed = true;
             }
         }
@@ -154,28 +448,76 @@ impl ClaudeCodeHook {
         Ok(changed)
     }
 
+    /// Leaves one current pulse command per event: duplicates and commands
+    /// an older pulse wrote are removed, keeping the matcher of the entry
+    /// they replace. Hooks of other tools are not touched.
+    fn repair_hooks(value: &mut Value, pulse_bin: Option<&str>) -> Result<bool> {
+        let hooks_map = Self::hooks_map(value)?;
+        let mut changed = false;
+        let mut empty_events = Vec::new();
+        for (event, entries) in hooks_map.iter_mut() {
+            let Some(entries) = entries.as_array_mut() else {
+                continue;
+            };
+            let command = HOOK_DEFINITIONS
+                .iter()
+                .find(|(name, _)| name == event)
+                .map(|(_, command)| *command);
+            let desired = command.map(|command| hook_command(command, pulse_bin));
+            let matcher = entries
+                .iter()
+                .find(|entry| !pulse_commands(entry).is_empty())
+                .map(|entry| entry_matcher(entry).to_string());
+
+            let mut kept = false;
+            for entry in entries.iter_mut() {
+                changed |= retain_commands(entry, |hook_command| {
+                    if !is_pulse_command(hook_command) {
+                        return true;
+                    }
+                    let keep = !kept && desired.as_deref() == Some(hook_command);
+                    kept |= keep;
+                    keep
+                });
+            }
+            entries.retain(|entry| !entry_is_empty(entry));
+            if let (Some(command), Some(matcher), false) = (command, &matcher, kept) {
+                Self::ensure_command(entries, command, pulse_bin, Some(matcher));
+            }
+            if entries.is_empty() {
+                empty_events.push(event.clone());
+            }
+        }
+        for event in empty_events {
+            hooks_map.remove(&event);
+        }
+        Ok(changed)
+    }
+
     fn current_status(&self) -> Result<HookStatus> {
-        if !self.settings_path.exists() {
+        if !self.is_detected() {
             return Ok(HookStatus::not_detected(
                 self.tool_name(),
                 self.settings_path.clone(),
             ));
         }
-        let Some(value) = self.read_settings()? else {
-            return Ok(HookStatus::not_detected(
-                self.tool_name(),
-                self.settings_path.clone(),
-            ));
-        };
+        let value = self.read_settings()?.unwrap_or(Value::Object(Map::new()));
         let (installed, total, names) = installed_hook_counts(&value);
         let connected = installed == total;
+        let problems = hook_problems(&value, self.pulse_bin.as_deref());
+        let message = (!problems.is_empty()).then(|| {
+            format!(
+                "Needs repair: {}. Run `pulse connect --repair`",
+                problems.join("; ")
+            )
+        });
         Ok(HookStatus {
             tool: self.tool_name(),
             detected: true,
             connected,
             modified: false,
             path: Some(self.settings_path.clone()),
-            message: None,
+            message,
             installed_hooks: installed,
             total_hooks: total,
             installed_hook_names: names,
@@ -184,26 +526,53 @@ impl ClaudeCodeHook {
 }
 
 impl ToolHook for ClaudeCodeHook {
+    fn id(&self) -> &'static str {
+        CLAUDE_SOURCE
+    }
+
     fn tool_name(&self) -> &'static str {
-        CLAUDE_TOOL_NAME
+        self.tool_name
+    }
+
+    fn managed_hooks(&self) -> Vec<ManagedHook> {
+        HOOK_DEFINITIONS
+            .iter()
+            .map(|(event, command)| {
+                ManagedHook::event(
+                    event,
+                    hook_command(command, self.pulse_bin.as_deref()),
+                    &self.settings_path,
+                )
+            })
+            .collect()
     }
 
     fn status(&self) -> Result<HookStatus> {
         self.current_status()
     }
 
-    fn connect(&self) -> Result<HookStatus> {
-        if !self.settings_path.exists() {
+    fn connect_opts(&self, options: &ConnectOptions) -> Result<HookStatus> {
+        if !self.is_detected() {
             return Ok(HookStatus::not_detected(
                 self.tool_name(),
                 self.settings_path.clone(),
             ));
         }
         let mut value = self.read_settings()?.unwrap_or(Value::Object(Map::new()));
-        let changed = Self::insert_hooks(&mut value)?;
-        if changed {
-            self.write_settings(&value)?;
-        }
+        let original = value.clone();
+        let repaired = options.repair && Self::repair_hooks(&mut value, self.pulse_bin.as_deref())?;
+        let changed = Self::insert_hooks(
+            &mut value,
+            self.pulse_bin.as_deref(),
+            self.matcher.as_deref(),
+        )? || repaired;
+        let message = if !changed {
+            None
+        } else if options.dry_run {
+            Some(json_diff(&original, &value)?)
+        } else {
+            self.save_settings(&value)?
+        };
         let (installed, total, names) = installed_hook_counts(&value);
         let connected = installed == total;
         Ok(HookStatus {
@@ -212,15 +581,15 @@ impl ToolHook for ClaudeCodeHook {
             connected,
             modified: changed,
             path: Some(self.settings_path.clone()),
-            message: None,
+            message,
             installed_hooks: installed,
             total_hooks: total,
             installed_hook_names: names,
         })
     }
 
-    fn disconnect(&self) -> Result<HookStatus> {
-        if !self.settings_path.exists() {
+    fn disconnect_opts(&self, options: &ConnectOptions) -> Result<HookStatus> {
+        if !self.is_detected() {
             return Ok(HookStatus::not_detected(
                 self.tool_name(),
                 self.settings_path.clone(),
@@ -230,10 +599,15 @@ impl ToolHook for ClaudeCodeHook {
             Some(value) => value,
             None => Value::Object(Map::new()),
         };
+        let original = value.clone();
         let changed = Self::remove_hooks(&mut value)?;
-        if changed {
-            self.write_settings(&value)?;
-        }
+        let message = if !changed {
+            None
+        } else if options.dry_run {
+            Some(json_diff(&original, &value)?)
+        } else {
+            self.save_settings(&value)?
+        };
         let (installed, total, names) = installed_hook_counts(&value);
         let connected = installed == total;
         Ok(HookStatus {
@@ -242,7 +616,7 @@ impl ToolHook for ClaudeCodeHook {
             connected,
             modified: changed,
             path: Some(self.settings_path.clone()),
-            message: None,
+            message,
             installed_hooks: installed,
             total_hooks: total,
             installed_hook_names: names,
@@ -250,6 +624,16 @@ impl ToolHook for ClaudeCodeHook {
     }
 }
 
+/// Every form of a command pulse may have written: bare and absolute path.
+fn command_forms(command: &str, pulse_bin: Option<&str>) -> Vec<String> {
+    let mut forms = vec![command.to_string()];
+    let pinned = hook_command(command, pulse_bin);
+    if pinned != command {
+        forms.push(pinned);
+    }
+    forms
+}
+
 fn installed_hook_counts(value: &Value) -> (usize, usize, Vec<String>) {
     let total = HOOK_DEFINITIONS.len();
     let hooks_map = match value
@@ -291,14 +675,25 @@ fn entry_contains_command(entry: &Value, command: &str) -> bool {
                 hook.as_object()
                     .and_then(|hook_obj| hook_obj.get("command"))
                     .and_then(|cmd| cmd.as_str())
-                    .map(|value| value == command)
+                    .map(|value| same_command(value, command))
                     .unwrap_or(false)
             })
         })
         .unwrap_or(false)
 }
 
+/// An entry's matcher, where a missing one means every tool.
+fn entry_matcher(entry: &Value) -> &str {
+    entry.get("matcher").and_then(Value::as_str).unwrap_or("")
+}
+
 fn remove_command(entry: &mut Value, command: &str) -> bool {
+    retain_commands(entry, |hook_command| !same_command(hook_command, command))
+}
+
+/// Keeps the entry's command hooks for which `keep` returns true. Returns
+/// whether any were removed.
+fn retain_commands(entry: &mut Value, mut keep: impl FnMut(&str) -> bool) -> bool {
     let hooks = match entry
         .as_object_mut()
         .and_then(|obj| obj.get_mut("hooks"))
@@ -312,12 +707,64 @@ fn remove_command(entry: &mut Value, command: &str) -> bool {
         hook.as_object()
             .and_then(|obj| obj.get("command"))
             .and_then(|cmd| cmd.as_str())
-            .map(|value| value != command)
+            .map(&mut keep)
             .unwrap_or(true)
     });
     hooks.len() != initial_len
 }
 
+/// Whether a hook command runs `pulse emit`, under any path to the binary
+/// and whatever the event. Broader than the exact forms pulse writes, so
+/// hand-edited and outdated entries are found too.
+fn is_pulse_command(command: &str) -> bool {
+    pulse_arguments(command).is_some_and(|rest| rest.split_whitespace().next() == Some("emit"))
+}
+
+/// The pulse commands in one settings entry.
+fn pulse_commands(entry: &Value) -> Vec<&str> {
+    entry
+        .get("hooks")
+        .and_then(Value::as_array)
+        .into_iter()
+        .flatten()
+        .filter_map(|hook| hook.get("command").and_then(Value::as_str))
+        .filter(|command| is_pulse_command(command))
+        .collect()
+}
+
+/// Duplicate and outdated pulse commands, one description per event.
+fn hook_problems(value: &Value, pulse_bin: Option<&str>) -> Vec<String> {
+    let Some(hooks_map) = value.get("hooks").and_then(Value::as_object) else {
+        return Vec::new();
+    };
+    let mut problems = Vec::new();
+    for (event, entries) in hooks_map {
+        let commands: Vec<&str> = entries
+            .as_array()
+            .into_iter()
+            .flatten()
+            .flat_map(pulse_commands)
+            .collect();
+        if commands.is_empty() {
+            continue;
+        }
+        let Some((_, command)) = HOOK_DEFINITIONS.iter().find(|(name, _)| name == event) else {
+            problems.push(format!("{event} is no longer used by pulse"));
+            continue;
+        };
+        let forms = command_forms(command, pulse_bin);
+        if let Some(stale) = commands
+            .iter()
+            .find(|command| !forms.iter().any(|form| form == *command))
+        {
+            problems.push(format!("{event} runs outdated `{stale}`"));
+        } else if commands.len() > 1 {
+            problems.push(format!("{event} has {} pulse hooks", commands.len()));
+        }
+    }
+    problems
+}
+
 fn entry_is_empty(entry: &Value) -> bool {
     entry
         .as_object()
@@ -330,6 +777,7 @@ fn entry_is_empty(entry: &Value) -> bool {
 #[cfg(test)]
 mod tests {
     use super::*;
+    use tempfile::TempDir;
 
     #[test]
     fn test_hook_definitions_count() {
@@ -357,7 +805,7 @@ mod tests {
     #[test]
     fn test_insert_hooks_into_empty_settings() {
         let mut value = json!({});
-        let changed = ClaudeCodeHook::insert_hooks(&mut value).unwrap();
+        let changed = ClaudeCodeHook::insert_hooks(&mut value, None, None).unwrap();
         assert!(changed);
 
         let (installed, total, names) = installed_hook_counts(&value);
@@ -369,15 +817,15 @@ mod tests {
     #[test]
     fn test_insert_hooks_is_idempotent() {
         let mut value = json!({});
-        ClaudeCodeHook::insert_hooks(&mut value).unwrap();
-        let changed = ClaudeCodeHook::insert_hooks(&mut value).unwrap();
+        ClaudeCodeHook::insert_hooks(&mut value, None, None).unwrap();
+        let changed = ClaudeCodeHook::insert_hooks(&mut value, None, None).unwrap();
         assert!(!changed, "second insert should not change anything");
     }
 
     #[test]
     fn test_remove_hooks_cleans_up() {
         let mut value = json!({});
-        ClaudeCodeHook::insert_hooks(&mut value).unwrap();
+        ClaudeCodeHook::insert_hooks(&mut value, None, None).unwrap();
         let changed = ClaudeCodeHook::remove_hooks(&mut value).unwrap();
         assert!(changed);
 
@@ -402,7 +850,7 @@ mod tests {
                 }]
             }
         });
-        ClaudeCodeHook::insert_hooks(&mut value).unwrap();
+        ClaudeCodeHook::insert_hooks(&mut value, None, None).unwrap();
 
         // The existing hook entry should still be there
         let post_tool = value["hooks"]["PostToolUse"].as_array().unwrap();
@@ -419,7 +867,7 @@ mod tests {
                 }]
             }
         });
-        ClaudeCodeHook::insert_hooks(&mut value).unwrap();
+        ClaudeCodeHook::insert_hooks(&mut value, None, None).unwrap();
         ClaudeCodeHook::remove_hooks(&mut value).unwrap();
 
         // The non-pulse hook should remain
@@ -435,7 +883,7 @@ mod tests {
     fn test_installed_hook_counts_partial() {
         // Simulate an old install with only 3 hooks
         let mut value = json!({});
-        ClaudeCodeHook::insert_hooks(&mut value).unwrap();
+        ClaudeCodeHook::insert_hooks(&mut value, None, None).unwrap();
 
         // Remove some hooks manually
         let hooks_map = value["hooks"].as_object_mut().unwrap();
@@ -450,4 +898,448 @@ mod tests {
         assert!(!names.contains(&"PreToolUse".to_string()));
         assert!(!names.contains(&"SubagentStart".to_string()));
     }
+
+    #[test]
+    fn test_managed_hooks_list_every_definition() {
+        let tmp = TempDir::new().unwrap();
+        let hook = make_hook(&tmp);
+        let managed = hook.managed_hooks();
+        assert_eq!(managed.len(), 10);
+        assert_eq!(managed.len(), HOOK_DEFINITIONS.len());
+        assert_eq!(managed[0].event.as_deref(), Some("PreToolUse"));
+        assert_eq!(
+            managed[0].command.as_deref(),
+            Some("pulse emit pre_tool_use")
+        );
+        assert!(
+            managed
+                .iter()
+                .all(|hook| hook.path == tmp.path().join("settings.json"))
+        );
+
+        let pinned = hook.with_pulse_bin(Some("/opt/pulse/bin/pulse".to_string()));
+        assert_eq!(
+            pinned.managed_hooks()[9].command.as_deref(),
+            Some("/opt/pulse/bin/pulse emit notification")
+        );
+    }
+
+    #[test]
+    fn test_insert_with_pulse_bin_writes_absolute_commands() {
+        let mut value = json!({});
+        let bin = Some("/opt/pulse/bin/pulse");
+        ClaudeCodeHook::insert_hooks(&mut value, bin, None).unwrap();
+
+        assert_eq!(
+            value["hooks"]["PreToolUse"][0]["hooks"][0]["command"],
+            "/opt/pulse/bin/pulse emit pre_tool_use"
+        );
+        let (installed, total, _) = installed_hook_counts(&value);
+        assert_eq!(installed, total);
+
+        let changed = ClaudeCodeHook::remove_hooks(&mut value).unwrap();
+        assert!(changed);
+        let (installed, _, _) = installed_hook_counts(&value);
+        assert_eq!(installed, 0);
+    }
+
+    #[test]
+    fn test_insert_with_pulse_bin_replaces_bare_commands() {
+        let mut value = json!({});
+        ClaudeCodeHook::insert_hooks(&mut value, None, None).unwrap();
+        let bin = Some("/Users/me/My Tools/pulse");
+        let changed = ClaudeCodeHook::insert_hooks(&mut value, bin, None).unwrap();
+        assert!(changed);
+
+        let post_tool = value["hooks"]["PostToolUse"].as_array().unwrap();
+        assert_eq!(post_tool.len(), 1, "bare entry should be replaced");
+        assert_eq!(
+            post_tool[0]["hooks"][0]["command"],
+            "\"/Users/me/My Tools/pulse\" emit post_tool_use"
+        );
+
+        // And back to the bare form once the pin is dropped.
+        assert!(ClaudeCodeHook::insert_hooks(&mut value, None, None).unwrap());
+        let post_tool = value["hooks"]["PostToolUse"].as_array().unwrap();
+        assert_eq!(post_tool.len(), 1);
+        assert_eq!(
+            post_tool[0]["hooks"][0]["command"],
+            "pulse emit post_tool_use"
+        );
+    }
+
+    #[test]
+    fn test_same_command_ignores_the_pulse_path() {
+        assert!(same_command(
+            "/home/me/.cargo/bin/pulse emit stop",
+            "pulse emit stop"
+        ));
+        assert!(same_command(
+            "\"/Users/me/My Tools/pulse\"  emit stop",
+            "pulse emit stop"
+        ));
+        assert!(!same_command(
+            "/usr/bin/pulse emit stop",
+            "pulse emit session_end"
+        ));
+        assert!(!same_command(
+            "/usr/bin/pulsar emit stop",
+            "pulse emit stop"
+        ));
+        assert!(same_command(
+            "other-tool do something",
+            "other-tool do something"
+        ));
+    }
+
+    #[test]
+    fn test_self_exe_hooks_are_detected_and_removed_from_any_path() {
+        let tmp = TempDir::new().unwrap();
+        let pinned = make_hook(&tmp).with_pulse_bin(Some("/home/me/.cargo/bin/pulse".to_string()));
+        assert!(pinned.connect().unwrap().connected);
+        let settings = fs::read_to_string(&pinned.settings_path).unwrap();
+        assert!(settings.contains("/home/me/.cargo/bin/pulse emit post_tool_use"));
+        assert!(!settings.contains("\"pulse emit"));
+
+        // A hook that does not know the pin, e.g. another pulse install,
+        // still sees and removes the pinned commands.
+        let unpinned = make_hook_at(&pinned.settings_path);
+        let status = unpinned.status().unwrap();
+        assert!(status.connected);
+        assert_eq!(status.installed_hooks, HOOK_DEFINITIONS.len());
+
+        let status = unpinned.disconnect().unwrap();
+        assert!(status.modified);
+        assert_eq!(status.installed_hooks, 0);
+        let settings = fs::read_to_string(&pinned.settings_path).unwrap();
+        assert!(!settings.contains("emit"), "{settings}");
+        assert!(settings.contains("\"theme\""));
+    }
+
+    #[test]
+    fn test_matcher_written_on_tool_use_entries() {
+        let mut value = json!({});
+        ClaudeCodeHook::insert_hooks(&mut value, None, Some("Bash|Edit")).unwrap();
+
+        for event in TOOL_USE_EVENTS {
+            assert_eq!(value["hooks"][event][0]["matcher"], "Bash|Edit");
+        }
+        assert_eq!(value["hooks"]["SessionStart"][0]["matcher"], "");
+        let (installed, total, _) = installed_hook_counts(&value);
+        assert_eq!(installed, total);
+
+        // Connecting again without a matcher keeps it.
+        let changed = ClaudeCodeHook::insert_hooks(&mut value, None, None).unwrap();
+        assert!(!changed);
+
+        assert!(ClaudeCodeHook::remove_hooks(&mut value).unwrap());
+        let (installed, _, _) = installed_hook_counts(&value);
+        assert_eq!(installed, 0);
+        assert!(value.get("hooks").is_none());
+    }
+
+    #[test]
+    fn test_changed_matcher_replaces_entry() {
+        let mut value = json!({});
+        ClaudeCodeHook::insert_hooks(&mut value, None, Some("Bash")).unwrap();
+        let changed = ClaudeCodeHook::insert_hooks(&mut value, None, Some("Edit")).unwrap();
+        assert!(changed);
+
+        let pre_tool = value["hooks"]["PreToolUse"].as_array().unwrap();
+        assert_eq!(pre_tool.len(), 1);
+        assert_eq!(pre_tool[0]["matcher"], "Edit");
+
+        // An explicit empty matcher goes back to tracing every tool.
+        ClaudeCodeHook::insert_hooks(&mut value, None, Some("")).unwrap();
+        assert_eq!(value["hooks"]["PostToolUse"][0]["matcher"], "");
+    }
+
+    #[test]
+    fn test_is_pulse_command() {
+        assert!(is_pulse_command("pulse emit post_tool_use"));
+        assert!(is_pulse_command("/opt/old/bin/pulse emit post_tool_use"));
+        assert!(is_pulse_command("\"/Users/me/My Tools/pulse\" emit stop"));
+        assert!(is_pulse_command(
+            "pulse emit tool_result --source claude_code"
+        ));
+        assert!(!is_pulse_command("pulse flush"));
+        assert!(!is_pulse_command("other-tool emit post_tool_use"));
+        assert!(!is_pulse_command("pulsecheck emit post_tool_use"));
+    }
+
+    #[test]
+    fn test_repair_dedupes_and_rewrites_stale_commands() {
+        let pulse_entry = |command: &str| {
+            json!({
+                "matcher": "Bash",
+                "hooks": [{"type": "command", "command": command, "async": true}]
+            })
+        };
+        let mut value = json!({
+            "hooks": {
+                "PostToolUse": [
+                    pulse_entry("pulse emit post_tool_use"),
+                    {
+                        "matcher": "",
+                        "hooks": [
+                            {"type": "command", "command": "other-tool do something"},
+                            {"type": "command", "command": "/old/pulse emit post_tool_use"}
+                        ]
+                    },
+                    pulse_entry("pulse emit post_tool_use")
+                ],
+                "PreToolUse": [pulse_entry("pulse emit tool_start")],
+                "ToolStart": [pulse_entry("pulse emit tool_start")]
+            }
+        });
+        let problems = hook_problems(&value, None);
+        assert_eq!(problems.len(), 3, "{problems:?}");
+        assert!(
+            problems
+                .iter()
+                .any(|p| p.starts_with("PostToolUse runs outdated"))
+        );
+        assert!(
+            problems
+                .iter()
+                .any(|p| p.starts_with("PreToolUse runs outdated"))
+        );
+        assert!(problems.contains(&"ToolStart is no longer used by pulse".to_string()));
+
+        assert!(ClaudeCodeHook::repair_hooks(&mut value, None).unwrap());
+        assert!(hook_problems(&value, None).is_empty());
+        let post_tool = value["hooks"]["PostToolUse"].as_array().unwrap();
+        assert_eq!(post_tool.len(), 2);
+        assert!(entry_contains_command(
+            &post_tool[0],
+            "pulse emit post_tool_use"
+        ));
+        assert_eq!(pulse_commands(&post_tool[1]), Vec::<&str>::new());
+        assert!(entry_contains_command(
+            &post_tool[1],
+            "other-tool do something"
+        ));
+
+        let pre_tool = value["hooks"]["PreToolUse"].as_array().unwrap();
+        assert_eq!(pre_tool.len(), 1);
+        assert_eq!(pre_tool[0]["matcher"], "Bash");
+        assert!(entry_contains_command(
+            &pre_tool[0],
+            "pulse emit pre_tool_use"
+        ));
+        assert!(value["hooks"].get("ToolStart").is_none());
+
+        assert!(!ClaudeCodeHook::repair_hooks(&mut value, None).unwrap());
+    }
+
+    #[test]
+    fn test_status_reports_duplicate_pulse_entries() {
+        let tmp = TempDir::new().unwrap();
+        let hook = make_hook(&tmp).with_backup(false);
+        hook.connect().unwrap();
+        assert!(hook.status().unwrap().message.is_none());
+
+        let mut value = hook.read_settings().unwrap().unwrap();
+        let entry = value["hooks"]["PostToolUse"][0].clone();
+        value["hooks"]["PostToolUse"]
+            .as_array_mut()
+            .unwrap()
+            .push(entry);
+        hook.write_settings(&value).unwrap();
+
+        let message = hook.status().unwrap().message.unwrap();
+        assert!(
+            message.contains("PostToolUse has 2 pulse hooks"),
+            "{message}"
+        );
+        assert!(!hook.connect().unwrap().modified);
+
+        let repair = ConnectOptions {
+            repair: true,
+            ..ConnectOptions::default()
+        };
+        assert!(hook.connect_opts(&repair).unwrap().modified);
+        let status = hook.status().unwrap();
+        assert!(status.connected);
+        assert!(status.message.is_none());
+    }
+
+    fn make_hook(tmp: &TempDir) -> ClaudeCodeHook {
+        let settings_path = tmp.path().join("settings.json");
+        fs::write(&settings_path, "{\n  \"theme\": \"dark\"\n}").unwrap();
+        make_hook_at(&settings_path)
+    }
+
+    #[test]
+    fn test_connect_backs_up_settings() {
+        let tmp = TempDir::new().unwrap();
+        let hook = make_hook(&tmp);
+        let original = fs::read_to_string(&hook.settings_path).unwrap();
+
+        let status = hook.connect().unwrap();
+        assert!(status.modified);
+        let backups = hook.backups().unwrap();
+        assert_eq!(backups.len(), 1);
+        assert_eq!(fs::read_to_string(&backups[0].path).unwrap(), original);
+        assert!(
+            status
+                .message
+                .unwrap()
+                .contains(&backups[0].path.display().to_string())
+        );
+
+        // Nothing changes on a second connect, so no new backup is written.
+        let status = hook.connect().unwrap();
+        assert!(status.message.is_none());
+        assert_eq!(hook.backups().unwrap().len(), 1);
+    }
+
+    #[test]
+    fn test_connect_without_settings_file_skips_backup() {
+        let tmp = TempDir::new().unwrap();
+        fs::create_dir(tmp.path().join(CLAUDE_PROJECT_DIR)).unwrap();
+        let hook = ClaudeCodeHook::for_project(tmp.path(), ProjectSettings::Shared);
+        assert!(!hook.settings_path.exists());
+
+        let status = hook.connect().unwrap();
+        assert!(status.connected);
+        assert!(status.message.is_none());
+        assert!(hook.settings_path.exists());
+        assert!(hook.backups().unwrap().is_empty());
+    }
+
+    #[test]
+    fn test_backup_dir_then_restore_round_trips() {
+        let tmp = TempDir::new().unwrap();
+        let backup_dir = tmp.path().join("backups");
+        let hook = make_hook(&tmp).with_backup_dir(Some(backup_dir.clone()));
+        let original = fs::read_to_string(&hook.settings_path).unwrap();
+
+        hook.connect().unwrap();
+        let connected = fs::read_to_string(&hook.settings_path).unwrap();
+        let backups = hook.backups().unwrap();
+        assert_eq!(backups.len(), 1);
+        assert!(backups[0].path.starts_with(&backup_dir));
+        let name = backups[0].path.file_name().unwrap().to_str().unwrap();
+        assert!(
+            name.ends_with(&format!("{BACKUP_MARKER}{}", backups[0].timestamp)),
+            "{name}"
+        );
+        // Nothing was written next to the settings file.
+        assert_eq!(make_hook_at(&hook.settings_path).backups().unwrap(), []);
+
+        let saved = hook.restore(&backups[0]).unwrap().unwrap();
+        assert_eq!(fs::read_to_string(&hook.settings_path).unwrap(), original);
+        assert_eq!(fs::read_to_string(&saved).unwrap(), connected);
+        // The pre-restore copy sorts after the one it replaced.
+        let backups = hook.backups().unwrap();
+        assert_eq!(backups.len(), 2);
+        assert_eq!(backups[1].path, saved);
+    }
+
+    #[test]
+    fn test_restore_rejects_invalid_json() {
+        let tmp = TempDir::new().unwrap();
+        let hook = make_hook(&tmp);
+        let original = fs::read_to_string(&hook.settings_path).unwrap();
+        let backup = SettingsBackup {
+            timestamp: 1,
+            path: tmp.path().join(format!("settings.json{BACKUP_MARKER}1")),
+        };
+        fs::write(&backup.path, "{ not json").unwrap();
+
+        let err = hook.restore(&backup).unwrap_err();
+        assert!(err.to_string().contains("not valid JSON"), "{err}");
+        assert_eq!(fs::read_to_string(&hook.settings_path).unwrap(), original);
+        assert_eq!(hook.backups().unwrap(), [backup]);
+    }
+
+    fn make_hook_at(settings_path: &Path) -> ClaudeCodeHook {
+        ClaudeCodeHook::with_settings_path(settings_path.to_path_buf())
+    }
+
+    #[test]
+    fn test_dry_run_leaves_settings_untouched() {
+        let tmp = TempDir::new().unwrap();
+        let hook = make_hook(&tmp);
+        let original = fs::read_to_string(&hook.settings_path).unwrap();
+
+        let status = hook
+            .connect_opts(&ConnectOptions {
+                dry_run: true,
+                ..ConnectOptions::default()
+            })
+            .unwrap();
+        assert!(status.modified);
+        assert!(status.connected);
+        let diff = status.message.unwrap();
+        assert!(diff.contains("+ ") && diff.contains("pulse emit stop"));
+        assert_eq!(fs::read_to_string(&hook.settings_path).unwrap(), original);
+        assert!(hook.backups().unwrap().is_empty());
+    }
+
+    #[test]
+    fn test_no_backup_skips_copy() {
+        let tmp = TempDir::new().unwrap();
+        let hook = make_hook(&tmp).with_backup(false);
+        let status = hook.connect().unwrap();
+        assert!(status.modified);
+        assert!(status.message.is_none());
+        assert!(hook.backups().unwrap().is_empty());
+    }
+
+    #[test]
+    fn test_prune_keeps_newest_backups() {
+        let tmp = TempDir::new().unwrap();
+        let hook = make_hook(&tmp);
+        for timestamp in 1..=MAX_SETTINGS_BACKUPS as u64 + 3 {
+            let name = format!("settings.json{BACKUP_MARKER}{timestamp}");
+            fs::write(tmp.path().join(name), "{}").unwrap();
+        }
+        hook.prune_backups().unwrap();
+
+        let timestamps: Vec<u64> = hook
+            .backups()
+            .unwrap()
+            .iter()
+            .map(|backup| backup.timestamp)
+            .collect();
+        assert_eq!(timestamps, (4..=8).collect::<Vec<u64>>());
+    }
+
+    #[test]
+    fn test_project_hook_needs_claude_dir() {
+        let tmp = TempDir::new().unwrap();
+        let hook = ClaudeCodeHook::for_project(tmp.path(), ProjectSettings::Shared);
+        assert!(!hook.status().unwrap().detected);
+        assert!(!hook.connect().unwrap().detected);
+        assert!(!tmp.path().join(".claude").exists());
+    }
+
+    #[test]
+    fn test_project_hooks_target_their_own_files() {
+        let tmp = TempDir::new().unwrap();
+        fs::create_dir_all(tmp.path().join(".claude")).unwrap();
+        let shared = ClaudeCodeHook::for_project(tmp.path(), ProjectSettings::Shared);
+        let local = ClaudeCodeHook::for_project(tmp.path(), ProjectSettings::Local);
+        assert_eq!(shared.tool_name(), "Claude Code (project)");
+
+        // The file does not exist yet, so there is nothing to back up.
+        let status = shared.connect().unwrap();
+        assert!(status.connected);
+        assert!(status.message.is_none());
+        assert_eq!(
+            status.path.unwrap(),
+            tmp.path().join(".claude/settings.json")
+        );
+        assert!(!local.settings_exist());
+        assert!(!local.status().unwrap().connected);
+
+        local.connect().unwrap();
+        assert!(local.status().unwrap().connected);
+        let status = shared.disconnect().unwrap();
+        assert!(status.modified);
+        assert!(!shared.status().unwrap().connected);
+        assert!(local.status().unwrap().connected);
+    }
 }
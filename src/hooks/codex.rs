@@ -0,0 +1,478 @@
+use std::{env, fs, io::ErrorKind, path::PathBuf};
+
+use dirs::home_dir;
+use toml::{Table, Value};
+
+use crate::error::{PulseError, Result};
+
+use super::{ConnectOptions, HookStatus, ManagedHook, ToolHook, is_pulse_program, line_diff};
+
+// Codex CLI has no hook or plugin directory. Its one extension point is the
+// top-level `notify` key in `~/.codex/config.toml` (or `$CODEX_HOME`): a
+// command Codex runs after every agent turn, with a JSON description of the
+// turn appended as the last argument:
+//
+//   {"type": "agent-turn-complete", "thread-id": "...", "turn-id": "...",
+//    "cwd": "...", "input-messages": [...], "last-assistant-message": "..."}
+//
+// So this is closest to `CursorHook`, editing a key in the agent's own config
+// file, except that the file is TOML the user writes by hand. Rewriting it
+// through the `toml` crate would drop their comments and layout, so only the
+// `notify` line is added or removed, and the result is parsed again to make
+// sure nothing else changed. Each turn becomes a `stop` span; `span::extract`
+// reads `thread-id` as the session id. Codex runs a single notify command,
+// so one the user set up themselves is left alone and reported instead.
+const CODEX_CONFIG_DIR: &str = ".codex";
+const CODEX_CONFIG_FILE: &str = "config.toml";
+const CODEX_TOOL_NAME: &str = "Codex CLI";
+const NOTIFY_KEY: &str = "notify";
+/// The `notify` arguments after the program, which is `pulse` or `pulse_bin`.
+const NOTIFY_ARGS: &[&str] = &["emit", "stop", "--source", "codex", "--payload"];
+
+#[derive(Debug, Clone)]
+pub struct CodexHook {
+    config_dir: PathBuf,
+    config_path: PathBuf,
+    pulse_bin: Option<String>,
+}
+
+/// What `notify` in the Codex config is set to.
+#[derive(Debug, PartialEq, Eq)]
+enum Notify {
+    Unset,
+    /// A `pulse emit` command, and whether it is the current one.
+    Pulse {
+        current: bool,
+    },
+    /// Someone else's command, which pulse does not replace.
+    Other(String),
+}
+
+impl CodexHook {
+    pub fn new() -> Result<Self> {
+        let config_dir = match env::var_os("CODEX_HOME").filter(|dir| !dir.is_empty()) {
+            Some(dir) => PathBuf::from(dir),
+            None => home_dir()
+                .ok_or(PulseError::HomeDirNotFound)?
+                .join(CODEX_CONFIG_DIR),
+        };
+        Ok(Self::with_config_dir(config_dir))
+    }
+
+    /// Hook for a Codex config directory (normally `$CODEX_HOME` or `~/.codex`).
+    pub fn with_config_dir(config_dir: PathBuf) -> Self {
+        let config_path = config_dir.join(CODEX_CONFIG_FILE);
+        Self {
+            config_dir,
+            config_path,
+            pulse_bin: None,
+        }
+    }
+
+    /// Runs an absolute `pulse` path from `notify` instead of relying on
+    /// `PATH`, like `ClaudeCodeHook::with_pulse_bin`.
+    pub fn with_pulse_bin(mut self, pulse_bin: Option<String>) -> Self {
+        self.pulse_bin = pulse_bin.filter(|bin| !bin.trim().is_empty());
+        self
+    }
+
+    /// The `notify` command pulse writes.
+    fn notify_command(&self) -> Vec<String> {
+        let program = self.pulse_bin.as_deref().unwrap_or("pulse");
+        std::iter::once(program)
+            .chain(NOTIFY_ARGS.iter().copied())
+            .map(str::to_string)
+            .collect()
+    }
+
+    fn is_detected(&self) -> bool {
+        self.config_dir.exists()
+    }
+
+    fn read_config(&self) -> Result<String> {
+        match fs::read_to_string(&self.config_path) {
+            Ok(contents) => Ok(contents),
+            Err(err) if err.kind() == ErrorKind::NotFound => Ok(String::new()),
+            Err(err) => Err(err.into()),
+        }
+    }
+
+    fn build_status(&self, notify: &Notify, modified: bool, message: Option<String>) -> HookStatus {
+        let installed = matches!(notify, Notify::Pulse { .. });
+        let message = message.or_else(|| match notify {
+            Notify::Pulse { current: false } => {
+                Some("Notify command installed but outdated".to_string())
+            }
+            Notify::Other(command) => Some(format!(
+                "`notify` is already set to {command}; Codex runs only one notify command, so remove it and run `pulse connect` again"
+            )),
+            _ => None,
+        });
+        HookStatus {
+            tool: self.tool_name(),
+            detected: true,
+            connected: installed,
+            modified,
+            path: Some(self.config_path.clone()),
+            message,
+            installed_hooks: usize::from(installed),
+            total_hooks: 1,
+            installed_hook_names: if installed {
+                vec![NOTIFY_KEY.to_string()]
+            } else {
+                Vec::new()
+            },
+        }
+    }
+
+    /// Writes `updated` in place of `original`, or only describes the change
+    /// under `--dry-run`.
+    fn apply(
+        &self,
+        original: &str,
+        updated: &str,
+        options: &ConnectOptions,
+    ) -> Result<Option<String>> {
+        if options.dry_run {
+            return Ok(Some(line_diff(original, updated)));
+        }
+        fs::create_dir_all(&self.config_dir)?;
+        fs::write(&self.config_path, updated)?;
+        Ok(None)
+    }
+}
+
+impl ToolHook for CodexHook {
+    fn id(&self) -> &'static str {
+        "codex"
+    }
+
+    fn tool_name(&self) -> &'static str {
+        CODEX_TOOL_NAME
+    }
+
+    fn managed_hooks(&self) -> Vec<ManagedHook> {
+        vec![ManagedHook::event(
+            NOTIFY_KEY,
+            self.notify_command().join(" "),
+            &self.config_path,
+        )]
+    }
+
+    fn status(&self) -> Result<HookStatus> {
+        if !self.is_detected() {
+            return Ok(HookStatus::not_detected(
+                self.tool_name(),
+                self.config_dir.clone(),
+            ));
+        }
+        let notify = notify_state(&self.read_config()?, &self.notify_command())?;
+        Ok(self.build_status(&notify, false, None))
+    }
+
+    fn connect_opts(&self, options: &ConnectOptions) -> Result<HookStatus> {
+        if !self.is_detected() {
+            return Ok(HookStatus::not_detected(
+                self.tool_name(),
+                self.config_dir.clone(),
+            ));
+        }
+        let original = self.read_config()?;
+        let command = self.notify_command();
+        let notify = notify_state(&original, &command)?;
+        if matches!(notify, Notify::Pulse { current: true } | Notify::Other(_)) {
+            return Ok(self.build_status(&notify, false, None));
+        }
+
+        let updated = match notify {
+            Notify::Unset => with_notify_line(&original, &command),
+            _ => with_notify_line(&remove_notify_line(&original)?, &command),
+        };
+        let message = self.apply(&original, &updated, options)?;
+        Ok(self.build_status(&Notify::Pulse { current: true }, true, message))
+    }
+
+    fn disconnect_opts(&self, options: &ConnectOptions) -> Result<HookStatus> {
+        if !self.is_detected() {
+            return Ok(HookStatus::not_detected(
+                self.tool_name(),
+                self.config_dir.clone(),
+            ));
+        }
+        let original = self.read_config()?;
+        let notify = notify_state(&original, &self.notify_command())?;
+        if !matches!(notify, Notify::Pulse { .. }) {
+            return Ok(self.build_status(&notify, false, None));
+        }
+
+        let updated = remove_notify_line(&original)?;
+        let message = self.apply(&original, &updated, options)?;
+        Ok(self.build_status(&Notify::Unset, true, message))
+    }
+}
+
+/// What `notify` is set to, where `command` is the one pulse would write.
+/// A `pulse emit` under any other path is pulse's, but outdated.
+fn notify_state(contents: &str, command: &[String]) -> Result<Notify> {
+    let table: Table = toml::from_str(contents)?;
+    let Some(value) = table.get(NOTIFY_KEY) else {
+        return Ok(Notify::Unset);
+    };
+    let args: Vec<&str> = value
+        .as_array()
+        .map(|items| items.iter().filter_map(Value::as_str).collect())
+        .unwrap_or_default();
+    let is_pulse = args
+        .first()
+        .is_some_and(|program| is_pulse_program(program))
+        && args.get(1) == Some(&"emit");
+    Ok(if is_pulse {
+        Notify::Pulse {
+            current: args == command,
+        }
+    } else {
+        Notify::Other(value.to_string())
+    })
+}
+
+fn notify_line(command: &[String]) -> String {
+    let args: Vec<String> = command
+        .iter()
+        .map(|arg| Value::String(arg.clone()).to_string())
+        .collect();
+    format!("{NOTIFY_KEY} = [{}]", args.join(", "))
+}
+
+/// `contents` with pulse's `notify` line first. A top-level key has to come
+/// before the first `[table]`, so the top of the file is the one place that
+/// always works.
+fn with_notify_line(contents: &str, command: &[String]) -> String {
+    format!("{}\n{contents}", notify_line(command))
+}
+
+/// `contents` without the top-level `notify` line. Fails, rather than
+/// guessing, when `notify` spans several lines or removing the line would
+/// change anything else in the file.
+fn remove_notify_line(contents: &str) -> Result<String> {
+    let mut removed = false;
+    let mut in_table = false;
+    let mut kept = Vec::new();
+    for line in contents.split_inclusive('\n') {
+        let trimmed = line.trim_start();
+        in_table |= trimmed.starts_with('[');
+        let is_notify = !in_table
+            && trimmed
+                .strip_prefix(NOTIFY_KEY)
+                .is_some_and(|rest| rest.trim_start().starts_with('='));
+        if is_notify && !removed {
+            removed = true;
+        } else {
+            kept.push(line);
+        }
+    }
+    let updated = kept.concat();
+
+    let mut expected: Table = toml::from_str(contents)?;
+    expected.remove(NOTIFY_KEY);
+    if !removed || toml::from_str::<Table>(&updated).ok() != Some(expected) {
+        return Err(PulseError::message(
+            "could not update `notify` in the Codex config; edit it by hand",
+        ));
+    }
+    Ok(updated)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::TempDir;
+
+    const USER_CONFIG: &str =
+        "# my settings\nmodel = \"o4-mini\"\n\n[sandbox]\nmode = \"workspace-write\"\n";
+
+    fn make_hook(tmp: &TempDir) -> CodexHook {
+        CodexHook::with_config_dir(tmp.path().join(CODEX_CONFIG_DIR))
+    }
+
+    fn detected_hook(tmp: &TempDir) -> CodexHook {
+        let hook = make_hook(tmp);
+        fs::create_dir_all(&hook.config_dir).unwrap();
+        fs::write(&hook.config_path, USER_CONFIG).unwrap();
+        hook
+    }
+
+    #[test]
+    fn test_not_detected_when_config_dir_missing() {
+        let tmp = TempDir::new().unwrap();
+        let hook = make_hook(&tmp);
+        let status = hook.status().unwrap();
+        assert!(!status.detected);
+        assert!(!status.connected);
+    }
+
+    #[test]
+    fn test_detected_but_not_connected() {
+        let tmp = TempDir::new().unwrap();
+        let hook = detected_hook(&tmp);
+
+        let status = hook.status().unwrap();
+        assert!(status.detected);
+        assert!(!status.connected);
+        assert_eq!(status.installed_hooks, 0);
+    }
+
+    #[test]
+    fn test_connect_adds_notify_and_keeps_the_rest() {
+        let tmp = TempDir::new().unwrap();
+        let hook = detected_hook(&tmp);
+
+        let status = hook.connect().unwrap();
+        assert!(status.connected);
+        assert!(status.modified);
+        assert_eq!(status.installed_hooks, 1);
+        let contents = fs::read_to_string(&hook.config_path).unwrap();
+        let command = hook.notify_command();
+        assert_eq!(
+            contents,
+            format!("{}\n{USER_CONFIG}", notify_line(&command))
+        );
+        assert_eq!(
+            notify_state(&contents, &command).unwrap(),
+            Notify::Pulse { current: true }
+        );
+    }
+
+    #[test]
+    fn test_connect_creates_missing_config_file() {
+        let tmp = TempDir::new().unwrap();
+        let hook = make_hook(&tmp);
+        fs::create_dir_all(&hook.config_dir).unwrap();
+
+        assert!(hook.connect().unwrap().connected);
+        assert!(hook.status().unwrap().connected);
+    }
+
+    #[test]
+    fn test_connect_is_idempotent() {
+        let tmp = TempDir::new().unwrap();
+        let hook = detected_hook(&tmp);
+
+        hook.connect().unwrap();
+        let status = hook.connect().unwrap();
+        assert!(!status.modified, "second connect should not modify");
+        assert!(status.connected);
+    }
+
+    #[test]
+    fn test_connect_updates_outdated_command() {
+        let tmp = TempDir::new().unwrap();
+        let hook = detected_hook(&tmp);
+        fs::write(
+            &hook.config_path,
+            format!("notify = [\"/usr/local/bin/pulse\", \"emit\", \"stop\"]\n{USER_CONFIG}"),
+        )
+        .unwrap();
+        assert!(hook.status().unwrap().message.is_some());
+
+        let status = hook.connect().unwrap();
+        assert!(status.modified);
+        let contents = fs::read_to_string(&hook.config_path).unwrap();
+        assert_eq!(
+            contents,
+            format!("{}\n{USER_CONFIG}", notify_line(&hook.notify_command()))
+        );
+    }
+
+    #[test]
+    fn test_pulse_bin_notify_is_installed_detected_and_removed() {
+        let tmp = TempDir::new().unwrap();
+        let hook = detected_hook(&tmp).with_pulse_bin(Some("/opt/pulse bin/pulse".to_string()));
+
+        assert!(hook.connect().unwrap().modified);
+        let contents = fs::read_to_string(&hook.config_path).unwrap();
+        assert!(
+            contents.starts_with("notify = [\"/opt/pulse bin/pulse\", \"emit\", \"stop\""),
+            "{contents}"
+        );
+        let status = hook.status().unwrap();
+        assert!(status.connected);
+        assert_eq!(status.message, None);
+        assert!(!hook.connect().unwrap().modified, "the pin is kept");
+
+        // Without the pin, the same entry is pulse's but outdated.
+        let bare = make_hook(&tmp);
+        assert!(bare.status().unwrap().message.is_some());
+
+        assert!(hook.disconnect().unwrap().modified);
+        assert_eq!(fs::read_to_string(&hook.config_path).unwrap(), USER_CONFIG);
+    }
+
+    #[test]
+    fn test_connect_leaves_other_notify_alone() {
+        let tmp = TempDir::new().unwrap();
+        let hook = detected_hook(&tmp);
+        let config = format!("notify = [\"notify-send\", \"Codex\"]\n{USER_CONFIG}");
+        fs::write(&hook.config_path, &config).unwrap();
+
+        let status = hook.connect().unwrap();
+        assert!(!status.modified);
+        assert!(!status.connected);
+        assert!(status.message.unwrap().contains("notify-send"));
+        assert_eq!(fs::read_to_string(&hook.config_path).unwrap(), config);
+    }
+
+    #[test]
+    fn test_disconnect_restores_config() {
+        let tmp = TempDir::new().unwrap();
+        let hook = detected_hook(&tmp);
+
+        hook.connect().unwrap();
+        let status = hook.disconnect().unwrap();
+        assert!(status.modified);
+        assert!(!status.connected);
+        assert_eq!(fs::read_to_string(&hook.config_path).unwrap(), USER_CONFIG);
+    }
+
+    #[test]
+    fn test_disconnect_noop_when_not_installed() {
+        let tmp = TempDir::new().unwrap();
+        let hook = detected_hook(&tmp);
+
+        let status = hook.disconnect().unwrap();
+        assert!(!status.modified);
+        assert!(!status.connected);
+    }
+
+    #[test]
+    fn test_dry_run_writes_nothing() {
+        let tmp = TempDir::new().unwrap();
+        let hook = detected_hook(&tmp);
+        let options = ConnectOptions {
+            dry_run: true,
+            ..ConnectOptions::default()
+        };
+
+        let status = hook.connect_opts(&options).unwrap();
+        assert!(status.modified);
+        assert!(
+            status
+                .message
+                .unwrap()
+                .contains(&notify_line(&hook.notify_command()))
+        );
+        assert_eq!(fs::read_to_string(&hook.config_path).unwrap(), USER_CONFIG);
+    }
+
+    #[test]
+    fn test_multiline_notify_is_not_guessed_at() {
+        let config = "notify = [\n  \"pulse\",\n  \"emit\",\n]\n";
+        let command = make_hook(&TempDir::new().unwrap()).notify_command();
+        assert_eq!(
+            notify_state(config, &command).unwrap(),
+            Notify::Pulse { current: false }
+        );
+        assert!(remove_notify_line(config).is_err());
+        // A `notify` key inside a table is not the top-level one.
+        assert!(remove_notify_line("[tui]\nnotify = true\n").is_err());
+    }
+}
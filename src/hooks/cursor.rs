@@ -0,0 +1,444 @@
+use std::{fs, io::ErrorKind, path::PathBuf};
+
+use dirs::home_dir;
+use serde_json::{Map, Value, json};
+
+use crate::error::{PulseError, Result};
+
+use super::{
+    ConnectOptions, HookStatus, ManagedHook, ToolHook, hook_command, json_diff, same_command,
+};
+
+// Cursor reads command hooks from `~/.cursor/hooks.json`:
+//
+//   { "version": 1, "hooks": { "<event>": [{ "command": "..." }] } }
+//
+// That is the same "JSON file with per-event command arrays" model as Claude
+// Code's settings.json, minus the matcher wrapper, so this hook follows
+// `ClaudeCodeHook` rather than the plugin-file installers. Cursor's events map
+// onto pulse event types as listed below; it names the session
+// `conversation_id`, which `span::extract` accepts as a fallback.
+const CURSOR_CONFIG_DIR: &str = ".cursor";
+const CURSOR_HOOKS_FILE: &str = "hooks.json";
+const CURSOR_TOOL_NAME: &str = "Cursor";
+const HOOKS_VERSION: u64 = 1;
+pub const CURSOR_HOOK_DEFINITIONS: &[(&str, &str)] = &[
+    (
+        "beforeSubmitPrompt",
+        "pulse emit user_prompt_submit --source cursor",
+    ),
+    (
+        "beforeShellExecution",
+        "pulse emit pre_tool_use --source cursor",
+    ),
+    (
+        "afterShellExecution",
+        "pulse emit post_tool_use --source cursor",
+    ),
+    (
+        "beforeMCPExecution",
+        "pulse emit pre_tool_use --source cursor",
+    ),
+    (
+        "afterMCPExecution",
+        "pulse emit post_tool_use --source cursor",
+    ),
+    ("afterFileEdit", "pulse emit post_tool_use --source cursor"),
+    (
+        "afterAgentResponse",
+        "pulse emit assistant_message --source cursor",
+    ),
+    ("stop", "pulse emit stop --source cursor"),
+];
+
+#[derive(Debug, Clone)]
+pub struct CursorHook {
+    config_dir: PathBuf,
+    hooks_path: PathBuf,
+    pulse_bin: Option<String>,
+}
+
+impl CursorHook {
+    pub fn new() -> Result<Self> {
+        let home = home_dir().ok_or(PulseError::HomeDirNotFound)?;
+        Ok(Self::with_config_dir(home.join(CURSOR_CONFIG_DIR)))
+    }
+
+    /// Hook for a Cursor config directory (normally `~/.cursor`).
+    pub fn with_config_dir(config_dir: PathBuf) -> Self {
+        let hooks_path = config_dir.join(CURSOR_HOOKS_FILE);
+        Self {
+            config_dir,
+            hooks_path,
+            pulse_bin: None,
+        }
+    }
+
+    /// Writes hook commands with an absolute `pulse` path instead of relying
+    /// on `PATH`, like `ClaudeCodeHook::with_pulse_bin`.
+    pub fn with_pulse_bin(mut self, pulse_bin: Option<String>) -> Self {
+        self.pulse_bin = pulse_bin.filter(|bin| !bin.trim().is_empty());
+        self
+    }
+
+    fn is_detected(&self) -> bool {
+        self.config_dir.exists()
+    }
+
+    fn read_hooks_file(&self) -> Result<Value> {
+        match fs::read_to_string(&self.hooks_path) {
+            Ok(contents) => Ok(serde_json::from_str(&contents)?),
+            Err(err) if err.kind() == ErrorKind::NotFound => {
+                Ok(json!({ "version": HOOKS_VERSION }))
+            }
+            Err(err) => Err(err.into()),
+        }
+    }
+
+    fn write_hooks_file(&self, value: &Value) -> Result<()> {
+        fs::create_dir_all(&self.config_dir)?;
+        let body = serde_json::to_string_pretty(value)?;
+        fs::write(&self.hooks_path, body)?;
+        Ok(())
+    }
+
+    /// Adds the pulse commands that are missing and rewrites the ones
+    /// running `pulse` under another path.
+    fn insert_hooks(value: &mut Value, pulse_bin: Option<&str>) -> Result<bool> {
+        let obj = value
+            .as_object_mut()
+            .ok_or_else(|| PulseError::message("Cursor hooks.json must contain a JSON object"))?;
+        obj.entry("version").or_insert_with(|| json!(HOOKS_VERSION));
+        let hooks_map = obj
+            .entry("hooks")
+            .or_insert_with(|| Value::Object(Map::new()))
+            .as_object_mut()
+            .ok_or_else(|| PulseError::message("`hooks` field must be a JSON object"))?;
+
+        let mut changed = false;
+        for (event, command) in CURSOR_HOOK_DEFINITIONS {
+            let entries = hooks_map
+                .entry((*event).to_string())
+                .or_insert_with(|| Value::Array(Vec::new()))
+                .as_array_mut()
+                .ok_or_else(|| PulseError::message("Hook event entries must be arrays"))?;
+            let desired = hook_command(command, pulse_bin);
+            if entries
+                .iter()
+                .any(|entry| entry_command(entry) == Some(desired.as_str()))
+            {
+                continue;
+            }
+            match entries.iter_mut().find(|entry| is_command(entry, command)) {
+                Some(entry) => entry["command"] = json!(desired),
+                None => entries.push(json!({ "command": desired })),
+            }
+            changed = true;
+        }
+        Ok(changed)
+    }
+
+    fn remove_hooks(value: &mut Value) -> Result<bool> {
+        let Some(hooks_map) = value
+            .as_object_mut()
+            .and_then(|obj| obj.get_mut("hooks"))
+            .and_then(|hooks| hooks.as_object_mut())
+        else {
+            return Ok(false);
+        };
+
+        let mut changed = false;
+        for (event, command) in CURSOR_HOOK_DEFINITIONS {
+            let Some(entries) = hooks_map.get_mut(*event) else {
+                continue;
+            };
+            let entries = entries
+                .as_array_mut()
+                .ok_or_else(|| PulseError::message("Hook event entries must be arrays"))?;
+            let before = entries.len();
+            entries.retain(|entry| !is_command(entry, command));
+            if entries.len() != before {
+                changed = true;
+            }
+            if entries.is_empty() {
+                hooks_map.remove(*event);
+            }
+        }
+        Ok(changed)
+    }
+
+    fn build_status(&self, value: &Value, modified: bool, message: Option<String>) -> HookStatus {
+        let (installed, total, names) = installed_hook_counts(value);
+        HookStatus {
+            tool: self.tool_name(),
+            detected: true,
+            connected: installed == total,
+            modified,
+            path: Some(self.hooks_path.clone()),
+            message,
+            installed_hooks: installed,
+            total_hooks: total,
+            installed_hook_names: names,
+        }
+    }
+}
+
+impl ToolHook for CursorHook {
+    fn id(&self) -> &'static str {
+        "cursor"
+    }
+
+    fn tool_name(&self) -> &'static str {
+        CURSOR_TOOL_NAME
+    }
+
+    fn managed_hooks(&self) -> Vec<ManagedHook> {
+        CURSOR_HOOK_DEFINITIONS
+            .iter()
+            .map(|(event, command)| {
+                ManagedHook::event(
+                    event,
+                    hook_command(command, self.pulse_bin.as_deref()),
+                    &self.hooks_path,
+                )
+            })
+            .collect()
+    }
+
+    fn status(&self) -> Result<HookStatus> {
+        if !self.is_detected() {
+            return Ok(HookStatus::not_detected(
+                self.tool_name(),
+                self.config_dir.clone(),
+            ));
+        }
+        let value = self.read_hooks_file()?;
+        Ok(self.build_status(&value, false, None))
+    }
+
+    fn connect_opts(&self, options: &ConnectOptions) -> Result<HookStatus> {
+        if !self.is_detected() {
+            return Ok(HookStatus::not_detected(
+                self.tool_name(),
+                self.config_dir.clone(),
+            ));
+        }
+        let mut value = self.read_hooks_file()?;
+        let original = value.clone();
+        let changed = Self::insert_hooks(&mut value, self.pulse_bin.as_deref())?;
+        let mut message = None;
+        if changed && options.dry_run {
+            message = Some(json_diff(&original, &value)?);
+        } else if changed {
+            self.write_hooks_file(&value)?;
+        }
+        Ok(self.build_status(&value, changed, message))
+    }
+
+    fn disconnect_opts(&self, options: &ConnectOptions) -> Result<HookStatus> {
+        if !self.is_detected() {
+            return Ok(HookStatus::not_detected(
+                self.tool_name(),
+                self.config_dir.clone(),
+            ));
+        }
+        if !self.hooks_path.exists() {
+            let value = json!({});
+            return Ok(self.build_status(&value, false, None));
+        }
+        let mut value = self.read_hooks_file()?;
+        let original = value.clone();
+        let changed = Self::remove_hooks(&mut value)?;
+        let mut message = None;
+        if changed && options.dry_run {
+            message = Some(json_diff(&original, &value)?);
+        } else if changed {
+            self.write_hooks_file(&value)?;
+        }
+        Ok(self.build_status(&value, changed, message))
+    }
+}
+
+fn installed_hook_counts(value: &Value) -> (usize, usize, Vec<String>) {
+    let total = CURSOR_HOOK_DEFINITIONS.len();
+    let hooks_map = value
+        .as_object()
+        .and_then(|obj| obj.get("hooks"))
+        .and_then(|hooks| hooks.as_object());
+    let names: Vec<String> = CURSOR_HOOK_DEFINITIONS
+        .iter()
+        .filter(|(event, command)| {
+            hooks_map
+                .and_then(|map| map.get(*event))
+                .and_then(|entries| entries.as_array())
+                .map(|entries| entries.iter().any(|entry| is_command(entry, command)))
+                .unwrap_or(false)
+        })
+        .map(|(event, _)| (*event).to_string())
+        .collect();
+    (names.len(), total, names)
+}
+
+fn entry_command(entry: &Value) -> Option<&str> {
+    entry.get("command").and_then(Value::as_str)
+}
+
+/// Whether `entry` runs `command`, with `pulse` under any path.
+fn is_command(entry: &Value, command: &str) -> bool {
+    entry_command(entry).is_some_and(|value| same_command(value, command))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::TempDir;
+
+    fn make_hook(tmp: &TempDir) -> CursorHook {
+        CursorHook::with_config_dir(tmp.path().join(CURSOR_CONFIG_DIR))
+    }
+
+    #[test]
+    fn test_not_detected_when_config_dir_missing() {
+        let tmp = TempDir::new().unwrap();
+        let hook = make_hook(&tmp);
+        let status = hook.status().unwrap();
+        assert!(!status.detected);
+        assert!(!status.connected);
+    }
+
+    #[test]
+    fn test_detected_but_not_connected() {
+        let tmp = TempDir::new().unwrap();
+        let hook = make_hook(&tmp);
+        fs::create_dir_all(&hook.config_dir).unwrap();
+
+        let status = hook.status().unwrap();
+        assert!(status.detected);
+        assert!(!status.connected);
+        assert_eq!(status.installed_hooks, 0);
+        assert_eq!(status.total_hooks, CURSOR_HOOK_DEFINITIONS.len());
+    }
+
+    #[test]
+    fn test_connect_installs_hooks() {
+        let tmp = TempDir::new().unwrap();
+        let hook = make_hook(&tmp);
+        fs::create_dir_all(&hook.config_dir).unwrap();
+
+        let status = hook.connect().unwrap();
+        assert!(status.connected);
+        assert!(status.modified);
+        assert_eq!(status.installed_hooks, CURSOR_HOOK_DEFINITIONS.len());
+
+        let value: Value =
+            serde_json::from_str(&fs::read_to_string(&hook.hooks_path).unwrap()).unwrap();
+        assert_eq!(value["version"], 1);
+        assert_eq!(
+            value["hooks"]["stop"][0]["command"],
+            "pulse emit stop --source cursor"
+        );
+    }
+
+    #[test]
+    fn test_pulse_bin_hooks_are_installed_detected_and_removed() {
+        let tmp = TempDir::new().unwrap();
+        let bare = make_hook(&tmp);
+        fs::create_dir_all(&bare.config_dir).unwrap();
+        bare.connect().unwrap();
+
+        // Pinning rewrites the bare commands rather than adding more.
+        let hook = make_hook(&tmp).with_pulse_bin(Some("/opt/pulse/bin/pulse".to_string()));
+        assert!(hook.connect().unwrap().modified);
+        let value: Value =
+            serde_json::from_str(&fs::read_to_string(&hook.hooks_path).unwrap()).unwrap();
+        let stop = value["hooks"]["stop"].as_array().unwrap();
+        assert_eq!(stop.len(), 1);
+        assert_eq!(
+            stop[0]["command"],
+            "/opt/pulse/bin/pulse emit stop --source cursor"
+        );
+        assert!(!hook.connect().unwrap().modified);
+        assert!(hook.status().unwrap().connected);
+        assert!(bare.status().unwrap().connected, "any path counts as pulse");
+
+        let status = bare.disconnect().unwrap();
+        assert!(status.modified);
+        assert_eq!(status.installed_hooks, 0);
+    }
+
+    #[test]
+    fn test_connect_is_idempotent() {
+        let tmp = TempDir::new().unwrap();
+        let hook = make_hook(&tmp);
+        fs::create_dir_all(&hook.config_dir).unwrap();
+
+        hook.connect().unwrap();
+        let status = hook.connect().unwrap();
+        assert!(!status.modified, "second connect should not modify");
+        assert!(status.connected);
+    }
+
+    #[test]
+    fn test_disconnect_removes_hooks() {
+        let tmp = TempDir::new().unwrap();
+        let hook = make_hook(&tmp);
+        fs::create_dir_all(&hook.config_dir).unwrap();
+
+        hook.connect().unwrap();
+        let status = hook.disconnect().unwrap();
+        assert!(status.modified);
+        assert!(!status.connected);
+        assert_eq!(status.installed_hooks, 0);
+    }
+
+    #[test]
+    fn test_disconnect_noop_when_not_installed() {
+        let tmp = TempDir::new().unwrap();
+        let hook = make_hook(&tmp);
+        fs::create_dir_all(&hook.config_dir).unwrap();
+
+        let status = hook.disconnect().unwrap();
+        assert!(!status.modified);
+        assert!(!status.connected);
+        assert!(!hook.hooks_path.exists());
+    }
+
+    #[test]
+    fn test_dry_run_connect_writes_nothing() {
+        let tmp = TempDir::new().unwrap();
+        let hook = make_hook(&tmp);
+        fs::create_dir_all(&hook.config_dir).unwrap();
+
+        let status = hook
+            .connect_opts(&ConnectOptions {
+                dry_run: true,
+                ..ConnectOptions::default()
+            })
+            .unwrap();
+        assert!(status.modified);
+        assert!(status.connected);
+        assert!(status.message.unwrap().contains("+ "));
+        assert!(!hook.hooks_path.exists());
+    }
+
+    #[test]
+    fn test_disconnect_keeps_foreign_hooks() {
+        let tmp = TempDir::new().unwrap();
+        let hook = make_hook(&tmp);
+        fs::create_dir_all(&hook.config_dir).unwrap();
+        fs::write(
+            &hook.hooks_path,
+            r#"{"version": 1, "hooks": {"stop": [{"command": "./audit.sh"}]}}"#,
+        )
+        .unwrap();
+
+        hook.connect().unwrap();
+        hook.disconnect().unwrap();
+
+        let value: Value =
+            serde_json::from_str(&fs::read_to_string(&hook.hooks_path).unwrap()).unwrap();
+        let stop = value["hooks"]["stop"].as_array().unwrap();
+        assert_eq!(stop.len(), 1);
+        assert_eq!(stop[0]["command"], "./audit.sh");
+    }
+}
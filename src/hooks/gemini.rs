@@ -0,0 +1,353 @@
+use std::{borrow::Cow, fs, path::PathBuf};
+
+use dirs::home_dir;
+
+use crate::error::{PulseError, Result};
+
+use super::{
+    ConnectOptions, HookStatus, ManagedHook, ToolHook, content_hash, hook_command, installed_hash,
+    outdated_message, would_write,
+};
+
+// Gemini CLI loads extensions from `~/.gemini/extensions/<name>/`, and an
+// extension can ship command hooks in `hooks/hooks.json`. Installing a whole
+// extension directory keeps the user's settings.json untouched, the same way
+// the OpenCode and OpenClaw installers drop in their own files. The bundled
+// hooks map SessionStart/SessionEnd, BeforeAgent (prompt), AfterAgent (stop),
+// BeforeTool/AfterTool and Notification onto `pulse emit ... --source gemini`.
+const GEMINI_CONFIG_DIR: &str = ".gemini";
+const GEMINI_EXTENSION_DIR: &str = "pulse";
+const GEMINI_TOOL_NAME: &str = "Gemini CLI";
+
+const MANIFEST_SOURCE: &str = include_str!("../../plugins/gemini/gemini-extension.json");
+const HOOKS_JSON_SOURCE: &str = include_str!("../../plugins/gemini/hooks/hooks.json");
+
+#[derive(Debug, Clone)]
+pub struct GeminiHook {
+    config_dir: PathBuf,
+    extension_dir: PathBuf,
+    manifest_path: PathBuf,
+    hooks_json_path: PathBuf,
+    pulse_bin: Option<String>,
+}
+
+impl GeminiHook {
+    pub fn new() -> Result<Self> {
+        let home = home_dir().ok_or(PulseError::HomeDirNotFound)?;
+        Ok(Self::with_config_dir(home.join(GEMINI_CONFIG_DIR)))
+    }
+
+    /// Hook for a Gemini CLI config directory (normally `~/.gemini`).
+    pub fn with_config_dir(config_dir: PathBuf) -> Self {
+        let extension_dir = config_dir.join("extensions").join(GEMINI_EXTENSION_DIR);
+        let manifest_path = extension_dir.join("gemini-extension.json");
+        let hooks_json_path = extension_dir.join("hooks").join("hooks.json");
+        Self {
+            config_dir,
+            extension_dir,
+            manifest_path,
+            hooks_json_path,
+            pulse_bin: None,
+        }
+    }
+
+    /// Writes hook commands with an absolute `pulse` path instead of relying
+    /// on `PATH`, like `ClaudeCodeHook::with_pulse_bin`.
+    pub fn with_pulse_bin(mut self, pulse_bin: Option<String>) -> Self {
+        self.pulse_bin = pulse_bin.filter(|bin| !bin.trim().is_empty());
+        self
+    }
+
+    /// `hooks/hooks.json` as `connect` writes it.
+    fn hooks_json(&self) -> Cow<'static, str> {
+        let Some(bin) = self.pulse_bin.as_deref() else {
+            return Cow::Borrowed(HOOKS_JSON_SOURCE);
+        };
+        let pinned = serde_json::Value::from(hook_command("pulse emit ", Some(bin))).to_string();
+        // Without its closing quote, since each command goes on after `emit `.
+        let prefix = &pinned[..pinned.len() - 1];
+        Cow::Owned(HOOKS_JSON_SOURCE.replace("\"pulse emit ", prefix))
+    }
+
+    fn is_detected(&self) -> bool {
+        self.config_dir.exists()
+    }
+
+    fn files_installed(&self) -> bool {
+        self.manifest_path.exists() && self.hooks_json_path.exists()
+    }
+
+    fn files_match(&self) -> bool {
+        let manifest_ok = fs::read_to_string(&self.manifest_path)
+            .map(|c| c == MANIFEST_SOURCE)
+            .unwrap_or(false);
+        let hooks_ok = fs::read_to_string(&self.hooks_json_path)
+            .map(|c| c == self.hooks_json())
+            .unwrap_or(false);
+        manifest_ok && hooks_ok
+    }
+}
+
+impl ToolHook for GeminiHook {
+    fn id(&self) -> &'static str {
+        "gemini"
+    }
+
+    fn tool_name(&self) -> &'static str {
+        GEMINI_TOOL_NAME
+    }
+
+    fn managed_hooks(&self) -> Vec<ManagedHook> {
+        vec![
+            ManagedHook::file(&self.manifest_path),
+            ManagedHook::file(&self.hooks_json_path),
+        ]
+    }
+
+    fn status(&self) -> Result<HookStatus> {
+        if !self.is_detected() {
+            return Ok(HookStatus::not_detected(
+                self.tool_name(),
+                self.config_dir.clone(),
+            ));
+        }
+
+        let installed = self.files_installed();
+        let up_to_date = installed && self.files_match();
+
+        Ok(HookStatus {
+            tool: self.tool_name(),
+            detected: true,
+            connected: installed,
+            modified: false,
+            path: Some(self.extension_dir.clone()),
+            message: if installed && !up_to_date {
+                Some(outdated_message(
+                    "Extension",
+                    &installed_hash(&[&self.manifest_path, &self.hooks_json_path]),
+                    &content_hash(&[MANIFEST_SOURCE.as_bytes(), self.hooks_json().as_bytes()]),
+                ))
+            } else {
+                None
+            },
+            installed_hooks: if installed { 1 } else { 0 },
+            total_hooks: 1,
+            installed_hook_names: if installed {
+                vec!["pulse-extension".to_string()]
+            } else {
+                Vec::new()
+            },
+        })
+    }
+
+    fn connect_opts(&self, options: &ConnectOptions) -> Result<HookStatus> {
+        if !self.is_detected() {
+            return Ok(HookStatus::not_detected(
+                self.tool_name(),
+                self.config_dir.clone(),
+            ));
+        }
+
+        let installed = self.files_installed();
+        let already_current = installed && self.files_match();
+        let mut message = None;
+
+        if !already_current && options.dry_run {
+            message = Some(would_write(installed, &self.extension_dir));
+        } else if !already_current {
+            if let Some(parent) = self.hooks_json_path.parent() {
+                fs::create_dir_all(parent)?;
+            }
+            fs::write(&self.manifest_path, MANIFEST_SOURCE)?;
+            fs::write(&self.hooks_json_path, self.hooks_json().as_bytes())?;
+        }
+
+        Ok(HookStatus {
+            tool: self.tool_name(),
+            detected: true,
+            connected: true,
+            modified: !already_current,
+            path: Some(self.extension_dir.clone()),
+            message,
+            installed_hooks: 1,
+            total_hooks: 1,
+            installed_hook_names: vec!["pulse-extension".to_string()],
+        })
+    }
+
+    fn disconnect_opts(&self, options: &ConnectOptions) -> Result<HookStatus> {
+        if !self.is_detected() {
+            return Ok(HookStatus::not_detected(
+                self.tool_name(),
+                self.config_dir.clone(),
+            ));
+        }
+
+        let was_installed = self.extension_dir.exists();
+        let mut message = None;
+        if was_installed && options.dry_run {
+            message = Some(format!("would remove {}", self.extension_dir.display()));
+        } else if was_installed {
+            fs::remove_dir_all(&self.extension_dir)?;
+        }
+
+        Ok(HookStatus {
+            tool: self.tool_name(),
+            detected: true,
+            connected: false,
+            modified: was_installed,
+            path: Some(self.extension_dir.clone()),
+            message,
+            installed_hooks: 0,
+            total_hooks: 1,
+            installed_hook_names: Vec::new(),
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::TempDir;
+
+    fn make_hook(tmp: &TempDir) -> GeminiHook {
+        GeminiHook::with_config_dir(tmp.path().join(GEMINI_CONFIG_DIR))
+    }
+
+    #[test]
+    fn test_not_detected_when_config_dir_missing() {
+        let tmp = TempDir::new().unwrap();
+        let hook = make_hook(&tmp);
+        let status = hook.status().unwrap();
+        assert!(!status.detected);
+        assert!(!status.connected);
+    }
+
+    #[test]
+    fn test_detected_but_not_connected() {
+        let tmp = TempDir::new().unwrap();
+        let hook = make_hook(&tmp);
+        fs::create_dir_all(&hook.config_dir).unwrap();
+
+        let status = hook.status().unwrap();
+        assert!(status.detected);
+        assert!(!status.connected);
+        assert_eq!(status.installed_hooks, 0);
+    }
+
+    #[test]
+    fn test_connect_installs_extension() {
+        let tmp = TempDir::new().unwrap();
+        let hook = make_hook(&tmp);
+        fs::create_dir_all(&hook.config_dir).unwrap();
+
+        let status = hook.connect().unwrap();
+        assert!(status.detected);
+        assert!(status.connected);
+        assert!(status.modified);
+        assert_eq!(status.installed_hooks, 1);
+        assert_eq!(
+            fs::read_to_string(&hook.manifest_path).unwrap(),
+            MANIFEST_SOURCE
+        );
+        assert_eq!(
+            fs::read_to_string(&hook.hooks_json_path).unwrap(),
+            HOOKS_JSON_SOURCE
+        );
+    }
+
+    #[test]
+    fn test_pulse_bin_hooks_are_installed_detected_and_removed() {
+        let tmp = TempDir::new().unwrap();
+        let hook = make_hook(&tmp).with_pulse_bin(Some("/opt/pulse bin/pulse".to_string()));
+        fs::create_dir_all(&hook.config_dir).unwrap();
+
+        assert!(hook.connect().unwrap().modified);
+        let value: serde_json::Value =
+            serde_json::from_str(&fs::read_to_string(&hook.hooks_json_path).unwrap()).unwrap();
+        assert_eq!(
+            value["hooks"]["SessionStart"][0]["hooks"][0]["command"],
+            "\"/opt/pulse bin/pulse\" emit session_start --source gemini"
+        );
+        assert_eq!(hook.status().unwrap().message, None);
+        assert!(!hook.connect().unwrap().modified);
+        // Without the pin, the installed extension no longer matches.
+        assert!(make_hook(&tmp).status().unwrap().message.is_some());
+
+        assert!(hook.disconnect().unwrap().modified);
+        assert!(!hook.extension_dir.exists());
+    }
+
+    #[test]
+    fn test_connect_is_idempotent() {
+        let tmp = TempDir::new().unwrap();
+        let hook = make_hook(&tmp);
+        fs::create_dir_all(&hook.config_dir).unwrap();
+
+        hook.connect().unwrap();
+        let status = hook.connect().unwrap();
+        assert!(!status.modified, "second connect should not modify");
+        assert!(status.connected);
+    }
+
+    #[test]
+    fn test_disconnect_removes_extension() {
+        let tmp = TempDir::new().unwrap();
+        let hook = make_hook(&tmp);
+        fs::create_dir_all(&hook.config_dir).unwrap();
+
+        hook.connect().unwrap();
+        let status = hook.disconnect().unwrap();
+        assert!(status.modified);
+        assert!(!status.connected);
+        assert!(!hook.extension_dir.exists());
+    }
+
+    #[test]
+    fn test_disconnect_noop_when_not_installed() {
+        let tmp = TempDir::new().unwrap();
+        let hook = make_hook(&tmp);
+        fs::create_dir_all(&hook.config_dir).unwrap();
+
+        let status = hook.disconnect().unwrap();
+        assert!(!status.modified);
+        assert!(!status.connected);
+    }
+
+    #[test]
+    fn test_connect_updates_outdated_extension() {
+        let tmp = TempDir::new().unwrap();
+        let hook = make_hook(&tmp);
+        fs::create_dir_all(&hook.config_dir).unwrap();
+
+        fs::create_dir_all(hook.hooks_json_path.parent().unwrap()).unwrap();
+        fs::write(&hook.manifest_path, MANIFEST_SOURCE).unwrap();
+        fs::write(&hook.hooks_json_path, "{}").unwrap();
+
+        let status = hook.status().unwrap();
+        assert!(status.message.is_some(), "outdated extension is reported");
+
+        let status = hook.connect().unwrap();
+        assert!(status.modified, "should update outdated extension");
+        assert_eq!(
+            fs::read_to_string(&hook.hooks_json_path).unwrap(),
+            HOOKS_JSON_SOURCE
+        );
+    }
+
+    #[test]
+    fn test_bundled_hooks_use_gemini_source() {
+        let value: serde_json::Value = serde_json::from_str(HOOKS_JSON_SOURCE).unwrap();
+        let hooks = value["hooks"].as_object().unwrap();
+        assert!(!hooks.is_empty());
+        for entries in hooks.values() {
+            for entry in entries.as_array().unwrap() {
+                for hook in entry["hooks"].as_array().unwrap() {
+                    let command = hook["command"].as_str().unwrap();
+                    assert!(command.starts_with("pulse emit "), "{command}");
+                    assert!(command.ends_with("--source gemini"), "{command}");
+                }
+            }
+        }
+    }
+}
@@ -1,16 +1,30 @@
 mod claude_code;
+mod codex;
+mod cursor;
+mod gemini;
 mod openclaw;
 mod opencode;
 pub mod span;
 
-pub use claude_code::{CLAUDE_SOURCE, ClaudeCodeHook};
+pub use claude_code::{CLAUDE_SOURCE, ClaudeCodeHook, ProjectSettings, SettingsBackup};
+pub use codex::CodexHook;
+pub use cursor::CursorHook;
+pub use gemini::GeminiHook;
 pub use openclaw::OpenClawHook;
 pub use opencode::OpenCodeHook;
 
-use crate::error::Result;
-use std::path::PathBuf;
+use crate::error::{PulseError, Result};
+use serde::Serialize;
+use serde_json::Value;
+use sha2::{Digest, Sha256};
+use std::{
+    borrow::Cow,
+    fs,
+    path::{Path, PathBuf},
+};
 
-#[derive(Debug, Clone)]
+/// What a tool's hooks look like after `status`, `connect`, or `disconnect`.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
 pub struct HookStatus {
     pub tool: &'static str,
     pub detected: bool,
@@ -40,11 +54,288 @@ impl HookStatus {
             installed_hook_names: Vec::new(),
         }
     }
+
+    /// A tool whose hooks could not be read or written, with the error in
+    /// `message`, so one broken settings file is reported alongside the
+    /// other tools instead of stopping them.
+    pub fn failed(tool: &'static str, err: &PulseError) -> Self {
+        Self {
+            tool,
+            detected: true,
+            connected: false,
+            modified: false,
+            path: None,
+            message: Some(err.to_string()),
+            installed_hooks: 0,
+            total_hooks: 0,
+            installed_hook_names: Vec::new(),
+        }
+    }
 }
 
+/// Something pulse installs for a tool, as listed by `pulse hooks list`:
+/// an event registered in the tool's settings with the command it runs, or
+/// a whole file such as a plugin.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct ManagedHook {
+    /// The tool's name for the event; `None` for a file.
+    pub event: Option<String>,
+    pub command: Option<String>,
+    /// The settings file the event is registered in, or the file itself.
+    pub path: PathBuf,
+}
+
+impl ManagedHook {
+    pub(crate) fn event(event: &str, command: impl Into<String>, path: &Path) -> Self {
+        Self {
+            event: Some(event.to_string()),
+            command: Some(command.into()),
+            path: path.to_path_buf(),
+        }
+    }
+
+    pub(crate) fn file(path: &Path) -> Self {
+        Self {
+            event: None,
+            command: None,
+            path: path.to_path_buf(),
+        }
+    }
+}
+
+/// How `connect`/`disconnect` should apply their changes.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct ConnectOptions {
+    /// Compute the result without writing anything. The returned status
+    /// describes what would change, with a preview in `message`.
+    pub dry_run: bool,
+    /// Also drop duplicate and outdated pulse entries, for hooks that can
+    /// end up with more than one (Claude Code settings).
+    pub repair: bool,
+}
+
+/// One supported agent: detecting it and installing or removing the pulse
+/// hooks in its configuration.
 pub trait ToolHook {
+    /// Stable identifier used by `--tools`, matching the span `source`.
+    fn id(&self) -> &'static str;
     fn tool_name(&self) -> &'static str;
     fn status(&self) -> Result<HookStatus>;
-    fn connect(&self) -> Result<HookStatus>;
-    fn disconnect(&self) -> Result<HookStatus>;
+    fn connect_opts(&self, options: &ConnectOptions) -> Result<HookStatus>;
+    fn disconnect_opts(&self, options: &ConnectOptions) -> Result<HookStatus>;
+    /// Everything `connect` installs, whether or not it is installed now.
+    fn managed_hooks(&self) -> Vec<ManagedHook>;
+
+    fn connect(&self) -> Result<HookStatus> {
+        self.connect_opts(&ConnectOptions::default())
+    }
+
+    fn disconnect(&self) -> Result<HookStatus> {
+        self.disconnect_opts(&ConnectOptions::default())
+    }
+}
+
+/// Dry-run note for hooks that install whole files rather than editing one.
+pub(crate) fn would_write(exists: bool, path: &Path) -> String {
+    let verb = if exists { "update" } else { "create" };
+    format!("would {verb} {}", path.display())
+}
+
+/// The command written for a hook definition's `pulse ...` command: as is,
+/// or with `pulse_bin` (quoted when it has spaces) in place of `pulse` after
+/// `connect --absolute-path`.
+pub(crate) fn hook_command(command: &str, pulse_bin: Option<&str>) -> String {
+    match (pulse_bin, command.strip_prefix("pulse ")) {
+        (Some(bin), Some(rest)) if bin.contains(char::is_whitespace) => {
+            format!("\"{bin}\" {rest}")
+        }
+        (Some(bin), Some(rest)) => format!("{bin} {rest}"),
+        _ => command.to_string(),
+    }
+}
+
+/// The arguments of a hook command that runs `pulse`, under any path to
+/// the binary, quoted or not. `None` when it runs something else.
+pub(crate) fn pulse_arguments(command: &str) -> Option<&str> {
+    let command = command.trim_start();
+    let (program, rest) = match command.strip_prefix('"') {
+        Some(quoted) => quoted.split_once('"')?,
+        None => command
+            .split_once(char::is_whitespace)
+            .unwrap_or((command, "")),
+    };
+    is_pulse_program(program).then_some(rest)
+}
+
+/// Whether `program` is the pulse binary, under any path.
+pub(crate) fn is_pulse_program(program: &str) -> bool {
+    Path::new(program)
+        .file_name()
+        .is_some_and(|name| name == "pulse" || name == "pulse.exe")
+}
+
+/// Whether `hook_command` is `command`, taking `pulse` under any path as
+/// the same binary. Hooks written by `connect --self-exe` from another
+/// install, or before `pulse_bin` changed, still count as ours.
+pub(crate) fn same_command(hook_command: &str, command: &str) -> bool {
+    if hook_command == command {
+        return true;
+    }
+    match (pulse_arguments(hook_command), pulse_arguments(command)) {
+        (Some(installed), Some(wanted)) => {
+            installed.split_whitespace().eq(wanted.split_whitespace())
+        }
+        _ => false,
+    }
+}
+
+/// A bundled plugin script with `pulse_bin` in place of the `"pulse"` it
+/// spawns. The path goes in as a JSON string, which is also a valid
+/// JavaScript/TypeScript string literal.
+pub(crate) fn plugin_source(source: &'static str, pulse_bin: Option<&str>) -> Cow<'static, str> {
+    match pulse_bin {
+        Some(bin) => Cow::Owned(source.replace("\"pulse\"", &Value::from(bin).to_string())),
+        None => Cow::Borrowed(source),
+    }
+}
+
+/// Short SHA-256 over the contents of a plugin's files, in a fixed order.
+/// Shown in `status` so a drifted install can be told apart from the bundled
+/// copy and checked again after a fix.
+pub(crate) fn content_hash(files: &[&[u8]]) -> String {
+    let mut hasher = Sha256::new();
+    for contents in files {
+        // Length-prefix each file so moving bytes between files changes the hash.
+        hasher.update((contents.len() as u64).to_le_bytes());
+        hasher.update(contents);
+    }
+    hasher
+        .finalize()
+        .iter()
+        .take(6)
+        .map(|byte| format!("{byte:02x}"))
+        .collect()
+}
+
+/// Hash of the installed copies of `paths`; a missing file counts as empty.
+pub(crate) fn installed_hash(paths: &[&Path]) -> String {
+    let contents: Vec<Vec<u8>> = paths
+        .iter()
+        .map(|path| fs::read(path).unwrap_or_default())
+        .collect();
+    let parts: Vec<&[u8]> = contents.iter().map(Vec::as_slice).collect();
+    content_hash(&parts)
+}
+
+/// Status message for a file-based hook whose installed files have drifted.
+pub(crate) fn outdated_message(what: &str, installed: &str, expected: &str) -> String {
+    format!("{what} installed but outdated (installed {installed}, expected {expected})")
+}
+
+/// Line diff of two JSON documents as they would be written to disk.
+pub(crate) fn json_diff(before: &Value, after: &Value) -> Result<String> {
+    let before = serde_json::to_string_pretty(before)?;
+    let after = serde_json::to_string_pretty(after)?;
+    Ok(line_diff(&before, &after))
+}
+
+const DIFF_CONTEXT: usize = 2;
+
+/// Minimal line diff: changed lines prefixed with `-`/`+`, a little
+/// unchanged context around them, and `...` where lines were skipped.
+pub(crate) fn line_diff(before: &str, after: &str) -> String {
+    let old: Vec<&str> = before.lines().collect();
+    let new: Vec<&str> = after.lines().collect();
+
+    // Longest common subsequence table, filled from the end.
+    let mut lcs = vec![vec![0usize; new.len() + 1]; old.len() + 1];
+    for i in (0..old.len()).rev() {
+        for j in (0..new.len()).rev() {
+            lcs[i][j] = if old[i] == new[j] {
+                lcs[i + 1][j + 1] + 1
+            } else {
+                lcs[i + 1][j].max(lcs[i][j + 1])
+            };
+        }
+    }
+
+    let mut ops: Vec<(char, &str)> = Vec::new();
+    let (mut i, mut j) = (0, 0);
+    while i < old.len() && j < new.len() {
+        if old[i] == new[j] {
+            ops.push((' ', old[i]));
+            i += 1;
+            j += 1;
+        } else if lcs[i + 1][j] >= lcs[i][j + 1] {
+            ops.push(('-', old[i]));
+            i += 1;
+        } else {
+            ops.push(('+', new[j]));
+            j += 1;
+        }
+    }
+    ops.extend(old[i..].iter().map(|line| ('-', *line)));
+    ops.extend(new[j..].iter().map(|line| ('+', *line)));
+
+    let changed: Vec<usize> = ops
+        .iter()
+        .enumerate()
+        .filter(|(_, (tag, _))| *tag != ' ')
+        .map(|(index, _)| index)
+        .collect();
+    let near_change = |index: usize| {
+        changed
+            .iter()
+            .any(|&c| index + DIFF_CONTEXT >= c && index <= c + DIFF_CONTEXT)
+    };
+
+    let mut out = Vec::new();
+    let mut skipped = false;
+    for (index, (tag, line)) in ops.iter().enumerate() {
+        if near_change(index) {
+            if skipped && !out.is_empty() {
+                out.push("  ...".to_string());
+            }
+            skipped = false;
+            out.push(format!("{tag} {line}"));
+        } else {
+            skipped = true;
+        }
+    }
+    out.join("\n")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_line_diff_marks_added_and_removed_lines() {
+        let before = "a\nb\nc\nd\ne\nf\ng\nh";
+        let after = "a\nb\nc\nd\nE\nf\ng\nh";
+        assert_eq!(line_diff(before, after), "  c\n  d\n- e\n+ E\n  f\n  g");
+    }
+
+    #[test]
+    fn test_line_diff_elides_distant_context() {
+        let before = "1\n2\n3\n4\n5\n6\n7\n8\n9";
+        let after = "0\n1\n2\n3\n4\n5\n6\n7\n8\n9\n10";
+        assert_eq!(
+            line_diff(before, after),
+            "+ 0\n  1\n  2\n  ...\n  8\n  9\n+ 10"
+        );
+    }
+
+    #[test]
+    fn test_line_diff_identical_is_empty() {
+        assert_eq!(line_diff("a\nb", "a\nb"), "");
+    }
+
+    #[test]
+    fn test_content_hash_depends_on_file_boundaries() {
+        let hash = content_hash(&[b"ab", b"c"]);
+        assert_eq!(hash.len(), 12);
+        assert_eq!(hash, content_hash(&[b"ab", b"c"]));
+        assert_ne!(hash, content_hash(&[b"a", b"bc"]));
+    }
 }
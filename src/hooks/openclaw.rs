@@ -1,12 +1,21 @@
-use std::{fs, path::PathBuf};
+use std::{
+    borrow::Cow,
+    env, fs,
+    path::{Path, PathBuf},
+};
 
 use dirs::home_dir;
 
 use crate::error::{PulseError, Result};
 
-use super::{HookStatus, ToolHook};
+use super::{
+    ConnectOptions, HookStatus, ManagedHook, ToolHook, content_hash, installed_hash,
+    outdated_message, plugin_source, would_write,
+};
 
 const OPENCLAW_CONFIG_DIR: &str = ".openclaw";
+/// OpenClaw's own override for its state directory.
+const OPENCLAW_STATE_DIR_ENV: &str = "OPENCLAW_STATE_DIR";
 const OPENCLAW_HOOK_DIR: &str = "pulse-hook";
 const OPENCLAW_TOOL_NAME: &str = "OpenClaw";
 
@@ -19,21 +28,41 @@ pub struct OpenClawHook {
     hook_dir: PathBuf,
     hook_md_path: PathBuf,
     handler_ts_path: PathBuf,
+    pulse_bin: Option<String>,
 }
 
 impl OpenClawHook {
     pub fn new() -> Result<Self> {
         let home = home_dir().ok_or(PulseError::HomeDirNotFound)?;
-        let config_dir = home.join(OPENCLAW_CONFIG_DIR);
+        Ok(Self::with_config_dir(default_config_dir(&home, |name| {
+            env::var(name).ok()
+        })))
+    }
+
+    /// Hook for an OpenClaw state directory (normally `~/.openclaw`).
+    pub fn with_config_dir(config_dir: PathBuf) -> Self {
         let hook_dir = config_dir.join("hooks").join(OPENCLAW_HOOK_DIR);
         let hook_md_path = hook_dir.join("HOOK.md");
         let handler_ts_path = hook_dir.join("handler.ts");
-        Ok(Self {
+        Self {
             config_dir,
             hook_dir,
             hook_md_path,
             handler_ts_path,
-        })
+            pulse_bin: None,
+        }
+    }
+
+    /// Spawns an absolute `pulse` path from the handler instead of relying
+    /// on `PATH`, like `ClaudeCodeHook::with_pulse_bin`.
+    pub fn with_pulse_bin(mut self, pulse_bin: Option<String>) -> Self {
+        self.pulse_bin = pulse_bin.filter(|bin| !bin.trim().is_empty());
+        self
+    }
+
+    /// The handler as `connect` writes it.
+    fn handler(&self) -> Cow<'static, str> {
+        plugin_source(HANDLER_TS_SOURCE, self.pulse_bin.as_deref())
     }
 
     fn is_detected(&self) -> bool {
@@ -49,17 +78,28 @@ impl OpenClawHook {
             .map(|c| c == HOOK_MD_SOURCE)
             .unwrap_or(false);
         let ts_ok = fs::read_to_string(&self.handler_ts_path)
-            .map(|c| c == HANDLER_TS_SOURCE)
+            .map(|c| c == self.handler())
             .unwrap_or(false);
         md_ok && ts_ok
     }
 }
 
 impl ToolHook for OpenClawHook {
+    fn id(&self) -> &'static str {
+        "openclaw"
+    }
+
     fn tool_name(&self) -> &'static str {
         OPENCLAW_TOOL_NAME
     }
 
+    fn managed_hooks(&self) -> Vec<ManagedHook> {
+        vec![
+            ManagedHook::file(&self.hook_md_path),
+            ManagedHook::file(&self.handler_ts_path),
+        ]
+    }
+
     fn status(&self) -> Result<HookStatus> {
         if !self.is_detected() {
             return Ok(HookStatus::not_detected(
@@ -78,7 +118,11 @@ impl ToolHook for OpenClawHook {
             modified: false,
             path: Some(self.hook_dir.clone()),
             message: if installed && !up_to_date {
-                Some("Hook installed but outdated".to_string())
+                Some(outdated_message(
+                    "Hook",
+                    &installed_hash(&[&self.hook_md_path, &self.handler_ts_path]),
+                    &content_hash(&[HOOK_MD_SOURCE.as_bytes(), self.handler().as_bytes()]),
+                ))
             } else {
                 None
             },
@@ -92,7 +136,7 @@ impl ToolHook for OpenClawHook {
         })
     }
 
-    fn connect(&self) -> Result<HookStatus> {
+    fn connect_opts(&self, options: &ConnectOptions) -> Result<HookStatus> {
         if !self.is_detected() {
             return Ok(HookStatus::not_detected(
                 self.tool_name(),
@@ -100,12 +144,16 @@ impl ToolHook for OpenClawHook {
             ));
         }
 
-        let already_current = self.files_installed() && self.files_match();
+        let installed = self.files_installed();
+        let already_current = installed && self.files_match();
+        let mut message = None;
 
-        if !already_current {
+        if !already_current && options.dry_run {
+            message = Some(would_write(installed, &self.hook_dir));
+        } else if !already_current {
             fs::create_dir_all(&self.hook_dir)?;
             fs::write(&self.hook_md_path, HOOK_MD_SOURCE)?;
-            fs::write(&self.handler_ts_path, HANDLER_TS_SOURCE)?;
+            fs::write(&self.handler_ts_path, self.handler().as_bytes())?;
         }
 
         Ok(HookStatus {
@@ -114,14 +162,14 @@ impl ToolHook for OpenClawHook {
             connected: true,
             modified: !already_current,
             path: Some(self.hook_dir.clone()),
-            message: None,
+            message,
             installed_hooks: 1,
             total_hooks: 1,
             installed_hook_names: vec!["pulse-hook".to_string()],
         })
     }
 
-    fn disconnect(&self) -> Result<HookStatus> {
+    fn disconnect_opts(&self, options: &ConnectOptions) -> Result<HookStatus> {
         if !self.is_detected() {
             return Ok(HookStatus::not_detected(
                 self.tool_name(),
@@ -130,7 +178,10 @@ impl ToolHook for OpenClawHook {
         }
 
         let was_installed = self.files_installed();
-        if was_installed {
+        let mut message = None;
+        if was_installed && options.dry_run {
+            message = Some(format!("would remove {}", self.hook_dir.display()));
+        } else if was_installed {
             fs::remove_dir_all(&self.hook_dir)?;
         }
 
@@ -140,7 +191,7 @@ impl ToolHook for OpenClawHook {
             connected: false,
             modified: was_installed,
             path: Some(self.hook_dir.clone()),
-            message: None,
+            message,
             installed_hooks: 0,
             total_hooks: 1,
             installed_hook_names: Vec::new(),
@@ -148,6 +199,14 @@ impl ToolHook for OpenClawHook {
     }
 }
 
+/// `$OPENCLAW_STATE_DIR`, else `~/.openclaw`.
+fn default_config_dir(home: &Path, env: impl Fn(&str) -> Option<String>) -> PathBuf {
+    env(OPENCLAW_STATE_DIR_ENV)
+        .filter(|value| !value.is_empty())
+        .map(PathBuf::from)
+        .unwrap_or_else(|| home.join(OPENCLAW_CONFIG_DIR))
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -155,16 +214,20 @@ mod tests {
     use tempfile::TempDir;
 
     fn make_hook(tmp: &TempDir) -> OpenClawHook {
-        let config_dir = tmp.path().join(OPENCLAW_CONFIG_DIR);
-        let hook_dir = config_dir.join("hooks").join(OPENCLAW_HOOK_DIR);
-        let hook_md_path = hook_dir.join("HOOK.md");
-        let handler_ts_path = hook_dir.join("handler.ts");
-        OpenClawHook {
-            config_dir,
-            hook_dir,
-            hook_md_path,
-            handler_ts_path,
-        }
+        OpenClawHook::with_config_dir(tmp.path().join(OPENCLAW_CONFIG_DIR))
+    }
+
+    #[test]
+    fn test_state_dir_env_overrides_home() {
+        let home = Path::new("/home/me");
+        assert_eq!(
+            default_config_dir(home, |_| None),
+            Path::new("/home/me/.openclaw")
+        );
+        assert_eq!(
+            default_config_dir(home, |_| Some("/srv/openclaw".to_string())),
+            Path::new("/srv/openclaw")
+        );
     }
 
     #[test]
@@ -209,6 +272,27 @@ mod tests {
         assert_eq!(ts, HANDLER_TS_SOURCE);
     }
 
+    #[test]
+    fn test_pulse_bin_handler_is_installed_detected_and_removed() {
+        let tmp = TempDir::new().unwrap();
+        let hook = make_hook(&tmp).with_pulse_bin(Some("/opt/pulse/bin/pulse".to_string()));
+        fs::create_dir_all(&hook.config_dir).unwrap();
+
+        assert!(hook.connect().unwrap().modified);
+        let ts = fs::read_to_string(&hook.handler_ts_path).unwrap();
+        assert!(
+            ts.contains(r#"spawn("/opt/pulse/bin/pulse", ["emit""#),
+            "{ts}"
+        );
+        assert_eq!(hook.status().unwrap().message, None);
+        assert!(!hook.connect().unwrap().modified);
+        // Without the pin, the installed handler no longer matches.
+        assert!(make_hook(&tmp).status().unwrap().message.is_some());
+
+        assert!(hook.disconnect().unwrap().modified);
+        assert!(!hook.hook_dir.exists());
+    }
+
     #[test]
     fn test_connect_is_idempotent() {
         let tmp = TempDir::new().unwrap();
@@ -1,12 +1,21 @@
-use std::{fs, path::PathBuf};
+use std::{
+    borrow::Cow,
+    env, fs,
+    path::{Path, PathBuf},
+};
 
 use dirs::home_dir;
 
 use crate::error::{PulseError, Result};
 
-use super::{HookStatus, ToolHook};
+use super::{
+    ConnectOptions, HookStatus, ManagedHook, ToolHook, content_hash, installed_hash,
+    outdated_message, plugin_source, would_write,
+};
 
-const OPENCODE_CONFIG_DIR: &str = ".config/opencode";
+const OPENCODE_CONFIG_DIR: &str = "opencode";
+/// Set by OpenCode users who keep its config outside the XDG location.
+const OPENCODE_CONFIG_DIR_ENV: &str = "OPENCODE_CONFIG_DIR";
 const OPENCODE_PLUGIN_FILENAME: &str = "pulse-plugin.ts";
 const OPENCODE_TOOL_NAME: &str = "OpenCode";
 const PLUGIN_SOURCE: &str = include_str!("../../plugins/opencode/pulse-plugin.ts");
@@ -15,17 +24,37 @@ const PLUGIN_SOURCE: &str = include_str!("../../plugins/opencode/pulse-plugin.ts
 pub struct OpenCodeHook {
     config_dir: PathBuf,
     plugin_path: PathBuf,
+    pulse_bin: Option<String>,
 }
 
 impl OpenCodeHook {
     pub fn new() -> Result<Self> {
         let home = home_dir().ok_or(PulseError::HomeDirNotFound)?;
-        let config_dir = home.join(OPENCODE_CONFIG_DIR);
+        Ok(Self::with_config_dir(default_config_dir(&home, |name| {
+            env::var(name).ok()
+        })))
+    }
+
+    /// Hook for an OpenCode config directory (normally `~/.config/opencode`).
+    pub fn with_config_dir(config_dir: PathBuf) -> Self {
         let plugin_path = config_dir.join("plugin").join(OPENCODE_PLUGIN_FILENAME);
-        Ok(Self {
+        Self {
             config_dir,
             plugin_path,
-        })
+            pulse_bin: None,
+        }
+    }
+
+    /// Spawns an absolute `pulse` path from the plugin instead of relying on
+    /// `PATH`, like `ClaudeCodeHook::with_pulse_bin`.
+    pub fn with_pulse_bin(mut self, pulse_bin: Option<String>) -> Self {
+        self.pulse_bin = pulse_bin.filter(|bin| !bin.trim().is_empty());
+        self
+    }
+
+    /// The plugin as `connect` writes it.
+    fn plugin(&self) -> Cow<'static, str> {
+        plugin_source(PLUGIN_SOURCE, self.pulse_bin.as_deref())
     }
 
     fn is_detected(&self) -> bool {
@@ -38,17 +67,25 @@ impl OpenCodeHook {
 
     fn plugin_matches(&self) -> bool {
         match fs::read_to_string(&self.plugin_path) {
-            Ok(contents) => contents == PLUGIN_SOURCE,
+            Ok(contents) => contents == self.plugin(),
             Err(_) => false,
         }
     }
 }
 
 impl ToolHook for OpenCodeHook {
+    fn id(&self) -> &'static str {
+        "opencode"
+    }
+
     fn tool_name(&self) -> &'static str {
         OPENCODE_TOOL_NAME
     }
 
+    fn managed_hooks(&self) -> Vec<ManagedHook> {
+        vec![ManagedHook::file(&self.plugin_path)]
+    }
+
     fn status(&self) -> Result<HookStatus> {
         if !self.is_detected() {
             return Ok(HookStatus::not_detected(
@@ -67,7 +104,11 @@ impl ToolHook for OpenCodeHook {
             modified: false,
             path: Some(self.plugin_path.clone()),
             message: if installed && !up_to_date {
-                Some("Plugin installed but outdated".to_string())
+                Some(outdated_message(
+                    "Plugin",
+                    &installed_hash(&[&self.plugin_path]),
+                    &content_hash(&[self.plugin().as_bytes()]),
+                ))
             } else {
                 None
             },
@@ -81,7 +122,7 @@ impl ToolHook for OpenCodeHook {
         })
     }
 
-    fn connect(&self) -> Result<HookStatus> {
+    fn connect_opts(&self, options: &ConnectOptions) -> Result<HookStatus> {
         if !self.is_detected() {
             return Ok(HookStatus::not_detected(
                 self.tool_name(),
@@ -89,13 +130,17 @@ impl ToolHook for OpenCodeHook {
             ));
         }
 
-        let already_current = self.plugin_installed() && self.plugin_matches();
+        let installed = self.plugin_installed();
+        let already_current = installed && self.plugin_matches();
+        let mut message = None;
 
-        if !already_current {
+        if !already_current && options.dry_run {
+            message = Some(would_write(installed, &self.plugin_path));
+        } else if !already_current {
             if let Some(parent) = self.plugin_path.parent() {
                 fs::create_dir_all(parent)?;
             }
-            fs::write(&self.plugin_path, PLUGIN_SOURCE)?;
+            fs::write(&self.plugin_path, self.plugin().as_bytes())?;
         }
 
         Ok(HookStatus {
@@ -104,14 +149,14 @@ impl ToolHook for OpenCodeHook {
             connected: true,
             modified: !already_current,
             path: Some(self.plugin_path.clone()),
-            message: None,
+            message,
             installed_hooks: 1,
             total_hooks: 1,
             installed_hook_names: vec!["pulse-plugin".to_string()],
         })
     }
 
-    fn disconnect(&self) -> Result<HookStatus> {
+    fn disconnect_opts(&self, options: &ConnectOptions) -> Result<HookStatus> {
         if !self.is_detected() {
             return Ok(HookStatus::not_detected(
                 self.tool_name(),
@@ -120,7 +165,10 @@ impl ToolHook for OpenCodeHook {
         }
 
         let was_installed = self.plugin_installed();
-        if was_installed {
+        let mut message = None;
+        if was_installed && options.dry_run {
+            message = Some(format!("would remove {}", self.plugin_path.display()));
+        } else if was_installed {
             fs::remove_file(&self.plugin_path)?;
         }
 
@@ -130,7 +178,7 @@ impl ToolHook for OpenCodeHook {
             connected: false,
             modified: was_installed,
             path: Some(self.plugin_path.clone()),
-            message: None,
+            message,
             installed_hooks: 0,
             total_hooks: 1,
             installed_hook_names: Vec::new(),
@@ -138,6 +186,19 @@ impl ToolHook for OpenCodeHook {
     }
 }
 
+/// `$OPENCODE_CONFIG_DIR`, else `$XDG_CONFIG_HOME/opencode`, else
+/// `~/.config/opencode`, matching where OpenCode itself looks.
+fn default_config_dir(home: &Path, env: impl Fn(&str) -> Option<String>) -> PathBuf {
+    let var = |name: &str| env(name).filter(|value| !value.is_empty());
+    if let Some(dir) = var(OPENCODE_CONFIG_DIR_ENV) {
+        return PathBuf::from(dir);
+    }
+    var("XDG_CONFIG_HOME")
+        .map(PathBuf::from)
+        .unwrap_or_else(|| home.join(".config"))
+        .join(OPENCODE_CONFIG_DIR)
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -145,12 +206,29 @@ mod tests {
     use tempfile::TempDir;
 
     fn make_hook(tmp: &TempDir) -> OpenCodeHook {
-        let config_dir = tmp.path().join(".config/opencode");
-        let plugin_path = config_dir.join("plugin").join(OPENCODE_PLUGIN_FILENAME);
-        OpenCodeHook {
-            config_dir,
-            plugin_path,
-        }
+        OpenCodeHook::with_config_dir(tmp.path().join(".config/opencode"))
+    }
+
+    #[test]
+    fn test_config_dir_follows_env() {
+        let home = Path::new("/home/me");
+        assert_eq!(
+            default_config_dir(home, |_| None),
+            Path::new("/home/me/.config/opencode")
+        );
+        assert_eq!(
+            default_config_dir(home, |name| {
+                (name == "XDG_CONFIG_HOME").then(|| "/xdg".to_string())
+            }),
+            Path::new("/xdg/opencode")
+        );
+        assert_eq!(
+            default_config_dir(home, |name| match name {
+                OPENCODE_CONFIG_DIR_ENV => Some("/oc".to_string()),
+                _ => Some("/xdg".to_string()),
+            }),
+            Path::new("/oc")
+        );
     }
 
     #[test]
@@ -191,6 +269,27 @@ mod tests {
         assert_eq!(contents, PLUGIN_SOURCE);
     }
 
+    #[test]
+    fn test_pulse_bin_plugin_is_installed_detected_and_removed() {
+        let tmp = TempDir::new().unwrap();
+        let hook = make_hook(&tmp).with_pulse_bin(Some("/opt/pulse/bin/pulse".to_string()));
+        fs::create_dir_all(&hook.config_dir).unwrap();
+
+        assert!(hook.connect().unwrap().modified);
+        let contents = fs::read_to_string(&hook.plugin_path).unwrap();
+        assert!(
+            contents.contains(r#"Bun.spawn(["/opt/pulse/bin/pulse", "emit""#),
+            "{contents}"
+        );
+        assert_eq!(hook.status().unwrap().message, None);
+        assert!(!hook.connect().unwrap().modified);
+        // Without the pin, the installed plugin no longer matches.
+        assert!(make_hook(&tmp).status().unwrap().message.is_some());
+
+        assert!(hook.disconnect().unwrap().modified);
+        assert!(!hook.plugin_path.exists());
+    }
+
     #[test]
     fn test_connect_is_idempotent() {
         let tmp = TempDir::new().unwrap();
@@ -227,6 +326,28 @@ mod tests {
         assert!(!status.connected);
     }
 
+    #[test]
+    fn test_dry_run_reports_without_writing() {
+        let tmp = TempDir::new().unwrap();
+        let hook = make_hook(&tmp);
+        fs::create_dir_all(&hook.config_dir).unwrap();
+        let dry_run = ConnectOptions {
+            dry_run: true,
+            ..ConnectOptions::default()
+        };
+
+        let status = hook.connect_opts(&dry_run).unwrap();
+        assert!(status.modified);
+        assert!(status.message.unwrap().starts_with("would create "));
+        assert!(!hook.plugin_path.exists());
+
+        hook.connect().unwrap();
+        let status = hook.disconnect_opts(&dry_run).unwrap();
+        assert!(status.modified);
+        assert!(status.message.unwrap().starts_with("would remove "));
+        assert!(hook.plugin_path.exists());
+    }
+
     #[test]
     fn test_connect_updates_outdated_plugin() {
         let tmp = TempDir::new().unwrap();
@@ -243,4 +364,31 @@ mod tests {
         let contents = fs::read_to_string(&hook.plugin_path).unwrap();
         assert_eq!(contents, PLUGIN_SOURCE);
     }
+
+    #[test]
+    fn test_tampered_plugin_reports_hashes() {
+        let tmp = TempDir::new().unwrap();
+        let hook = make_hook(&tmp);
+        fs::create_dir_all(&hook.config_dir).unwrap();
+        hook.connect().unwrap();
+        assert!(hook.status().unwrap().message.is_none());
+
+        let mut bytes = fs::read(&hook.plugin_path).unwrap();
+        bytes[0] ^= 1;
+        fs::write(&hook.plugin_path, bytes).unwrap();
+
+        assert!(!hook.plugin_matches());
+        let message = hook.status().unwrap().message.unwrap();
+        let expected = content_hash(&[PLUGIN_SOURCE.as_bytes()]);
+        let installed = installed_hash(&[&hook.plugin_path]);
+        assert_ne!(installed, expected);
+        assert!(
+            message.contains(&format!("installed {installed}")),
+            "{message}"
+        );
+        assert!(
+            message.contains(&format!("expected {expected}")),
+            "{message}"
+        );
+    }
 }
@@ -1,10 +1,20 @@
-use serde_json::Value;
+use std::{borrow::Cow, collections::BTreeMap, path::Path};
 
-use crate::http::SpanPayload;
+use chrono::{DateTime, Utc};
+use serde_json::{Map, Value, json};
+use uuid::Uuid;
 
+use crate::{correlation::CorrelationStore, http::SpanPayload};
+
+/// What `extract` pulls out of one hook payload, before it becomes a
+/// `SpanPayload` with `into_span`.
+#[derive(Debug, Clone, Default, PartialEq)]
 pub struct SpanFields {
     pub session_id: Option<String>,
     pub cwd: Option<String>,
+    /// Where the tool itself ran, when the payload says and it differs
+    /// from the session's `cwd` (e.g. a Bash call in a subdirectory).
+    pub tool_cwd: Option<String>,
     pub tool_use_id: Option<String>,
     pub tool_name: Option<String>,
     pub tool_input: Option<Value>,
@@ -13,45 +23,40 @@ pub struct SpanFields {
     pub is_interrupt: Option<bool>,
     pub model: Option<String>,
     pub agent_name: Option<String>,
+    /// Subagent the event belongs to, from `agent_id` or else
+    /// `parent_tool_use_id`. Tool spans with one nest under that subagent.
+    pub agent_id: Option<String>,
     pub metadata: Option<Value>,
     pub source: Option<String>,
 }
 
 impl SpanFields {
-    fn new() -> Self {
-        Self {
-            session_id: None,
-            cwd: None,
-            tool_use_id: None,
-            tool_name: None,
-            tool_input: None,
-            tool_response: None,
-            error: None,
-            is_interrupt: None,
-            model: None,
-            agent_name: None,
-            metadata: None,
-            source: None,
-        }
-    }
-
     pub fn into_span(
         self,
         span_id: String,
         timestamp: String,
         event_type: String,
         source: String,
+        parent_span_id: Option<String>,
+        overrides: MappingOverrides<'_>,
     ) -> Option<SpanPayload> {
         let session_id = self.session_id?;
+        let kind = overrides.kind(&event_type);
+        // A subagent's tool calls hang off its `agent_run` span rather than
+        // the session.
+        let parent_span_id = match self.agent_id.as_deref() {
+            Some(agent_id) if kind == "tool_use" => Some(agent_span_id(&session_id, agent_id)),
+            _ => parent_span_id,
+        };
         Some(SpanPayload {
             span_id,
             session_id,
-            parent_span_id: None,
+            parent_span_id,
             timestamp,
             duration_ms: None,
             source,
-            kind: event_type_to_kind(&event_type).to_string(),
-            status: event_type_to_status(&event_type).to_string(),
+            kind: kind.to_string(),
+            status: overrides.status(&event_type).to_string(),
             event_type,
             tool_use_id: self.tool_use_id,
             tool_name: self.tool_name,
@@ -60,6 +65,7 @@ impl SpanFields {
             error: self.error,
             is_interrupt: self.is_interrupt,
             cwd: self.cwd,
+            tool_cwd: self.tool_cwd,
             model: self.model,
             agent_name: self.agent_name,
             metadata: self.metadata,
@@ -67,6 +73,127 @@ impl SpanFields {
     }
 }
 
+impl SpanFields {
+    /// Caps `tool_input`, `tool_response`, and the `raw` payload copy in
+    /// metadata at `max_bytes` each (see `truncate_value`).
+    pub fn truncate_large_fields(&mut self, max_bytes: usize) {
+        for value in [self.tool_input.as_mut(), self.tool_response.as_mut()]
+            .into_iter()
+            .flatten()
+        {
+            truncate_value(value, max_bytes);
+        }
+        if let Some(raw) = self
+            .metadata
+            .as_mut()
+            .and_then(|metadata| metadata.get_mut("raw"))
+        {
+            truncate_value(raw, max_bytes);
+        }
+    }
+}
+
+/// Replaces `value` with a truncation marker when its serialized form is
+/// longer than `max_bytes`. The marker keeps a prefix of the serialized JSON
+/// as a string, so the result is always valid JSON:
+///
+/// `{ "_truncated": true, "_original_bytes": N, "_preview": "..." }`
+///
+/// Values within the limit are left untouched.
+pub fn truncate_value(value: &mut Value, max_bytes: usize) {
+    let serialized = match &*value {
+        Value::String(text) => Cow::Borrowed(text.as_str()),
+        other => Cow::Owned(other.to_string()),
+    };
+    if serialized.len() <= max_bytes {
+        return;
+    }
+    let mut end = max_bytes;
+    while !serialized.is_char_boundary(end) {
+        end -= 1;
+    }
+    let marker = json!({
+        "_truncated": true,
+        "_original_bytes": serialized.len(),
+        "_preview": &serialized[..end],
+    });
+    *value = marker;
+}
+
+/// Payload keys a source may send in place of the snake_case ones `extract`
+/// reads, as (source key, canonical key). The bundled plugins already send
+/// canonical keys; these cover integrations that pass the tool's own event
+/// objects through, like OpenCode's `{ tool, sessionID, callID, args }`.
+/// Claude Code's keys are the canonical ones.
+pub const SOURCE_FIELD_MAPS: &[(&str, &[(&str, &str)])] = &[
+    ("claude_code", &[]),
+    (
+        "opencode",
+        &[
+            ("sessionID", "session_id"),
+            ("sessionId", "session_id"),
+            ("callID", "tool_use_id"),
+            ("toolName", "tool_name"),
+            ("tool", "tool_name"),
+            ("args", "tool_input"),
+            ("output", "tool_response"),
+            ("modelID", "model"),
+            ("agent", "agent_name"),
+        ],
+    ),
+    (
+        "openclaw",
+        &[
+            ("sessionKey", "session_id"),
+            ("sessionId", "session_id"),
+            ("toolName", "tool_name"),
+            ("toolCallId", "tool_use_id"),
+            ("params", "tool_input"),
+            ("result", "tool_response"),
+            ("workspaceDir", "cwd"),
+        ],
+    ),
+];
+
+/// `payload` with its top-level keys renamed to the canonical ones for
+/// `source`: the built-in map first, then `overrides` (source key to
+/// canonical key), which win. A canonical key the payload already has is
+/// never replaced, and nested keys are left alone.
+pub fn canonical_payload<'a>(
+    source: &str,
+    payload: &'a Value,
+    overrides: Option<&BTreeMap<String, String>>,
+) -> Cow<'a, Value> {
+    let mut renames: BTreeMap<&str, &str> = SOURCE_FIELD_MAPS
+        .iter()
+        .filter(|(name, _)| *name == source)
+        .flat_map(|(_, map)| map.iter().copied())
+        .collect();
+    for (from, to) in overrides.into_iter().flatten() {
+        renames.insert(from, to);
+    }
+
+    let Some(object) = payload.as_object() else {
+        return Cow::Borrowed(payload);
+    };
+    // Cloned on the first rename; most payloads need none.
+    let mut renamed: Option<Map<String, Value>> = None;
+    for (from, to) in renames {
+        let current = renamed.as_ref().unwrap_or(object);
+        if from == to || !current.contains_key(from) || current.contains_key(to) {
+            continue;
+        }
+        let object = renamed.get_or_insert_with(|| object.clone());
+        if let Some(value) = object.remove(from) {
+            object.insert(to.to_string(), value);
+        }
+    }
+    match renamed {
+        Some(object) => Cow::Owned(Value::Object(object)),
+        None => Cow::Borrowed(payload),
+    }
+}
+
 pub fn extract(event_type: &str, payload: &Value) -> SpanFields {
     let mut fields = extract_common(payload);
 
@@ -76,7 +203,7 @@ pub fn extract(event_type: &str, payload: &Value) -> SpanFields {
         "post_tool_use_failure" => extract_post_tool_use_failure(payload, &mut fields),
         "session_start" => extract_session_start(payload, &mut fields),
         "session_end" => extract_session_end(payload, &mut fields),
-        "stop" => {}
+        "stop" | "heartbeat" => {}
         "subagent_start" => extract_subagent(payload, &mut fields),
         "subagent_stop" => extract_subagent(payload, &mut fields),
         "user_prompt_submit" => extract_user_prompt(payload, &mut fields),
@@ -88,25 +215,164 @@ pub fn extract(event_type: &str, payload: &Value) -> SpanFields {
     fields
 }
 
-pub fn event_type_to_kind(event_type: &str) -> &str {
+/// Stable span id for a session, so spans emitted by separate processes can
+/// point at the same parent.
+pub fn session_span_id(session_id: &str) -> String {
+    Uuid::new_v5(
+        &Uuid::NAMESPACE_URL,
+        format!("pulse:session:{session_id}").as_bytes(),
+    )
+    .to_string()
+}
+
+/// Stable span id for a subagent run, derived like `session_span_id`. The
+/// `subagent_start` span uses it, and the subagent's tool spans point at it.
+pub fn agent_span_id(session_id: &str, agent_id: &str) -> String {
+    Uuid::new_v5(
+        &Uuid::NAMESPACE_URL,
+        format!("pulse:agent:{session_id}:{agent_id}").as_bytes(),
+    )
+    .to_string()
+}
+
+/// Records the session span on `session_start` and returns it as the parent
+/// for later non-session spans in the same session. Returns `None` when the
+/// session start was never seen.
+pub fn link_session(
+    store: &CorrelationStore,
+    event_type: &str,
+    session_id: &str,
+) -> Option<String> {
+    let key = format!("session-{session_id}");
+    if event_type == "session_start" {
+        let _ = store.put(&key, &session_span_id(session_id));
+        return None;
+    }
+    if event_type_to_kind(event_type) == "session" {
+        return None;
+    }
+    store.get(&key).map(|id| id.trim().to_string())
+}
+
+/// Records when a tool call starts on `pre_tool_use` and, on the matching
+/// `post_tool_use`/`post_tool_use_failure`, returns the elapsed milliseconds.
+/// Returns `None` when there is no tool id or no recorded start.
+pub fn correlate_tool_duration(
+    store: &CorrelationStore,
+    event_type: &str,
+    tool_use_id: Option<&str>,
+    now: DateTime<Utc>,
+) -> Option<f64> {
+    let key = format!("tool-{}", tool_use_id?);
     match event_type {
-        "pre_tool_use" | "post_tool_use" | "post_tool_use_failure" => "tool_use",
-        "session_start" | "session_end" | "stop" => "session",
-        "subagent_start" | "subagent_stop" => "agent_run",
-        "user_prompt_submit" => "user_prompt",
-        "assistant_message" => "llm_response",
-        "notification" => "notification",
-        _ => "session",
+        "pre_tool_use" => {
+            let _ = store.put(&key, &now.to_rfc3339());
+            None
+        }
+        "post_tool_use" | "post_tool_use_failure" => elapsed_ms(&store.take(&key)?, now),
+        _ => None,
     }
 }
 
-pub fn event_type_to_status(event_type: &str) -> &str {
+/// Records when a session starts on `session_start` and returns the session
+/// length so far on `stop` and `session_end`. `stop` can fire once per turn,
+/// so only `session_end` clears the start. Returns `None` when the start was
+/// never seen, e.g. when pulse was installed mid-session.
+pub fn correlate_session_duration(
+    store: &CorrelationStore,
+    event_type: &str,
+    session_id: &str,
+    now: DateTime<Utc>,
+) -> Option<f64> {
+    let key = format!("session_start-{session_id}");
     match event_type {
-        "post_tool_use_failure" => "error",
-        _ => "success",
+        "session_start" => {
+            let _ = store.put(&key, &now.to_rfc3339());
+            None
+        }
+        "stop" => elapsed_ms(&store.get(&key)?, now),
+        "session_end" => elapsed_ms(&store.take(&key)?, now),
+        _ => None,
     }
 }
 
+fn elapsed_ms(started: &str, now: DateTime<Utc>) -> Option<f64> {
+    let started = DateTime::parse_from_rfc3339(started.trim()).ok()?;
+    let elapsed = now.signed_duration_since(started.with_timezone(&Utc));
+    Some(elapsed.num_microseconds()? as f64 / 1000.0).filter(|ms| *ms >= 0.0)
+}
+
+/// Built-in `kind` per event type. Any other event type is a `session` span.
+pub const DEFAULT_KINDS: &[(&str, &str)] = &[
+    ("pre_tool_use", "tool_use"),
+    ("post_tool_use", "tool_use"),
+    ("post_tool_use_failure", "tool_use"),
+    ("session_start", "session"),
+    ("session_end", "session"),
+    ("stop", "session"),
+    ("heartbeat", "session"),
+    ("subagent_start", "agent_run"),
+    ("subagent_stop", "agent_run"),
+    ("user_prompt_submit", "user_prompt"),
+    ("assistant_message", "llm_response"),
+    ("notification", "notification"),
+];
+
+/// Built-in `status` per event type. Any other event type is a `success`.
+pub const DEFAULT_STATUSES: &[(&str, &str)] = &[("post_tool_use_failure", "error")];
+
+/// `[kinds]` and `[statuses]` from the config, consulted before the
+/// built-in tables.
+#[derive(Debug, Clone, Copy)]
+pub struct MappingOverrides<'a> {
+    pub kinds: &'a BTreeMap<String, String>,
+    pub statuses: &'a BTreeMap<String, String>,
+}
+
+static NO_OVERRIDES: BTreeMap<String, String> = BTreeMap::new();
+
+impl Default for MappingOverrides<'_> {
+    fn default() -> Self {
+        Self {
+            kinds: &NO_OVERRIDES,
+            statuses: &NO_OVERRIDES,
+        }
+    }
+}
+
+impl<'a> MappingOverrides<'a> {
+    pub fn kind(&self, event_type: &str) -> &'a str {
+        self.kinds
+            .get(event_type)
+            .map_or_else(|| event_type_to_kind(event_type), String::as_str)
+    }
+
+    pub fn status(&self, event_type: &str) -> &'a str {
+        self.statuses
+            .get(event_type)
+            .map_or_else(|| event_type_to_status(event_type), String::as_str)
+    }
+}
+
+pub fn event_type_to_kind(event_type: &str) -> &'static str {
+    mapped(event_type, DEFAULT_KINDS, "session")
+}
+
+pub fn event_type_to_status(event_type: &str) -> &'static str {
+    mapped(event_type, DEFAULT_STATUSES, "success")
+}
+
+fn mapped(
+    event_type: &str,
+    defaults: &[(&str, &'static str)],
+    fallback: &'static str,
+) -> &'static str {
+    defaults
+        .iter()
+        .find(|(name, _)| *name == event_type)
+        .map_or(fallback, |(_, value)| value)
+}
+
 fn str_field(payload: &Value, key: &str) -> Option<String> {
     payload
         .get(key)
@@ -116,15 +382,57 @@ fn str_field(payload: &Value, key: &str) -> Option<String> {
 }
 
 fn extract_common(payload: &Value) -> SpanFields {
-    let mut fields = SpanFields::new();
-    fields.session_id = str_field(payload, "session_id");
-    fields.cwd = str_field(payload, "cwd");
-    fields.model = str_field(payload, "model");
-    fields.source = str_field(payload, "source");
-    fields
+    SpanFields {
+        // Cursor calls the session a conversation, and Codex a thread.
+        session_id: str_field(payload, "session_id")
+            .or_else(|| str_field(payload, "conversation_id"))
+            .or_else(|| str_field(payload, "thread-id")),
+        cwd: str_field(payload, "cwd"),
+        model: str_field(payload, "model"),
+        source: str_field(payload, "source"),
+        ..SpanFields::default()
+    }
+}
+
+fn agent_ref(payload: &Value) -> Option<String> {
+    str_field(payload, "agent_id").or_else(|| str_field(payload, "parent_tool_use_id"))
+}
+
+/// Keys a tool's input may use for the directory it runs in.
+const TOOL_CWD_KEYS: [&str; 3] = ["cwd", "workdir", "directory"];
+
+/// Splits the tool's working directory from the session's. A directory in
+/// `tool_input` is the tool's own, relative to `cwd`. Cursor's shell events
+/// send the command's directory as `cwd` and the project as
+/// `workspace_roots`, so there the root becomes the session `cwd`.
+fn extract_tool_cwd(payload: &Value, fields: &mut SpanFields) {
+    let from_input = payload
+        .get("tool_input")
+        .and_then(|input| TOOL_CWD_KEYS.iter().find_map(|key| str_field(input, key)));
+    let tool_cwd = match from_input {
+        Some(dir) => match &fields.cwd {
+            Some(cwd) => Some(Path::new(cwd).join(dir).display().to_string()),
+            None => Some(dir),
+        },
+        None => {
+            let root = payload
+                .get("workspace_roots")
+                .and_then(Value::as_array)
+                .and_then(|roots| roots.first())
+                .and_then(Value::as_str)
+                .filter(|root| !root.is_empty());
+            match root {
+                Some(root) => fields.cwd.replace(root.to_string()),
+                None => None,
+            }
+        }
+    };
+    fields.tool_cwd = tool_cwd.filter(|dir| fields.cwd.as_deref() != Some(dir.as_str()));
 }
 
 fn extract_tool_common(payload: &Value, fields: &mut SpanFields) {
+    extract_tool_cwd(payload, fields);
+    fields.agent_id = agent_ref(payload);
     fields.tool_use_id = str_field(payload, "tool_use_id");
     fields.tool_name = str_field(payload, "tool_name");
     if let Some(input) = payload.get("tool_input").cloned() {
@@ -151,6 +459,24 @@ fn extract_post_tool_use_failure(payload: &Value, fields: &mut SpanFields) {
     if let Some(is_interrupt) = payload.get("is_interrupt").and_then(|v| v.as_bool()) {
         fields.is_interrupt = Some(is_interrupt);
     }
+
+    // Shell tools report how the command ended, either at the top level or
+    // inside `tool_response`.
+    let lookup = |key: &str| {
+        payload
+            .get(key)
+            .or_else(|| payload.get("tool_response").and_then(|r| r.get(key)))
+            .cloned()
+    };
+    let exit_code = lookup("exit_code").filter(Value::is_i64);
+    let signal = lookup("signal").filter(|v| v.as_str().is_some_and(|s| !s.is_empty()));
+    for (key, value) in [("exit_code", exit_code), ("signal", signal)] {
+        let Some(value) = value else { continue };
+        let meta = fields.metadata.get_or_insert_with(|| serde_json::json!({}));
+        if let Some(obj) = meta.as_object_mut() {
+            obj.insert(key.to_string(), value);
+        }
+    }
 }
 
 fn extract_session_start(payload: &Value, fields: &mut SpanFields) {
@@ -168,6 +494,7 @@ fn extract_subagent(payload: &Value, fields: &mut SpanFields) {
     if fields.agent_name.is_none() {
         fields.agent_name = str_field(payload, "agent_name");
     }
+    fields.agent_id = agent_ref(payload);
     if let Some(id) = str_field(payload, "agent_id") {
         let meta = fields.metadata.get_or_insert_with(|| serde_json::json!({}));
         if let Some(obj) = meta.as_object_mut() {
@@ -205,10 +532,10 @@ fn extract_assistant_message(payload: &Value, fields: &mut SpanFields) {
         }
     }
 
-    if let Some(cost) = payload.get("cost").and_then(|v| v.as_f64()) {
-        if let Some(n) = serde_json::Number::from_f64(cost) {
-            usage.insert("cost".to_string(), Value::Number(n));
-        }
+    if let Some(cost) = payload.get("cost").and_then(|v| v.as_f64())
+        && let Some(n) = serde_json::Number::from_f64(cost)
+    {
+        usage.insert("cost".to_string(), Value::Number(n));
     }
 
     if !usage.is_empty() {
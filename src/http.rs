@@ -1,39 +1,56 @@
-use std::time::Duration;
+use std::{fs, io::Write, time::Duration};
 
-use reqwest::{Client, Url};
-use serde::Serialize;
+use flate2::{Compression, write::GzEncoder};
+use reqwest::{
+    Certificate, Client, ClientBuilder, Identity, NoProxy, Proxy, Response, StatusCode, Url,
+    header::{AUTHORIZATION, CONTENT_ENCODING, CONTENT_TYPE, HeaderName},
+};
+use serde::{Deserialize, Serialize};
 use serde_json::Value;
+use sha2::{Digest, Sha256};
 
 use crate::{
     config::PulseConfig,
-    error::{PulseError, Result},
+    debug,
+    error::{PulseError, Result, describe_http_error},
 };
 
 const USER_AGENT: &str = concat!("pulse-cli/", env!("CARGO_PKG_VERSION"));
-const DEFAULT_TIMEOUT: Duration = Duration::from_secs(5);
-const EMIT_TIMEOUT: Duration = Duration::from_secs(2);
+const IDEMPOTENCY_KEY: &str = "Idempotency-Key";
+/// Bodies smaller than this go out uncompressed; a single span rarely gains
+/// enough to pay for the gzip header and CPU time.
+const COMPRESS_MIN_BYTES: usize = 1024;
+pub(crate) const INSECURE_WARNING: &str =
+    "Warning: TLS certificate verification is disabled (danger_accept_invalid_certs).";
 
 #[derive(Clone)]
 pub struct TraceHttpClient {
     client: Client,
     base_url: Url,
     api_key: String,
+    auth_header: AuthHeader,
     project_id: String,
+    spans_path: String,
+    health_path: String,
+    compress: bool,
+    emit_timeout: Duration,
 }
 
 impl TraceHttpClient {
     pub fn new(config: &PulseConfig) -> Result<Self> {
         let base = normalize_base_url(&config.api_url)?;
-        let client = Client::builder()
-            .user_agent(USER_AGENT)
-            .timeout(DEFAULT_TIMEOUT)
-            .build()?;
+        let client = client_builder(config)?.build()?;
 
         Ok(Self {
             client,
             base_url: base,
-            api_key: config.api_key.clone(),
+            api_key: config.resolve_api_key()?,
+            auth_header: AuthHeader::parse(config.auth_header())?,
             project_id: config.project_id.clone(),
+            spans_path: validate_endpoint_path(config.spans_path())?.to_string(),
+            health_path: validate_endpoint_path(config.health_path())?.to_string(),
+            compress: config.compress(),
+            emit_timeout: config.emit_timeout(),
         })
     }
 
@@ -44,30 +61,300 @@ impl TraceHttpClient {
     }
 
     fn auth_headers(&self, builder: reqwest::RequestBuilder) -> reqwest::RequestBuilder {
-        builder
-            .header("Authorization", format!("Bearer {}", self.api_key))
-            .header("X-Project-Id", &self.project_id)
+        let builder = match &self.auth_header {
+            AuthHeader::Bearer => builder.header(AUTHORIZATION, format!("Bearer {}", self.api_key)),
+            AuthHeader::Named(name) => builder.header(name.clone(), &self.api_key),
+        };
+        builder.header("X-Project-Id", &self.project_id)
     }
 
     pub async fn health_check(&self) -> Result<()> {
-        let url = self.make_url("/health")?;
-        self.client.get(url).send().await?.error_for_status()?;
-        Ok(())
+        let url = self.make_url(&self.health_path)?;
+        let response = self
+            .client
+            .get(url.clone())
+            .send()
+            .await
+            .inspect_err(|err| log_transport_error("GET", &url, err))?;
+        finish("GET", response).await
+    }
+
+    /// Sends an empty batch through the authenticated spans endpoint and
+    /// returns the response status, so callers can tell whether the API key
+    /// and project id are accepted without recording anything.
+    pub async fn probe_spans_endpoint(&self) -> Result<StatusCode> {
+        let url = self.make_url(&self.spans_path)?;
+        let response = self
+            .auth_headers(self.client.post(url))
+            .json(&Vec::<SpanPayload>::new())
+            .send()
+            .await?;
+        Ok(response.status())
     }
 
     pub async fn post_spans(&self, spans: &[SpanPayload]) -> Result<()> {
         if spans.is_empty() {
             return Ok(());
         }
-        let url = self.make_url("/v1/spans/async")?;
-        self.auth_headers(self.client.post(url))
-            .timeout(EMIT_TIMEOUT)
-            .json(spans)
+        let (body, gzipped) = encode_body(spans, self.compress)?;
+        let key = idempotency_key(spans);
+        let response = self.send_spans(body, gzipped, &key).await?;
+        // A server without gzip support answers 415; send the batch again as
+        // plain JSON rather than losing it.
+        let response = if gzipped && response.status() == StatusCode::UNSUPPORTED_MEDIA_TYPE {
+            debug::log_http(
+                "POST",
+                response.url().as_str(),
+                "415 Unsupported Media Type, retrying uncompressed",
+                None,
+            );
+            self.send_spans(serde_json::to_vec(spans)?, false, &key)
+                .await?
+        } else {
+            response
+        };
+        finish("POST", response).await
+    }
+
+    async fn send_spans(&self, body: Vec<u8>, gzipped: bool, key: &str) -> Result<Response> {
+        let url = self.make_url(&self.spans_path)?;
+        let mut request = self
+            .auth_headers(self.client.post(url.clone()))
+            .timeout(self.emit_timeout)
+            .header(CONTENT_TYPE, "application/json")
+            .header(IDEMPOTENCY_KEY, key);
+        if gzipped {
+            request = request.header(CONTENT_ENCODING, "gzip");
+        }
+        Ok(request
+            .body(body)
             .send()
-            .await?
-            .error_for_status()?;
-        Ok(())
+            .await
+            .inspect_err(|err| log_transport_error("POST", &url, err))?)
+    }
+}
+
+/// The `Idempotency-Key` sent with a batch: a SHA-256 over its span ids, so
+/// an identical batch sent again (a retry) carries the same key and a server
+/// can drop the repeat. The offline queue and session buffers re-batch
+/// spans, which changes the key, so deduplication across those depends on
+/// `span_id`: it is generated once, when the event is recorded, and never
+/// changes afterwards.
+pub fn idempotency_key(spans: &[SpanPayload]) -> String {
+    let mut hasher = Sha256::new();
+    for span in spans {
+        hasher.update(span.span_id.as_bytes());
+        hasher.update(b"\n");
+    }
+    hasher
+        .finalize()
+        .iter()
+        .map(|byte| format!("{byte:02x}"))
+        .collect()
+}
+
+/// Where the API key goes on span requests, from the `auth_header` setting.
+#[derive(Debug, Clone, PartialEq)]
+pub(crate) enum AuthHeader {
+    /// `Authorization: Bearer <key>`
+    Bearer,
+    /// The bare key in a header of this name, e.g. `X-API-Key`.
+    Named(HeaderName),
+}
+
+impl AuthHeader {
+    pub(crate) fn parse(value: &str) -> Result<Self> {
+        let value = value.trim();
+        if value.eq_ignore_ascii_case("bearer") {
+            return Ok(Self::Bearer);
+        }
+        HeaderName::from_bytes(value.as_bytes())
+            .map(Self::Named)
+            .map_err(|_| {
+                PulseError::message(format!(
+                    "`{value}` is not `bearer` or a valid HTTP header name"
+                ))
+            })
+    }
+}
+
+/// Turns a non-success status into an error, writing the exchange to the
+/// debug log (with the response body on failure) when `PULSE_DEBUG` is on.
+pub(crate) async fn finish(method: &str, response: Response) -> Result<()> {
+    let url = response.url().to_string();
+    let status = response.status();
+    if let Some(err) = PulseError::from_response(&response) {
+        if debug::enabled() {
+            let body = response.text().await.ok();
+            debug::log_http(method, &url, &status.to_string(), body.as_deref());
+        }
+        return Err(err);
+    }
+    debug::log_http(method, &url, &status.to_string(), None);
+    Ok(())
+}
+
+pub(crate) fn log_transport_error(method: &str, url: &Url, err: &reqwest::Error) {
+    let outcome = format!("error: {}", describe_http_error(err));
+    debug::log_http(method, url.as_str(), &outcome, None);
+}
+
+/// Client builder with the pulse user agent and the transport settings from
+/// `config` applied: `user_agent_suffix`, `http_timeout_ms`, `proxy_url`,
+/// `ca_cert_path`, `client_cert_path`/`client_key_path`, and
+/// `danger_accept_invalid_certs`. Without a configured proxy, reqwest picks
+/// up `HTTPS_PROXY`/`NO_PROXY` on its own; with one, `NO_PROXY` still exempts
+/// matching hosts.
+pub(crate) fn client_builder(config: &PulseConfig) -> Result<ClientBuilder> {
+    let mut builder = Client::builder()
+        .user_agent(user_agent(config.user_agent_suffix.as_deref())?)
+        .timeout(config.http_timeout());
+    if let Some(proxy_url) = non_empty(config.proxy_url.as_deref()) {
+        builder = builder.proxy(parse_proxy(proxy_url)?);
+    }
+    if let Some(path) = non_empty(config.ca_cert_path.as_deref()) {
+        for certificate in load_ca_certs(path)? {
+            builder = builder.add_root_certificate(certificate);
+        }
+    }
+    let client_key = non_empty(config.client_key_path.as_deref());
+    match non_empty(config.client_cert_path.as_deref()) {
+        Some(cert_path) => builder = builder.identity(load_identity(cert_path, client_key)?),
+        None if client_key.is_some() => {
+            return Err(PulseError::message(
+                "client_key_path is set without client_cert_path; set both for a client certificate",
+            ));
+        }
+        None => {}
+    }
+    if config.accept_invalid_certs() {
+        builder = builder.danger_accept_invalid_certs(true);
+    }
+    Ok(builder)
+}
+
+/// `pulse-cli/<version>`, with the `user_agent_suffix` setting appended in
+/// parentheses so a proxy in front of many installs can tell them apart,
+/// e.g. `pulse-cli/0.3.0 (teamA)`.
+pub(crate) fn user_agent(suffix: Option<&str>) -> Result<String> {
+    match non_empty(suffix) {
+        Some(suffix) => {
+            validate_user_agent_suffix(suffix)?;
+            Ok(format!("{USER_AGENT} ({suffix})"))
+        }
+        None => Ok(USER_AGENT.to_string()),
+    }
+}
+
+/// Printable ASCII only, and no parentheses since the suffix is already
+/// wrapped in a pair. Anything else could not be sent as a header or would
+/// garble the product token.
+pub(crate) fn validate_user_agent_suffix(suffix: &str) -> Result<()> {
+    let bad = suffix
+        .chars()
+        .find(|c| !(c.is_ascii_graphic() || *c == ' ') || matches!(c, '(' | ')'));
+    match bad {
+        Some(c) => Err(PulseError::message(format!(
+            "user agent suffix `{suffix}` contains {c:?}; use printable ASCII without parentheses"
+        ))),
+        None => Ok(()),
+    }
+}
+
+fn non_empty(value: Option<&str>) -> Option<&str> {
+    value.map(str::trim).filter(|value| !value.is_empty())
+}
+
+pub(crate) fn parse_proxy(proxy_url: &str) -> Result<Proxy> {
+    let proxy = Proxy::all(proxy_url)
+        .map_err(|err| PulseError::message(format!("invalid proxy url `{proxy_url}`: {err}")))?;
+    Ok(proxy.no_proxy(NoProxy::from_env()))
+}
+
+/// Serializes `spans` as a JSON array, gzipped when `compress` is on and the
+/// body is large enough. Returns the bytes and whether they are gzipped.
+fn encode_body(spans: &[SpanPayload], compress: bool) -> Result<(Vec<u8>, bool)> {
+    let json = serde_json::to_vec(spans)?;
+    if !compress || json.len() < COMPRESS_MIN_BYTES {
+        return Ok((json, false));
+    }
+    let mut encoder = GzEncoder::new(Vec::new(), Compression::default());
+    encoder.write_all(&json)?;
+    Ok((encoder.finish()?, true))
+}
+
+pub(crate) fn load_ca_certs(path: &str) -> Result<Vec<Certificate>> {
+    let pem = fs::read(path)
+        .map_err(|err| PulseError::message(format!("cannot read CA certificate {path}: {err}")))?;
+    let certificates = Certificate::from_pem_bundle(&pem)
+        .map_err(|err| PulseError::message(format!("invalid CA certificate {path}: {err}")))?;
+    if certificates.is_empty() {
+        return Err(PulseError::message(format!(
+            "no PEM certificates found in {path}"
+        )));
     }
+    Ok(certificates)
+}
+
+/// The client certificate for mutual TLS: a PEM certificate (chain) with its
+/// private key, either in the same file or in `key_path`. PKCS#12 bundles
+/// need native TLS, which pulse is not built with, so they are turned away
+/// with the command that converts one.
+pub(crate) fn load_identity(cert_path: &str, key_path: Option<&str>) -> Result<Identity> {
+    let mut pem = fs::read(cert_path).map_err(|err| {
+        PulseError::message(format!("cannot read client certificate {cert_path}: {err}"))
+    })?;
+    if !String::from_utf8_lossy(&pem).contains("-----BEGIN ") {
+        return Err(PulseError::message(format!(
+            "client certificate {cert_path} is not PEM; PKCS#12 (.p12/.pfx) files are not supported, \
+             convert one with `openssl pkcs12 -in {cert_path} -out client.pem -nodes`"
+        )));
+    }
+    if let Some(key_path) = key_path {
+        let key = fs::read(key_path).map_err(|err| {
+            PulseError::message(format!("cannot read client key {key_path}: {err}"))
+        })?;
+        pem.push(b'\n');
+        pem.extend_from_slice(&key);
+    }
+    let key_source = key_path.unwrap_or(cert_path);
+    Identity::from_pem(&pem).map_err(|err| {
+        PulseError::message(format!(
+            "invalid client certificate {cert_path} or key {key_source}: {}",
+            root_cause(&err)
+        ))
+    })
+}
+
+/// Checks that the configured client key belongs to its certificate. The
+/// pair is only compared when a client is built, so this builds a throwaway
+/// one; `init`, `setup`, `config set`, and `doctor` call it, rather than
+/// every `emit` paying for a second client.
+pub(crate) fn check_client_identity(config: &PulseConfig) -> Result<()> {
+    let Some(cert_path) = non_empty(config.client_cert_path.as_deref()) else {
+        return Ok(());
+    };
+    let key_path = non_empty(config.client_key_path.as_deref());
+    let identity = load_identity(cert_path, key_path)?;
+    Client::builder()
+        .identity(identity)
+        .build()
+        .map_err(|err| {
+            PulseError::message(format!(
+                "client certificate {cert_path} and key {} do not match: {}",
+                key_path.unwrap_or(cert_path),
+                root_cause(&err)
+            ))
+        })?;
+    Ok(())
+}
+
+fn root_cause(err: &(dyn std::error::Error + 'static)) -> String {
+    let mut cause = err;
+    while let Some(source) = cause.source() {
+        cause = source;
+    }
+    cause.to_string()
 }
 
 fn normalize_base_url(raw: &str) -> Result<Url> {
@@ -75,7 +362,23 @@ fn normalize_base_url(raw: &str) -> Result<Url> {
     Url::parse(trimmed).map_err(|err| PulseError::message(format!("invalid API url: {err}")))
 }
 
-#[derive(Debug, Serialize)]
+/// Checks that an endpoint path from the config is relative to the API url,
+/// so a typo cannot send spans (and the API key) to another host.
+pub fn validate_endpoint_path(path: &str) -> Result<&str> {
+    let path = path.trim();
+    if path.is_empty() {
+        return Err(PulseError::message("endpoint path must not be empty"));
+    }
+    if path.starts_with("//") || Url::parse(path).is_ok() {
+        return Err(PulseError::message(format!(
+            "endpoint path `{path}` must be relative to the API url, e.g. /v1/spans/async"
+        )));
+    }
+    Ok(path)
+}
+
+/// One span as sent to the trace service (see "Span Schema" in the README).
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
 pub struct SpanPayload {
     pub span_id: String,
     pub session_id: String,
@@ -102,6 +405,9 @@ pub struct SpanPayload {
     pub is_interrupt: Option<bool>,
     #[serde(skip_serializing_if = "Option::is_none")]
     pub cwd: Option<String>,
+    /// Working directory of the tool call when it differs from `cwd`.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub tool_cwd: Option<String>,
     #[serde(skip_serializing_if = "Option::is_none")]
     pub model: Option<String>,
     #[serde(skip_serializing_if = "Option::is_none")]
@@ -109,3 +415,139 @@ pub struct SpanPayload {
     #[serde(skip_serializing_if = "Option::is_none")]
     pub metadata: Option<Value>,
 }
+
+#[cfg(test)]
+mod tests {
+    use std::io::Read;
+
+    use flate2::read::GzDecoder;
+
+    use super::*;
+
+    fn span(tool_input: Value) -> SpanPayload {
+        SpanPayload {
+            tool_input: Some(tool_input),
+            ..SpanPayload::test("span")
+        }
+    }
+
+    fn auth_request(auth_header: Option<&str>) -> reqwest::Request {
+        let config = PulseConfig {
+            api_url: "http://localhost:3000".to_string(),
+            api_key: "key_123".to_string(),
+            project_id: "proj_1".to_string(),
+            auth_header: auth_header.map(str::to_string),
+            ..PulseConfig::default()
+        };
+        let client = TraceHttpClient::new(&config).unwrap();
+        client
+            .auth_headers(client.client.post(client.base_url.clone()))
+            .build()
+            .unwrap()
+    }
+
+    #[test]
+    fn test_auth_header_schemes() {
+        for scheme in [None, Some("bearer"), Some("Bearer")] {
+            let request = auth_request(scheme);
+            assert_eq!(request.headers()[AUTHORIZATION], "Bearer key_123");
+            assert!(request.headers().get("x-api-key").is_none());
+        }
+
+        let request = auth_request(Some("x-api-key"));
+        assert_eq!(request.headers()["X-API-Key"], "key_123");
+        assert!(request.headers().get(AUTHORIZATION).is_none());
+
+        let request = auth_request(Some("X-Gateway-Token"));
+        assert_eq!(request.headers()["x-gateway-token"], "key_123");
+        assert_eq!(request.headers()["x-project-id"], "proj_1");
+    }
+
+    #[test]
+    fn test_auth_header_must_be_a_header_name() {
+        assert!(AuthHeader::parse("").is_err());
+        assert!(AuthHeader::parse("X API Key").is_err());
+        assert!(AuthHeader::parse("x-api-key:").is_err());
+        assert_eq!(AuthHeader::parse(" bearer ").unwrap(), AuthHeader::Bearer);
+    }
+
+    #[test]
+    fn test_compressed_body_round_trips() {
+        let spans = vec![span(Value::String("x".repeat(4096))); 3];
+        let (body, gzipped) = encode_body(&spans, true).unwrap();
+        assert!(gzipped);
+
+        let mut decoded = String::new();
+        GzDecoder::new(body.as_slice())
+            .read_to_string(&mut decoded)
+            .unwrap();
+        let decoded: Vec<SpanPayload> = serde_json::from_str(&decoded).unwrap();
+        assert_eq!(decoded.len(), 3);
+        assert_eq!(decoded[0].tool_input, spans[0].tool_input);
+    }
+
+    #[test]
+    fn test_small_or_disabled_bodies_are_plain() {
+        let small = vec![span(Value::Null)];
+        assert!(!encode_body(&small, true).unwrap().1);
+
+        let large = vec![span(Value::String("x".repeat(4096)))];
+        let (body, gzipped) = encode_body(&large, false).unwrap();
+        assert!(!gzipped);
+        assert_eq!(body, serde_json::to_vec(&large).unwrap());
+    }
+
+    const FIXTURES: &str = concat!(env!("CARGO_MANIFEST_DIR"), "/tests/fixtures");
+
+    fn client_config(cert: Option<&str>, key: Option<&str>) -> PulseConfig {
+        PulseConfig {
+            client_cert_path: cert.map(|name| format!("{FIXTURES}/{name}")),
+            client_key_path: key.map(|name| format!("{FIXTURES}/{name}")),
+            ..PulseConfig::default()
+        }
+    }
+
+    #[test]
+    fn test_client_builds_with_client_certificate() {
+        let config = client_config(Some("client.pem"), Some("client.key"));
+        client_builder(&config).unwrap().build().unwrap();
+
+        // Certificate and key in one file.
+        let tmp = tempfile::TempDir::new().unwrap();
+        let combined = tmp.path().join("client-with-key.pem");
+        let mut pem = fs::read(format!("{FIXTURES}/client.pem")).unwrap();
+        pem.extend(fs::read(format!("{FIXTURES}/client.key")).unwrap());
+        fs::write(&combined, pem).unwrap();
+        load_identity(combined.to_str().unwrap(), None).unwrap();
+    }
+
+    #[test]
+    fn test_unusable_client_certificate_is_reported() {
+        let err = |config: PulseConfig| client_builder(&config).unwrap_err().to_string();
+
+        let mismatched =
+            check_client_identity(&client_config(Some("client.pem"), Some("other.key")))
+                .unwrap_err()
+                .to_string();
+        assert!(mismatched.contains("do not match"), "{mismatched}");
+        check_client_identity(&client_config(Some("client.pem"), Some("client.key"))).unwrap();
+        check_client_identity(&PulseConfig::default()).unwrap();
+        let missing = err(client_config(Some("missing.pem"), Some("client.key")));
+        assert!(
+            missing.contains("cannot read client certificate"),
+            "{missing}"
+        );
+        let no_key = err(client_config(Some("client.pem"), None));
+        assert!(no_key.contains("invalid client certificate"), "{no_key}");
+        let key_only = err(client_config(None, Some("client.key")));
+        assert!(key_only.contains("without client_cert_path"), "{key_only}");
+
+        let tmp = tempfile::TempDir::new().unwrap();
+        let p12 = tmp.path().join("client.p12");
+        fs::write(&p12, [0x30, 0x82, 0x01, 0x00]).unwrap();
+        let pkcs12 = load_identity(p12.to_str().unwrap(), None)
+            .unwrap_err()
+            .to_string();
+        assert!(pkcs12.contains("openssl pkcs12"), "{pkcs12}");
+    }
+}
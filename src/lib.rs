@@ -1,5 +1,78 @@
+//! The library behind the `pulse` binary. Besides the commands, a few
+//! pieces are meant to be used directly by tools that embed pulse instead
+//! of running it:
+//!
+//! - Hook detection: [`commands::registered_hooks`] (or
+//!   [`commands::registered_hooks_with`] and [`commands::HookOptions`]) returns
+//!   a [`hooks::ToolHook`] per supported agent, and each reports a
+//!   [`hooks::HookStatus`]. The concrete hooks in [`hooks`] can also be built
+//!   for a given location, e.g. [`hooks::CursorHook::with_config_dir`].
+//! - Span extraction: [`hooks::span::extract`] reads one hook payload into
+//!   [`hooks::span::SpanFields`], and [`hooks::span::SpanFields::into_span`]
+//!   turns those into an [`http::SpanPayload`]. [`commands::emit::build_span`]
+//!   does everything `pulse emit` does to an event (metadata, redaction,
+//!   truncation, sampling), given a [`config::PulseConfig`].
+//! - Sending: [`http::TraceHttpClient`].
+//!
+//! Listing the hooks reads the agents' configuration but changes nothing:
+//!
+//! ```no_run
+//! use pulse::{commands::registered_hooks, hooks::ToolHook};
+//!
+//! for hook in registered_hooks()? {
+//!     let status = hook.status()?;
+//!     println!("{}: detected={} connected={}", hook.id(), status.detected, status.connected);
+//! }
+//! # Ok::<(), pulse::error::PulseError>(())
+//! ```
+//!
+//! Extraction is pure:
+//!
+//! ```
+//! use pulse::hooks::span;
+//! use serde_json::json;
+//!
+//! let payload = json!({"session_id": "s1", "tool_name": "Bash", "tool_use_id": "t1"});
+//! let fields = span::extract("pre_tool_use", &payload);
+//! assert_eq!(fields.tool_name.as_deref(), Some("Bash"));
+//!
+//! let span = fields
+//!     .into_span(
+//!         "span-1".to_string(),
+//!         "2025-01-01T00:00:00Z".to_string(),
+//!         "pre_tool_use".to_string(),
+//!         "claude_code".to_string(),
+//!         None,
+//!         span::MappingOverrides::default(),
+//!     )
+//!     .unwrap();
+//! assert_eq!(span.kind, "tool_use");
+//! ```
+//!
+//! Everything else is public for the binary's sake and may change between
+//! releases.
+
+pub mod breaker;
 pub mod commands;
 pub mod config;
+pub mod correlation;
+pub mod debug;
+pub mod emit_log;
 pub mod error;
+pub mod export;
+pub mod git;
+pub mod health;
+pub mod heartbeat;
+pub mod history;
 pub mod hooks;
 pub mod http;
+pub mod net;
+pub mod output;
+pub mod paths;
+pub mod queue;
+pub mod record;
+pub mod redact;
+pub mod sampling;
+pub mod source;
+#[cfg(test)]
+mod test_support;
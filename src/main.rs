@@ -1,11 +1,18 @@
-use clap::{Parser, Subcommand};
-use std::process::ExitCode;
+use clap::{CommandFactory, Parser, Subcommand};
+use clap_complete::Shell;
+use std::{io, process::ExitCode};
 
 use pulse::commands::{
-    DashboardArgs, EmitArgs, InitArgs, SetupArgs, run_connect, run_dashboard, run_disconnect,
-    run_emit, run_init, run_setup, run_status,
+    ConfigArgs, ConnectArgs, DaemonArgs, DashboardArgs, DisconnectArgs, EmitArgs, ExportArgs,
+    FlushArgs, HooksArgs, InitArgs, LogsArgs, MigrateConfigArgs, PingArgs, ProjectArgs,
+    ReconnectArgs, ReplayArgs, RestoreArgs, SessionsArgs, SetupArgs, StatusArgs, TailArgs,
+    UninstallArgs, run_config, run_connect, run_daemon, run_dashboard, run_disconnect, run_doctor,
+    run_emit, run_export, run_flush, run_hooks, run_init, run_logs, run_migrate_config, run_ping,
+    run_project, run_reconnect, run_replay, run_restore, run_sessions, run_setup, run_status,
+    run_tail, run_uninstall, run_version,
 };
 use pulse::error::Result;
+use pulse::output::{self, ColorChoice, Verbosity};
 
 #[derive(Parser, Debug)]
 #[command(
@@ -14,6 +21,18 @@ use pulse::error::Result;
     version
 )]
 struct Cli {
+    /// Print machine-readable JSON (status, connect, disconnect, reconnect, restore, ping, project list, hooks list, sessions, logs, migrate-config, version)
+    #[arg(long, global = true)]
+    json: bool,
+    /// Print only results and errors
+    #[arg(long, short, global = true, conflicts_with = "verbose")]
+    quiet: bool,
+    /// Also print resolved paths and request URLs (on stderr)
+    #[arg(long, short, global = true)]
+    verbose: bool,
+    /// Color status, connect, and disconnect summaries (auto: only on a terminal without NO_COLOR)
+    #[arg(long, global = true, value_enum, default_value_t = ColorChoice::Auto)]
+    color: ColorChoice,
     #[command(subcommand)]
     command: Commands,
 }
@@ -23,24 +42,72 @@ enum Commands {
     Init(InitArgs),
     Setup(SetupArgs),
     Dashboard(DashboardArgs),
-    Connect,
-    Disconnect,
-    Status,
+    Config(ConfigArgs),
+    MigrateConfig(MigrateConfigArgs),
+    Connect(ConnectArgs),
+    Disconnect(DisconnectArgs),
+    Reconnect(ReconnectArgs),
+    Status(StatusArgs),
+    Hooks(HooksArgs),
+    Doctor,
     Emit(EmitArgs),
+    Flush(FlushArgs),
+    Daemon(DaemonArgs),
+    Export(ExportArgs),
+    Ping(PingArgs),
+    Project(ProjectArgs),
+    Replay(ReplayArgs),
+    Restore(RestoreArgs),
+    Sessions(SessionsArgs),
+    Tail(TailArgs),
+    Logs(LogsArgs),
+    Uninstall(UninstallArgs),
+    /// Print the version; with --verbose also the build, config file, and detected tools
+    Version,
+    /// Print a shell completion script (e.g. `pulse completions zsh > ~/.zfunc/_pulse`)
+    #[command(hide = true)]
+    Completions {
+        shell: Shell,
+    },
 }
 
 #[tokio::main(flavor = "current_thread")]
 async fn main() -> ExitCode {
-    let cli = Cli::parse();
+    let cli = match Cli::try_parse() {
+        Ok(cli) => cli,
+        Err(err) => return usage_error(err),
+    };
+    output::set_verbosity(Verbosity::from_flags(cli.quiet, cli.verbose));
+    output::set_color(cli.color);
     let result: Result<()> = match cli.command {
         Commands::Init(args) => run_init(args).await,
         Commands::Setup(args) => run_setup(args).await,
-        Commands::Dashboard(args) => run_dashboard(args).await,
-        Commands::Connect => run_connect(),
-        Commands::Disconnect => run_disconnect(),
-        Commands::Status => run_status().await,
-        Commands::Emit(args) => {
-            run_emit(args).await;
+        Commands::Dashboard(args) => run_dashboard(args, cli.json).await,
+        Commands::Config(args) => run_config(args),
+        Commands::MigrateConfig(args) => run_migrate_config(args, cli.json),
+        Commands::Connect(args) => run_connect(args, cli.json),
+        Commands::Disconnect(args) => run_disconnect(args, cli.json),
+        Commands::Reconnect(args) => run_reconnect(args, cli.json),
+        Commands::Status(args) => run_status(args, cli.json).await,
+        Commands::Hooks(args) => run_hooks(args, cli.json),
+        Commands::Doctor => run_doctor().await,
+        Commands::Emit(args) => run_emit(args).await,
+        Commands::Flush(args) => run_flush(args).await,
+        Commands::Daemon(args) => run_daemon(args).await,
+        Commands::Export(args) => run_export(args),
+        Commands::Ping(args) => run_ping(args, cli.json).await,
+        Commands::Project(args) => run_project(args, cli.json),
+        Commands::Replay(args) => run_replay(args).await,
+        Commands::Restore(args) => run_restore(args, cli.json),
+        Commands::Sessions(args) => run_sessions(args, cli.json),
+        Commands::Tail(args) => run_tail(args).await,
+        Commands::Logs(args) => run_logs(args, cli.json),
+        Commands::Uninstall(args) => run_uninstall(args),
+        Commands::Version => run_version(cli.json),
+        Commands::Completions { shell } => {
+            // Built from the same derive as the parser, so new subcommands
+            // and flags are always included.
+            clap_complete::generate(shell, &mut Cli::command(), "pulse", &mut io::stdout());
             Ok(())
         }
     };
@@ -48,8 +115,33 @@ async fn main() -> ExitCode {
     match result {
         Ok(()) => ExitCode::SUCCESS,
         Err(err) => {
-            eprintln!("Error: {err}");
+            if cli.json {
+                println!("{}", serde_json::json!({ "error": err.to_string() }));
+            } else {
+                eprintln!("Error: {err}");
+            }
             ExitCode::FAILURE
         }
     }
 }
+
+/// Reports a command-line error, or prints `--help`/`--version`. With
+/// `--json` on the command line, errors come out as `{"error": "..."}` on
+/// stdout like every other error.
+fn usage_error(err: clap::Error) -> ExitCode {
+    let wants_json = std::env::args_os()
+        .skip(1)
+        .take_while(|arg| arg != "--")
+        .any(|arg| arg == "--json");
+    if !wants_json || !err.use_stderr() {
+        err.exit();
+    }
+    let rendered = err.to_string();
+    let message = rendered
+        .lines()
+        .next()
+        .unwrap_or_default()
+        .trim_start_matches("error: ");
+    println!("{}", serde_json::json!({ "error": message }));
+    ExitCode::from(2)
+}
@@ -0,0 +1,75 @@
+//! Host checks shared by commands that treat a local trace service
+//! differently from a hosted one.
+
+use std::net::IpAddr;
+
+use reqwest::Url;
+
+/// Whether `url` points at this machine: `localhost` and any `*.localhost`
+/// name, the loopback ranges (`127.0.0.0/8`, `::1`), or the unspecified
+/// address (`0.0.0.0`, `::`) that local servers often bind to. Anything
+/// else, including names that merely contain "localhost", is remote.
+pub fn is_local_host(url: &Url) -> bool {
+    let Some(host) = url.host_str() else {
+        return false;
+    };
+    // IPv6 hosts come bracketed, `[::1]`.
+    let host = host.trim_start_matches('[').trim_end_matches(']');
+    if let Ok(ip) = host.parse::<IpAddr>() {
+        let ip = ip.to_canonical();
+        return ip.is_loopback() || ip.is_unspecified();
+    }
+    let host = host.strip_suffix('.').unwrap_or(host);
+    host == "localhost" || host.ends_with(".localhost")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn local(url: &str) -> bool {
+        is_local_host(&Url::parse(url).unwrap())
+    }
+
+    #[test]
+    fn test_local_hosts() {
+        for url in [
+            "http://localhost:3000",
+            "http://LOCALHOST",
+            "http://localhost.:3000",
+            "http://foo.localhost:3000",
+            "http://a.b.localhost",
+            "http://127.0.0.1:3000",
+            "http://127.0.0.2",
+            "http://127.255.255.254",
+            "http://127.1",
+            "http://0.0.0.0:3000",
+            "http://[::1]:3000",
+            "http://[::]:3000",
+            "http://[::ffff:127.0.0.1]",
+        ] {
+            assert!(local(url), "{url} should be local");
+        }
+    }
+
+    #[test]
+    fn test_remote_hosts() {
+        for url in [
+            "https://trace.example.com",
+            "http://localhost.example.com",
+            "http://localhost-proxy:3000",
+            "http://mylocalhost",
+            "http://foolocalhost",
+            "http://127.0.0.1.nip.io",
+            "http://128.0.0.1",
+            "http://10.0.0.1",
+            "http://192.168.1.10",
+            "http://0.0.0.1",
+            "http://[::2]",
+            "http://[2001:db8::1]",
+            "file:///tmp/trace",
+        ] {
+            assert!(!local(url), "{url} should be remote");
+        }
+    }
+}
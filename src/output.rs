@@ -0,0 +1,239 @@
+//! How much the commands print, from the global `--quiet` and `--verbose`
+//! flags. Results, warnings, and errors always print; progress and hints go
+//! through [`info!`](crate::info) and extra detail through
+//! [`verbose!`](crate::verbose). Also whether human-readable summaries are
+//! colored, from `--color`, and the helpers that color them.
+
+use std::{
+    env,
+    io::{self, IsTerminal},
+    sync::atomic::{AtomicBool, AtomicU8, Ordering},
+};
+
+use clap::ValueEnum;
+use serde::Serialize;
+
+use crate::error::Result;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum Verbosity {
+    /// Only results and errors (`--quiet`).
+    Quiet,
+    Normal,
+    /// Also resolved paths and request URLs (`--verbose`).
+    Verbose,
+}
+
+static LEVEL: AtomicU8 = AtomicU8::new(Verbosity::Normal as u8);
+
+impl Verbosity {
+    pub fn from_flags(quiet: bool, verbose: bool) -> Self {
+        match (quiet, verbose) {
+            (true, _) => Self::Quiet,
+            (false, true) => Self::Verbose,
+            (false, false) => Self::Normal,
+        }
+    }
+}
+
+/// Sets the level for the rest of the process; `main` calls it once.
+pub fn set_verbosity(level: Verbosity) {
+    LEVEL.store(level as u8, Ordering::Relaxed);
+}
+
+pub fn verbosity() -> Verbosity {
+    match LEVEL.load(Ordering::Relaxed) {
+        0 => Verbosity::Quiet,
+        1 => Verbosity::Normal,
+        _ => Verbosity::Verbose,
+    }
+}
+
+/// Whether output meant for `level` is shown.
+pub fn shows(level: Verbosity) -> bool {
+    verbosity() >= level
+}
+
+/// When summaries are colored (`--color`).
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, ValueEnum)]
+pub enum ColorChoice {
+    /// When stdout is a terminal and `NO_COLOR` is not set.
+    #[default]
+    Auto,
+    Always,
+    Never,
+}
+
+static COLOR: AtomicBool = AtomicBool::new(false);
+
+/// Sets coloring for the rest of the process; `main` calls it once.
+pub fn set_color(choice: ColorChoice) {
+    let no_color = env::var_os("NO_COLOR").is_some_and(|value| !value.is_empty())
+        || env::var_os("TERM").is_some_and(|term| term == "dumb");
+    COLOR.store(
+        color_for(choice, no_color, io::stdout().is_terminal()),
+        Ordering::Relaxed,
+    );
+}
+
+fn color_for(choice: ColorChoice, no_color: bool, terminal: bool) -> bool {
+    match choice {
+        ColorChoice::Always => true,
+        ColorChoice::Never => false,
+        ColorChoice::Auto => terminal && !no_color,
+    }
+}
+
+pub fn color_enabled() -> bool {
+    COLOR.load(Ordering::Relaxed)
+}
+
+/// What a summary line reports, which picks its color and symbol.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Tone {
+    /// Connected, reachable, done.
+    Good,
+    /// Something to fix.
+    Bad,
+    Warning,
+}
+
+impl Tone {
+    fn ansi(self) -> &'static str {
+        match self {
+            Self::Good => "32",
+            Self::Bad => "31",
+            Self::Warning => "33",
+        }
+    }
+
+    fn symbol(self) -> &'static str {
+        match self {
+            Self::Good => "✓",
+            Self::Bad => "✗",
+            Self::Warning => "!",
+        }
+    }
+}
+
+/// `text` in the tone's color, or unchanged when color is off.
+pub fn paint(tone: Tone, text: &str) -> String {
+    if color_enabled() {
+        format!("\x1b[{}m{text}\x1b[0m", tone.ansi())
+    } else {
+        text.to_string()
+    }
+}
+
+/// A list bullet: the tone's colored symbol, or `-` when color is off.
+pub fn bullet(tone: Tone) -> String {
+    if color_enabled() {
+        paint(tone, tone.symbol())
+    } else {
+        "-".to_string()
+    }
+}
+
+/// The tone's colored symbol and a space, to lead a line that has no
+/// bullet; empty when color is off, so plain output is unchanged.
+pub fn mark(tone: Tone) -> String {
+    if color_enabled() {
+        format!("{} ", paint(tone, tone.symbol()))
+    } else {
+        String::new()
+    }
+}
+
+/// How JSON meant for people or for other tools is laid out.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, ValueEnum)]
+pub enum JsonFormat {
+    /// Indented, for reading.
+    #[default]
+    Pretty,
+    /// One line per value, for `jq -c` and other line-based tools.
+    Compact,
+}
+
+impl JsonFormat {
+    /// `pretty` or `compact`, ignoring case; anything else is `None`.
+    pub fn parse(value: &str) -> Option<Self> {
+        Self::from_str(value.trim(), true).ok()
+    }
+
+    pub fn to_string(self, value: &impl Serialize) -> Result<String> {
+        Ok(match self {
+            Self::Pretty => serde_json::to_string_pretty(value)?,
+            Self::Compact => serde_json::to_string(value)?,
+        })
+    }
+}
+
+/// Prints a progress or informational line to stdout, unless `--quiet`.
+#[macro_export]
+macro_rules! info {
+    ($($arg:tt)*) => {
+        if $crate::output::shows($crate::output::Verbosity::Normal) {
+            println!($($arg)*);
+        }
+    };
+}
+
+/// Prints a detail line to stderr with `--verbose`, so it never mixes into
+/// `--json` output.
+#[macro_export]
+macro_rules! verbose {
+    ($($arg:tt)*) => {
+        if $crate::output::shows($crate::output::Verbosity::Verbose) {
+            eprintln!($($arg)*);
+        }
+    };
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_from_flags() {
+        assert_eq!(Verbosity::from_flags(true, true), Verbosity::Quiet);
+        assert_eq!(Verbosity::from_flags(false, true), Verbosity::Verbose);
+        assert_eq!(Verbosity::from_flags(false, false), Verbosity::Normal);
+        assert!(Verbosity::Verbose > Verbosity::Normal);
+        assert!(Verbosity::Normal > Verbosity::Quiet);
+    }
+
+    #[test]
+    fn test_color_choice() {
+        assert!(color_for(ColorChoice::Auto, false, true));
+        assert!(!color_for(ColorChoice::Auto, false, false));
+        assert!(!color_for(ColorChoice::Auto, true, true));
+        assert!(color_for(ColorChoice::Always, true, false));
+        assert!(!color_for(ColorChoice::Never, false, true));
+
+        // Color is off unless `main` turns it on, so text is left as is.
+        assert_eq!(paint(Tone::Good, "connected"), "connected");
+        assert_eq!(bullet(Tone::Bad), "-");
+        assert_eq!(mark(Tone::Warning), "");
+    }
+
+    #[test]
+    fn test_json_formats_hold_the_same_value() {
+        let value =
+            serde_json::json!({ "session_id": "s1", "tool_input": { "command": "ls\n-la" } });
+        let pretty = JsonFormat::Pretty.to_string(&value).unwrap();
+        let compact = JsonFormat::Compact.to_string(&value).unwrap();
+        assert!(pretty.lines().count() > 1);
+        assert_eq!(compact.lines().count(), 1);
+        assert_eq!(
+            serde_json::from_str::<serde_json::Value>(&pretty).unwrap(),
+            value
+        );
+        assert_eq!(
+            serde_json::from_str::<serde_json::Value>(&compact).unwrap(),
+            value
+        );
+
+        assert_eq!(JsonFormat::parse(" Compact"), Some(JsonFormat::Compact));
+        assert_eq!(JsonFormat::parse("yaml"), None);
+    }
+}
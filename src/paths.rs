@@ -0,0 +1,97 @@
+//! Where pulse keeps its local state. Everything lives under `~/.pulse`
+//! except the debug log, which `PULSE_DEBUG_LOG` can move.
+
+use std::{env, path::PathBuf};
+
+use dirs::home_dir;
+
+use crate::{config::ConfigStore, error::Result};
+
+pub(crate) const QUEUE_FILE: &str = "queue.ndjson";
+pub(crate) const BUFFER_DIR: &str = "buffer";
+pub(crate) const PENDING_DIR: &str = "pending";
+pub(crate) const HISTORY_FILE: &str = "history.ndjson";
+pub(crate) const FLUSH_LOCK_FILE: &str = "flush.lock";
+pub(crate) const SESSION_CACHE_FILE: &str = "session.json";
+pub(crate) const DAEMON_SOCKET_FILE: &str = "daemon.sock";
+pub(crate) const RECORD_FILE: &str = "sent.ndjson";
+pub(crate) const BREAKER_FILE: &str = "breaker.json";
+pub(crate) const SERVER_LOG_FILE: &str = "server.log";
+pub(crate) const EMIT_LOG_FILE: &str = "emit.ndjson";
+pub const DEBUG_LOG_ENV: &str = "PULSE_DEBUG_LOG";
+pub const RECORD_LOG_ENV: &str = "PULSE_RECORD_LOG";
+pub const EMIT_LOG_ENV: &str = "PULSE_LOG_FILE";
+
+/// Spans waiting to be sent (`queue.ndjson`).
+pub fn queue_file() -> Result<PathBuf> {
+    Ok(ConfigStore::config_dir()?.join(QUEUE_FILE))
+}
+
+/// Per-session span buffers (`buffer/`).
+pub fn buffer_dir() -> Result<PathBuf> {
+    Ok(ConfigStore::config_dir()?.join(BUFFER_DIR))
+}
+
+/// Correlation store for tool durations and session parents (`pending/`).
+pub fn pending_dir() -> Result<PathBuf> {
+    Ok(ConfigStore::config_dir()?.join(PENDING_DIR))
+}
+
+/// Local span history behind `pulse sessions list` (`history.ndjson`).
+pub fn history_file() -> Result<PathBuf> {
+    Ok(ConfigStore::config_dir()?.join(HISTORY_FILE))
+}
+
+/// Held by whichever `pulse flush` is sending the offline queue (`flush.lock`).
+pub fn flush_lock() -> Result<PathBuf> {
+    Ok(ConfigStore::config_dir()?.join(FLUSH_LOCK_FILE))
+}
+
+/// Session cookie cached by `pulse setup` (`session.json`).
+pub fn session_cache() -> Result<PathBuf> {
+    Ok(ConfigStore::config_dir()?.join(SESSION_CACHE_FILE))
+}
+
+/// Unix socket `pulse daemon` listens on (`daemon.sock`).
+pub fn daemon_socket() -> Result<PathBuf> {
+    Ok(ConfigStore::config_dir()?.join(DAEMON_SOCKET_FILE))
+}
+
+/// Circuit breaker state shared by emits (`breaker.json`).
+pub fn breaker_state() -> Result<PathBuf> {
+    Ok(ConfigStore::config_dir()?.join(BREAKER_FILE))
+}
+
+/// Output of the `pulse-server` that `pulse setup` started (`server.log`).
+pub fn server_log() -> Result<PathBuf> {
+    Ok(ConfigStore::config_dir()?.join(SERVER_LOG_FILE))
+}
+
+/// Record of spans the trace service accepted, with `record_spans`:
+/// `PULSE_RECORD_LOG`, else `~/.pulse/sent.ndjson`.
+pub fn record_log() -> Result<PathBuf> {
+    if let Ok(path) = env::var(RECORD_LOG_ENV) {
+        return Ok(PathBuf::from(path));
+    }
+    Ok(ConfigStore::config_dir()?.join(RECORD_FILE))
+}
+
+/// Structured log of emit outcomes, with `PULSE_LOG_JSON`:
+/// `PULSE_LOG_FILE`, else `~/.pulse/emit.ndjson`.
+pub fn emit_log() -> Result<PathBuf> {
+    if let Ok(path) = env::var(EMIT_LOG_ENV) {
+        return Ok(PathBuf::from(path));
+    }
+    Ok(ConfigStore::config_dir()?.join(EMIT_LOG_FILE))
+}
+
+/// Where the debug log is written: `PULSE_DEBUG_LOG`, else
+/// `~/.pulse/debug.log`, else `/tmp/pulse-debug.log` without a home dir.
+pub fn debug_log() -> PathBuf {
+    if let Ok(path) = env::var(DEBUG_LOG_ENV) {
+        return PathBuf::from(path);
+    }
+    home_dir()
+        .map(|h| h.join(".pulse/debug.log"))
+        .unwrap_or_else(|| PathBuf::from("/tmp/pulse-debug.log"))
+}
@@ -0,0 +1,530 @@
+use std::{
+    collections::HashSet,
+    fs::{self, File, OpenOptions},
+    io::{BufRead, BufReader, ErrorKind, Read, Seek, SeekFrom, Write},
+    path::{Path, PathBuf},
+};
+
+use crate::{correlation::file_safe_key, error::Result, http::SpanPayload, paths};
+
+const BUFFER_EXTENSION: &str = "ndjson";
+pub const MAX_QUEUE_ENTRIES: usize = 10_000;
+/// Length of the shortest line a serialized span can be (every required
+/// field empty, without the newline). Below `max_entries` times this the file cannot be over the
+/// cap, so appends skip counting its lines.
+const MIN_LINE_BYTES: u64 = 95;
+
+/// Newline-delimited JSON file holding spans that could not be delivered.
+///
+/// Every operation takes an exclusive lock on the file so concurrent
+/// `pulse emit` processes can append safely. Appends only add to the end;
+/// anything that drops lines writes a new file and renames it into place, so
+/// a crash part way through leaves the queue as it was.
+#[derive(Debug, Clone)]
+pub struct SpanQueue {
+    path: PathBuf,
+    max_entries: usize,
+}
+
+impl SpanQueue {
+    pub fn new() -> Result<Self> {
+        Ok(Self {
+            path: paths::queue_file()?,
+            max_entries: MAX_QUEUE_ENTRIES,
+        })
+    }
+
+    pub fn at(path: PathBuf) -> Self {
+        Self {
+            path,
+            max_entries: MAX_QUEUE_ENTRIES,
+        }
+    }
+
+    pub fn path(&self) -> &PathBuf {
+        &self.path
+    }
+
+    /// Appends one span; see `enqueue_all`.
+    pub fn enqueue(&self, span: &SpanPayload) -> Result<usize> {
+        self.enqueue_all(std::slice::from_ref(span))
+    }
+
+    /// Appends `spans`, then drops the oldest entries beyond `max_entries`.
+    /// Returns how many were dropped.
+    pub fn enqueue_all(&self, spans: &[SpanPayload]) -> Result<usize> {
+        if spans.is_empty() {
+            return Ok(0);
+        }
+        let mut appended = String::new();
+        for span in spans {
+            appended.push_str(&serde_json::to_string(span)?);
+            appended.push('\n');
+        }
+        let mut file = self.open_locked()?;
+        file.write_all(appended.as_bytes())?;
+        file.flush()?;
+
+        if file.metadata()?.len() <= self.max_entries as u64 * MIN_LINE_BYTES
+            || count_lines(&mut file)? <= self.max_entries
+        {
+            return Ok(0);
+        }
+        // Drop the oldest entries so the file stays bounded.
+        let mut lines = read_lines(&mut file)?;
+        let overflow = lines.len().saturating_sub(self.max_entries);
+        lines.drain(..overflow);
+        self.replace(&lines)?;
+        Ok(overflow)
+    }
+
+    /// Removes and returns every queued span. Lines that no longer parse are
+    /// discarded.
+    pub fn drain(&self) -> Result<Vec<SpanPayload>> {
+        if !self.path.exists() {
+            return Ok(Vec::new());
+        }
+        let mut file = self.open_locked()?;
+        let lines = read_lines(&mut file)?;
+        self.replace(&[])?;
+        Ok(lines
+            .iter()
+            .filter_map(|line| serde_json::from_str(line).ok())
+            .collect())
+    }
+
+    /// Like `drain`, but deletes the file while it is still locked, so an
+    /// append waiting on the lock starts a new file instead of writing to
+    /// the deleted one.
+    pub fn take(&self) -> Result<Vec<SpanPayload>> {
+        if !self.path.exists() {
+            return Ok(Vec::new());
+        }
+        let mut file = self.open_locked()?;
+        let lines = read_lines(&mut file)?;
+        fs::remove_file(&self.path)?;
+        Ok(lines
+            .iter()
+            .filter_map(|line| serde_json::from_str(line).ok())
+            .collect())
+    }
+
+    /// Appends every span here to `queue`, then deletes this file. The lock
+    /// is held throughout, so a failed append leaves the spans here and
+    /// nothing written in the meantime is lost. Returns how many entries
+    /// `queue` dropped to stay under its cap.
+    pub fn move_to(&self, queue: &SpanQueue) -> Result<usize> {
+        if !self.path.exists() {
+            return Ok(0);
+        }
+        let mut file = self.open_locked()?;
+        let spans: Vec<SpanPayload> = read_lines(&mut file)?
+            .iter()
+            .filter_map(|line| serde_json::from_str(line).ok())
+            .collect();
+        let dropped = queue.enqueue_all(&spans)?;
+        fs::remove_file(&self.path)?;
+        Ok(dropped)
+    }
+
+    /// Every queued span, leaving the queue as it is.
+    pub fn peek(&self) -> Result<Vec<SpanPayload>> {
+        if !self.path.exists() {
+            return Ok(Vec::new());
+        }
+        let mut file = self.open_locked()?;
+        Ok(read_lines(&mut file)?
+            .iter()
+            .filter_map(|line| serde_json::from_str(line).ok())
+            .collect())
+    }
+
+    /// Deletes the entries for `spans` (matched by `span_id`), once they have
+    /// been sent or dropped, keeping anything queued since they were read.
+    /// Lines that no longer parse are discarded too.
+    pub fn remove(&self, spans: &[SpanPayload]) -> Result<()> {
+        if spans.is_empty() || !self.path.exists() {
+            return Ok(());
+        }
+        let ids: HashSet<&str> = spans.iter().map(|span| span.span_id.as_str()).collect();
+        let mut file = self.open_locked()?;
+        let kept: Vec<String> = read_lines(&mut file)?
+            .into_iter()
+            .filter(|line| {
+                serde_json::from_str::<SpanPayload>(line)
+                    .is_ok_and(|span| !ids.contains(span.span_id.as_str()))
+            })
+            .collect();
+        self.replace(&kept)
+    }
+
+    pub fn len(&self) -> Result<usize> {
+        if !self.path.exists() {
+            return Ok(0);
+        }
+        let mut file = self.open_locked()?;
+        Ok(read_lines(&mut file)?.len())
+    }
+
+    pub fn is_empty(&self) -> Result<bool> {
+        Ok(self.len()? == 0)
+    }
+
+    /// Opens and locks the file. If it was replaced or deleted while this
+    /// process waited for the lock, the handle points at the old copy, so
+    /// it opens the file again.
+    fn open_locked(&self) -> Result<File> {
+        if let Some(parent) = self.path.parent() {
+            fs::create_dir_all(parent)?;
+        }
+        loop {
+            let file = OpenOptions::new()
+                .read(true)
+                .append(true)
+                .create(true)
+                .open(&self.path)?;
+            file.lock()?;
+            if is_current(&file, &self.path)? {
+                return Ok(file);
+            }
+        }
+    }
+
+    /// Swaps in a file holding `lines`. Only call with the lock held.
+    fn replace(&self, lines: &[String]) -> Result<()> {
+        let staged = self.path.with_extension("tmp");
+        let mut file = File::create(&staged)?;
+        for line in lines {
+            writeln!(file, "{line}")?;
+        }
+        file.sync_all()?;
+        fs::rename(&staged, &self.path)?;
+        Ok(())
+    }
+}
+
+#[cfg(unix)]
+fn is_current(file: &File, path: &Path) -> Result<bool> {
+    use std::os::unix::fs::MetadataExt;
+    let open = file.metadata()?;
+    match fs::metadata(path) {
+        Ok(current) => Ok(current.dev() == open.dev() && current.ino() == open.ino()),
+        Err(err) if err.kind() == ErrorKind::NotFound => Ok(false),
+        Err(err) => Err(err.into()),
+    }
+}
+
+// Without inode numbers in std there is no cheap way to tell; on Windows a
+// waiting append can still land in a file that was just replaced.
+#[cfg(not(unix))]
+fn is_current(_file: &File, _path: &Path) -> Result<bool> {
+    Ok(true)
+}
+
+/// Per-session span buffers used when `buffer = true`. Each session gets its
+/// own queue file under `~/.pulse/buffer/` that is flushed as one batch.
+#[derive(Debug, Clone)]
+pub struct SpanBuffer {
+    dir: PathBuf,
+}
+
+impl SpanBuffer {
+    pub fn new() -> Result<Self> {
+        Ok(Self::with_dir(paths::buffer_dir()?))
+    }
+
+    pub fn with_dir(dir: PathBuf) -> Self {
+        Self { dir }
+    }
+
+    pub fn for_session(&self, session_id: &str) -> SpanQueue {
+        SpanQueue::at(
+            self.dir
+                .join(format!("{}.{BUFFER_EXTENSION}", file_safe_key(session_id))),
+        )
+    }
+
+    /// Every session buffer on disk, including ones left behind by sessions
+    /// that never reached `stop`.
+    pub fn sessions(&self) -> Result<Vec<SpanQueue>> {
+        let entries = match fs::read_dir(&self.dir) {
+            Ok(entries) => entries,
+            Err(err) if err.kind() == ErrorKind::NotFound => return Ok(Vec::new()),
+            Err(err) => return Err(err.into()),
+        };
+        let mut queues = Vec::new();
+        for entry in entries {
+            let path = entry?.path();
+            if path.extension().and_then(|ext| ext.to_str()) == Some(BUFFER_EXTENSION) {
+                queues.push(SpanQueue::at(path));
+            }
+        }
+        queues.sort_by(|a, b| a.path.cmp(&b.path));
+        Ok(queues)
+    }
+}
+
+fn read_lines(file: &mut File) -> Result<Vec<String>> {
+    file.seek(SeekFrom::Start(0))?;
+    let mut lines = Vec::new();
+    for line in BufReader::new(&*file).lines() {
+        let line = line?;
+        if !line.trim().is_empty() {
+            lines.push(line);
+        }
+    }
+    Ok(lines)
+}
+
+fn count_lines(file: &mut File) -> Result<usize> {
+    file.seek(SeekFrom::Start(0))?;
+    let mut reader = BufReader::new(&*file);
+    let mut count = 0;
+    let mut chunk = [0; 64 * 1024];
+    loop {
+        let read = reader.read(&mut chunk)?;
+        if read == 0 {
+            return Ok(count);
+        }
+        count += chunk[..read].iter().filter(|byte| **byte == b'\n').count();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::TempDir;
+
+    fn make_queue(tmp: &TempDir, max_entries: usize) -> SpanQueue {
+        SpanQueue {
+            path: tmp.path().join(paths::QUEUE_FILE),
+            max_entries,
+        }
+    }
+
+    #[test]
+    fn test_min_line_bytes_is_an_empty_span() {
+        let empty = SpanPayload {
+            session_id: String::new(),
+            timestamp: String::new(),
+            source: String::new(),
+            kind: String::new(),
+            event_type: String::new(),
+            status: String::new(),
+            ..SpanPayload::test("")
+        };
+        assert_eq!(
+            serde_json::to_string(&empty).unwrap().len() as u64,
+            MIN_LINE_BYTES
+        );
+    }
+
+    #[test]
+    fn test_drain_missing_file_is_empty() {
+        let tmp = TempDir::new().unwrap();
+        let queue = make_queue(&tmp, 10);
+        assert!(queue.drain().unwrap().is_empty());
+        assert!(!queue.path.exists());
+    }
+
+    #[test]
+    fn test_enqueue_then_drain_round_trips() {
+        let tmp = TempDir::new().unwrap();
+        let queue = make_queue(&tmp, 10);
+        queue.enqueue(&SpanPayload::test("a")).unwrap();
+        queue.enqueue(&SpanPayload::test("b")).unwrap();
+        assert_eq!(queue.len().unwrap(), 2);
+
+        assert_eq!(queue.peek().unwrap().len(), 2);
+        let drained = queue.drain().unwrap();
+        let ids: Vec<&str> = drained.iter().map(|s| s.span_id.as_str()).collect();
+        assert_eq!(ids, vec!["a", "b"]);
+        assert!(queue.is_empty().unwrap());
+    }
+
+    #[test]
+    fn test_enqueue_drops_oldest_beyond_limit() {
+        let tmp = TempDir::new().unwrap();
+        let queue = make_queue(&tmp, 3);
+        let dropped: Vec<usize> = ["a", "b", "c", "d", "e"]
+            .into_iter()
+            .map(|id| queue.enqueue(&SpanPayload::test(id)).unwrap())
+            .collect();
+        assert_eq!(dropped, [0, 0, 0, 1, 1]);
+
+        let drained = queue.drain().unwrap();
+        let ids: Vec<&str> = drained.iter().map(|s| s.span_id.as_str()).collect();
+        assert_eq!(ids, vec!["c", "d", "e"]);
+    }
+
+    #[test]
+    fn test_remove_keeps_unsent_and_newly_queued_spans() {
+        let tmp = TempDir::new().unwrap();
+        let queue = make_queue(&tmp, 10);
+        queue
+            .enqueue_all(&[
+                SpanPayload::test("a"),
+                SpanPayload::test("b"),
+                SpanPayload::test("c"),
+            ])
+            .unwrap();
+        let read = queue.peek().unwrap();
+        queue.enqueue(&SpanPayload::test("d")).unwrap();
+
+        queue.remove(&[read[0].clone(), read[2].clone()]).unwrap();
+        let ids: Vec<String> = queue
+            .peek()
+            .unwrap()
+            .into_iter()
+            .map(|s| s.span_id)
+            .collect();
+        assert_eq!(ids, ["b", "d"]);
+        assert!(!queue.path.with_extension("tmp").exists());
+    }
+
+    #[test]
+    fn test_appends_wait_out_a_rewrite() {
+        // Each remove swaps in a new file; an append that was waiting for
+        // the lock must land in the new one, not the file it replaced.
+        let tmp = TempDir::new().unwrap();
+        let queue = make_queue(&tmp, 1_000);
+        let writers: Vec<_> = (0..4)
+            .map(|t| {
+                let queue = queue.clone();
+                std::thread::spawn(move || {
+                    for i in 0..50 {
+                        queue
+                            .enqueue(&SpanPayload::test(&format!("{t}-{i}")))
+                            .unwrap();
+                    }
+                })
+            })
+            .collect();
+        let remover = {
+            let queue = queue.clone();
+            std::thread::spawn(move || {
+                for _ in 0..50 {
+                    queue.remove(&[SpanPayload::test("none")]).unwrap();
+                }
+            })
+        };
+        for handle in writers {
+            handle.join().unwrap();
+        }
+        remover.join().unwrap();
+        assert_eq!(queue.len().unwrap(), 200);
+    }
+
+    #[test]
+    fn test_drain_skips_corrupt_lines() {
+        let tmp = TempDir::new().unwrap();
+        let queue = make_queue(&tmp, 10);
+        queue.enqueue(&SpanPayload::test("a")).unwrap();
+        let mut file = OpenOptions::new().append(true).open(&queue.path).unwrap();
+        writeln!(file, "{{not json").unwrap();
+        drop(file);
+        queue.enqueue(&SpanPayload::test("b")).unwrap();
+
+        let drained = queue.drain().unwrap();
+        assert_eq!(drained.len(), 2);
+    }
+
+    #[test]
+    fn test_take_deletes_the_file_before_unlocking() {
+        let tmp = TempDir::new().unwrap();
+        let queue = make_queue(&tmp, 10);
+        queue.enqueue(&SpanPayload::test("a")).unwrap();
+
+        let held = queue.open_locked().unwrap();
+        let writer = {
+            let queue = queue.clone();
+            std::thread::spawn(move || queue.enqueue(&SpanPayload::test("b")).unwrap())
+        };
+        // Stand in for `take` with the writer already waiting.
+        std::thread::sleep(std::time::Duration::from_millis(100));
+        fs::remove_file(&queue.path).unwrap();
+        drop(held);
+        writer.join().unwrap();
+        let ids: Vec<String> = queue
+            .take()
+            .unwrap()
+            .into_iter()
+            .map(|s| s.span_id)
+            .collect();
+        assert_eq!(ids, ["b"]);
+        assert!(!queue.path.exists());
+    }
+
+    #[test]
+    fn test_move_to_deletes_the_source_only_after_the_append() {
+        let tmp = TempDir::new().unwrap();
+        let buffer = SpanQueue::at(tmp.path().join("session.ndjson"));
+        buffer
+            .enqueue_all(&[SpanPayload::test("a"), SpanPayload::test("b")])
+            .unwrap();
+
+        // A directory in the way makes the append fail.
+        let blocked = SpanQueue::at(tmp.path().to_path_buf());
+        assert!(buffer.move_to(&blocked).is_err());
+        assert_eq!(buffer.len().unwrap(), 2);
+
+        let queue = make_queue(&tmp, 2);
+        queue.enqueue(&SpanPayload::test("old")).unwrap();
+        assert_eq!(buffer.move_to(&queue).unwrap(), 1);
+        assert!(!buffer.path.exists());
+        let ids: Vec<String> = queue
+            .peek()
+            .unwrap()
+            .into_iter()
+            .map(|s| s.span_id)
+            .collect();
+        assert_eq!(ids, ["a", "b"]);
+    }
+
+    #[test]
+    fn test_buffer_lists_session_files() {
+        let tmp = TempDir::new().unwrap();
+        let buffer = SpanBuffer::with_dir(tmp.path().join(paths::BUFFER_DIR));
+        assert!(buffer.sessions().unwrap().is_empty());
+
+        buffer
+            .for_session("sess_1")
+            .enqueue(&SpanPayload::test("a"))
+            .unwrap();
+        buffer
+            .for_session("sess_1")
+            .enqueue(&SpanPayload::test("b"))
+            .unwrap();
+        buffer
+            .for_session("sess/2")
+            .enqueue(&SpanPayload::test("c"))
+            .unwrap();
+
+        let sessions = buffer.sessions().unwrap();
+        assert_eq!(sessions.len(), 2);
+        let total: usize = sessions.iter().map(|q| q.len().unwrap()).sum();
+        assert_eq!(total, 3);
+    }
+
+    #[test]
+    fn test_concurrent_enqueue_keeps_every_entry() {
+        let tmp = TempDir::new().unwrap();
+        let queue = make_queue(&tmp, 1_000);
+        let handles: Vec<_> = (0..8)
+            .map(|t| {
+                let queue = queue.clone();
+                std::thread::spawn(move || {
+                    for i in 0..20 {
+                        queue
+                            .enqueue(&SpanPayload::test(&format!("{t}-{i}")))
+                            .unwrap();
+                    }
+                })
+            })
+            .collect();
+        for handle in handles {
+            handle.join().unwrap();
+        }
+        assert_eq!(queue.len().unwrap(), 160);
+    }
+}
@@ -0,0 +1,75 @@
+use std::{io::Write, path::PathBuf};
+
+use crate::{debug, error::Result, http::SpanPayload, paths};
+
+/// Size at which the record is rotated to `.1` (and `.1` to `.2`).
+const MAX_RECORD_BYTES: u64 = 10 * 1024 * 1024;
+
+/// Write-through log of every span the trace service accepted, one
+/// `SpanPayload` per line. Unlike the offline queue (failures only) or the
+/// debug log (raw hook input), it records exactly what was sent.
+#[derive(Debug, Clone)]
+pub struct SpanRecord {
+    path: PathBuf,
+    max_bytes: u64,
+}
+
+impl SpanRecord {
+    pub fn new() -> Result<Self> {
+        Ok(Self::at(paths::record_log()?))
+    }
+
+    pub fn at(path: PathBuf) -> Self {
+        Self {
+            path,
+            max_bytes: MAX_RECORD_BYTES,
+        }
+    }
+
+    pub fn path(&self) -> &PathBuf {
+        &self.path
+    }
+
+    /// Appends one batch in a single write, so concurrent emits never
+    /// interleave lines.
+    pub fn append(&self, spans: &[SpanPayload]) -> Result<()> {
+        if spans.is_empty() {
+            return Ok(());
+        }
+        let mut lines = Vec::new();
+        for span in spans {
+            serde_json::to_writer(&mut lines, span)?;
+            lines.write_all(b"\n")?;
+        }
+        if let Some(dir) = self.path.parent() {
+            std::fs::create_dir_all(dir)?;
+        }
+        debug::append_rotating(&self.path, &lines, self.max_bytes)?;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::fs;
+
+    use super::*;
+    use tempfile::TempDir;
+
+    #[test]
+    fn test_append_writes_one_line_per_span() {
+        let tmp = TempDir::new().unwrap();
+        let record = SpanRecord::at(tmp.path().join(paths::RECORD_FILE));
+        record
+            .append(&[SpanPayload::test("a"), SpanPayload::test("b")])
+            .unwrap();
+        record.append(&[]).unwrap();
+
+        let contents = fs::read_to_string(record.path()).unwrap();
+        let ids: Vec<String> = contents
+            .lines()
+            .map(|line| serde_json::from_str::<SpanPayload>(line).unwrap().span_id)
+            .collect();
+        assert_eq!(ids, ["a", "b"]);
+    }
+}
@@ -0,0 +1,100 @@
+use crate::config::PulseConfig;
+
+pub const SAMPLE_RATE_ENV: &str = "PULSE_SAMPLE_RATE";
+
+/// Events that are always sent regardless of the sample rate.
+const ALWAYS_KEPT: &[&str] = &["session_start", "session_end", "post_tool_use_failure"];
+
+/// Resolves the effective sample rate: `PULSE_SAMPLE_RATE` wins over the
+/// config value, and anything unparseable or missing means "keep everything".
+pub fn sample_rate(config: &PulseConfig) -> f64 {
+    resolve_rate(
+        std::env::var(SAMPLE_RATE_ENV).ok().as_deref(),
+        config.sample_rate,
+    )
+}
+
+pub fn resolve_rate(env: Option<&str>, configured: Option<f64>) -> f64 {
+    env.and_then(|value| value.trim().parse::<f64>().ok())
+        .or(configured)
+        .filter(|rate| rate.is_finite())
+        .map(|rate| rate.clamp(0.0, 1.0))
+        .unwrap_or(1.0)
+}
+
+/// Decides whether a span should be sent. Sampling is per session: a session
+/// is either fully captured or fully dropped, except for lifecycle and
+/// failure events which are always kept.
+pub fn should_keep(event_type: &str, session_id: &str, rate: f64) -> bool {
+    if rate >= 1.0 || ALWAYS_KEPT.contains(&event_type) {
+        return true;
+    }
+    if rate <= 0.0 {
+        return false;
+    }
+    session_bucket(session_id) < rate
+}
+
+/// Maps a session id onto `[0, 1)` with FNV-1a plus a murmur3 finalizer,
+/// which is stable across processes and releases (unlike `DefaultHasher`).
+fn session_bucket(session_id: &str) -> f64 {
+    let mut hash: u64 = 0xcbf2_9ce4_8422_2325;
+    for byte in session_id.as_bytes() {
+        hash ^= u64::from(*byte);
+        hash = hash.wrapping_mul(0x0000_0100_0000_01b3);
+    }
+    hash ^= hash >> 33;
+    hash = hash.wrapping_mul(0xff51_afd7_ed55_8ccd);
+    hash ^= hash >> 33;
+    hash = hash.wrapping_mul(0xc4ce_b9fe_1a85_ec53);
+    hash ^= hash >> 33;
+    (hash >> 11) as f64 / (1u64 << 53) as f64
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_lifecycle_events_bypass_sampling() {
+        for event in ALWAYS_KEPT {
+            assert!(should_keep(event, "sess_1", 0.0), "{event} was dropped");
+        }
+        assert!(!should_keep("post_tool_use", "sess_1", 0.0));
+        assert!(!should_keep("stop", "sess_1", 0.0));
+    }
+
+    #[test]
+    fn test_full_rate_keeps_everything() {
+        assert!(should_keep("post_tool_use", "sess_1", 1.0));
+        assert!(should_keep("notification", "anything", 1.0));
+    }
+
+    #[test]
+    fn test_sampling_is_per_session() {
+        for i in 0..50 {
+            let session = format!("sess_{i}");
+            let first = should_keep("pre_tool_use", &session, 0.5);
+            assert_eq!(first, should_keep("post_tool_use", &session, 0.5));
+            assert_eq!(first, should_keep("user_prompt_submit", &session, 0.5));
+        }
+    }
+
+    #[test]
+    fn test_sampling_keeps_roughly_the_rate() {
+        let kept = (0..2000)
+            .filter(|i| should_keep("post_tool_use", &format!("session-{i}"), 0.25))
+            .count();
+        assert!((350..650).contains(&kept), "kept {kept} of 2000");
+    }
+
+    #[test]
+    fn test_resolve_rate_prefers_env_and_clamps() {
+        assert_eq!(resolve_rate(None, None), 1.0);
+        assert_eq!(resolve_rate(None, Some(0.3)), 0.3);
+        assert_eq!(resolve_rate(Some("0.1"), Some(0.3)), 0.1);
+        assert_eq!(resolve_rate(Some("junk"), Some(0.3)), 0.3);
+        assert_eq!(resolve_rate(Some("5"), None), 1.0);
+        assert_eq!(resolve_rate(None, Some(-1.0)), 0.0);
+    }
+}
@@ -0,0 +1,168 @@
+//! Works out which agent ran `pulse emit` when neither `--source`, the
+//! payload, nor `PULSE_SOURCE` says. The agents' own processes are the best
+//! signal, since environment variables leak into anything started from an
+//! agent's shell; variables the agents set for their children come second.
+
+use std::env;
+#[cfg(target_os = "linux")]
+use std::{fs, path::Path};
+
+use crate::config::PulseConfig;
+
+/// Per source, the process names and environment variables that identify it.
+const SOURCE_SIGNALS: &[(&str, &[&str], &[&str])] = &[
+    (
+        "claude_code",
+        &["claude"],
+        &["CLAUDECODE", "CLAUDE_CODE_ENTRYPOINT"],
+    ),
+    ("opencode", &["opencode"], &["OPENCODE"]),
+    ("openclaw", &["openclaw", "openclaw-gateway"], &[]),
+    ("cursor", &["cursor", "cursor-agent"], &[]),
+    ("gemini", &["gemini"], &["GEMINI_CLI"]),
+    ("codex", &["codex"], &[]),
+];
+
+/// How far up the process tree to look: the hook shell, the agent, and a
+/// wrapper or two (npx, node).
+#[cfg(target_os = "linux")]
+const MAX_ANCESTORS: usize = 4;
+
+/// `PULSE_SOURCE_AUTODETECT` wins over `source_autodetect` in the config, in
+/// either direction.
+pub fn enabled(config: &PulseConfig) -> bool {
+    match env::var("PULSE_SOURCE_AUTODETECT") {
+        Ok(v) => v == "1" || v == "true",
+        Err(_) => config.source_autodetect(),
+    }
+}
+
+/// The agent this process was started by, if it can be told.
+pub fn detect() -> Option<&'static str> {
+    detect_with(|name| env::var(name).ok(), &ancestor_names())
+}
+
+/// `ancestors` holds the names each parent process goes by, nearest first;
+/// the nearest one that names an agent wins. Otherwise the first source
+/// with one of its variables set (and not `0`) does.
+fn detect_with(
+    env: impl Fn(&str) -> Option<String>,
+    ancestors: &[Vec<String>],
+) -> Option<&'static str> {
+    let by_process = ancestors.iter().find_map(|names| {
+        SOURCE_SIGNALS.iter().find_map(|(source, processes, _)| {
+            names
+                .iter()
+                .any(|name| processes.contains(&name.as_str()))
+                .then_some(*source)
+        })
+    });
+    by_process.or_else(|| {
+        SOURCE_SIGNALS.iter().find_map(|(source, _, vars)| {
+            vars.iter()
+                .any(|var| env(var).is_some_and(|value| !value.is_empty() && value != "0"))
+                .then_some(*source)
+        })
+    })
+}
+
+/// Names of the parent processes, nearest first: the kernel's short name
+/// plus the file names of the first two arguments, so `node .../gemini`
+/// still reads as `gemini`. Only Linux exposes this without running `ps`,
+/// which would cost every hook a process spawn, so elsewhere it is empty.
+#[cfg(target_os = "linux")]
+fn ancestor_names() -> Vec<Vec<String>> {
+    let mut names = Vec::new();
+    let mut pid = std::os::unix::process::parent_id();
+    while pid > 1 && names.len() < MAX_ANCESTORS {
+        let proc_dir = Path::new("/proc").join(pid.to_string());
+        let Ok(stat) = fs::read_to_string(proc_dir.join("stat")) else {
+            break;
+        };
+        // `pid (comm) state ppid ...`, where comm may contain anything.
+        let (Some(open), Some(close)) = (stat.find('('), stat.rfind(')')) else {
+            break;
+        };
+        let mut process = vec![normalize(&stat[open + 1..close])];
+        if let Ok(cmdline) = fs::read(proc_dir.join("cmdline")) {
+            process.extend(cmdline.split(|byte| *byte == 0).take(2).filter_map(|arg| {
+                Path::new(&*String::from_utf8_lossy(arg))
+                    .file_name()
+                    .map(|name| normalize(&name.to_string_lossy()))
+            }));
+        }
+        names.push(process);
+        pid = match stat[close + 1..]
+            .split_whitespace()
+            .nth(1)
+            .and_then(|ppid| ppid.parse().ok())
+        {
+            Some(ppid) => ppid,
+            None => break,
+        };
+    }
+    names
+}
+
+#[cfg(not(target_os = "linux"))]
+fn ancestor_names() -> Vec<Vec<String>> {
+    Vec::new()
+}
+
+#[cfg(target_os = "linux")]
+fn normalize(name: &str) -> String {
+    name.trim().to_ascii_lowercase()
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::HashMap;
+
+    use super::*;
+
+    fn env_of(vars: &[(&str, &str)]) -> impl Fn(&str) -> Option<String> {
+        let vars: HashMap<String, String> = vars
+            .iter()
+            .map(|(name, value)| (name.to_string(), value.to_string()))
+            .collect();
+        move |name| vars.get(name).cloned()
+    }
+
+    fn names(list: &[&[&str]]) -> Vec<Vec<String>> {
+        list.iter()
+            .map(|names| names.iter().map(|name| name.to_string()).collect())
+            .collect()
+    }
+
+    #[test]
+    fn test_detects_source_from_env() {
+        let cases = [
+            (vec![("CLAUDECODE", "1")], Some("claude_code")),
+            (vec![("CLAUDE_CODE_ENTRYPOINT", "cli")], Some("claude_code")),
+            (vec![("OPENCODE", "1")], Some("opencode")),
+            (vec![("GEMINI_CLI", "1")], Some("gemini")),
+            (vec![("OPENCODE", "0")], None),
+            (vec![("GEMINI_CLI", "")], None),
+            (vec![("HOME", "/home/me")], None),
+        ];
+        for (vars, expected) in cases {
+            assert_eq!(detect_with(env_of(&vars), &[]), expected, "{vars:?}");
+        }
+    }
+
+    #[test]
+    fn test_nearest_agent_process_wins_over_env() {
+        // OpenCode started from a Claude Code shell inherits CLAUDECODE.
+        let env = env_of(&[("CLAUDECODE", "1")]);
+        let ancestors = names(&[&["sh", "sh"], &["opencode", "opencode"], &["claude"]]);
+        assert_eq!(detect_with(&env, &ancestors), Some("opencode"));
+
+        // `node /usr/local/bin/gemini`
+        let ancestors = names(&[&["node", "node", "gemini"]]);
+        assert_eq!(detect_with(env_of(&[]), &ancestors), Some("gemini"));
+
+        let ancestors = names(&[&["bash", "bash"], &["sshd"]]);
+        assert_eq!(detect_with(&env, &ancestors), Some("claude_code"));
+        assert_eq!(detect_with(env_of(&[]), &ancestors), None);
+    }
+}
@@ -0,0 +1,76 @@
+//! Fixtures shared by the unit tests.
+
+use std::{
+    io::{BufRead, BufReader, Read, Write},
+    net::TcpListener,
+    thread,
+};
+
+use crate::http::SpanPayload;
+
+impl SpanPayload {
+    /// A finished tool call in session `sess_1` with only the required
+    /// fields set.
+    pub(crate) fn test(span_id: &str) -> Self {
+        Self {
+            span_id: span_id.to_string(),
+            session_id: "sess_1".to_string(),
+            parent_span_id: None,
+            timestamp: "2025-01-01T00:00:00+00:00".to_string(),
+            duration_ms: None,
+            source: "claude_code".to_string(),
+            kind: "tool_use".to_string(),
+            event_type: "post_tool_use".to_string(),
+            status: "success".to_string(),
+            tool_use_id: None,
+            tool_name: None,
+            tool_input: None,
+            tool_response: None,
+            error: None,
+            is_interrupt: None,
+            cwd: None,
+            tool_cwd: None,
+            model: None,
+            agent_name: None,
+            metadata: None,
+        }
+    }
+}
+
+/// A trace service on a local port that answers every request with the
+/// status `respond` picks from the request body, e.g. `"202 Accepted"`.
+/// Returns its URL.
+pub(crate) fn serve(respond: impl Fn(&str) -> &'static str + Send + Sync + 'static) -> String {
+    let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+    let url = format!("http://{}", listener.local_addr().unwrap());
+    let respond = std::sync::Arc::new(respond);
+    thread::spawn(move || {
+        for stream in listener.incoming() {
+            let stream = stream.unwrap();
+            let respond = respond.clone();
+            thread::spawn(move || {
+                let mut reader = BufReader::new(stream);
+                let mut length = 0;
+                loop {
+                    let mut line = String::new();
+                    reader.read_line(&mut line).unwrap();
+                    if line == "\r\n" {
+                        break;
+                    }
+                    if let Some((name, value)) = line.split_once(':')
+                        && name.eq_ignore_ascii_case("content-length")
+                    {
+                        length = value.trim().parse().unwrap();
+                    }
+                }
+                let mut body = vec![0; length];
+                reader.read_exact(&mut body).unwrap();
+                let status = respond(&String::from_utf8_lossy(&body));
+                let response =
+                    format!("HTTP/1.1 {status}\r\ncontent-length: 0\r\nconnection: close\r\n\r\n");
+                reader.get_mut().write_all(response.as_bytes()).unwrap();
+            });
+        }
+    });
+    url
+}
@@ -1,4 +1,7 @@
-use pulse::http::SpanPayload;
+use pulse::config::PulseConfig;
+use pulse::error::PulseError;
+use pulse::http::{SpanPayload, TraceHttpClient, idempotency_key, validate_endpoint_path};
+use pulse::queue::SpanQueue;
 use serde_json::json;
 
 fn minimal_span() -> SpanPayload {
@@ -19,6 +22,7 @@ fn minimal_span() -> SpanPayload {
         error: None,
         is_interrupt: None,
         cwd: None,
+        tool_cwd: None,
         model: None,
         agent_name: None,
         metadata: None,
@@ -106,3 +110,219 @@ fn serialization_batch_format() {
     assert!(json.is_array());
     assert_eq!(json.as_array().unwrap().len(), 2);
 }
+
+#[test]
+fn endpoint_paths_must_be_relative() {
+    assert!(validate_endpoint_path("/pulse/v1/spans/async").is_ok());
+    assert!(validate_endpoint_path("v1/spans/async").is_ok());
+    assert!(validate_endpoint_path("").is_err());
+    assert!(validate_endpoint_path("https://other.example.com/v1/spans").is_err());
+    assert!(validate_endpoint_path("//other.example.com/v1/spans").is_err());
+}
+
+#[test]
+fn client_rejects_absolute_spans_path() {
+    let config = PulseConfig {
+        api_url: "http://localhost:8080".to_string(),
+        spans_path: Some("http://elsewhere/v1/spans".to_string()),
+        ..PulseConfig::default()
+    };
+    assert!(TraceHttpClient::new(&config).is_err());
+}
+
+#[test]
+fn client_builds_with_proxy() {
+    let config = PulseConfig {
+        api_url: "http://localhost:8080".to_string(),
+        proxy_url: Some("http://proxy.corp:3128".to_string()),
+        ..PulseConfig::default()
+    };
+    assert!(TraceHttpClient::new(&config).is_ok());
+}
+
+#[test]
+fn client_rejects_malformed_proxy() {
+    let config = PulseConfig {
+        api_url: "http://localhost:8080".to_string(),
+        proxy_url: Some("not a proxy url".to_string()),
+        ..PulseConfig::default()
+    };
+    let err = TraceHttpClient::new(&config).err().unwrap();
+    assert!(err.to_string().contains("invalid proxy url"), "{err}");
+}
+
+#[test]
+fn client_rejects_ca_file_without_certificates() {
+    let dir = tempfile::TempDir::new().unwrap();
+    let path = dir.path().join("ca.pem");
+    std::fs::write(&path, "not a certificate").unwrap();
+    let config = PulseConfig {
+        api_url: "https://localhost:8443".to_string(),
+        ca_cert_path: Some(path.display().to_string()),
+        ..PulseConfig::default()
+    };
+    let err = TraceHttpClient::new(&config).err().unwrap();
+    assert!(err.to_string().contains("no PEM certificates"), "{err}");
+}
+
+#[test]
+fn client_builds_with_invalid_certs_accepted() {
+    let config = PulseConfig {
+        api_url: "https://localhost:8443".to_string(),
+        danger_accept_invalid_certs: Some(true),
+        ..PulseConfig::default()
+    };
+    assert!(TraceHttpClient::new(&config).is_ok());
+}
+
+#[tokio::test]
+async fn connection_refused_is_reported_as_such() {
+    // Bind and drop a listener to get a local port nothing is listening on.
+    let port = std::net::TcpListener::bind("127.0.0.1:0")
+        .unwrap()
+        .local_addr()
+        .unwrap()
+        .port();
+    let config = PulseConfig {
+        api_url: format!("http://127.0.0.1:{port}"),
+        ..PulseConfig::default()
+    };
+    let client = TraceHttpClient::new(&config).unwrap();
+    let err = client.health_check().await.unwrap_err();
+    assert!(err.to_string().contains("connection refused"), "{err}");
+    assert!(matches!(err, PulseError::Connection { .. }), "{err:?}");
+    assert!(err.remediation().is_some());
+}
+
+#[tokio::test]
+async fn timeouts_come_from_config() {
+    // A listener that accepts connections but never answers.
+    let listener = std::net::TcpListener::bind("127.0.0.1:0").unwrap();
+    let config = PulseConfig {
+        api_url: format!("http://{}", listener.local_addr().unwrap()),
+        http_timeout_ms: Some(100),
+        emit_timeout_ms: Some(150),
+        ..PulseConfig::default()
+    };
+    let client = TraceHttpClient::new(&config).unwrap();
+
+    let started = std::time::Instant::now();
+    assert!(client.health_check().await.is_err());
+    assert!(started.elapsed() < std::time::Duration::from_secs(1));
+
+    let started = std::time::Instant::now();
+    assert!(client.post_spans(&[minimal_span()]).await.is_err());
+    assert!(started.elapsed() < std::time::Duration::from_secs(1));
+}
+
+/// A request as the mock server saw it: lowercase header names, and the body.
+struct CapturedRequest {
+    headers: std::collections::HashMap<String, String>,
+    body: Vec<u8>,
+}
+
+/// Answers one request with 200 and returns what was sent.
+fn capture_request() -> (String, std::thread::JoinHandle<Option<CapturedRequest>>) {
+    use std::io::{BufRead, BufReader, Read, Write};
+
+    let listener = std::net::TcpListener::bind("127.0.0.1:0").unwrap();
+    let url = format!("http://{}", listener.local_addr().unwrap());
+    let handle = std::thread::spawn(move || {
+        let (stream, _) = listener.accept().ok()?;
+        let mut reader = BufReader::new(stream.try_clone().ok()?);
+        let mut headers = std::collections::HashMap::new();
+        let mut line = String::new();
+        while reader.read_line(&mut line).ok()? > 2 {
+            if let Some((name, value)) = line.split_once(':') {
+                headers.insert(name.to_ascii_lowercase(), value.trim().to_string());
+            }
+            line.clear();
+        }
+        let length = headers
+            .get("content-length")
+            .and_then(|value| value.parse().ok())
+            .unwrap_or(0);
+        let mut body = vec![0; length];
+        reader.read_exact(&mut body).ok()?;
+        let mut stream = stream;
+        let _ =
+            stream.write_all(b"HTTP/1.1 200 OK\r\ncontent-length: 0\r\nconnection: close\r\n\r\n");
+        Some(CapturedRequest { headers, body })
+    });
+    (url, handle)
+}
+
+#[tokio::test]
+async fn user_agent_suffix_is_sent() {
+    let (url, handle) = capture_request();
+    let config = PulseConfig {
+        api_url: url,
+        user_agent_suffix: Some("teamA".to_string()),
+        ..PulseConfig::default()
+    };
+    TraceHttpClient::new(&config)
+        .unwrap()
+        .health_check()
+        .await
+        .unwrap();
+    let request = handle.join().unwrap().unwrap();
+    assert_eq!(
+        request.headers.get("user-agent").map(String::as_str),
+        Some(concat!("pulse-cli/", env!("CARGO_PKG_VERSION"), " (teamA)"))
+    );
+}
+
+#[tokio::test]
+async fn replayed_queued_span_keeps_its_id() {
+    let tmp = tempfile::TempDir::new().unwrap();
+    let queue = SpanQueue::at(tmp.path().join("queue.ndjson"));
+    let span = minimal_span();
+    queue.enqueue(&span).unwrap();
+    let replayed = queue.drain().unwrap();
+
+    let (url, handle) = capture_request();
+    let config = PulseConfig {
+        api_url: url,
+        ..PulseConfig::default()
+    };
+    TraceHttpClient::new(&config)
+        .unwrap()
+        .post_spans(&replayed)
+        .await
+        .unwrap();
+
+    let request = handle.join().unwrap().unwrap();
+    let sent: Vec<SpanPayload> = serde_json::from_slice(&request.body).unwrap();
+    assert_eq!(sent.len(), 1);
+    assert_eq!(sent[0].span_id, span.span_id);
+    // The key depends only on the span ids, so a retry sends the same one.
+    assert_eq!(
+        request.headers.get("idempotency-key"),
+        Some(&idempotency_key(&[span]))
+    );
+}
+
+#[test]
+fn idempotency_key_follows_span_ids() {
+    let first = minimal_span();
+    let mut second = minimal_span();
+    second.span_id = "6ba7b810-9dad-11d1-80b4-00c04fd430c8".to_string();
+    let mut edited = first.clone();
+    edited.status = "error".to_string();
+
+    let key = idempotency_key(std::slice::from_ref(&first));
+    assert_eq!(key, idempotency_key(&[edited]));
+    assert_ne!(key, idempotency_key(std::slice::from_ref(&second)));
+    assert_ne!(key, idempotency_key(&[first, second]));
+}
+
+#[test]
+fn client_rejects_unsafe_user_agent_suffix() {
+    let config = PulseConfig {
+        api_url: "http://localhost:8080".to_string(),
+        user_agent_suffix: Some("team\r\nX-Evil: 1".to_string()),
+        ..PulseConfig::default()
+    };
+    let err = TraceHttpClient::new(&config).err().unwrap();
+    assert!(err.to_string().contains("user agent suffix"), "{err}");
+}
@@ -1,5 +1,7 @@
-use pulse::hooks::span;
+use chrono::{Duration, Utc};
+use pulse::{correlation::CorrelationStore, hooks::span};
 use serde_json::json;
+use tempfile::TempDir;
 
 #[test]
 fn event_type_to_kind_mappings() {
@@ -12,6 +14,7 @@ fn event_type_to_kind_mappings() {
     assert_eq!(span::event_type_to_kind("session_start"), "session");
     assert_eq!(span::event_type_to_kind("session_end"), "session");
     assert_eq!(span::event_type_to_kind("stop"), "session");
+    assert_eq!(span::event_type_to_kind("heartbeat"), "session");
     assert_eq!(span::event_type_to_kind("subagent_start"), "agent_run");
     assert_eq!(span::event_type_to_kind("subagent_stop"), "agent_run");
     assert_eq!(
@@ -72,6 +75,39 @@ fn extract_pre_tool_use() {
     assert!(fields.tool_response.is_none());
 }
 
+#[test]
+fn extract_tool_cwd_separate_from_session_cwd() {
+    let payload = json!({
+        "session_id": "sess_1",
+        "cwd": "/work/repo",
+        "tool_name": "Bash",
+        "tool_input": {"command": "cargo test", "cwd": "crates/core"}
+    });
+    let fields = span::extract("pre_tool_use", &payload);
+    assert_eq!(fields.cwd.as_deref(), Some("/work/repo"));
+    assert_eq!(fields.tool_cwd.as_deref(), Some("/work/repo/crates/core"));
+
+    // Cursor sends the shell's directory as `cwd` next to the project root.
+    let payload = json!({
+        "conversation_id": "conv_1",
+        "command": "ls",
+        "cwd": "/work/repo/docs",
+        "workspace_roots": ["/work/repo"]
+    });
+    let fields = span::extract("pre_tool_use", &payload);
+    assert_eq!(fields.cwd.as_deref(), Some("/work/repo"));
+    assert_eq!(fields.tool_cwd.as_deref(), Some("/work/repo/docs"));
+
+    // A tool running in the session directory adds nothing.
+    let payload = json!({
+        "session_id": "sess_1",
+        "cwd": "/work/repo",
+        "tool_input": {"command": "ls"}
+    });
+    let fields = span::extract("post_tool_use", &payload);
+    assert!(fields.tool_cwd.is_none());
+}
+
 #[test]
 fn extract_post_tool_use() {
     let payload = json!({
@@ -102,6 +138,30 @@ fn extract_post_tool_use_failure() {
     assert_eq!(fields.tool_name.as_deref(), Some("Bash"));
     assert_eq!(fields.error, Some(json!("permission denied")));
     assert_eq!(fields.is_interrupt, Some(true));
+    assert!(fields.metadata.is_none());
+}
+
+#[test]
+fn extract_post_tool_use_failure_exit_code_and_signal() {
+    let payload = json!({
+        "session_id": "sess_1",
+        "tool_name": "Bash",
+        "error": "command not found",
+        "exit_code": 127
+    });
+    let fields = span::extract("post_tool_use_failure", &payload);
+    assert_eq!(fields.metadata, Some(json!({"exit_code": 127})));
+
+    let payload = json!({
+        "session_id": "sess_1",
+        "tool_name": "Bash",
+        "tool_response": {"exit_code": 137, "signal": "SIGKILL"}
+    });
+    let fields = span::extract("post_tool_use_failure", &payload);
+    assert_eq!(
+        fields.metadata,
+        Some(json!({"exit_code": 137, "signal": "SIGKILL"}))
+    );
 }
 
 #[test]
@@ -196,6 +256,34 @@ fn extract_assistant_message() {
     assert_eq!(usage["cost"], 0.0042);
 }
 
+#[test]
+fn extract_plugin_assistant_message() {
+    // The OpenCode and OpenClaw plugins send every count, zero-filled.
+    let payload = json!({
+        "session_id": "agent:main:main",
+        "cwd": "/work",
+        "model": "anthropic/claude-sonnet-4",
+        "tokens": {
+            "input": 2048,
+            "output": 312,
+            "reasoning": 0,
+            "cache": { "read": 1500, "write": 0 }
+        },
+        "cost": 0,
+        "source": "openclaw"
+    });
+    let fields = span::extract("assistant_message", &payload);
+    assert_eq!(fields.source.as_deref(), Some("openclaw"));
+    assert_eq!(fields.model.as_deref(), Some("anthropic/claude-sonnet-4"));
+    let usage = &fields.metadata.as_ref().unwrap()["usage"];
+    assert_eq!(usage["input_tokens"], 2048);
+    assert_eq!(usage["output_tokens"], 312);
+    assert_eq!(usage["reasoning_tokens"], 0);
+    assert_eq!(usage["cache_read_tokens"], 1500);
+    assert_eq!(usage["cache_write_tokens"], 0);
+    assert_eq!(usage["cost"], 0.0);
+}
+
 #[test]
 fn extract_assistant_message_partial_tokens() {
     let payload = json!({
@@ -242,6 +330,8 @@ fn into_span_returns_none_without_session_id() {
         "2025-01-01T00:00:00Z".to_string(),
         "post_tool_use".to_string(),
         "claude_code".to_string(),
+        None,
+        span::MappingOverrides::default(),
     );
     assert!(span.is_none());
 }
@@ -262,6 +352,8 @@ fn into_span_builds_correct_payload() {
             "2025-01-01T00:00:00Z".to_string(),
             "post_tool_use".to_string(),
             "claude_code".to_string(),
+            Some("parent-1".to_string()),
+            span::MappingOverrides::default(),
         )
         .unwrap();
 
@@ -273,4 +365,266 @@ fn into_span_builds_correct_payload() {
     assert_eq!(span.source, "claude_code");
     assert_eq!(span.tool_name.as_deref(), Some("Bash"));
     assert_eq!(span.cwd.as_deref(), Some("/tmp"));
+    assert_eq!(span.parent_span_id.as_deref(), Some("parent-1"));
+}
+
+#[test]
+fn subagent_tool_spans_nest_under_the_agent() {
+    let into_span = |event_type: &str, payload: serde_json::Value| {
+        span::extract(event_type, &payload)
+            .into_span(
+                "span-id".to_string(),
+                "2025-01-01T00:00:00Z".to_string(),
+                event_type.to_string(),
+                "claude_code".to_string(),
+                Some("session-parent".to_string()),
+                span::MappingOverrides::default(),
+            )
+            .unwrap()
+    };
+    let agent_span = span::agent_span_id("sess_1", "agent_7");
+
+    let tool = into_span(
+        "post_tool_use",
+        json!({ "session_id": "sess_1", "tool_name": "Read", "agent_id": "agent_7" }),
+    );
+    assert_eq!(tool.parent_span_id.as_deref(), Some(agent_span.as_str()));
+
+    let tool = into_span(
+        "pre_tool_use",
+        json!({ "session_id": "sess_1", "tool_name": "Read", "parent_tool_use_id": "agent_7" }),
+    );
+    assert_eq!(tool.parent_span_id.as_deref(), Some(agent_span.as_str()));
+
+    // The subagent span itself still hangs off the session.
+    let agent = into_span(
+        "subagent_start",
+        json!({ "session_id": "sess_1", "agent_id": "agent_7" }),
+    );
+    assert_eq!(agent.parent_span_id.as_deref(), Some("session-parent"));
+
+    assert_ne!(agent_span, span::agent_span_id("sess_2", "agent_7"));
+    assert_ne!(agent_span, span::session_span_id("sess_1"));
+}
+
+#[test]
+fn session_span_id_is_deterministic() {
+    let first = span::session_span_id("sess_1");
+    assert_eq!(first, span::session_span_id("sess_1"));
+    assert_ne!(first, span::session_span_id("sess_2"));
+    assert!(uuid::Uuid::parse_str(&first).is_ok());
+}
+
+#[test]
+fn link_session_parents_spans_after_session_start() {
+    let tmp = TempDir::new().unwrap();
+    let store = CorrelationStore::with_dir(tmp.path().to_path_buf());
+
+    // Nothing to link to before the session start is seen.
+    assert!(span::link_session(&store, "post_tool_use", "sess_1").is_none());
+
+    assert!(span::link_session(&store, "session_start", "sess_1").is_none());
+    let expected = span::session_span_id("sess_1");
+    assert_eq!(
+        span::link_session(&store, "pre_tool_use", "sess_1"),
+        Some(expected.clone())
+    );
+    assert_eq!(
+        span::link_session(&store, "user_prompt_submit", "sess_1"),
+        Some(expected)
+    );
+    assert!(span::link_session(&store, "session_end", "sess_1").is_none());
+    assert!(span::link_session(&store, "pre_tool_use", "sess_2").is_none());
+}
+
+#[test]
+fn tool_duration_correlates_pre_and_post() {
+    let tmp = TempDir::new().unwrap();
+    let store = CorrelationStore::with_dir(tmp.path().to_path_buf());
+    let start = Utc::now();
+
+    let pre = span::correlate_tool_duration(&store, "pre_tool_use", Some("tu_1"), start);
+    assert!(pre.is_none());
+
+    let end = start + Duration::milliseconds(250);
+    let post = span::correlate_tool_duration(&store, "post_tool_use", Some("tu_1"), end).unwrap();
+    assert!(post > 0.0);
+    assert_eq!(post, 250.0);
+
+    // The pending entry is consumed by the first post.
+    assert!(span::correlate_tool_duration(&store, "post_tool_use", Some("tu_1"), end).is_none());
+}
+
+#[test]
+fn tool_duration_covers_failures() {
+    let tmp = TempDir::new().unwrap();
+    let store = CorrelationStore::with_dir(tmp.path().to_path_buf());
+    let start = Utc::now();
+
+    span::correlate_tool_duration(&store, "pre_tool_use", Some("tu_2"), start);
+    let failed = span::correlate_tool_duration(
+        &store,
+        "post_tool_use_failure",
+        Some("tu_2"),
+        start + Duration::seconds(1),
+    );
+    assert_eq!(failed, Some(1000.0));
+}
+
+#[test]
+fn tool_duration_missing_start_is_none() {
+    let tmp = TempDir::new().unwrap();
+    let store = CorrelationStore::with_dir(tmp.path().to_path_buf());
+    assert!(
+        span::correlate_tool_duration(&store, "post_tool_use", Some("tu_3"), Utc::now()).is_none()
+    );
+    assert!(span::correlate_tool_duration(&store, "post_tool_use", None, Utc::now()).is_none());
+}
+
+#[test]
+fn session_duration_pairs_start_with_stop_and_end() {
+    let tmp = TempDir::new().unwrap();
+    let store = CorrelationStore::with_dir(tmp.path().to_path_buf());
+    let start = Utc::now();
+
+    assert!(span::correlate_session_duration(&store, "session_start", "sess_1", start).is_none());
+
+    // `stop` fires per turn and leaves the start in place.
+    let stop =
+        span::correlate_session_duration(&store, "stop", "sess_1", start + Duration::seconds(2));
+    assert_eq!(stop, Some(2000.0));
+    let end = span::correlate_session_duration(
+        &store,
+        "session_end",
+        "sess_1",
+        start + Duration::seconds(5),
+    );
+    assert_eq!(end, Some(5000.0));
+
+    // `session_end` consumes it; other events never get a session duration.
+    assert!(span::correlate_session_duration(&store, "stop", "sess_1", start).is_none());
+    assert!(span::correlate_session_duration(&store, "post_tool_use", "sess_1", start).is_none());
+}
+
+#[test]
+fn session_duration_missing_start_is_none() {
+    let tmp = TempDir::new().unwrap();
+    let store = CorrelationStore::with_dir(tmp.path().to_path_buf());
+    assert!(span::correlate_session_duration(&store, "stop", "sess_2", Utc::now()).is_none());
+    assert!(
+        span::correlate_session_duration(&store, "session_end", "sess_2", Utc::now()).is_none()
+    );
+}
+
+#[test]
+fn truncate_value_leaves_small_values_alone() {
+    let mut value = json!({"file_path": "/tmp/a.rs", "lines": [1, 2, 3]});
+    let original = value.clone();
+    span::truncate_value(&mut value, 1024);
+    assert_eq!(value, original);
+}
+
+#[test]
+fn truncate_value_replaces_large_values_with_marker() {
+    let mut value = json!({"content": "x".repeat(500)});
+    let original_bytes = value.to_string().len();
+    span::truncate_value(&mut value, 100);
+
+    assert_eq!(value["_truncated"], true);
+    assert_eq!(value["_original_bytes"], original_bytes);
+    let preview = value["_preview"].as_str().unwrap();
+    assert_eq!(preview.len(), 100);
+    assert!(preview.starts_with("{\"content\":\"xxx"));
+    // The marker itself round-trips as JSON.
+    let reparsed: serde_json::Value = serde_json::from_str(&value.to_string()).unwrap();
+    assert_eq!(reparsed, value);
+}
+
+#[test]
+fn truncate_value_respects_char_boundaries() {
+    let mut value = json!("é".repeat(100));
+    span::truncate_value(&mut value, 51);
+    let preview = value["_preview"].as_str().unwrap();
+    assert_eq!(preview.len(), 50);
+    assert_eq!(value["_original_bytes"], 200);
+}
+
+#[test]
+fn truncate_large_fields_covers_tool_fields_and_raw() {
+    let big = "y".repeat(300);
+    let payload = json!({
+        "session_id": "sess_1",
+        "tool_name": "Read",
+        "tool_input": {"file_path": "/tmp/a"},
+        "tool_response": big,
+    });
+    let mut fields = span::extract("post_tool_use", &payload);
+    fields.metadata = Some(json!({"raw": payload.clone()}));
+    fields.truncate_large_fields(200);
+
+    assert_eq!(fields.tool_input, Some(json!({"file_path": "/tmp/a"})));
+    assert_eq!(fields.tool_response.as_ref().unwrap()["_truncated"], true);
+    assert_eq!(fields.metadata.as_ref().unwrap()["raw"]["_truncated"], true);
+}
+
+#[test]
+fn extract_codex_notify() {
+    // The JSON Codex passes to its `notify` command after each turn.
+    let payload = json!({
+        "type": "agent-turn-complete",
+        "thread-id": "thread_1",
+        "turn-id": "turn_1",
+        "cwd": "/work/repo",
+        "input-messages": ["fix the build"],
+        "last-assistant-message": "Done."
+    });
+    let fields = span::extract("stop", &payload);
+    assert_eq!(fields.session_id.as_deref(), Some("thread_1"));
+    assert_eq!(fields.cwd.as_deref(), Some("/work/repo"));
+}
+
+#[test]
+fn extract_camel_case_opencode_payload() {
+    // OpenCode's own `tool.execute.before` shape, passed through unchanged.
+    let payload = json!({
+        "sessionID": "ses_1",
+        "tool": "bash",
+        "callID": "call_1",
+        "args": { "command": "ls" },
+    });
+    let canonical = span::canonical_payload("opencode", &payload, None);
+    let fields = span::extract("pre_tool_use", &canonical);
+    assert_eq!(fields.session_id.as_deref(), Some("ses_1"));
+    assert_eq!(fields.tool_name.as_deref(), Some("bash"));
+    assert_eq!(fields.tool_use_id.as_deref(), Some("call_1"));
+    assert_eq!(fields.tool_input, Some(json!({ "command": "ls" })));
+
+    // Another source's map does not apply.
+    let fields = span::extract(
+        "pre_tool_use",
+        &span::canonical_payload("claude_code", &payload, None),
+    );
+    assert!(fields.tool_name.is_none());
+}
+
+#[test]
+fn canonical_payload_keeps_existing_keys_and_applies_overrides() {
+    let payload = json!({
+        "session_id": "canonical",
+        "sessionID": "camel",
+        "toolLabel": "Read",
+    });
+    let overrides = [("toolLabel".to_string(), "tool_name".to_string())].into();
+    let canonical = span::canonical_payload("opencode", &payload, Some(&overrides));
+    assert_eq!(canonical["session_id"], "canonical");
+    assert_eq!(canonical["sessionID"], "camel");
+    assert_eq!(canonical["tool_name"], "Read");
+    assert!(canonical.get("toolLabel").is_none());
+
+    // Nothing to rename borrows the payload as is.
+    let plain = json!({ "session_id": "s1", "tool_name": "Bash" });
+    assert!(matches!(
+        span::canonical_payload("opencode", &plain, None),
+        std::borrow::Cow::Borrowed(_)
+    ));
 }